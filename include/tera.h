@@ -0,0 +1,31 @@
+/* C interface of Tera, built with the `ffi` feature. See the `ffi` module for the details. */
+#ifndef TERA_H
+#define TERA_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Tera Tera;
+
+typedef enum TeraStatus {
+    TERA_OK = 0,
+    TERA_NULL_ARGUMENT = 1,
+    TERA_INVALID_UTF8 = 2,
+    TERA_INVALID_CONTEXT = 3,
+    TERA_TEMPLATE_ERROR = 4,
+    TERA_PANIC = 5,
+} TeraStatus;
+
+Tera *tera_new(void);
+void tera_free(Tera *tera);
+TeraStatus tera_add_template(Tera *tera, const char *name, const char *content);
+TeraStatus tera_render_json(const Tera *tera, const char *name, const char *context, char **output);
+void tera_string_free(char *s);
+const char *tera_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TERA_H */
@@ -0,0 +1,365 @@
+//! Renders a Tera template from the command line.
+//!
+//! ```bash
+//! $ tera --context data.json page.html
+//! $ tera --context config.toml --templates "templates/**/*" page.html -o page.out.html
+//! # Renders all the templates of a directory again every time a template or the context changes
+//! $ tera --watch templates/ --context data.json -o out/
+//! # Like envsubst, with the template coming from stdin and the context from the environment
+//! $ cat config.tmpl | tera --stdin --env -D port=8080 -o -
+//! # Adds the filters and functions of the plugins of a directory
+//! $ tera --plugins /opt/tera/plugins --context data.json page.html
+//! # Rewrites the Jinja2 templates of a directory to the Tera syntax
+//! $ tera migrate jinja templates/ --trim-blocks
+//! ```
+use std::env;
+use std::error::Error as StdError;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use tera::{Context, Error, Result, Tera, Value};
+
+mod migrate;
+
+fn build_cli() -> App<'static, 'static> {
+    App::new("tera")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Renders a Tera template with the given context")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(
+            Arg::with_name("template")
+                .help("Path to the template to render")
+                .required_unless_one(&["watch", "stdin"]),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .conflicts_with("template")
+                .help("Reads the template to render from stdin"),
+        )
+        .arg(
+            Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .help("Adds all the environment variables to the context"),
+        )
+        .arg(
+            Arg::with_name("define")
+                .short("D")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KEY=VALUE")
+                .help("Adds a string variable to the context, overriding the other sources"),
+        )
+        .arg(
+            Arg::with_name("context")
+                .short("c")
+                .long("context")
+                .takes_value(true)
+                .help("A JSON, YAML or TOML file to use as context, based on its extension"),
+        )
+        .arg(
+            Arg::with_name("templates")
+                .short("t")
+                .long("templates")
+                .takes_value(true)
+                .help("A glob of other templates the template can extend, include or import"),
+        )
+        .arg(
+            Arg::with_name("plugins")
+                .short("p")
+                .long("plugins")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("DIR")
+                .help("Loads the filters and functions of the plugins found in that directory"),
+        )
+        .arg(Arg::with_name("output").short("o").long("output").takes_value(true).help(
+            "Where to write the rendered template, stdout if not given or `-`. \
+                     The output directory when using `--watch`",
+        ))
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .takes_value(true)
+                .value_name("DIR")
+                .conflicts_with_all(&["template", "templates", "stdin"])
+                .requires("output")
+                .help(
+                    "Renders all the templates of that directory in the output directory \
+                     and renders them again when they or the context file change",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Rewrites templates written for another engine to the Tera syntax")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("jinja")
+                        .about(
+                            "Rewrites the Jinja2 templates of a directory in place and reports \
+                             what needs to be translated by hand",
+                        )
+                        .arg(Arg::with_name("dir").required(true).help("The templates directory"))
+                        .arg(
+                            Arg::with_name("trim-blocks")
+                                .long("trim-blocks")
+                                .help("The templates were written for the `trim_blocks` option"),
+                        )
+                        .arg(
+                            Arg::with_name("lstrip-blocks")
+                                .long("lstrip-blocks")
+                                .help("The templates were written for the `lstrip_blocks` option"),
+                        )
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .long("dry-run")
+                                .help("Only reports, without writing the templates"),
+                        ),
+                ),
+        )
+}
+
+/// Parses the content of a context file according to its extension
+fn parse_context(path: &Path, content: &str) -> Result<Context> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let value: Value = match extension {
+        "json" => serde_json::from_str(content).map_err(Error::msg)?,
+        "yaml" | "yml" => serde_yaml::from_str(content).map_err(Error::msg)?,
+        "toml" => toml::from_str(content).map_err(Error::msg)?,
+        _ => {
+            return Err(Error::msg(format!(
+                "Unknown context file extension for `{}`: expected json, yaml, yml or toml",
+                path.display()
+            )))
+        }
+    };
+    Context::from_value(value)
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path)
+        .map_err(|e| Error::chain(format!("Couldn't read `{}`", path.display()), e))
+}
+
+/// Parses a `-D key=value` definition
+fn parse_define(define: &str) -> Result<(&str, &str)> {
+    match define.find('=') {
+        Some(i) if i > 0 => Ok((&define[..i], &define[i + 1..])),
+        _ => Err(Error::msg(format!("Invalid definition `{}`: expected `key=value`", define))),
+    }
+}
+
+/// Builds the context from the context file, the environment variables and the `-D`
+/// definitions, in that order of precedence
+fn load_context(matches: &ArgMatches) -> Result<Context> {
+    let mut context = match matches.value_of("context") {
+        Some(path) => {
+            let path = Path::new(path);
+            parse_context(path, &read_file(path)?)?
+        }
+        None => Context::new(),
+    };
+
+    if matches.is_present("env") {
+        for (key, value) in env::vars() {
+            context.insert(key, &value);
+        }
+    }
+
+    for define in matches.values_of("define").into_iter().flatten() {
+        let (key, value) = parse_define(define)?;
+        context.insert(key, value);
+    }
+
+    Ok(context)
+}
+
+fn load_plugins(tera: &mut Tera, matches: &ArgMatches) -> Result<()> {
+    for dir in matches.values_of("plugins").into_iter().flatten() {
+        // The plugins are given explicitly by whoever runs the binary
+        unsafe { tera.load_plugins(dir)? };
+    }
+    Ok(())
+}
+
+fn render(matches: &ArgMatches) -> Result<String> {
+    let mut tera = match matches.value_of("templates") {
+        Some(glob) => Tera::new(glob)?,
+        None => Tera::default(),
+    };
+    load_plugins(&mut tera, matches)?;
+
+    let template = if matches.is_present("stdin") {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| Error::chain("Couldn't read the template from stdin", e))?;
+        tera.add_raw_template("stdin", &content)?;
+        "stdin"
+    } else {
+        let template = matches.value_of("template").unwrap();
+        tera.add_template_file(template, None)?;
+        template
+    };
+    tera.render(template, &load_context(matches)?)
+}
+
+fn write_output(output: Option<&str>, rendered: &str) -> Result<()> {
+    match output {
+        None | Some("-") => io::stdout()
+            .write_all(rendered.as_bytes())
+            .map_err(|e| Error::chain("Couldn't write to stdout", e)),
+        Some(path) => fs::write(path, rendered)
+            .map_err(|e| Error::chain(format!("Couldn't write to `{}`", path), e)),
+    }
+}
+
+/// Renders every template of `dir` in `out_dir`, keeping the same relative paths
+fn render_dir(dir: &str, matches: &ArgMatches, out_dir: &Path) -> Result<usize> {
+    let mut tera = Tera::new(&format!("{}/**/*", dir.trim_end_matches('/')))?;
+    load_plugins(&mut tera, matches)?;
+    let context = load_context(matches)?;
+
+    for name in tera.templates.keys() {
+        let rendered = tera.render(name, &context)?;
+        let path = out_dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::chain(format!("Couldn't create `{}`", parent.display()), e))?;
+        }
+        write_output(path.to_str(), &rendered)?;
+    }
+
+    Ok(tera.templates.len())
+}
+
+fn render_dir_and_report(dir: &str, matches: &ArgMatches, out_dir: &Path) {
+    match render_dir(dir, matches, out_dir) {
+        Ok(count) => println!("Rendered {} templates in `{}`", count, out_dir.display()),
+        Err(e) => print_error(&e),
+    }
+}
+
+fn watch(dir: &str, matches: &ArgMatches) -> Result<()> {
+    let out_dir = PathBuf::from(matches.value_of("output").unwrap());
+    // So we can ignore the events of the files we write if the output is inside the watched dir
+    let canonical_out_dir = fs::create_dir_all(&out_dir)
+        .and_then(|_| out_dir.canonicalize())
+        .map_err(|e| Error::chain(format!("Couldn't create `{}`", out_dir.display()), e))?;
+
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, Duration::from_millis(200)).map_err(Error::msg)?;
+    watcher.watch(dir, RecursiveMode::Recursive).map_err(Error::msg)?;
+    if let Some(context) = matches.value_of("context") {
+        watcher.watch(context, RecursiveMode::NonRecursive).map_err(Error::msg)?;
+    }
+
+    render_dir_and_report(dir, matches, &out_dir);
+    println!("Watching `{}` for changes, press Ctrl+C to stop", dir);
+
+    loop {
+        let path = match rx.recv() {
+            Ok(DebouncedEvent::Create(p))
+            | Ok(DebouncedEvent::Write(p))
+            | Ok(DebouncedEvent::Remove(p))
+            | Ok(DebouncedEvent::Rename(_, p)) => p,
+            Ok(DebouncedEvent::Error(e, _)) => return Err(Error::msg(e)),
+            Ok(_) => continue,
+            Err(e) => return Err(Error::msg(e)),
+        };
+        if path.starts_with(&canonical_out_dir) || path.starts_with(&out_dir) {
+            continue;
+        }
+        println!("Change detected in `{}`", path.display());
+        render_dir_and_report(dir, matches, &out_dir);
+    }
+}
+
+fn migrate_jinja(matches: &ArgMatches) -> Result<()> {
+    let dir = Path::new(matches.value_of("dir").unwrap());
+    let options = migrate::Options {
+        trim_blocks: matches.is_present("trim-blocks"),
+        lstrip_blocks: matches.is_present("lstrip-blocks"),
+    };
+    let dry_run = matches.is_present("dry-run");
+    let (rewritten, reports) = migrate::migrate_dir(dir, options, dry_run)?;
+
+    for (path, report) in &reports {
+        println!("{}:{}: {}", path, report.line, report.message);
+    }
+    let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+    println!(
+        "{} {} templates, {} constructs need to be translated by hand",
+        verb,
+        rewritten,
+        reports.len()
+    );
+    Ok(())
+}
+
+fn print_error(e: &Error) {
+    eprintln!("Error: {}", e);
+    let mut cause = e.source();
+    while let Some(e) = cause {
+        eprintln!("Reason: {}", e);
+        cause = e.source();
+    }
+}
+
+fn main() {
+    let matches = build_cli().get_matches();
+    let migrate_matches = matches.subcommand_matches("migrate");
+    let res = match (migrate_matches, matches.value_of("watch")) {
+        (Some(migrate), _) => migrate_jinja(migrate.subcommand_matches("jinja").unwrap()),
+        (None, Some(dir)) => watch(dir, &matches),
+        (None, None) => {
+            render(&matches).and_then(|out| write_output(matches.value_of("output"), &out))
+        }
+    };
+    if let Err(e) = res {
+        print_error(&e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_context, parse_define};
+
+    #[test]
+    fn can_parse_context_files() {
+        let json = parse_context(Path::new("data.json"), r#"{"name": "Bob", "age": 42}"#).unwrap();
+        let yaml = parse_context(Path::new("data.yaml"), "name: Bob\nage: 42").unwrap();
+        let toml = parse_context(Path::new("data.toml"), "name = \"Bob\"\nage = 42").unwrap();
+        assert_eq!(json, yaml);
+        assert_eq!(json, toml);
+        assert_eq!(json.get("name").unwrap(), "Bob");
+    }
+
+    #[test]
+    fn errors_on_unknown_context_extension() {
+        assert!(parse_context(Path::new("data.txt"), "").is_err());
+        assert!(parse_context(Path::new("data.json"), "{").is_err());
+    }
+
+    #[test]
+    fn can_parse_definitions() {
+        assert_eq!(parse_define("name=Bob").unwrap(), ("name", "Bob"));
+        assert_eq!(parse_define("url=a=b").unwrap(), ("url", "a=b"));
+        assert_eq!(parse_define("empty=").unwrap(), ("empty", ""));
+        assert!(parse_define("name").is_err());
+        assert!(parse_define("=Bob").is_err());
+    }
+}
@@ -0,0 +1,821 @@
+//! Rewrites Jinja2 templates to the Tera syntax where the two differ in ways that can be
+//! translated, and reports the constructs that need to be translated by hand:
+//!
+//! - positional filter arguments get the name of the Tera argument, `join(", ")` becoming
+//!   `join(sep=", ")`, and the filters with a different name are renamed
+//! - tests written without parentheses, like `is divisibleby 3`, get them and the comparison
+//!   tests, like `is eq 3`, become operators
+//! - macros are called as `alias::macro()` and `self::macro()`
+//! - `range()` arguments get their name, `loop.cycle()` becomes `cycle()` and `.items()`
+//!   is removed
+//! - the whitespace the `trim_blocks` and `lstrip_blocks` options remove is removed
+use std::fs;
+use std::path::Path;
+
+use tera::{Error, Result};
+
+/// The Jinja options the templates were written for
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Options {
+    /// Whether the first newline after a tag is removed
+    pub trim_blocks: bool,
+    /// Whether the spaces and tabs before a tag at the start of a line are removed
+    pub lstrip_blocks: bool,
+}
+
+/// A construct of a template that couldn't be translated
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Jinja filter -> (Tera filter, its arguments as (Jinja name, Tera name if it has one))
+const FILTERS: [(&str, &str, &[(&str, Option<&str>)]); 18] = [
+    ("count", "length", &[]),
+    ("d", "default", &[("default_value", Some("value")), ("boolean", None)]),
+    ("default", "default", &[("default_value", Some("value")), ("boolean", None)]),
+    ("e", "escape", &[]),
+    ("float", "float", &[("default", Some("default"))]),
+    (
+        "groupby",
+        "group_by",
+        &[("attribute", Some("attribute")), ("default", None), ("case_sensitive", None)],
+    ),
+    ("int", "int", &[("default", Some("default")), ("base", Some("base"))]),
+    ("join", "join", &[("d", Some("sep")), ("attribute", None)]),
+    ("map", "map", &[("filter", None)]),
+    ("replace", "replace", &[("old", Some("from")), ("new", Some("to")), ("count", None)]),
+    ("round", "round", &[("precision", Some("precision")), ("method", Some("method"))]),
+    (
+        "sort",
+        "sort",
+        &[("reverse", None), ("case_sensitive", None), ("attribute", Some("attribute"))],
+    ),
+    ("string", "as_str", &[]),
+    ("tojson", "json_encode", &[("indent", None)]),
+    ("trim", "trim", &[("chars", None)]),
+    (
+        "truncate",
+        "truncate",
+        &[("length", Some("length")), ("killwords", None), ("end", Some("end")), ("leeway", None)],
+    ),
+    (
+        "unique",
+        "unique",
+        &[("case_sensitive", Some("case_sensitive")), ("attribute", Some("attribute"))],
+    ),
+    ("wordcount", "wordcount", &[]),
+];
+
+/// Jinja filters without an equivalent in Tera, or with a different behaviour
+const UNSUPPORTED_FILTERS: [&str; 22] = [
+    "abs",
+    "attr",
+    "batch",
+    "center",
+    "dictsort",
+    "forceescape",
+    "format",
+    "indent",
+    "items",
+    "list",
+    "max",
+    "min",
+    "pprint",
+    "random",
+    "reject",
+    "rejectattr",
+    "select",
+    "selectattr",
+    "slice",
+    "sum",
+    "urlize",
+    "wordwrap",
+];
+
+/// The Jinja tests that take an argument, which can be given without parentheses
+const TESTS_WITH_ARG: [&str; 17] = [
+    "divisibleby",
+    "eq",
+    "equalto",
+    "==",
+    "ne",
+    "!=",
+    "lt",
+    "lessthan",
+    "<",
+    "le",
+    "<=",
+    "gt",
+    "greaterthan",
+    ">",
+    "ge",
+    ">=",
+    "in",
+];
+
+/// The Jinja tests Tera has as well
+const SUPPORTED_TESTS: [&str; 13] = [
+    "array",
+    "boolean",
+    "defined",
+    "divisibleby",
+    "even",
+    "iterable",
+    "mapping",
+    "none",
+    "number",
+    "odd",
+    "object",
+    "string",
+    "undefined",
+];
+
+/// Rewrites the templates of `dir` and its subdirectories, unless `dry_run` is set, and
+/// returns the reports of each template along with the number of templates rewritten
+pub fn migrate_dir(
+    dir: &Path,
+    options: Options,
+    dry_run: bool,
+) -> Result<(usize, Vec<(String, Report)>)> {
+    let mut rewritten = 0;
+    let mut reports = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| Error::chain(format!("Couldn't read `{}`", dir.display()), e))?;
+        let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            // Not a template, images for example
+            let source = match fs::read_to_string(&path) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+
+            let (migrated, template_reports) = jinja(&source, options);
+            let name = path.display().to_string();
+            reports.extend(template_reports.into_iter().map(|r| (name.clone(), r)));
+            if migrated != source {
+                rewritten += 1;
+                if !dry_run {
+                    fs::write(&path, migrated)
+                        .map_err(|e| Error::chain(format!("Couldn't write `{}`", name), e))?;
+                }
+            }
+        }
+    }
+
+    Ok((rewritten, reports))
+}
+
+/// Rewrites a Jinja template to the Tera syntax, returning it along with the constructs
+/// that couldn't be translated
+pub fn jinja(source: &str, options: Options) -> (String, Vec<Report>) {
+    let tags = find_tags(source);
+    let mut rewriter = Rewriter { macros: vec![], aliases: vec![], messages: vec![] };
+    for &(start, end) in &tags {
+        let tokens = tokenize(&source[start + 2..end - 2]);
+        let words: Vec<_> = tokens.iter().filter(|t| is_ident(t)).collect();
+        match words.first().map(|w| w.as_str()) {
+            Some("macro") if words.len() > 1 => rewriter.macros.push(words[1].clone()),
+            Some("import") => {
+                if let Some(p) = words.iter().position(|w| *w == "as") {
+                    rewriter.aliases.extend(words.get(p + 1).cloned().cloned());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut res = String::with_capacity(source.len());
+    let mut reports = vec![];
+    let mut pos = 0;
+    for (start, end) in tags {
+        if start < pos {
+            continue;
+        }
+        let line = source[..start].matches('\n').count() + 1;
+        res.push_str(&source[pos..start]);
+        pos = end;
+
+        let delimiter = &source[start..start + 2];
+        let mut content = &source[start + 2..end - 2];
+        let mut start_marker = "";
+        let mut end_marker = "";
+        if delimiter != "{{" {
+            // `+` keeps the whitespace the options would remove, Tera only knows about `-`
+            if let Some(c) = content.strip_prefix('+') {
+                content = c;
+                start_marker = "+";
+            } else if content.starts_with('-') {
+                start_marker = "-";
+            }
+            if let Some(c) = content.strip_suffix('+') {
+                content = c;
+                end_marker = "+";
+            } else if content.ends_with('-') {
+                end_marker = "-";
+            }
+        }
+
+        let content = match delimiter {
+            "{{" => rewriter.rewrite_output(content),
+            "{%" => rewriter.rewrite_tag(content),
+            _ => content.to_string(),
+        };
+        reports.extend(rewriter.messages.drain(..).map(|message| Report { line, message }));
+
+        if delimiter == "{{" {
+            res.push_str(&format!("{{{{{}}}}}", content));
+            continue;
+        }
+        if options.lstrip_blocks && start_marker.is_empty() {
+            let line_start = res.rfind('\n').map_or(0, |i| i + 1);
+            if res[line_start..].chars().all(|c| c == ' ' || c == '\t') {
+                res.truncate(line_start);
+            }
+        }
+        let mut closing = &source[end - 2..end];
+        if options.trim_blocks && end_marker.is_empty() {
+            let after = &source[end..];
+            let newline_len = if after.starts_with("\r\n") {
+                2
+            } else if after.starts_with('\n') {
+                1
+            } else {
+                0
+            };
+            match after[newline_len..].chars().next() {
+                _ if newline_len == 0 => (),
+                // `-` removes all the whitespace, only the newline is there
+                Some(c) if !c.is_whitespace() => {
+                    closing = if delimiter == "{%" { "-%}" } else { "-#}" }
+                }
+                _ => pos += newline_len,
+            }
+        }
+        res.push_str(delimiter);
+        res.push_str(&content);
+        res.push_str(closing);
+    }
+    res.push_str(&source[pos..]);
+
+    (res, reports)
+}
+
+/// The byte ranges of the variable blocks, tags and comments of the template. The content of
+/// `raw` blocks is skipped and the scan stops at the first one that is not closed.
+fn find_tags(source: &str) -> Vec<(usize, usize)> {
+    let mut tags = vec![];
+    let mut pos = 0;
+
+    loop {
+        let next = ["{{", "{%", "{#"]
+            .iter()
+            .filter_map(|&start| source[pos..].find(start).map(|i| (pos + i, start)))
+            .min();
+        let (start, delimiter) = match next {
+            Some(n) => n,
+            None => break,
+        };
+        let end_delimiter = match delimiter {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+
+        let mut i = start + 2;
+        let end = loop {
+            let rest = &source[i..];
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => return tags,
+            };
+            if rest.starts_with(end_delimiter) {
+                break i + 2;
+            }
+            i += if delimiter != "{#" && (c == '"' || c == '\'') {
+                string_len(rest)
+            } else {
+                c.len_utf8()
+            };
+        };
+        tags.push((start, end));
+        pos = end;
+
+        if delimiter == "{%" && tag_name(&source[start + 2..end - 2]) == "raw" {
+            // Goes to the `endraw` tag
+            let mut search = pos;
+            pos = loop {
+                let i = match source[search..].find("{%") {
+                    Some(i) => search + i,
+                    None => return tags,
+                };
+                let close = match source[i..].find("%}") {
+                    Some(c) => i + c,
+                    None => return tags,
+                };
+                if tag_name(&source[i + 2..close]) == "endraw" {
+                    break i;
+                }
+                search = close + 2;
+            };
+        }
+    }
+
+    tags
+}
+
+fn tag_name(content: &str) -> &str {
+    let content = content.trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace());
+    content.split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or("")
+}
+
+/// The length of the string literal starting the input, all of it if it is never closed
+fn string_len(input: &str) -> usize {
+    let quote = input.chars().next().unwrap();
+    let mut escaped = false;
+    for (i, c) in input.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + 1;
+        }
+    }
+    input.len()
+}
+
+/// Splits an expression in tokens: strings, identifiers, numbers, whitespace and operators.
+/// Concatenating them gives the expression back.
+fn tokenize(expr: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < expr.len() {
+        let rest = &expr[i..];
+        let c = rest.chars().next().unwrap();
+        let len = if c == '"' || c == '\'' {
+            string_len(rest)
+        } else if c.is_whitespace() {
+            rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len())
+        } else if c.is_alphanumeric() || c == '_' {
+            rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len())
+        } else if ["==", "!=", "<=", ">=", "::", "//", "**"].iter().any(|op| rest.starts_with(op)) {
+            2
+        } else {
+            c.len_utf8()
+        };
+        tokens.push(rest[..len].to_string());
+        i += len;
+    }
+
+    tokens
+}
+
+fn is_ident(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_alphabetic() || c == '_')
+}
+
+fn is_space(token: &str) -> bool {
+    token.starts_with(char::is_whitespace)
+}
+
+fn next_non_space(tokens: &[String], from: usize) -> Option<usize> {
+    (from..tokens.len()).find(|&i| !is_space(&tokens[i]))
+}
+
+/// The index of the parenthesis closing the one at `open`
+fn closing_paren(tokens: &[String], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.as_str() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// The index after the operand starting at `start`: a literal or a variable, with its
+/// attributes, subscripts and call arguments
+fn operand_end(tokens: &[String], start: usize) -> usize {
+    let mut i = start;
+    while i < tokens.len() && !is_space(&tokens[i]) {
+        match tokens[i].as_str() {
+            "(" | "[" => i = closing_paren(tokens, i).unwrap_or(tokens.len() - 1) + 1,
+            "." => i += 1,
+            t if i == start && t == "-" => i += 1,
+            t if is_ident(t)
+                || t.starts_with(char::is_numeric)
+                || t.starts_with(&['"', '\''][..]) =>
+            {
+                i += 1
+            }
+            _ => break,
+        }
+    }
+    i
+}
+
+/// Splits the tokens of call arguments on the top-level commas, returning each argument as
+/// its name, if it is a keyword one, and value
+fn split_args(tokens: &[String]) -> Vec<(Option<String>, String)> {
+    let mut args = vec![];
+    let mut depth = 0;
+    let mut current: Vec<String> = vec![];
+
+    for token in tokens.iter().chain(std::iter::once(&",".to_string())) {
+        match token.as_str() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            "," if depth == 0 => {
+                let words: Vec<_> =
+                    (0..current.len()).filter(|&i| !is_space(&current[i])).collect();
+                if words.len() > 2 && is_ident(&current[words[0]]) && current[words[1]] == "=" {
+                    let value = current[words[1] + 1..].concat();
+                    args.push((Some(current[words[0]].clone()), value.trim().to_string()));
+                } else if !words.is_empty() {
+                    args.push((None, current.concat().trim().to_string()));
+                }
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(token.clone());
+    }
+
+    args
+}
+
+struct Rewriter {
+    /// The macros defined in the template, called without a namespace in Jinja
+    macros: Vec<String>,
+    /// The namespaces of the imported macros
+    aliases: Vec<String>,
+    /// The constructs of the current tag that couldn't be translated
+    messages: Vec<String>,
+}
+
+impl Rewriter {
+    fn rewrite_output(&mut self, content: &str) -> String {
+        if tokenize(content).iter().any(|t| t == "if") {
+            self.messages.push(
+                "inline `if` expressions are not supported: use an `{% if %}` tag".to_string(),
+            );
+        }
+        self.rewrite_expr(content)
+    }
+
+    fn rewrite_tag(&mut self, content: &str) -> String {
+        let tokens = tokenize(content);
+        let words: Vec<&str> = tokens.iter().filter(|t| is_ident(t)).map(|t| t.as_str()).collect();
+        let name = tag_name(content);
+        match name {
+            "macro" => return content.to_string(),
+            "from" => self.messages.push(
+                "`{% from ... import %}` is not supported: use `{% import ... as name %}` and \
+                 call the macros as `name::macro()`"
+                    .to_string(),
+            ),
+            "call" => {
+                let call = tokens.iter().position(|t| t == "call").unwrap();
+                if next_non_space(&tokens, call + 1).map_or(false, |i| tokens[i] == "(") {
+                    self.messages.push("`caller()` arguments are not supported".to_string())
+                }
+            }
+            "autoescape" => {
+                self.messages.push(format!("the `{{% {} %}}` tag is not supported", name))
+            }
+            "set" if !tokens.iter().any(|t| t == "=") => self.messages.push(
+                "`{% set %}` blocks are not supported: set the variable with an expression"
+                    .to_string(),
+            ),
+            "set" if words.contains(&"if") => self.messages.push(
+                "inline `if` expressions are not supported: use an `{% if %}` tag".to_string(),
+            ),
+            "for" if words.contains(&"recursive") => {
+                self.messages.push("recursive `for` loops are not supported".to_string())
+            }
+            "block" if words.contains(&"scoped") || words.contains(&"required") => {
+                self.messages.push("`scoped` and `required` blocks are not supported".to_string())
+            }
+            "include" if words.ends_with(&["without", "context"]) => {
+                self.messages.push("includes without context are not supported".to_string())
+            }
+            "include" if words.ends_with(&["with", "context"]) => {
+                // Tera includes always get the context
+                let end = content.rfind("with").unwrap();
+                let spaces = &content[content.trim_end().len()..];
+                return format!("{}{}", content[..end].trim_end(), spaces);
+            }
+            _ => (),
+        }
+        self.rewrite_expr(content)
+    }
+
+    fn rewrite_expr(&mut self, expr: &str) -> String {
+        let tokens = tokenize(expr);
+        let mut res = String::with_capacity(expr.len());
+        let mut i = 0;
+
+        while i < tokens.len() {
+            let token = tokens[i].as_str();
+            let previous =
+                (0..i).rev().find(|&p| !is_space(&tokens[p])).map(|p| tokens[p].as_str());
+            let next = tokens.get(i + 1).map(|t| t.as_str());
+
+            match token {
+                "|" => {
+                    if let Some(j) =
+                        next_non_space(&tokens, i + 1).filter(|&j| is_ident(&tokens[j]))
+                    {
+                        let (args, end) = match tokens.get(j + 1).map(|t| t.as_str()) {
+                            Some("(") => match closing_paren(&tokens, j + 1) {
+                                Some(close) => (split_args(&tokens[j + 2..close]), close + 1),
+                                None => (vec![], j + 1),
+                            },
+                            _ => (vec![], j + 1),
+                        };
+                        res.push_str(&tokens[i..j].concat());
+                        res.push_str(&self.filter(&tokens[j], args));
+                        i = end;
+                        continue;
+                    }
+                }
+                "is" if previous.is_some() => {
+                    if let Some((test, end)) = self.test(&tokens, i) {
+                        res.push_str(&test);
+                        i = end;
+                        continue;
+                    }
+                }
+                "." if i > 0 && tokens.get(i + 2).map(|t| t.as_str()) == Some("(") => {
+                    let method = tokens[i + 1].as_str();
+                    let is_root = i < 2 || tokens[i - 2] != ".";
+                    if is_root && self.aliases.contains(&tokens[i - 1]) {
+                        res.push_str("::");
+                        i += 1;
+                        continue;
+                    }
+                    if method == "items" && tokens.get(i + 3).map(|t| t.as_str()) == Some(")") {
+                        // Iterating on a map gives its keys and values in Tera
+                        i += 4;
+                        continue;
+                    }
+                    self.messages.push(format!(
+                        "method calls like `.{}()` are not supported: use a filter or function",
+                        method
+                    ));
+                }
+                "**" => self.messages.push("the `**` operator is not supported".to_string()),
+                "loop"
+                    if tokens.get(i + 1).map(|t| t.as_str()) == Some(".")
+                        && tokens.get(i + 2).map(|t| t.as_str()) == Some("cycle")
+                        && tokens.get(i + 3).map(|t| t.as_str()) == Some("(") =>
+                {
+                    if let Some(close) = closing_paren(&tokens, i + 3) {
+                        let values = self.rewrite_expr(&tokens[i + 4..close].concat());
+                        res.push_str(&format!("cycle(values=[{}])", values.trim()));
+                        i = close + 1;
+                        continue;
+                    }
+                }
+                _ if is_ident(token)
+                    && next == Some("(")
+                    && !matches!(previous, Some(".") | Some("::") | Some("|") | Some("is")) =>
+                {
+                    if self.macros.iter().any(|m| m == token) {
+                        res.push_str("self::");
+                    } else if token == "range" {
+                        if let Some(close) = closing_paren(&tokens, i + 1) {
+                            res.push_str(&self.range(split_args(&tokens[i + 2..close])));
+                            i = close + 1;
+                            continue;
+                        }
+                    } else if ["dict", "cycler", "joiner", "lipsum"].contains(&token) {
+                        self.messages.push(format!("the `{}` function is not supported", token));
+                    }
+                }
+                _ => (),
+            }
+
+            res.push_str(token);
+            i += 1;
+        }
+
+        res
+    }
+
+    /// The Tera call of a Jinja filter
+    fn filter(&mut self, name: &str, args: Vec<(Option<String>, String)>) -> String {
+        if UNSUPPORTED_FILTERS.contains(&name) {
+            self.messages.push(format!("the Jinja `{}` filter has no equivalent in Tera", name));
+        }
+        let (tera_name, arg_names) = match FILTERS.iter().find(|(jinja, ..)| *jinja == name) {
+            Some((_, tera_name, arg_names)) => (*tera_name, *arg_names),
+            None => (name, &[][..]),
+        };
+        if args.is_empty() {
+            return tera_name.to_string();
+        }
+
+        let mut tera_args = vec![];
+        for (i, (arg_name, value)) in args.into_iter().enumerate() {
+            let value = self.rewrite_expr(&value);
+            let jinja_arg = match &arg_name {
+                Some(n) => arg_names.iter().find(|(jinja, _)| jinja == n),
+                None => arg_names.get(i),
+            };
+            match (jinja_arg, arg_name) {
+                (Some((_, Some(tera_arg))), _) => tera_args.push(format!("{}={}", tera_arg, value)),
+                (Some((jinja, None)), _) => self.messages.push(format!(
+                    "the `{}` argument of the `{}` filter is not supported",
+                    jinja, name
+                )),
+                (None, Some(arg_name)) => tera_args.push(format!("{}={}", arg_name, value)),
+                (None, None) => {
+                    self.messages.push(format!(
+                        "the argument `{}` of the `{}` filter needs a name in Tera",
+                        value, name
+                    ));
+                    tera_args.push(value);
+                }
+            }
+        }
+
+        if tera_args.is_empty() {
+            tera_name.to_string()
+        } else {
+            format!("{}({})", tera_name, tera_args.join(", "))
+        }
+    }
+
+    /// Rewrites the test whose `is` is at `tokens[i]`, returning it along with the index of
+    /// the token after it
+    fn test(&mut self, tokens: &[String], i: usize) -> Option<(String, usize)> {
+        let mut j = next_non_space(tokens, i + 1)?;
+        let negated = tokens[j] == "not";
+        if negated {
+            j = next_non_space(tokens, j + 1)?;
+        }
+        let name = tokens[j].as_str();
+
+        let mut end = j + 1;
+        let mut arg = None;
+        if tokens.get(end).map(|t| t.as_str()) == Some("(") {
+            let close = closing_paren(tokens, end)?;
+            arg = Some(self.rewrite_expr(&tokens[end + 1..close].concat()));
+            end = close + 1;
+        } else if TESTS_WITH_ARG.contains(&name) {
+            let start = next_non_space(tokens, end)?;
+            end = operand_end(tokens, start);
+            arg = Some(self.rewrite_expr(&tokens[start..end].concat()));
+        }
+
+        let operators = match name {
+            "eq" | "equalto" | "==" => Some(("==", "!=")),
+            "ne" | "!=" => Some(("!=", "==")),
+            "lt" | "lessthan" | "<" => Some(("<", ">=")),
+            "le" | "<=" => Some(("<=", ">")),
+            "gt" | "greaterthan" | ">" => Some((">", "<=")),
+            "ge" | ">=" => Some((">=", "<")),
+            "in" => Some(("in", "not in")),
+            "true" => Some(("==", "!=")).filter(|_| arg.is_none()),
+            "false" => Some(("!=", "==")).filter(|_| arg.is_none()),
+            _ => None,
+        };
+        let not = if negated { "not " } else { "" };
+        let test = match (operators, arg) {
+            (Some((op, negated_op)), arg) => {
+                let op = if negated { negated_op } else { op };
+                let arg = arg.unwrap_or_else(|| "true".to_string());
+                format!("{} {}", op, arg)
+            }
+            (None, arg) => {
+                let tera_name = match name {
+                    "sequence" => "iterable",
+                    "integer" | "float" => "number",
+                    _ if SUPPORTED_TESTS.contains(&name) => name,
+                    _ => {
+                        self.messages
+                            .push(format!("the `{}` test has no equivalent in Tera", name));
+                        name
+                    }
+                };
+                match arg {
+                    Some(arg) => format!("is {}{}({})", not, tera_name, arg),
+                    None => format!("is {}{}", not, tera_name),
+                }
+            }
+        };
+
+        Some((test, end))
+    }
+
+    /// The Tera call of `range()`, whose arguments need a name
+    fn range(&mut self, args: Vec<(Option<String>, String)>) -> String {
+        let names: &[&str] = if args.len() == 1 { &["end"] } else { &["start", "end", "step_by"] };
+        let args: Vec<_> = args
+            .into_iter()
+            .zip(names)
+            .map(|((_, value), name)| format!("{}={}", name, self.rewrite_expr(&value)))
+            .collect();
+        format!("range({})", args.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{jinja, Options, Report};
+
+    fn migrate(source: &str) -> String {
+        let (migrated, reports) = jinja(source, Options::default());
+        assert_eq!(reports, vec![]);
+        migrated
+    }
+
+    #[test]
+    fn can_name_filter_arguments() {
+        let inputs = vec![
+            (r#"{{ a|join(", ") }}"#, r#"{{ a|join(sep=", ") }}"#),
+            (r#"{{ a | d("b") | e }}"#, r#"{{ a | default(value="b") | escape }}"#),
+            ("{{ a|truncate(10, end='…') }}", "{{ a|truncate(length=10, end='…') }}"),
+            ("{{ a|replace(b|first, 'c') }}", "{{ a|replace(from=b|first, to='c') }}"),
+            ("{{ a|map(attribute='b')|tojson }}", "{{ a|map(attribute='b')|json_encode }}"),
+            ("{% for a in b|sort(attribute='c') %}", "{% for a in b|sort(attribute='c') %}"),
+            ("{{ 'a|join(1)' }}", "{{ 'a|join(1)' }}"),
+        ];
+        for (input, expected) in inputs {
+            assert_eq!(migrate(input), expected);
+        }
+    }
+
+    #[test]
+    fn can_rewrite_tests() {
+        let inputs = vec![
+            ("{% if a is divisibleby 3 %}", "{% if a is divisibleby(3) %}"),
+            ("{% if a is not eq b.c %}", "{% if a != b.c %}"),
+            ("{% if a is in [1, 2] and b is sequence %}", "{% if a in [1, 2] and b is iterable %}"),
+            ("{% if a is defined %}", "{% if a is defined %}"),
+        ];
+        for (input, expected) in inputs {
+            assert_eq!(migrate(input), expected);
+        }
+    }
+
+    #[test]
+    fn can_rewrite_calls() {
+        let source = "{% import 'm.html' as m %}{% macro b(x) %}{% endmacro %}\
+                      {{ m.a(1) }}{{ b(2) }}{% for i in range(1, n) %}{% endfor %}\
+                      {% for k, v in d.items() %}{{ loop.cycle('a', b) }}{% endfor %}\
+                      {% include 'a' with context %}{% call m.card(1) %}x{% endcall %}";
+        let expected = "{% import 'm.html' as m %}{% macro b(x) %}{% endmacro %}\
+                        {{ m::a(1) }}{{ self::b(2) }}{% for i in range(start=1, end=n) %}{% endfor %}\
+                        {% for k, v in d %}{{ cycle(values=['a', b]) }}{% endfor %}\
+                        {% include 'a' %}{% call m::card(1) %}x{% endcall %}";
+        assert_eq!(migrate(source), expected);
+    }
+
+    #[test]
+    fn can_apply_whitespace_options() {
+        let options = Options { trim_blocks: true, lstrip_blocks: true };
+        let source =
+            "<ul>\n  {% for a in b %}\n  <li>{{ a }}</li>\n  {% endfor %}\n</ul>\n{%+ if c +%}\nd";
+        let (migrated, _) = jinja(source, options);
+        assert_eq!(
+            migrated,
+            "<ul>\n{% for a in b %}  <li>{{ a }}</li>\n{% endfor -%}\n</ul>\n{% if c %}\nd"
+        );
+        let (migrated, _) = jinja("{% if a %}\nb{% endif %}", options);
+        assert_eq!(migrated, "{% if a -%}\nb{% endif %}");
+    }
+
+    #[test]
+    fn reports_what_cant_be_translated() {
+        let source =
+            "{% from 'm' import a %}\n{{ a if b else c }}\n{{ a|batch(3) }}{{ a|custom(1) }}\n\
+                      {% if a is sameas b %}{% endif %}{{ a.upper() }}";
+        let (_, reports) = jinja(source, Options::default());
+        let lines: Vec<_> = reports.iter().map(|Report { line, .. }| *line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 3, 3, 4, 4]);
+        assert!(reports[3].message.contains("the argument `3` of the `batch` filter"));
+        assert!(reports[5].message.contains("the `sameas` test"));
+    }
+}
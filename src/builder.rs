@@ -0,0 +1,319 @@
+//! Configuring a `Tera` instance in one expression, before its templates are loaded
+use std::path::PathBuf;
+
+use crate::builtins::filters::Filter;
+use crate::builtins::functions::Function;
+use crate::builtins::testers::Test;
+use crate::cache::FragmentCache;
+use crate::errors::Result;
+use crate::hooks::RenderHooks;
+use crate::i18n::Translator;
+use crate::options::OutOfBounds;
+use crate::parser::delimiters::Delimiters;
+use crate::post_process::PostProcessor;
+use crate::pre_process::PreProcessor;
+use crate::tags::Tag;
+use crate::tera::{EscapeFn, Tera};
+use crate::urls::{AssetResolver, UrlResolver};
+
+/// Configures a `Tera` instance, returned by [Tera::builder](struct.Tera.html#method.builder).
+///
+/// Every setting is applied before the templates are loaded by
+/// [TeraBuilder::build](struct.TeraBuilder.html#method.build), so the ones changing how
+/// templates are parsed, like the delimiters, apply to all of them. The settings not given
+/// keep the defaults of `Tera::default`.
+///
+/// ```rust
+/// # use tera::{Context, Delimiters, Tera};
+/// let tera = Tera::builder()
+///     .delimiters(Delimiters { variable_start: "[[".to_string(), variable_end: "]]".to_string(), ..Delimiters::default() })
+///     .keep_trailing_newline(false)
+///     .template("hello.html", "Hello [[ name ]]\n")
+///     .build()
+///     .unwrap();
+/// let mut context = Context::new();
+/// context.insert("name", "Bob");
+/// assert_eq!(tera.render("hello.html", &context).unwrap(), "Hello Bob");
+/// ```
+pub struct TeraBuilder {
+    tera: Tera,
+    // Set with `TeraBuilder::delimiters`, validated when building
+    delimiters: Option<Delimiters>,
+    globs: Vec<String>,
+    templates: Vec<(String, String)>,
+    files: Vec<(PathBuf, Option<String>)>,
+    strict: bool,
+}
+
+impl TeraBuilder {
+    pub(crate) fn new() -> TeraBuilder {
+        TeraBuilder {
+            tera: Tera::default(),
+            delimiters: None,
+            globs: vec![],
+            templates: vec![],
+            files: vec![],
+            strict: false,
+        }
+    }
+
+    /// Loads the templates matching this glob, like [Tera::new](struct.Tera.html#method.new).
+    /// If it is called several times, the templates of the first globs override the ones with
+    /// the same name of the following globs, like
+    /// [Tera::new_layered](struct.Tera.html#method.new_layered).
+    pub fn glob(mut self, glob: &str) -> Self {
+        self.globs.push(glob.to_string());
+        self
+    }
+
+    /// Adds a template from a string, see
+    /// [Tera::add_raw_template](struct.Tera.html#method.add_raw_template)
+    pub fn template(mut self, name: &str, content: &str) -> Self {
+        self.templates.push((name.to_string(), content.to_string()));
+        self
+    }
+
+    /// Adds a template from a file, named after its path if `name` is `None`, see
+    /// [Tera::add_template_file](struct.Tera.html#method.add_template_file)
+    pub fn template_file<P: Into<PathBuf>>(mut self, path: P, name: Option<&str>) -> Self {
+        self.files.push((path.into(), name.map(|n| n.to_string())));
+        self
+    }
+
+    /// Whether building fails if a template uses a template, macro, filter, test, function or
+    /// tag that doesn't exist, as reported by [Tera::check](struct.Tera.html#method.check).
+    /// Off by default, those only failing the renders using them.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// See [Tera::autoescape_on](struct.Tera.html#method.autoescape_on)
+    pub fn autoescape_on(mut self, suffixes: Vec<&'static str>) -> Self {
+        self.tera.autoescape_on(suffixes);
+        self
+    }
+
+    /// See [Tera::set_escape_fn](struct.Tera.html#method.set_escape_fn)
+    pub fn escape_fn(mut self, function: EscapeFn) -> Self {
+        self.tera.set_escape_fn(function);
+        self
+    }
+
+    /// See [Tera::set_escape_fn_for](struct.Tera.html#method.set_escape_fn_for)
+    pub fn escape_fn_for(mut self, suffix: &'static str, function: EscapeFn) -> Self {
+        self.tera.set_escape_fn_for(suffix, function);
+        self
+    }
+
+    /// See [Tera::set_delimiters](struct.Tera.html#method.set_delimiters). Invalid delimiters
+    /// fail the build.
+    pub fn delimiters(mut self, delimiters: Delimiters) -> Self {
+        self.delimiters = Some(delimiters);
+        self
+    }
+
+    /// See [Tera::set_keep_trailing_newline](struct.Tera.html#method.set_keep_trailing_newline)
+    pub fn keep_trailing_newline(mut self, keep: bool) -> Self {
+        self.tera.set_keep_trailing_newline(keep);
+        self
+    }
+
+    /// See [Tera::set_normalize_newlines](struct.Tera.html#method.set_normalize_newlines)
+    pub fn normalize_newlines(mut self, normalize: bool) -> Self {
+        self.tera.set_normalize_newlines(normalize);
+        self
+    }
+
+    /// See [Tera::set_report_all_syntax_errors](struct.Tera.html#method.set_report_all_syntax_errors)
+    pub fn report_all_syntax_errors(mut self, report: bool) -> Self {
+        self.tera.set_report_all_syntax_errors(report);
+        self
+    }
+
+    /// See [Tera::set_remove_whitespace_between_tags](struct.Tera.html#method.set_remove_whitespace_between_tags)
+    pub fn remove_whitespace_between_tags(mut self, remove: bool) -> Self {
+        self.tera.set_remove_whitespace_between_tags(remove);
+        self
+    }
+
+    /// See [Tera::set_django_syntax](struct.Tera.html#method.set_django_syntax)
+    pub fn django_syntax(mut self, enabled: bool) -> Self {
+        self.tera.set_django_syntax(enabled);
+        self
+    }
+
+    /// See [Tera::liquid_syntax_on](struct.Tera.html#method.liquid_syntax_on)
+    pub fn liquid_syntax_on(mut self, suffixes: Vec<&'static str>) -> Self {
+        self.tera.liquid_syntax_on(suffixes);
+        self
+    }
+
+    /// See [Tera::set_out_of_bounds](struct.Tera.html#method.set_out_of_bounds)
+    pub fn out_of_bounds(mut self, out_of_bounds: OutOfBounds) -> Self {
+        self.tera.set_out_of_bounds(out_of_bounds);
+        self
+    }
+
+    /// See [Tera::set_minify_html](struct.Tera.html#method.set_minify_html)
+    pub fn minify_html(mut self, minify: bool) -> Self {
+        self.tera.set_minify_html(minify);
+        self
+    }
+
+    /// See [Tera::set_check_callables](struct.Tera.html#method.set_check_callables)
+    pub fn check_callables(mut self, check: bool) -> Self {
+        self.tera.set_check_callables(check);
+        self
+    }
+
+    /// See [Tera::register_filter](struct.Tera.html#method.register_filter)
+    pub fn filter<F: Filter + 'static>(mut self, name: &str, filter: F) -> Self {
+        self.tera.register_filter(name, filter);
+        self
+    }
+
+    /// See [Tera::register_tester](struct.Tera.html#method.register_tester)
+    pub fn tester<T: Test + 'static>(mut self, name: &str, tester: T) -> Self {
+        self.tera.register_tester(name, tester);
+        self
+    }
+
+    /// See [Tera::register_function](struct.Tera.html#method.register_function)
+    pub fn function<F: Function + 'static>(mut self, name: &str, function: F) -> Self {
+        self.tera.register_function(name, function);
+        self
+    }
+
+    /// See [Tera::register_tag](struct.Tera.html#method.register_tag)
+    pub fn tag<T: Tag + 'static>(mut self, name: &str, tag: T) -> Self {
+        self.tera.register_tag(name, tag);
+        self
+    }
+
+    /// See [Tera::set_translator](struct.Tera.html#method.set_translator)
+    pub fn translator<T: Translator + 'static>(mut self, translator: T) -> Self {
+        self.tera.set_translator(translator);
+        self
+    }
+
+    /// See [Tera::set_url_resolver](struct.Tera.html#method.set_url_resolver)
+    pub fn url_resolver<R: UrlResolver + 'static>(mut self, resolver: R, strict: bool) -> Self {
+        self.tera.set_url_resolver(resolver, strict);
+        self
+    }
+
+    /// See [Tera::set_asset_resolver](struct.Tera.html#method.set_asset_resolver)
+    pub fn asset_resolver<R: AssetResolver + 'static>(mut self, resolver: R) -> Self {
+        self.tera.set_asset_resolver(resolver);
+        self
+    }
+
+    /// See [Tera::set_render_hooks](struct.Tera.html#method.set_render_hooks)
+    pub fn render_hooks<H: RenderHooks + 'static>(mut self, hooks: H) -> Self {
+        self.tera.set_render_hooks(hooks);
+        self
+    }
+
+    /// See [Tera::register_pre_processor](struct.Tera.html#method.register_pre_processor)
+    pub fn pre_processor<P: PreProcessor + 'static>(mut self, processor: P) -> Self {
+        self.tera.register_pre_processor(processor);
+        self
+    }
+
+    /// See [Tera::register_post_processor](struct.Tera.html#method.register_post_processor)
+    pub fn post_processor<P: PostProcessor + 'static>(mut self, processor: P) -> Self {
+        self.tera.register_post_processor(processor);
+        self
+    }
+
+    /// See [Tera::set_fragment_cache](struct.Tera.html#method.set_fragment_cache)
+    pub fn fragment_cache<C: FragmentCache + 'static>(mut self, cache: C) -> Self {
+        self.tera.set_fragment_cache(cache);
+        self
+    }
+
+    /// Loads the templates with the settings given and returns the instance
+    pub fn build(self) -> Result<Tera> {
+        let mut tera = self.tera;
+        if let Some(delimiters) = self.delimiters {
+            tera.set_delimiters(delimiters)?;
+        }
+
+        if !self.globs.is_empty() {
+            tera.load_globs(&self.globs)?;
+        }
+        // Added together so they can depend on each other whatever their order
+        tera.add_raw_templates(self.templates)?;
+        tera.add_template_files(self.files)?;
+
+        if self.strict {
+            tera.check()?;
+        }
+        Ok(tera)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    use crate::context::Context;
+    use crate::errors::Result;
+    use crate::parser::delimiters::Delimiters;
+    use crate::tera::Tera;
+
+    fn shout(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+        Ok(Value::String(format!("{}!", value.as_str().unwrap())))
+    }
+
+    #[test]
+    fn can_build_configured_instance() {
+        let tera = Tera::builder()
+            .glob("examples/basic/templates/**/*")
+            .delimiters(Delimiters {
+                block_start: "<%".to_string(),
+                block_end: "%>".to_string(),
+                ..Delimiters::default()
+            })
+            .autoescape_on(vec![])
+            .filter("shout", shout)
+            .template(
+                "child",
+                "<% extends 'parent' %><% block a %>{{ name | shout }}<% endblock %>",
+            )
+            .template("parent", "<<% block a %><% endblock %>>")
+            .build()
+            .unwrap();
+
+        let mut context = Context::new();
+        context.insert("name", "<Bob>");
+        assert_eq!(tera.render("child", &context).unwrap(), "<<Bob>!>");
+        assert!(tera.get_template("base.html").is_ok());
+    }
+
+    #[test]
+    fn errors_on_invalid_settings_and_templates() {
+        let empty = Delimiters { variable_start: String::new(), ..Delimiters::default() };
+        assert!(Tera::builder().delimiters(empty).build().is_err());
+        assert!(Tera::builder().glob("templates").build().is_err());
+        assert!(Tera::builder().template("a", "{{ a").build().is_err());
+    }
+
+    #[test]
+    fn strict_fails_on_missing_references() {
+        let builder = || Tera::builder().template("page", "{% include 'nav' %}{{ a | shout }}");
+        assert!(builder().build().is_ok());
+
+        let err = builder().strict(true).build().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2 problems found:\n\
+             Template 'page': template `nav` not found\n\
+             Template 'page': filter `shout` not found at line 1, column 20"
+        );
+        assert!(builder().strict(true).filter("shout", shout).template("nav", "").build().is_ok());
+    }
+}
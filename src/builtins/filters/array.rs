@@ -1,11 +1,20 @@
 /// Filters operating on array
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 use crate::context::{get_json_pointer, ValueRender};
 use crate::errors::{Error, Result};
 use crate::filter_utils::{get_sort_strategy_for_type, get_unique_strategy_for_type};
+use crate::utils::quote_csv_field;
 use serde_json::value::{to_value, Map, Value};
 
+#[cfg(feature = "builtins-random")]
+use rand::rngs::StdRng;
+#[cfg(feature = "builtins-random")]
+use rand::seq::SliceRandom;
+#[cfg(feature = "builtins-random")]
+use rand::SeedableRng;
+
 /// Returns the nth value of an array
 /// If the array is empty, returns empty string
 pub fn nth(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
@@ -58,6 +67,49 @@ pub fn join(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     to_value(&rendered.join(&sep)).map_err(Error::json)
 }
 
+/// Joins all values in the array as the fields of a CSV row, quoting them following RFC 4180.
+/// The fields are separated by the `sep` argument, `,` by default.
+pub fn csv_row(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let arr = try_get_value!("csv_row", "value", Vec<Value>, value);
+    let sep = match args.get("sep") {
+        Some(val) => try_get_value!("csv_row", "sep", String, val),
+        None => ",".to_string(),
+    };
+
+    let fields = arr.iter().map(|v| quote_csv_field(&v.render(), &sep)).collect::<Vec<_>>();
+    to_value(fields.join(&sep)).map_err(Error::json)
+}
+
+/// Gets the random number generator of the filter `name`: seeded by its `seed` argument if
+/// there is one, for an output that stays the same from one render to the next
+#[cfg(feature = "builtins-random")]
+fn get_rng(name: &str, args: &HashMap<String, Value>) -> Result<StdRng> {
+    match args.get("seed") {
+        Some(val) => Ok(StdRng::seed_from_u64(try_get_value!(name, "seed", u64, val))),
+        None => Ok(StdRng::from_entropy()),
+    }
+}
+
+/// Returns a random value of the array, picked using the `seed` argument if given
+/// If the array is empty, returns empty string
+#[cfg(feature = "builtins-random")]
+pub fn random_choice(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let arr = try_get_value!("random_choice", "value", Vec<Value>, value);
+    let mut rng = get_rng("random_choice", args)?;
+
+    Ok(arr.choose(&mut rng).cloned().unwrap_or_else(|| to_value("").unwrap()))
+}
+
+/// Returns the values of the array in a random order, shuffled using the `seed` argument if given
+#[cfg(feature = "builtins-random")]
+pub fn shuffle(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let mut arr = try_get_value!("shuffle", "value", Vec<Value>, value);
+    let mut rng = get_rng("shuffle", args)?;
+    arr.shuffle(&mut rng);
+
+    Ok(arr.into())
+}
+
 /// Sorts the array in ascending order.
 /// Use the 'attribute' argument to define a field to sort by.
 pub fn sort(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
@@ -91,6 +143,87 @@ pub fn sort(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     Ok(sorted.into())
 }
 
+/// Returns the numbers of the array the aggregation filter `name` works on: its values or, with
+/// the `attribute` argument, the field of that name in each of them
+fn get_numbers(name: &str, value: &Value, args: &HashMap<String, Value>) -> Result<Vec<Value>> {
+    let arr = try_get_value!(name, "value", Vec<Value>, value);
+    let attribute = match args.get("attribute") {
+        Some(val) => Some(try_get_value!(name, "attribute", String, val)),
+        None => None,
+    };
+
+    let mut numbers = Vec::with_capacity(arr.len());
+    for v in arr {
+        let number = match attribute {
+            Some(ref attribute) => match v.pointer(&get_json_pointer(attribute)) {
+                Some(val) => val.clone(),
+                None => {
+                    return Err(Error::msg(format!(
+                        "attribute '{}' does not reference a field",
+                        attribute
+                    )))
+                }
+            },
+            None => v,
+        };
+        if !number.is_number() {
+            return Err(Error::msg(format!(
+                "Filter `{}` can only aggregate numbers, got `{}`",
+                name, number
+            )));
+        }
+        numbers.push(number);
+    }
+    Ok(numbers)
+}
+
+/// Returns the sum of the numbers of the array, or of their `attribute` field if given.
+/// The sum of integers is an integer, and `0` if the array is empty.
+pub fn sum(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let numbers = get_numbers("sum", value, args)?;
+
+    let int_sum =
+        numbers.iter().try_fold(0i64, |acc, n| n.as_i64().and_then(|n| acc.checked_add(n)));
+    match int_sum {
+        Some(sum) => Ok(to_value(sum).unwrap()),
+        None => Ok(to_value(numbers.iter().filter_map(Value::as_f64).sum::<f64>()).unwrap()),
+    }
+}
+
+/// Returns the average of the numbers of the array, or of their `attribute` field if given
+/// If the array is empty, returns empty string
+pub fn avg(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let numbers = get_numbers("avg", value, args)?;
+    if numbers.is_empty() {
+        return Ok(to_value("").unwrap());
+    }
+
+    let sum = numbers.iter().filter_map(Value::as_f64).sum::<f64>();
+    Ok(to_value(sum / numbers.len() as f64).unwrap())
+}
+
+/// Returns the smallest number of the array, or of their `attribute` field if given
+/// If the array is empty, returns empty string
+pub fn min(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let numbers = get_numbers("min", value, args)?;
+
+    Ok(numbers
+        .into_iter()
+        .min_by(|a, b| a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal))
+        .unwrap_or_else(|| to_value("").unwrap()))
+}
+
+/// Returns the largest number of the array, or of their `attribute` field if given
+/// If the array is empty, returns empty string
+pub fn max(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let numbers = get_numbers("max", value, args)?;
+
+    Ok(numbers
+        .into_iter()
+        .max_by(|a, b| a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal))
+        .unwrap_or_else(|| to_value("").unwrap()))
+}
+
 /// Remove duplicates from an array.
 /// Use the 'attribute' argument to define a field to filter on.
 /// For strings, use the 'case_sensitive' argument (defaults to false) to control the comparison.
@@ -281,6 +414,44 @@ pub fn slice(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     Ok(arr[start..end].into())
 }
 
+/// Pairs each value of the array with the value at the same index in the `with` array, as
+/// `[value, other]`, stopping at the end of the shorter one
+pub fn zip(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let arr = try_get_value!("zip", "value", Vec<Value>, value);
+    let with = match args.get("with") {
+        Some(val) => try_get_value!("zip", "with", Vec<Value>, val),
+        None => return Err(Error::msg("The `zip` filter has to have a `with` argument")),
+    };
+
+    let pairs =
+        arr.into_iter().zip(with).map(|(a, b)| Value::Array(vec![a, b])).collect::<Vec<_>>();
+    Ok(pairs.into())
+}
+
+/// Appends the values of `arr` to `flat`, replacing the arrays by their values up to `depth` levels
+fn flatten_into(flat: &mut Vec<Value>, arr: Vec<Value>, depth: usize) {
+    for v in arr {
+        match v {
+            Value::Array(inner) if depth > 0 => flatten_into(flat, inner, depth - 1),
+            _ => flat.push(v),
+        }
+    }
+}
+
+/// Flattens the arrays nested in the array by one level.
+/// Use the `depth` argument to flatten more levels.
+pub fn flatten(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let arr = try_get_value!("flatten", "value", Vec<Value>, value);
+    let depth = match args.get("depth") {
+        Some(val) => try_get_value!("flatten", "depth", usize, val),
+        None => 1,
+    };
+
+    let mut flat = Vec::with_capacity(arr.len());
+    flatten_into(&mut flat, arr, depth);
+    Ok(flat.into())
+}
+
 /// Concat the array with another one if the `with` parameter is an array or
 /// just append it otherwise
 pub fn concat(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
@@ -315,6 +486,119 @@ mod tests {
     use serde_json::value::{to_value, Value};
     use std::collections::HashMap;
 
+    #[cfg(feature = "builtins-random")]
+    #[test]
+    fn test_random_choice() {
+        let input = to_value(vec![1, 2, 3, 4, 5]).unwrap();
+        let mut args = HashMap::new();
+        let result = random_choice(&input, &args).unwrap();
+        assert!(input.as_array().unwrap().contains(&result));
+
+        args.insert("seed".to_string(), to_value(42).unwrap());
+        let result = random_choice(&input, &args).unwrap();
+        for _ in 0..10 {
+            assert_eq!(random_choice(&input, &args).unwrap(), result);
+        }
+    }
+
+    #[cfg(feature = "builtins-random")]
+    #[test]
+    fn test_random_choice_empty() {
+        let result = random_choice(&to_value(Vec::<Value>::new()).unwrap(), &HashMap::new());
+        assert_eq!(result.unwrap(), to_value("").unwrap());
+    }
+
+    #[cfg(feature = "builtins-random")]
+    #[test]
+    fn test_shuffle() {
+        let input: Vec<_> = (0..20).collect();
+        let mut args = HashMap::new();
+        let result = shuffle(&to_value(&input).unwrap(), &args).unwrap();
+        let mut values: Vec<i32> = serde_json::from_value(result).unwrap();
+        values.sort_unstable();
+        assert_eq!(values, input);
+
+        args.insert("seed".to_string(), to_value(42).unwrap());
+        let result = shuffle(&to_value(&input).unwrap(), &args).unwrap();
+        assert_ne!(result, to_value(&input).unwrap());
+        assert_eq!(shuffle(&to_value(&input).unwrap(), &args).unwrap(), result);
+    }
+
+    #[test]
+    fn test_sum() {
+        let result = sum(&json!([1, 2, 3]), &HashMap::new());
+        assert_eq!(result.unwrap(), json!(6));
+        let result = sum(&json!([1, 2.5, -3]), &HashMap::new());
+        assert_eq!(result.unwrap(), json!(0.5));
+        let result = sum(&json!([]), &HashMap::new());
+        assert_eq!(result.unwrap(), json!(0));
+
+        let mut args = HashMap::new();
+        args.insert("attribute".to_string(), to_value("price").unwrap());
+        let lines = json!([{"price": 10.5}, {"price": 4}, {"price": 0.25}]);
+        assert_eq!(sum(&lines, &args).unwrap(), json!(14.75));
+    }
+
+    #[test]
+    fn test_avg() {
+        let result = avg(&json!([1, 2, 3, 4]), &HashMap::new());
+        assert_eq!(result.unwrap(), json!(2.5));
+        let result = avg(&json!([]), &HashMap::new());
+        assert_eq!(result.unwrap(), json!(""));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let input = json!([3, -1.5, 8, 2]);
+        assert_eq!(min(&input, &HashMap::new()).unwrap(), json!(-1.5));
+        assert_eq!(max(&input, &HashMap::new()).unwrap(), json!(8));
+        assert_eq!(min(&json!([]), &HashMap::new()).unwrap(), json!(""));
+        assert_eq!(max(&json!([]), &HashMap::new()).unwrap(), json!(""));
+
+        let mut args = HashMap::new();
+        args.insert("attribute".to_string(), to_value("user.age").unwrap());
+        let people = json!([{"user": {"age": 40}}, {"user": {"age": 18}}, {"user": {"age": 33}}]);
+        assert_eq!(min(&people, &args).unwrap(), json!(18));
+        assert_eq!(max(&people, &args).unwrap(), json!(40));
+    }
+
+    #[test]
+    fn test_aggregation_errors() {
+        let result = sum(&json!([1, "2"]), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `sum` can only aggregate numbers, got `\"2\"`"
+        );
+
+        let mut args = HashMap::new();
+        args.insert("attribute".to_string(), to_value("price").unwrap());
+        let result = max(&json!([{"price": 1}, {"cost": 2}]), &args);
+        assert_eq!(result.unwrap_err().to_string(), "attribute 'price' does not reference a field");
+    }
+
+    #[test]
+    fn test_flatten() {
+        let input = json!([[1, 2], 3, [[4, 5], [6]], []]);
+        let mut args = HashMap::new();
+        assert_eq!(flatten(&input, &args).unwrap(), json!([1, 2, 3, [4, 5], [6]]));
+        args.insert("depth".to_string(), to_value(2).unwrap());
+        assert_eq!(flatten(&input, &args).unwrap(), json!([1, 2, 3, 4, 5, 6]));
+        args.insert("depth".to_string(), to_value(0).unwrap());
+        assert_eq!(flatten(&input, &args).unwrap(), input);
+    }
+
+    #[test]
+    fn test_zip() {
+        let mut args = HashMap::new();
+        args.insert("with".to_string(), json!([10, 7, 3]));
+        let result = zip(&json!(["ann", "bob"]), &args);
+        assert_eq!(result.unwrap(), json!([["ann", 10], ["bob", 7]]));
+
+        args.insert("with".to_string(), json!("nope"));
+        assert!(zip(&json!(["ann", "bob"]), &args).is_err());
+        assert!(zip(&json!(["ann", "bob"]), &HashMap::new()).is_err());
+    }
+
     #[test]
     fn test_nth() {
         let mut args = HashMap::new();
@@ -383,6 +667,17 @@ mod tests {
         assert_eq!(result.unwrap(), to_value(&"1.23.4").unwrap());
     }
 
+    #[test]
+    fn test_csv_row() {
+        let result = csv_row(&json!(["a", "b,c", "say \"hi\"", 1, null]), &HashMap::new());
+        assert_eq!(result.unwrap(), to_value(r#"a,"b,c","say ""hi""",1,"#).unwrap());
+
+        let mut args = HashMap::new();
+        args.insert("sep".to_owned(), to_value(";").unwrap());
+        let result = csv_row(&json!(["b,c", "d;e"]), &args);
+        assert_eq!(result.unwrap(), to_value(r#"b,c;"d;e""#).unwrap());
+    }
+
     #[test]
     fn test_join_empty() {
         let v: Vec<Value> = Vec::new();
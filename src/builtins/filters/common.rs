@@ -3,12 +3,12 @@ use std::collections::HashMap;
 use std::iter::FromIterator;
 
 use crate::errors::{Error, Result};
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-date")]
 use chrono::{
     format::{Item, StrftimeItems},
-    DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc,
+    DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc,
 };
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-date")]
 use chrono_tz::Tz;
 use serde_json::value::{to_value, Value};
 use serde_json::{to_string, to_string_pretty};
@@ -56,6 +56,93 @@ pub fn json_encode(value: &Value, args: &HashMap<String, Value>) -> Result<Value
     }
 }
 
+/// Formats any value to be read while debugging: the arrays and objects that don't fit in
+/// `width` characters, 80 by default, get one item per line, indented by two spaces.
+/// Unlike `json_encode`, the keys of objects are only quoted when they aren't identifiers
+/// and the result isn't meant to be parsed.
+pub fn pprint(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let width = match args.get("width") {
+        Some(val) => try_get_value!("pprint", "width", usize, val),
+        None => 80,
+    };
+
+    let mut res = String::new();
+    pprint_value(value, 0, 0, width, &mut res);
+    Ok(Value::String(res))
+}
+
+/// The value on a single line
+fn pprint_inline(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(pprint_inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", pprint_key(k), pprint_inline(v)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn pprint_key(key: &str) -> String {
+    let is_ident = key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if is_ident {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+/// Writes the value starting at `column`, with the items of arrays and objects that don't
+/// fit on the line indented by two more spaces than `indent`
+fn pprint_value(value: &Value, indent: usize, column: usize, width: usize, res: &mut String) {
+    let inline = pprint_inline(value);
+    let is_empty = match value {
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => true,
+    };
+    if is_empty || column + inline.chars().count() <= width {
+        res.push_str(&inline);
+        return;
+    }
+
+    let item_indent = " ".repeat(indent + 2);
+    match value {
+        Value::Array(items) => {
+            res.push_str("[\n");
+            for item in items {
+                res.push_str(&item_indent);
+                pprint_value(item, indent + 2, indent + 2, width, res);
+                res.push_str(",\n");
+            }
+            res.push_str(&" ".repeat(indent));
+            res.push(']');
+        }
+        Value::Object(map) => {
+            res.push_str("{\n");
+            for (key, item) in map {
+                let key = pprint_key(key);
+                res.push_str(&item_indent);
+                res.push_str(&key);
+                res.push_str(": ");
+                let column = indent + 2 + key.chars().count() + 2;
+                pprint_value(item, indent + 2, column, width, res);
+                res.push_str(",\n");
+            }
+            res.push_str(&" ".repeat(indent));
+            res.push('}');
+        }
+        _ => unreachable!(),
+    }
+}
+
 /// Returns a formatted time according to the given `format` argument.
 /// `format` defaults to the ISO 8601 `YYYY-MM-DD` format.
 ///
@@ -64,7 +151,7 @@ pub fn json_encode(value: &Value, args: &HashMap<String, Value>) -> Result<Value
 ///
 /// a full reference for the time formatting syntax is available
 /// on [chrono docs](https://lifthrasiir.github.io/rust-chrono/chrono/format/strftime/index.html)
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-date")]
 pub fn date(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     let format = match args.get("format") {
         Some(val) => try_get_value!("date", "format", String, val),
@@ -96,7 +183,13 @@ pub fn date(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
 
     let formatted = match value {
         Value::Number(n) => match n.as_i64() {
-            Some(i) => NaiveDateTime::from_timestamp(i, 0).format(&format),
+            Some(i) => {
+                let datetime = NaiveDateTime::from_timestamp(i, 0);
+                match timezone {
+                    Some(timezone) => timezone.from_utc_datetime(&datetime).format(&format),
+                    None => datetime.format(&format),
+                }
+            }
             None => return Err(Error::msg(format!("Filter `date` was invoked on a float: {}", n))),
         },
         Value::String(s) => {
@@ -140,17 +233,247 @@ pub fn date(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     to_value(&formatted.to_string()).map_err(Error::json)
 }
 
+/// The number of seconds since epoch of a timestamp or of a date string, the datetimes without
+/// an offset and the dates being in UTC
+#[cfg(feature = "builtins-date")]
+fn to_timestamp(filter: &str, arg: &str, value: &Value) -> Result<i64> {
+    let timestamp = match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) if s.contains('T') => match s.parse::<DateTime<FixedOffset>>() {
+            Ok(val) => Some(val.timestamp()),
+            Err(_) => {
+                s.parse::<NaiveDateTime>().ok().map(|val| Utc.from_utc_datetime(&val).timestamp())
+            }
+        },
+        Value::String(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|val| val.and_hms_opt(0, 0, 0))
+            .map(|val| Utc.from_utc_datetime(&val).timestamp()),
+        _ => None,
+    };
+
+    timestamp.ok_or_else(|| {
+        Error::msg(format!(
+            "Filter `{}` received an incorrect value for arg `{}`: got `{}` but expected an \
+             integer timestamp or a rfc3339, naive datetime or YYYY-MM-DD date string",
+            filter, arg, value
+        ))
+    })
+}
+
+/// Returns how long ago the given date was, like `3 hours ago`, or how long until it is, like
+/// `in 2 days`, in the largest unit of time.
+///
+/// Input can be a timestamp or a date string, like for the `date` filter. The time it is
+/// compared to is the `now` argument, in the same formats, or the current time by default.
+#[cfg(feature = "builtins-date")]
+pub fn timesince(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let date = to_timestamp("timesince", "value", value)?;
+    let now = match args.get("now") {
+        Some(val) => to_timestamp("timesince", "now", val)?,
+        None => Utc::now().timestamp(),
+    };
+
+    let seconds = (now - date).abs();
+    if seconds < 60 {
+        return Ok(to_value("just now").unwrap());
+    }
+    let (count, unit) = match seconds {
+        s if s < 3600 => (s / 60, "minute"),
+        s if s < 86400 => (s / 3600, "hour"),
+        s if s < 30 * 86400 => (s / 86400, "day"),
+        s if s < 365 * 86400 => (s / (30 * 86400), "month"),
+        s => (s / (365 * 86400), "year"),
+    };
+    let duration = format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" });
+
+    let res = if now >= date { format!("{} ago", duration) } else { format!("in {}", duration) };
+    Ok(to_value(res).unwrap())
+}
+
 // Returns the given value as a string.
 pub fn as_str(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     to_value(&value.render()).map_err(Error::json)
 }
 
+/// Formats the value according to the printf-style placeholder of the `spec` argument,
+/// such as `%.2f` or `Total: %08d`.
+/// A placeholder is `%[flags][width][.precision]type`, with the flags `-` (align left),
+/// `0` (pad numbers with zeros), `+` (always print the sign) and ` ` (space before positive
+/// numbers) and the types `s`, `d`/`i`, `f`, `x`, `X`, `o` and `b`. Use `%%` for a literal `%`.
+pub fn format(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let spec = match args.get("spec") {
+        Some(val) => try_get_value!("format", "spec", String, val),
+        None => return Err(Error::msg("Filter `format` expected an arg called `spec`")),
+    };
+
+    let mut res = String::with_capacity(spec.len());
+    let mut formatted = false;
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            res.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            res.push('%');
+            continue;
+        }
+        if formatted {
+            return Err(Error::msg(format!(
+                "Filter `format` received the spec `{}` but it can only contain one placeholder",
+                spec
+            )));
+        }
+
+        let mut placeholder = Placeholder::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => placeholder.left_align = true,
+                '0' => placeholder.zero_pad = true,
+                '+' => placeholder.sign = "+",
+                ' ' if placeholder.sign.is_empty() => placeholder.sign = " ",
+                ' ' => (),
+                _ => break,
+            }
+            chars.next();
+        }
+        placeholder.width = take_number(&mut chars).unwrap_or(0);
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            placeholder.precision = Some(take_number(&mut chars).unwrap_or(0));
+        }
+        match chars.next() {
+            Some(kind) => res.push_str(&placeholder.format(kind, value)?),
+            None => {
+                return Err(Error::msg(format!(
+                "Filter `format` received the spec `{}` which ends with an incomplete placeholder",
+                spec
+            )))
+            }
+        }
+        formatted = true;
+    }
+
+    if !formatted {
+        return Err(Error::msg(format!(
+            "Filter `format` received the spec `{}` but it doesn't contain a placeholder",
+            spec
+        )));
+    }
+    Ok(Value::String(res))
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut num = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        num = Some(num.unwrap_or(0) * 10 + digit as usize);
+        chars.next();
+    }
+    num
+}
+
+/// A `%` placeholder of the `format` filter
+#[derive(Default)]
+struct Placeholder {
+    left_align: bool,
+    zero_pad: bool,
+    /// What to print before positive numbers: nothing, `+` or a space
+    sign: &'static str,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Placeholder {
+    fn format(&self, kind: char, value: &Value) -> Result<String> {
+        let number = || {
+            value.as_f64().ok_or_else(|| {
+                Error::msg(format!(
+                    "Filter `format` received `{}` for the placeholder `%{}`, \
+                     but it can only be used with numbers",
+                    value, kind
+                ))
+            })
+        };
+        // Integers are kept as i64/u64 when possible so big ones are printed exactly
+        let integer = |radix: u32| -> Result<(bool, String)> {
+            let (negative, abs) = match (value.as_i64(), value.as_u64()) {
+                (Some(i), _) if i < 0 => (true, (-i128::from(i)) as u128),
+                (Some(i), _) => (false, i as u128),
+                (_, Some(u)) => (false, u128::from(u)),
+                _ => {
+                    let f = number()?.trunc();
+                    (f < 0.0, f.abs() as u128)
+                }
+            };
+            let digits = match radix {
+                2 => format!("{:b}", abs),
+                8 => format!("{:o}", abs),
+                16 => format!("{:x}", abs),
+                _ => abs.to_string(),
+            };
+            Ok((negative, digits))
+        };
+
+        let (negative, body) = match kind {
+            's' => {
+                let s = value.render();
+                let s = match self.precision {
+                    Some(p) => s.chars().take(p).collect(),
+                    None => s.into_owned(),
+                };
+                return Ok(self.pad("", s, false));
+            }
+            'd' | 'i' => integer(10)?,
+            'o' => integer(8)?,
+            'b' => integer(2)?,
+            'x' => integer(16)?,
+            'X' => {
+                let (negative, digits) = integer(16)?;
+                (negative, digits.to_uppercase())
+            }
+            'f' => {
+                let f = number()?;
+                (
+                    f.is_sign_negative() && f != 0.0,
+                    format!("{:.*}", self.precision.unwrap_or(6), f.abs()),
+                )
+            }
+            _ => {
+                return Err(Error::msg(format!(
+                    "Filter `format` received the unknown placeholder type `{}`",
+                    kind
+                )))
+            }
+        };
+
+        Ok(self.pad(if negative { "-" } else { self.sign }, body, true))
+    }
+
+    fn pad(&self, sign: &str, body: String, is_number: bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        if len >= self.width {
+            return format!("{}{}", sign, body);
+        }
+        let padding = self.width - len;
+        if self.left_align {
+            format!("{}{}{}", sign, body, " ".repeat(padding))
+        } else if self.zero_pad && is_number {
+            format!("{}{}{}", sign, "0".repeat(padding), body)
+        } else {
+            format!("{}{}{}", " ".repeat(padding), sign, body)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     use chrono::{DateTime, Local};
     use serde_json;
+    use serde_json::json;
     use serde_json::value::to_value;
     use std::collections::HashMap;
 
@@ -229,7 +552,7 @@ mod tests {
         );
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_default() {
         let args = HashMap::new();
@@ -238,7 +561,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("2016-12-26").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_custom_format() {
         let mut args = HashMap::new();
@@ -250,7 +573,7 @@ mod tests {
 
     // https://zola.discourse.group/t/can-i-generate-a-random-number-within-a-range/238?u=keats
     // https://github.com/chronotope/chrono/issues/47
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_errors_on_incorrect_format() {
         let mut args = HashMap::new();
@@ -259,7 +582,7 @@ mod tests {
         assert!(result.is_err());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_rfc3339() {
         let args = HashMap::new();
@@ -269,7 +592,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value(dt.format("%Y-%m-%d").to_string()).unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_rfc3339_preserves_timezone() {
         let mut args = HashMap::new();
@@ -279,7 +602,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("1996-12-19 -0800").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_yyyy_mm_dd() {
         let mut args = HashMap::new();
@@ -289,7 +612,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("Sun, 05 Mar 2017 00:00:00 +0000").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_from_naive_datetime() {
         let mut args = HashMap::new();
@@ -301,7 +624,7 @@ mod tests {
     }
 
     // https://github.com/getzola/zola/issues/1279
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_format_doesnt_panic() {
         let mut args = HashMap::new();
@@ -310,7 +633,7 @@ mod tests {
         assert!(result.is_ok());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_with_timezone() {
         let mut args = HashMap::new();
@@ -320,7 +643,45 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("2019-09-18").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
+    #[test]
+    fn date_timestamp_with_timezone() {
+        let mut args = HashMap::new();
+        args.insert("format".to_string(), to_value("%Y-%m-%d %H:%M").unwrap());
+        args.insert("timezone".to_string(), to_value("Europe/Paris").unwrap());
+        let result = date(&to_value(1482720453).unwrap(), &args);
+        assert_eq!(result.unwrap(), to_value("2016-12-26 03:47").unwrap());
+    }
+
+    #[cfg(feature = "builtins-date")]
+    #[test]
+    fn test_timesince() {
+        let tests: Vec<(Value, &str)> = vec![
+            (to_value(1_600_000_000).unwrap(), "just now"),
+            (to_value(1_599_999_941).unwrap(), "just now"),
+            (to_value(1_599_999_940).unwrap(), "1 minute ago"),
+            (to_value(1_600_000_000 - 3 * 3600 - 59).unwrap(), "3 hours ago"),
+            (to_value("2020-09-12T00:00:00+00:00").unwrap(), "1 day ago"),
+            (to_value("2020-07-01T12:00:00").unwrap(), "2 months ago"),
+            (to_value("2017-01-01").unwrap(), "3 years ago"),
+            (to_value(1_600_000_000 + 2 * 86400).unwrap(), "in 2 days"),
+        ];
+        let mut args = HashMap::new();
+        args.insert("now".to_string(), to_value("2020-09-13T12:26:40Z").unwrap());
+        for (input, expected) in tests {
+            let result = timesince(&input, &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+
+        let result = timesince(&to_value(true).unwrap(), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `timesince` received an incorrect value for arg `value`: got `true` but \
+             expected an integer timestamp or a rfc3339, naive datetime or YYYY-MM-DD date string"
+        );
+    }
+
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn date_with_invalid_timezone() {
         let mut args = HashMap::new();
@@ -351,4 +712,87 @@ mod tests {
             to_value("{\n  \"key\": [\n    \"value1\",\n    2,\n    true\n  ]\n}").unwrap()
         );
     }
+
+    #[test]
+    fn test_pprint() {
+        // Keys already sorted, to get the same order with and without `preserve_order`
+        let value = json!({"n": null, "name": "Bob", "tags": ["a", "b"], "the key": {}});
+        let result = pprint(&value, &HashMap::new());
+        assert_eq!(
+            result.unwrap(),
+            to_value(r#"{n: null, name: "Bob", tags: ["a", "b"], "the key": {}}"#).unwrap()
+        );
+
+        let mut args = HashMap::new();
+        args.insert("width".to_string(), to_value(20).unwrap());
+        let result = pprint(&value, &args);
+        assert_eq!(
+            result.unwrap(),
+            to_value(
+                "{\n  n: null,\n  name: \"Bob\",\n  tags: [\"a\", \"b\"],\n  \"the key\": {},\n}"
+            )
+            .unwrap()
+        );
+
+        args.insert("width".to_string(), to_value(10).unwrap());
+        let result = pprint(&json!([[1, 2], "some text"]), &args);
+        assert_eq!(result.unwrap(), to_value("[\n  [1, 2],\n  \"some text\",\n]").unwrap());
+    }
+
+    #[test]
+    fn format_numbers() {
+        let tests: Vec<(Value, &str, &str)> = vec![
+            (to_value(3.14159).unwrap(), "%.2f", "3.14"),
+            (to_value(2).unwrap(), "%.2f", "2.00"),
+            (to_value(-2.5).unwrap(), "%08.3f", "-002.500"),
+            (to_value(2.5).unwrap(), "%+f", "+2.500000"),
+            (to_value(42).unwrap(), "%5d", "   42"),
+            (to_value(42).unwrap(), "%-5d|", "42   |"),
+            (to_value(-42).unwrap(), "%05i", "-0042"),
+            (to_value(42).unwrap(), "% d", " 42"),
+            (to_value(42.9).unwrap(), "%d", "42"),
+            (to_value(u64::max_value()).unwrap(), "%d", "18446744073709551615"),
+            (to_value(255).unwrap(), "%x %%", "ff %"),
+            (to_value(255).unwrap(), "#%06X", "#0000FF"),
+            (to_value(8).unwrap(), "%o", "10"),
+            (to_value(5).unwrap(), "%08b", "00000101"),
+            (to_value(12).unwrap(), "Total: %d items", "Total: 12 items"),
+        ];
+        for (value, spec, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("spec".to_string(), to_value(spec).unwrap());
+            assert_eq!(format(&value, &args).unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn format_strings() {
+        let tests =
+            vec![("%s", "Bob"), ("[%5s]", "[  Bob]"), ("[%-5s]", "[Bob  ]"), ("%.1s.", "B.")];
+        for (spec, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("spec".to_string(), to_value(spec).unwrap());
+            assert_eq!(
+                format(&to_value("Bob").unwrap(), &args).unwrap(),
+                to_value(expected).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn format_errors() {
+        for spec in &["no placeholder", "%d %d", "%5", "%y"] {
+            let mut args = HashMap::new();
+            args.insert("spec".to_string(), to_value(spec).unwrap());
+            assert!(format(&to_value(1).unwrap(), &args).is_err());
+        }
+        let mut args = HashMap::new();
+        args.insert("spec".to_string(), to_value("%d").unwrap());
+        let result = format(&to_value("Bob").unwrap(), &args);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `format` received `\"Bob\"` for the placeholder `%d`, but it can only be used with numbers"
+        );
+        assert!(format(&to_value(1).unwrap(), &HashMap::new()).is_err());
+    }
 }
@@ -1,7 +1,7 @@
 /// Filters operating on numbers
 use std::collections::HashMap;
 
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-number")]
 use humansize::{file_size_opts, FileSize};
 use serde_json::value::{to_value, Value};
 
@@ -59,8 +59,168 @@ pub fn round(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     }
 }
 
+/// How numbers are written in a locale known by `num_format` and `currency`
+struct NumberLocale {
+    name: &'static str,
+    /// The thousands separator
+    sep: &'static str,
+    /// The decimal mark
+    decimal: &'static str,
+    /// Whether the currency symbol goes after the amount, as `1.234,56 €`, or before it, as `€1,234.56`
+    symbol_after: bool,
+}
+
+const NUMBER_LOCALES: &[NumberLocale] = &[
+    NumberLocale { name: "en", sep: ",", decimal: ".", symbol_after: false },
+    NumberLocale { name: "de", sep: ".", decimal: ",", symbol_after: true },
+    NumberLocale { name: "de-CH", sep: "'", decimal: ".", symbol_after: false },
+    NumberLocale { name: "es", sep: ".", decimal: ",", symbol_after: true },
+    NumberLocale { name: "fr", sep: "\u{202f}", decimal: ",", symbol_after: true },
+    NumberLocale { name: "it", sep: ".", decimal: ",", symbol_after: true },
+    NumberLocale { name: "ja", sep: ",", decimal: ".", symbol_after: false },
+    NumberLocale { name: "nl", sep: ".", decimal: ",", symbol_after: false },
+    NumberLocale { name: "pl", sep: "\u{a0}", decimal: ",", symbol_after: true },
+    NumberLocale { name: "pt", sep: ".", decimal: ",", symbol_after: true },
+    NumberLocale { name: "ru", sep: "\u{a0}", decimal: ",", symbol_after: true },
+    NumberLocale { name: "sv", sep: "\u{a0}", decimal: ",", symbol_after: true },
+    NumberLocale { name: "zh", sep: ",", decimal: ".", symbol_after: false },
+];
+
+/// Returns how numbers are written in `locale`, falling back to its language if the region
+/// isn't known: `fr_CA` and `fr-CA` both use `fr`
+fn find_number_locale(locale: &str) -> Option<&'static NumberLocale> {
+    let locale = locale.replace('_', "-");
+    let language = locale.split('-').next().unwrap_or_default();
+    NUMBER_LOCALES
+        .iter()
+        .find(|l| l.name.eq_ignore_ascii_case(&locale))
+        .or_else(|| NUMBER_LOCALES.iter().find(|l| l.name.eq_ignore_ascii_case(language)))
+}
+
+/// Gets the `locale` argument of the filter `name`, `en` by default
+fn get_number_locale(name: &str, args: &HashMap<String, Value>) -> Result<&'static NumberLocale> {
+    let locale = match args.get("locale") {
+        Some(val) => try_get_value!(name, "locale", String, val),
+        None => "en".to_string(),
+    };
+    find_number_locale(&locale).ok_or_else(|| {
+        Error::msg(format!("Filter `{}` received an unknown locale: `{}`", name, locale))
+    })
+}
+
+/// Adds `sep` between each group of 3 digits of the integer part of `number`, a number written
+/// as `-1234567.5`, and replaces its `.` by `decimal`
+pub(crate) fn group_digits(number: &str, sep: &str, decimal: &str) -> String {
+    let (sign, number) = if number.starts_with('-') { ("-", &number[1..]) } else { ("", number) };
+    let (integer, fraction) = match number.find('.') {
+        Some(i) => (&number[..i], Some(&number[i + 1..])),
+        None => (number, None),
+    };
+
+    let mut res = String::with_capacity(number.len() + integer.len() / 3 * sep.len() + 1);
+    res.push_str(sign);
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            res.push_str(sep);
+        }
+        res.push(c);
+    }
+    if let Some(fraction) = fraction {
+        res.push_str(decimal);
+        res.push_str(fraction);
+    }
+    res
+}
+
+/// Returns the number with its thousands grouped and the decimal mark of the `locale` given,
+/// `en` by default: `1234567.5` is `1.234.567,5` with `locale="de"`.
+/// `sep` and `decimal` override the separators of the locale and `precision` sets the number
+/// of decimals, which are otherwise left as they are.
+pub fn num_format(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let num = try_get_value!("num_format", "value", f64, value);
+    let locale = get_number_locale("num_format", args)?;
+    let sep = match args.get("sep") {
+        Some(val) => try_get_value!("num_format", "sep", String, val),
+        None => locale.sep.to_string(),
+    };
+    let decimal = match args.get("decimal") {
+        Some(val) => try_get_value!("num_format", "decimal", String, val),
+        None => locale.decimal.to_string(),
+    };
+    let number = match args.get("precision") {
+        Some(val) => {
+            let precision = try_get_value!("num_format", "precision", usize, val);
+            format!("{:.*}", precision, num)
+        }
+        // Integers are kept as they are rather than going through f64
+        None => value.to_string(),
+    };
+
+    Ok(to_value(group_digits(&number, &sep, &decimal)).unwrap())
+}
+
+/// The symbol and number of decimals of the currencies known by `currency`
+const CURRENCIES: &[(&str, &str, usize)] = &[
+    ("AUD", "A$", 2),
+    ("BRL", "R$", 2),
+    ("CAD", "CA$", 2),
+    ("CHF", "CHF", 2),
+    ("CNY", "CN¥", 2),
+    ("EUR", "€", 2),
+    ("GBP", "£", 2),
+    ("INR", "₹", 2),
+    ("JPY", "¥", 0),
+    ("KRW", "₩", 0),
+    ("PLN", "zł", 2),
+    ("RUB", "₽", 2),
+    ("SEK", "kr", 2),
+    ("USD", "$", 2),
+];
+
+/// Returns the amount rounded to the decimals of the currency `code` and written with its
+/// symbol, as `€1,234.50`, using the separators and symbol placement of the `locale` given,
+/// `en` by default: `1.234,50 €` with `locale="de"`.
+/// A currency that isn't known uses its code as symbol and 2 decimals.
+/// Negative amounts start with a `-` or, if `accounting` is `true`, are wrapped in parentheses.
+pub fn currency(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let num = try_get_value!("currency", "value", f64, value);
+    let code = match args.get("code") {
+        Some(val) => try_get_value!("currency", "code", String, val).to_uppercase(),
+        None => return Err(Error::msg("Filter `currency` expected an arg called `code`")),
+    };
+    let locale = get_number_locale("currency", args)?;
+    let accounting = match args.get("accounting") {
+        Some(val) => try_get_value!("currency", "accounting", bool, val),
+        None => false,
+    };
+    let (symbol, decimals) = match CURRENCIES.iter().find(|(c, _, _)| *c == code) {
+        Some(&(_, symbol, decimals)) => (symbol, decimals),
+        None => (code.as_str(), 2),
+    };
+
+    let multiplier = 10.0_f64.powi(decimals as i32);
+    let amount = format!("{:.*}", decimals, (num.abs() * multiplier).round() / multiplier);
+    // Something like -0.001 is rounded to 0.00, which isn't negative anymore
+    let negative = num < 0.0 && amount.bytes().any(|b| b.is_ascii_digit() && b != b'0');
+    let amount = group_digits(&amount, locale.sep, locale.decimal);
+    let amount = if locale.symbol_after {
+        format!("{}\u{a0}{}", amount, symbol)
+    } else if symbol.chars().all(char::is_alphabetic) {
+        format!("{}\u{a0}{}", symbol, amount)
+    } else {
+        format!("{}{}", symbol, amount)
+    };
+
+    let res = match (negative, accounting) {
+        (false, _) => amount,
+        (true, false) => format!("-{}", amount),
+        (true, true) => format!("({})", amount),
+    };
+    Ok(to_value(res).unwrap())
+}
+
 /// Returns a human-readable file size (i.e. '110 MB') from an integer
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-number")]
 pub fn filesizeformat(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let num = try_get_value!("filesizeformat", "value", usize, value);
     num.file_size(file_size_opts::CONVENTIONAL)
@@ -173,7 +333,116 @@ mod tests {
         assert_eq!(result.unwrap(), to_value(2.9).unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[test]
+    fn test_num_format() {
+        let tests: Vec<(Value, Vec<(&str, Value)>, &str)> = vec![
+            (to_value(1234567.5).unwrap(), vec![], "1,234,567.5"),
+            (to_value(-1234567).unwrap(), vec![], "-1,234,567"),
+            (to_value(123).unwrap(), vec![], "123"),
+            (
+                to_value(1234567.5).unwrap(),
+                vec![("locale", to_value("de").unwrap())],
+                "1.234.567,5",
+            ),
+            (
+                to_value(1234567.5).unwrap(),
+                vec![("locale", to_value("fr").unwrap())],
+                "1\u{202f}234\u{202f}567,5",
+            ),
+            (
+                to_value(1234567.5).unwrap(),
+                vec![("locale", to_value("de_CH").unwrap())],
+                "1'234'567.5",
+            ),
+            (
+                to_value(1234567.5).unwrap(),
+                vec![("locale", to_value("de-AT").unwrap())],
+                "1.234.567,5",
+            ),
+            (
+                to_value(1234567.5).unwrap(),
+                vec![("locale", to_value("de").unwrap()), ("sep", to_value(" ").unwrap())],
+                "1 234 567,5",
+            ),
+            (
+                to_value(1234567.5).unwrap(),
+                vec![("sep", to_value("_").unwrap()), ("decimal", to_value(",").unwrap())],
+                "1_234_567,5",
+            ),
+            (to_value(1234.5678).unwrap(), vec![("precision", to_value(2).unwrap())], "1,234.57"),
+            (to_value(999999.5).unwrap(), vec![("precision", to_value(0).unwrap())], "1,000,000"),
+        ];
+        for (value, arguments, expected) in tests {
+            let mut args = HashMap::new();
+            for (name, arg) in arguments {
+                args.insert(name.to_string(), arg);
+            }
+            let result = num_format(&value, &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_num_format_unknown_locale() {
+        let mut args = HashMap::new();
+        args.insert("locale".to_string(), to_value("xx").unwrap());
+        let result = num_format(&to_value(1234).unwrap(), &args);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `num_format` received an unknown locale: `xx`"
+        );
+    }
+
+    #[test]
+    fn test_currency() {
+        let tests: Vec<(Value, Vec<(&str, Value)>, &str)> = vec![
+            (to_value(1234.5).unwrap(), vec![("code", to_value("USD").unwrap())], "$1,234.50"),
+            (to_value(1234.567).unwrap(), vec![("code", to_value("eur").unwrap())], "€1,234.57"),
+            (to_value(-1234.5).unwrap(), vec![("code", to_value("GBP").unwrap())], "-£1,234.50"),
+            (to_value(1234.5).unwrap(), vec![("code", to_value("JPY").unwrap())], "¥1,235"),
+            (
+                to_value(1234.5).unwrap(),
+                vec![("code", to_value("CHF").unwrap())],
+                "CHF\u{a0}1,234.50",
+            ),
+            (to_value(12).unwrap(), vec![("code", to_value("XYZ").unwrap())], "XYZ\u{a0}12.00"),
+            (to_value(-0.001).unwrap(), vec![("code", to_value("USD").unwrap())], "$0.00"),
+            (
+                to_value(-1234.5).unwrap(),
+                vec![("code", to_value("EUR").unwrap()), ("locale", to_value("de").unwrap())],
+                "-1.234,50\u{a0}€",
+            ),
+            (
+                to_value(1234.5).unwrap(),
+                vec![("code", to_value("CHF").unwrap()), ("locale", to_value("de-CH").unwrap())],
+                "CHF\u{a0}1'234.50",
+            ),
+            (
+                to_value(-1234.5).unwrap(),
+                vec![("code", to_value("USD").unwrap()), ("accounting", to_value(true).unwrap())],
+                "($1,234.50)",
+            ),
+        ];
+        for (value, arguments, expected) in tests {
+            let mut args = HashMap::new();
+            for (name, arg) in arguments {
+                args.insert(name.to_string(), arg);
+            }
+            let result = currency(&value, &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_currency_requires_code() {
+        let result = currency(&to_value(12).unwrap(), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `currency` expected an arg called `code`"
+        );
+    }
+
+    #[cfg(feature = "builtins-number")]
     #[test]
     fn test_filesizeformat() {
         let args = HashMap::new();
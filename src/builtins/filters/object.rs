@@ -1,9 +1,11 @@
 /// Filters operating on numbers
 use std::collections::HashMap;
 
-use serde_json::value::Value;
+use serde_json::json;
+use serde_json::value::{Map, Value};
 
 use crate::errors::{Error, Result};
+use crate::filter_utils::get_sort_strategy_for_type;
 
 /// Returns a value by a `key` argument from a given object
 pub fn get(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
@@ -20,10 +22,122 @@ pub fn get(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     }
 }
 
+/// Returns the object the filter `name` was used on
+fn get_object<'a>(name: &str, value: &'a Value) -> Result<&'a Map<String, Value>> {
+    value.as_object().ok_or_else(|| {
+        Error::msg(format!("Filter `{}` was used on a value that isn't an object", name))
+    })
+}
+
+/// Returns the `[key, value]` pairs of an object, in the order of its keys
+pub fn items(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let obj = get_object("items", value)?;
+    Ok(Value::Array(obj.iter().map(|(k, v)| json!([k, v])).collect()))
+}
+
+/// Returns the keys of an object
+pub fn keys(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let obj = get_object("keys", value)?;
+    Ok(Value::Array(obj.keys().map(|k| Value::String(k.clone())).collect()))
+}
+
+/// Returns the values of an object, in the order of its keys
+pub fn values(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let obj = get_object("values", value)?;
+    Ok(Value::Array(obj.values().cloned().collect()))
+}
+
+/// Returns the `[key, value]` pairs of an object sorted by key or, with `by="value"`, by value.
+/// Use the `reverse` argument (defaults to false) to sort them in descending order.
+pub fn dictsort(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let obj = get_object("dictsort", value)?;
+    let by = match args.get("by") {
+        Some(val) => try_get_value!("dictsort", "by", String, val),
+        None => "key".to_string(),
+    };
+    let reverse = match args.get("reverse") {
+        Some(val) => try_get_value!("dictsort", "reverse", bool, val),
+        None => false,
+    };
+
+    let mut pairs: Vec<_> = obj.iter().collect();
+    pairs.sort_by(|a, b| a.0.cmp(b.0));
+    let pairs: Vec<Value> = pairs.into_iter().map(|(k, v)| json!([k, v])).collect();
+
+    let mut sorted = match by.as_str() {
+        "key" => pairs,
+        "value" if pairs.is_empty() => pairs,
+        "value" => {
+            let mut strategy = get_sort_strategy_for_type(&pairs[0][1])?;
+            for pair in &pairs {
+                strategy.try_add_pair(pair, &pair[1])?;
+            }
+            strategy.sort()
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "Filter `dictsort` received an incorrect value for arg `by`: got `{}`, \
+                 only key and value are allowed",
+                by
+            )))
+        }
+    };
+    if reverse {
+        sorted.reverse();
+    }
+
+    Ok(Value::Array(sorted))
+}
+
+/// Adds the entries of `overrides` to `base`, replacing the ones with the same key or, if `deep`
+/// is true and both values are objects, merging them the same way
+fn merge_objects(base: &mut Map<String, Value>, overrides: &Map<String, Value>, deep: bool) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(Value::Object(base_obj)), Value::Object(obj)) if deep => {
+                merge_objects(base_obj, obj, deep)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Returns an object with the entries of the object given as `with` added to the ones of the
+/// object, replacing the existing ones. With `deep=true`, nested objects are merged as well.
+/// Arrays are concatenated instead.
+pub fn merge(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let with = match args.get("with") {
+        Some(val) => val,
+        None => return Err(Error::msg("The `merge` filter has to have a `with` argument")),
+    };
+    let deep = match args.get("deep") {
+        Some(val) => try_get_value!("merge", "deep", bool, val),
+        None => false,
+    };
+
+    match (value, with) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            let mut merged = base.clone();
+            merge_objects(&mut merged, overrides, deep);
+            Ok(Value::Object(merged))
+        }
+        (Value::Array(base), Value::Array(values)) => {
+            Ok(Value::Array(base.iter().chain(values).cloned().collect()))
+        }
+        _ => Err(Error::msg(format!(
+            "Filter `merge` can only merge two objects or two arrays, got `{}` and `{}`",
+            value, with
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::get;
-    use serde_json::value::to_value;
+    use super::{dictsort, get, items, keys, merge, values};
+    use serde_json::json;
+    use serde_json::value::{to_value, Value};
     use std::collections::HashMap;
 
     #[test]
@@ -50,4 +164,82 @@ mod tests {
         let result = get(&to_value(&obj).unwrap(), &args);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_dictsort() {
+        let obj = json!({"b": 3, "c": 1, "a": 2});
+        let mut args = HashMap::new();
+        assert_eq!(dictsort(&obj, &args).unwrap(), json!([["a", 2], ["b", 3], ["c", 1]]));
+
+        args.insert("by".to_string(), to_value("value").unwrap());
+        assert_eq!(dictsort(&obj, &args).unwrap(), json!([["c", 1], ["a", 2], ["b", 3]]));
+
+        args.insert("reverse".to_string(), to_value(true).unwrap());
+        assert_eq!(dictsort(&obj, &args).unwrap(), json!([["b", 3], ["a", 2], ["c", 1]]));
+
+        assert_eq!(dictsort(&json!({}), &args).unwrap(), json!([]));
+    }
+
+    #[test]
+    fn test_dictsort_errors() {
+        let mut args = HashMap::new();
+        assert!(dictsort(&json!([1, 2]), &args).is_err());
+
+        args.insert("by".to_string(), to_value("value").unwrap());
+        assert!(dictsort(&json!({"a": 1, "b": "2"}), &args).is_err());
+
+        args.insert("by".to_string(), to_value("size").unwrap());
+        assert!(dictsort(&json!({"a": 1}), &args).is_err());
+    }
+
+    #[test]
+    fn test_items_keys_values() {
+        // The order depends on the `preserve_order` feature
+        let sorted = |value: Value| {
+            let mut values = value.as_array().unwrap().clone();
+            values.sort_by_key(|v| v.to_string());
+            Value::Array(values)
+        };
+        let obj = json!({"b": 2, "a": 1});
+        let args = HashMap::new();
+        assert_eq!(sorted(items(&obj, &args).unwrap()), json!([["a", 1], ["b", 2]]));
+        assert_eq!(sorted(keys(&obj, &args).unwrap()), json!(["a", "b"]));
+        assert_eq!(sorted(values(&obj, &args).unwrap()), json!([1, 2]));
+        assert_eq!(
+            keys(&json!("a"), &args).unwrap_err().to_string(),
+            "Filter `keys` was used on a value that isn't an object"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let defaults = json!({"title": "Home", "theme": {"color": "blue", "font": "serif"}});
+        let overrides = json!({"theme": {"color": "red"}, "lang": "fr"});
+        let mut args = HashMap::new();
+        args.insert("with".to_string(), overrides);
+        assert_eq!(
+            merge(&defaults, &args).unwrap(),
+            json!({"title": "Home", "theme": {"color": "red"}, "lang": "fr"})
+        );
+
+        args.insert("deep".to_string(), to_value(true).unwrap());
+        assert_eq!(
+            merge(&defaults, &args).unwrap(),
+            json!({"title": "Home", "theme": {"color": "red", "font": "serif"}, "lang": "fr"})
+        );
+
+        args.insert("with".to_string(), json!([3, 4]));
+        assert_eq!(merge(&json!([1, 2]), &args).unwrap(), json!([1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let mut args = HashMap::new();
+        assert!(merge(&json!({}), &args).is_err());
+        args.insert("with".to_string(), json!([1]));
+        assert_eq!(
+            merge(&json!({"a": 1}), &args).unwrap_err().to_string(),
+            "Filter `merge` can only merge two objects or two arrays, got `{\"a\":1}` and `[1]`"
+        );
+    }
 }
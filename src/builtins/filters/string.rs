@@ -5,25 +5,32 @@ use lazy_static::lazy_static;
 use regex::{Captures, Regex};
 use serde_json::value::{to_value, Value};
 
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
-#[cfg(feature = "builtins")]
+#[cfg(feature = "markdown")]
+use pulldown_cmark::{html, Options, Parser};
+#[cfg(feature = "hashes")]
+use sha2::Digest;
+#[cfg(feature = "builtins-string")]
 use unic_segment::GraphemeIndices;
 
+#[cfg(feature = "markdown")]
+use crate::builtins::filters::Filter;
+use crate::context::ValueRender;
 use crate::errors::{Error, Result};
-use crate::utils;
+use crate::utils::{self, RegexCache};
 
 /// https://url.spec.whatwg.org/#fragment-percent-encode-set
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 const FRAGMENT_ENCODE_SET: &AsciiSet =
     &percent_encoding::CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
 
 /// https://url.spec.whatwg.org/#path-percent-encode-set
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 const PATH_ENCODE_SET: &AsciiSet = &FRAGMENT_ENCODE_SET.add(b'#').add(b'?').add(b'{').add(b'}');
 
 /// https://url.spec.whatwg.org/#userinfo-percent-encode-set
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 const USERINFO_ENCODE_SET: &AsciiSet = &PATH_ENCODE_SET
     .add(b'/')
     .add(b':')
@@ -39,7 +46,7 @@ const USERINFO_ENCODE_SET: &AsciiSet = &PATH_ENCODE_SET
 /// Same as Python quote
 /// https://github.com/python/cpython/blob/da27d9b9dc44913ffee8f28d9638985eaaa03755/Lib/urllib/parse.py#L787
 /// with `/` not escaped
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 const PYTHON_ENCODE_SET: &AsciiSet = &USERINFO_ENCODE_SET
     .remove(b'/')
     .add(b':')
@@ -154,7 +161,7 @@ pub fn trim_end_matches(value: &Value, args: &HashMap<String, Value>) -> Result<
 /// The return value of this function might be longer than `length`: the `end`
 /// string is *added* after the truncation occurs.
 ///
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 pub fn truncate(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("truncate", "value", String, value);
     let length = match args.get("length") {
@@ -177,6 +184,101 @@ pub fn truncate(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     Ok(to_value(&result).unwrap())
 }
 
+/// Truncates a string to the number of words given by the `count` argument, keeping the
+/// whitespace between them as is, and adds the `end` string (`…` by default) if it was truncated.
+pub fn truncatewords(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let s = try_get_value!("truncatewords", "value", String, value);
+    let count = match args.get("count") {
+        Some(val) => try_get_value!("truncatewords", "count", usize, val),
+        None => return Err(Error::msg("Filter `truncatewords` expected an arg called `count`")),
+    };
+    let end = match args.get("end") {
+        Some(val) => try_get_value!("truncatewords", "end", String, val),
+        None => "…".to_string(),
+    };
+
+    let mut words = s.split_whitespace();
+    // The words are slices of `s`, so where the last one kept ends can be found from its address
+    let cut = match count.checked_sub(1).map(|n| words.nth(n)) {
+        Some(Some(last)) => last.as_ptr() as usize - s.as_ptr() as usize + last.len(),
+        Some(None) => return Ok(to_value(&s).unwrap()),
+        None => 0,
+    };
+    // Nothing to truncate?
+    if words.next().is_none() {
+        return Ok(to_value(&s).unwrap());
+    }
+
+    Ok(to_value(s[..cut].to_string() + &end).unwrap())
+}
+
+/// Pads the value given to the filter `name`, rendered as a string, with its `fill` character (a space by default) up
+/// to `width` characters, adding `left` of them to its left and the others to its right
+fn pad(
+    name: &str,
+    value: &Value,
+    args: &HashMap<String, Value>,
+    left: fn(usize) -> usize,
+) -> Result<Value> {
+    let s = value.render();
+    let width = match args.get("width") {
+        Some(val) => try_get_value!(name, "width", usize, val),
+        None => {
+            return Err(Error::msg(format!("Filter `{}` expected an arg called `width`", name)))
+        }
+    };
+    let fill = match args.get("fill") {
+        Some(val) => {
+            let fill = try_get_value!(name, "fill", String, val);
+            let mut chars = fill.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => {
+                    return Err(Error::msg(format!(
+                        "Filter `{}` received fill={:?} but `fill` can only be a single character",
+                        name, fill
+                    )))
+                }
+            }
+        }
+        None => ' ',
+    };
+
+    let missing = width.saturating_sub(s.chars().count());
+    let left = left(missing);
+    let mut res = String::with_capacity(s.len() + missing * fill.len_utf8());
+    res.extend(std::iter::repeat(fill).take(left));
+    res.push_str(&s);
+    res.extend(std::iter::repeat(fill).take(missing - left));
+    Ok(to_value(res).unwrap())
+}
+
+/// Right-aligns the string in `width` characters by padding its left with `fill`
+pub fn pad_left(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    pad("pad_left", value, args, |missing| missing)
+}
+
+/// Left-aligns the string in `width` characters by padding its right with `fill`
+pub fn pad_right(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    pad("pad_right", value, args, |_| 0)
+}
+
+/// Centers the string in `width` characters by padding both sides with `fill`, with the extra
+/// character on the right if the padding can't be split evenly
+pub fn center(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    pad("center", value, args, |missing| missing / 2)
+}
+
+/// Repeats the value, rendered as a string, the number of times given by the `count` argument
+pub fn repeat(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let count = match args.get("count") {
+        Some(val) => try_get_value!("repeat", "count", usize, val),
+        None => return Err(Error::msg("Filter `repeat` expected an arg called `count`")),
+    };
+
+    Ok(to_value(value.render().repeat(count)).unwrap())
+}
+
 /// Gets the number of words in a string.
 pub fn wordcount(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("wordcount", "value", String, value);
@@ -201,6 +303,32 @@ pub fn replace(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
     Ok(to_value(&s.replace(&from, &to)).unwrap())
 }
 
+/// Replaces the matches of the `pattern` regex with `rep`, which can refer to the capture
+/// groups with `$1` or `$name`. The regex is compiled once and kept in `regexes`.
+pub fn regex_replace(
+    regexes: &RegexCache,
+    value: &Value,
+    args: &HashMap<String, Value>,
+) -> Result<Value> {
+    let s = try_get_value!("regex_replace", "value", String, value);
+
+    let pattern = match args.get("pattern") {
+        Some(val) => try_get_value!("regex_replace", "pattern", String, val),
+        None => return Err(Error::msg("Filter `regex_replace` expected an arg called `pattern`")),
+    };
+
+    let rep = match args.get("rep") {
+        Some(val) => try_get_value!("regex_replace", "rep", String, val),
+        None => return Err(Error::msg("Filter `regex_replace` expected an arg called `rep`")),
+    };
+
+    let regex = regexes.get(&pattern).map_err(|e| {
+        Error::msg(format!("Filter `regex_replace`: Invalid regular expression: {}", e))
+    })?;
+
+    Ok(to_value(regex.replace_all(&s, rep.as_str())).unwrap())
+}
+
 /// First letter of the string is uppercase rest is lowercase
 pub fn capitalize(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("capitalize", "value", String, value);
@@ -215,7 +343,7 @@ pub fn capitalize(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
 }
 
 /// Percent-encodes reserved URI characters
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 pub fn urlencode(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("urlencode", "value", String, value);
     let encoded = percent_encode(s.as_bytes(), &PYTHON_ENCODE_SET).to_string();
@@ -223,13 +351,44 @@ pub fn urlencode(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
 }
 
 /// Percent-encodes all non-alphanumeric characters
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 pub fn urlencode_strict(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("urlencode_strict", "value", String, value);
     let encoded = percent_encode(s.as_bytes(), &NON_ALPHANUMERIC).to_string();
     Ok(Value::String(encoded))
 }
 
+/// Gets the base64 alphabet picked by the `url_safe` argument of the filter `name`
+#[cfg(feature = "builtins-string")]
+fn base64_config(name: &str, args: &HashMap<String, Value>) -> Result<base64::Config> {
+    let url_safe = match args.get("url_safe") {
+        Some(val) => try_get_value!(name, "url_safe", bool, val),
+        None => false,
+    };
+    Ok(if url_safe { base64::URL_SAFE } else { base64::STANDARD })
+}
+
+/// Encodes the string in base64, using `-` and `_` instead of `+` and `/` if `url_safe` is `true`
+#[cfg(feature = "builtins-string")]
+pub fn base64_encode(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let s = try_get_value!("base64_encode", "value", String, value);
+    let config = base64_config("base64_encode", args)?;
+    Ok(Value::String(base64::encode_config(s.as_bytes(), config)))
+}
+
+/// Decodes a base64 string, written with `-` and `_` instead of `+` and `/` if `url_safe` is `true`
+#[cfg(feature = "builtins-string")]
+pub fn base64_decode(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let s = try_get_value!("base64_decode", "value", String, value);
+    let config = base64_config("base64_decode", args)?;
+    let bytes = base64::decode_config(s.as_bytes(), config).map_err(|e| {
+        Error::msg(format!("Filter `base64_decode` received an invalid base64 string: {}", e))
+    })?;
+    String::from_utf8(bytes).map(Value::String).map_err(|_| {
+        Error::msg("Filter `base64_decode` decoded bytes that aren't a valid UTF-8 string")
+    })
+}
+
 /// Escapes quote characters
 pub fn addslashes(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("addslashes", "value", String, value);
@@ -237,12 +396,81 @@ pub fn addslashes(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
 }
 
 /// Transform a string into a slug
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-string")]
 pub fn slugify(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("slugify", "value", String, value);
     Ok(to_value(&slug::slugify(s)).unwrap())
 }
 
+/// Renders a Markdown string as HTML, with tables, footnotes, strikethrough and task lists.
+/// The output is safe so it doesn't get escaped: the HTML written in the Markdown is kept as is.
+/// With `inline=true`, the paragraph around a single line of text is removed.
+#[cfg(feature = "markdown")]
+pub struct Markdown;
+
+#[cfg(feature = "markdown")]
+impl Filter for Markdown {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+        let s = try_get_value!("markdown", "value", String, value);
+        let inline = match args.get("inline") {
+            Some(val) => try_get_value!("markdown", "inline", bool, val),
+            None => false,
+        };
+
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        options.insert(Options::ENABLE_FOOTNOTES);
+        options.insert(Options::ENABLE_STRIKETHROUGH);
+        options.insert(Options::ENABLE_TASKLISTS);
+
+        let mut output = String::with_capacity(s.len() * 3 / 2);
+        html::push_html(&mut output, Parser::new_ext(&s, options));
+
+        if inline && output.starts_with("<p>") && output.ends_with("</p>\n") {
+            let inner = &output["<p>".len()..output.len() - "</p>\n".len()];
+            if !inner.contains("<p>") {
+                output = inner.to_string();
+            }
+        }
+
+        Ok(Value::String(output))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the digest of the string given by the hash function `D` as a lowercase hex string
+#[cfg(feature = "hashes")]
+fn hex_digest<D: Digest>(name: &str, value: &Value) -> Result<Value> {
+    let s = try_get_value!(name, "value", String, value);
+    let digest = D::digest(s.as_bytes());
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    Ok(Value::String(hex))
+}
+
+/// Returns the SHA-256 digest of the string as a hex string
+#[cfg(feature = "hashes")]
+pub fn sha256(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    hex_digest::<sha2::Sha256>("sha256", value)
+}
+
+/// Returns the SHA-1 digest of the string as a hex string
+#[cfg(feature = "hashes")]
+pub fn sha1(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    hex_digest::<sha1::Sha1>("sha1", value)
+}
+
+/// Returns the MD5 digest of the string as a hex string
+#[cfg(feature = "hashes")]
+pub fn md5(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    hex_digest::<md5::Md5>("md5", value)
+}
+
 /// Capitalizes each word in the string
 pub fn title(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
     let s = try_get_value!("title", "value", String, value);
@@ -270,20 +498,62 @@ pub fn escape_html(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
 /// Returns the given text with all special XML characters encoded
 /// Very similar to `escape_html`, just a few characters less are encoded
 pub fn escape_xml(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
-    let s = try_get_value!("escape_html", "value", String, value);
+    let s = try_get_value!("escape_xml", "value", String, value);
+    Ok(Value::String(utils::escape_xml(&s)))
+}
 
-    let mut output = String::with_capacity(s.len() * 2);
-    for c in s.chars() {
-        match c {
-            '&' => output.push_str("&amp;"),
-            '<' => output.push_str("&lt;"),
-            '>' => output.push_str("&gt;"),
-            '"' => output.push_str("&quot;"),
-            '\'' => output.push_str("&apos;"),
-            _ => output.push(c),
+/// Returns the given text with all special LaTeX characters escaped
+pub fn latex_escape(value: &Value, _: &HashMap<String, Value>) -> Result<Value> {
+    let s = try_get_value!("latex_escape", "value", String, value);
+    Ok(Value::String(utils::escape_latex(&s)))
+}
+
+/// Quotes the given value as a CSV field, following RFC 4180.
+/// The fields are separated by the `sep` argument, `,` by default.
+pub fn csv_escape(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let sep = match args.get("sep") {
+        Some(val) => try_get_value!("csv_escape", "sep", String, val),
+        None => ",".to_string(),
+    };
+    Ok(Value::String(utils::quote_csv_field(&value.render(), &sep)))
+}
+
+/// Quotes the given text to be used as a single argument in a shell command.
+///
+/// The `shell` argument is either `posix` (the default), for sh and bash, or `powershell`.
+pub fn shell_quote(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let s = try_get_value!("shell_quote", "value", String, value);
+    let shell = match args.get("shell") {
+        Some(val) => try_get_value!("shell_quote", "shell", String, val),
+        None => "posix".to_string(),
+    };
+
+    let quoted = match shell.as_str() {
+        // Nothing is special inside of single quotes, which can't be escaped: the quote is
+        // closed, an escaped quote added and the quote reopened
+        "posix" => format!("'{}'", s.replace('\'', "'\\''")),
+        // PowerShell also treats the typographic single quotes as single quotes
+        "powershell" => {
+            let mut quoted = String::with_capacity(s.len() + 2);
+            quoted.push('\'');
+            for c in s.chars() {
+                if let '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' = c {
+                    quoted.push(c);
+                }
+                quoted.push(c);
+            }
+            quoted.push('\'');
+            quoted
         }
-    }
-    Ok(Value::String(output))
+        _ => {
+            return Err(Error::msg(format!(
+            "Filter `shell_quote` received an unknown shell `{}`, expected `posix` or `powershell`",
+            shell
+        )))
+        }
+    };
+
+    Ok(Value::String(quoted))
 }
 
 /// Split the given string by the given pattern.
@@ -455,7 +725,7 @@ mod tests {
         }
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_truncate_smaller_than_length() {
         let mut args = HashMap::new();
@@ -465,7 +735,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("hello").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_truncate_when_required() {
         let mut args = HashMap::new();
@@ -475,7 +745,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("日本…").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_truncate_custom_end() {
         let mut args = HashMap::new();
@@ -486,7 +756,7 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("日本").unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_truncate_multichar_grapheme() {
         let mut args = HashMap::new();
@@ -497,6 +767,79 @@ mod tests {
         assert_eq!(result.unwrap(), to_value("👨‍👩‍👧‍👦 fam…").unwrap());
     }
 
+    #[test]
+    fn test_truncatewords() {
+        let tests = vec![
+            ("Joel is a slug", 2, None, "Joel is…"),
+            ("  Joel\n is   a slug", 3, None, "  Joel\n is   a…"),
+            ("Joel is a slug", 2, Some(" [...]"), "Joel is [...]"),
+            ("Joel is a slug", 4, None, "Joel is a slug"),
+            ("Joel is a slug ", 10, None, "Joel is a slug "),
+            ("Joel is a slug", 0, None, "…"),
+            ("", 0, None, ""),
+        ];
+        for (input, count, end, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("count".to_string(), to_value(count).unwrap());
+            if let Some(end) = end {
+                args.insert("end".to_string(), to_value(end).unwrap());
+            }
+            let result = truncatewords(&to_value(input).unwrap(), &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        type PadFilter = fn(&Value, &HashMap<String, Value>) -> Result<Value>;
+        let tests: Vec<(PadFilter, Value, usize, Option<&str>, &str)> = vec![
+            (pad_left, to_value(42).unwrap(), 5, None, "   42"),
+            (pad_left, to_value("42").unwrap(), 5, Some("0"), "00042"),
+            (pad_right, to_value("name").unwrap(), 6, None, "name  "),
+            (pad_right, to_value("été").unwrap(), 5, Some("."), "été.."),
+            (center, to_value("ab").unwrap(), 7, Some("*"), "**ab***"),
+            (center, to_value("title").unwrap(), 3, None, "title"),
+        ];
+        for (filter, input, width, fill, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("width".to_string(), to_value(width).unwrap());
+            if let Some(fill) = fill {
+                args.insert("fill".to_string(), to_value(fill).unwrap());
+            }
+            let result = filter(&input, &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_padding_errors() {
+        let result = pad_left(&to_value("a").unwrap(), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `pad_left` expected an arg called `width`"
+        );
+
+        let mut args = HashMap::new();
+        args.insert("width".to_string(), to_value(3).unwrap());
+        args.insert("fill".to_string(), to_value("ab").unwrap());
+        let result = center(&to_value("a").unwrap(), &args);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `center` received fill=\"ab\" but `fill` can only be a single character"
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut args = HashMap::new();
+        args.insert("count".to_string(), to_value(5).unwrap());
+        assert_eq!(repeat(&to_value("-").unwrap(), &args).unwrap(), to_value("-----").unwrap());
+        assert_eq!(repeat(&to_value(12).unwrap(), &args).unwrap(), to_value("1212121212").unwrap());
+        args.insert("count".to_string(), to_value(0).unwrap());
+        assert_eq!(repeat(&to_value("ab").unwrap(), &args).unwrap(), to_value("").unwrap());
+        assert!(repeat(&to_value("ab").unwrap(), &HashMap::new()).is_err());
+    }
+
     #[test]
     fn test_lower() {
         let result = lower(&to_value("HELLO").unwrap(), &HashMap::new());
@@ -544,6 +887,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_regex_replace() {
+        let regexes = RegexCache::default();
+        let tests = vec![
+            (r"^/api/(\w+)", "/v2/$1", "/api/users/1", "/v2/users/1"),
+            (r"(?P<y>\d{4})-(?P<m>\d{2})", "$m/$y", "2021-03", "03/2021"),
+            (r"\s+", " ", "a  b \n c", "a b c"),
+        ];
+        for (pattern, rep, input, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("pattern".to_string(), to_value(pattern).unwrap());
+            args.insert("rep".to_string(), to_value(rep).unwrap());
+            let result = regex_replace(&regexes, &to_value(input).unwrap(), &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_regex_replace_invalid_regex() {
+        let mut args = HashMap::new();
+        args.insert("pattern".to_string(), to_value("(unclosed").unwrap());
+        args.insert("rep".to_string(), to_value("").unwrap());
+        let result = regex_replace(&RegexCache::default(), &to_value("a").unwrap(), &args);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_capitalize() {
         let tests = vec![("CAPITAL IZE", "Capital ize"), ("capital ize", "Capital ize")];
@@ -573,7 +942,7 @@ mod tests {
         }
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_slugify() {
         // slug crate already has tests for general slugification so we just
@@ -587,7 +956,7 @@ mod tests {
         }
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_urlencode() {
         let tests = vec![
@@ -611,7 +980,7 @@ mod tests {
         }
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-string")]
     #[test]
     fn test_urlencode_strict() {
         let tests = vec![
@@ -635,6 +1004,41 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "builtins-string")]
+    #[test]
+    fn test_base64() {
+        let tests = vec![
+            ("hello", false, "aGVsbG8="),
+            ("user:pa$$word", false, "dXNlcjpwYSQkd29yZA=="),
+            ("<<??>>", false, "PDw/Pz4+"),
+            ("<<??>>", true, "PDw_Pz4-"),
+            ("", false, ""),
+        ];
+        for (input, url_safe, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("url_safe".to_string(), to_value(url_safe).unwrap());
+            let result = base64_encode(&to_value(input).unwrap(), &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+            let result = base64_decode(&to_value(expected).unwrap(), &args);
+            assert_eq!(result.unwrap(), to_value(input).unwrap());
+        }
+    }
+
+    #[cfg(feature = "builtins-string")]
+    #[test]
+    fn test_base64_decode_errors() {
+        let result = base64_decode(&to_value("not base64!").unwrap(), &HashMap::new());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Filter `base64_decode` received an invalid base64 string"));
+        let result = base64_decode(&to_value("/w==").unwrap(), &HashMap::new());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Filter `base64_decode` decoded bytes that aren't a valid UTF-8 string"
+        );
+    }
+
     #[test]
     fn test_title() {
         let tests = vec![
@@ -661,6 +1065,43 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn test_markdown() {
+        let tests = vec![
+            ("# Hello *world*", false, "<h1>Hello <em>world</em></h1>\n"),
+            ("Hello *world*", true, "Hello <em>world</em>"),
+            ("One\n\nTwo", true, "<p>One</p>\n<p>Two</p>\n"),
+            ("~~old~~ <b>new</b>", false, "<p><del>old</del> <b>new</b></p>\n"),
+        ];
+        for (input, inline, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("inline".to_string(), to_value(inline).unwrap());
+            let result = Markdown.filter(&to_value(input).unwrap(), &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+        assert!(Markdown.is_safe());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_hashes() {
+        let value = to_value("hello@example.com").unwrap();
+        let args = HashMap::new();
+        assert_eq!(
+            sha256(&value, &args).unwrap(),
+            to_value("1753bdb368271a785887ddbfb926164f2f7c6a88f609c07ff0401c5572955206").unwrap()
+        );
+        assert_eq!(
+            sha1(&value, &args).unwrap(),
+            to_value("4637ca915d37ccfa5e3a6415aa475d988ec4725f").unwrap()
+        );
+        assert_eq!(
+            md5(&value, &args).unwrap(),
+            to_value("cb8419c1d471d55fbca0d63d1fb2b6ac").unwrap()
+        );
+    }
+
     #[test]
     fn test_striptags() {
         let tests = vec![
@@ -729,6 +1170,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_latex_escape() {
+        let result = latex_escape(&to_value("100% of R&D_{2024}").unwrap(), &HashMap::new());
+        assert_eq!(result.unwrap(), to_value(r"100\% of R\&D\_\{2024\}").unwrap());
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        let tests: Vec<(Value, &str, &str)> = vec![
+            (to_value("a,b").unwrap(), ",", r#""a,b""#),
+            (to_value("a,b").unwrap(), ";", "a,b"),
+            (to_value("a;\"b\"").unwrap(), ";", r#""a;""b""""#),
+            (to_value(1.5).unwrap(), ",", "1.5"),
+        ];
+        for (input, sep, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("sep".to_string(), to_value(sep).unwrap());
+            let result = csv_escape(&input, &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        let tests = vec![
+            ("posix", "", "''"),
+            ("posix", "hello world", "'hello world'"),
+            ("posix", "it's $HOME; rm -rf /", r"'it'\''s $HOME; rm -rf /'"),
+            ("powershell", "it's $env:HOME", "'it''s $env:HOME'"),
+            ("powershell", "it\u{2019}s", "'it\u{2019}\u{2019}s'"),
+        ];
+        for (shell, input, expected) in tests {
+            let mut args = HashMap::new();
+            args.insert("shell".to_string(), to_value(shell).unwrap());
+            let result = shell_quote(&to_value(input).unwrap(), &args);
+            assert_eq!(result.unwrap(), to_value(expected).unwrap());
+        }
+
+        let result = shell_quote(&to_value("a").unwrap(), &HashMap::new());
+        assert_eq!(result.unwrap(), to_value("'a'").unwrap());
+        let mut args = HashMap::new();
+        args.insert("shell".to_string(), to_value("fish").unwrap());
+        assert!(shell_quote(&to_value("a").unwrap(), &args).is_err());
+    }
+
     #[test]
     fn test_int_decimal_strings() {
         let tests: Vec<(&str, i64)> = vec![
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-date")]
 use chrono::prelude::*;
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-date")]
+use chrono_tz::Tz;
+#[cfg(feature = "builtins-random")]
 use rand::Rng;
 use serde_json::value::{from_value, to_value, Value};
 
@@ -83,7 +85,7 @@ pub fn range(args: &HashMap<String, Value>) -> Result<Value> {
     Ok(to_value(res).unwrap())
 }
 
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-date")]
 pub fn now(args: &HashMap<String, Value>) -> Result<Value> {
     let utc = match args.get("utc") {
         Some(val) => match from_value::<bool>(val.clone()) {
@@ -109,8 +111,29 @@ pub fn now(args: &HashMap<String, Value>) -> Result<Value> {
         },
         None => false,
     };
+    let timezone = match args.get("timezone") {
+        Some(Value::String(timezone)) => match timezone.parse::<Tz>() {
+            Ok(timezone) => Some(timezone),
+            Err(_) => {
+                return Err(Error::msg(format!("Error parsing `{}` as a timezone", timezone)))
+            }
+        },
+        Some(val) => {
+            return Err(Error::msg(format!(
+                "Function `now` received timezone={} but `timezone` can only be a string",
+                val
+            )));
+        }
+        None => None,
+    };
 
-    if utc {
+    if let Some(timezone) = timezone {
+        let datetime = Utc::now().with_timezone(&timezone);
+        if timestamp {
+            return Ok(to_value(datetime.timestamp()).unwrap());
+        }
+        Ok(to_value(datetime.to_rfc3339()).unwrap())
+    } else if utc {
         let datetime = Utc::now();
         if timestamp {
             return Ok(to_value(datetime.timestamp()).unwrap());
@@ -125,6 +148,12 @@ pub fn now(args: &HashMap<String, Value>) -> Result<Value> {
     }
 }
 
+/// Returns an object with the given arguments as attributes, which can be modified
+/// with `{% set ns.attribute = value %}`
+pub fn namespace(args: &HashMap<String, Value>) -> Result<Value> {
+    Ok(Value::Object(args.iter().map(|(k, v)| (k.clone(), v.clone())).collect()))
+}
+
 pub fn throw(args: &HashMap<String, Value>) -> Result<Value> {
     match args.get("message") {
         Some(val) => match from_value::<String>(val.clone()) {
@@ -138,7 +167,7 @@ pub fn throw(args: &HashMap<String, Value>) -> Result<Value> {
     }
 }
 
-#[cfg(feature = "builtins")]
+#[cfg(feature = "builtins-random")]
 pub fn get_random(args: &HashMap<String, Value>) -> Result<Value> {
     let start = match args.get("start") {
         Some(val) => match from_value::<i32>(val.clone()) {
@@ -240,7 +269,7 @@ mod tests {
         assert_eq!(res, to_value(vec![0, 2, 4, 6, 8]).unwrap());
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn now_default() {
         let args = HashMap::new();
@@ -250,7 +279,7 @@ mod tests {
         assert!(res.as_str().unwrap().contains("T"));
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn now_datetime_utc() {
         let mut args = HashMap::new();
@@ -264,7 +293,21 @@ mod tests {
         assert!(val.contains("+00:00"));
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-date")]
+    #[test]
+    fn now_datetime_timezone() {
+        let mut args = HashMap::new();
+        args.insert("timezone".to_string(), to_value("Asia/Kolkata").unwrap());
+
+        let res = now(&args).unwrap();
+        assert!(res.as_str().unwrap().ends_with("+05:30"));
+
+        args.insert("timezone".to_string(), to_value("Narnia").unwrap());
+        let res = now(&args);
+        assert_eq!(res.unwrap_err().to_string(), "Error parsing `Narnia` as a timezone");
+    }
+
+    #[cfg(feature = "builtins-date")]
     #[test]
     fn now_timestamp() {
         let mut args = HashMap::new();
@@ -285,7 +328,7 @@ mod tests {
         assert_eq!(err.to_string(), "Hello");
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-random")]
     #[test]
     fn get_random_no_start() {
         let mut args = HashMap::new();
@@ -297,7 +340,7 @@ mod tests {
         assert!(res.as_i64().unwrap() < 10);
     }
 
-    #[cfg(feature = "builtins")]
+    #[cfg(feature = "builtins-random")]
     #[test]
     fn get_random_with_start() {
         let mut args = HashMap::new();
@@ -1,6 +1,6 @@
 use crate::context::ValueNumber;
 use crate::errors::{Error, Result};
-use regex::Regex;
+use crate::utils::RegexCache;
 use serde_json::value::Value;
 
 /// The tester function type definition
@@ -79,6 +79,17 @@ pub fn undefined(value: Option<&Value>, params: &[Value]) -> Result<bool> {
     Ok(value.is_none())
 }
 
+/// Returns true if `value` is defined and null, like the `none` literal or a `None` option
+/// serialized in the context. Otherwise, returns false.
+pub fn none(value: Option<&Value>, params: &[Value]) -> Result<bool> {
+    number_args_allowed("none", 0, params.len())?;
+
+    match value {
+        Some(Value::Null) => Ok(true),
+        _ => Ok(false),
+    }
+}
+
 /// Returns true if `value` is a string. Otherwise, returns false.
 pub fn string(value: Option<&Value>, params: &[Value]) -> Result<bool> {
     number_args_allowed("string", 0, params.len())?;
@@ -101,6 +112,22 @@ pub fn number(value: Option<&Value>, params: &[Value]) -> Result<bool> {
     }
 }
 
+/// Returns true if `value` is a boolean. Otherwise, returns false.
+pub fn boolean(value: Option<&Value>, params: &[Value]) -> Result<bool> {
+    number_args_allowed("boolean", 0, params.len())?;
+    value_defined("boolean", value)?;
+
+    Ok(value.unwrap().is_boolean())
+}
+
+/// Returns true if `value` is an array. Otherwise, returns false.
+pub fn array(value: Option<&Value>, params: &[Value]) -> Result<bool> {
+    number_args_allowed("array", 0, params.len())?;
+    value_defined("array", value)?;
+
+    Ok(value.unwrap().is_array())
+}
+
 /// Returns true if `value` is an odd number. Otherwise, returns false.
 pub fn odd(value: Option<&Value>, params: &[Value]) -> Result<bool> {
     number_args_allowed("odd", 0, params.len())?;
@@ -197,14 +224,15 @@ pub fn containing(value: Option<&Value>, params: &[Value]) -> Result<bool> {
 }
 
 /// Returns true if `value` is a string and matches the regex in the argument. Otherwise, returns false.
-pub fn matching(value: Option<&Value>, params: &[Value]) -> Result<bool> {
+/// The regex is compiled once and kept in `regexes`.
+pub fn matching(regexes: &RegexCache, value: Option<&Value>, params: &[Value]) -> Result<bool> {
     number_args_allowed("matching", 1, params.len())?;
     value_defined("matching", value)?;
 
     let value = extract_string("matching", "on a variable", value)?;
     let regex = extract_string("matching", "with a parameter", params.first())?;
 
-    let regex = match Regex::new(regex) {
+    let regex = match regexes.get(regex) {
         Ok(regex) => regex,
         Err(err) => {
             return Err(Error::msg(format!(
@@ -222,11 +250,12 @@ mod tests {
     use std::collections::HashMap;
 
     use super::{
-        containing, defined, divisible_by, ending_with, iterable, matching, object, starting_with,
-        string,
+        array, boolean, containing, defined, divisible_by, ending_with, iterable, matching, none,
+        object, starting_with, string,
     };
 
-    use serde_json::value::to_value;
+    use crate::utils::RegexCache;
+    use serde_json::value::{to_value, Value};
 
     #[test]
     fn test_number_args_ok() {
@@ -269,6 +298,30 @@ mod tests {
         assert_eq!(iterable(Some(&to_value("hello").unwrap()), &[]).unwrap(), false);
     }
 
+    #[test]
+    fn test_none() {
+        assert_eq!(none(Some(&Value::Null), &[]).unwrap(), true);
+        assert_eq!(none(Some(&to_value(0).unwrap()), &[]).unwrap(), false);
+        assert_eq!(none(None, &[]).unwrap(), false);
+    }
+
+    #[test]
+    fn test_boolean() {
+        assert_eq!(boolean(Some(&to_value(false).unwrap()), &[]).unwrap(), true);
+        assert_eq!(boolean(Some(&to_value(1).unwrap()), &[]).unwrap(), false);
+        assert_eq!(boolean(Some(&to_value("true").unwrap()), &[]).unwrap(), false);
+        assert!(boolean(None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_array() {
+        let mut h = HashMap::new();
+        h.insert("a", 1);
+        assert_eq!(array(Some(&to_value(vec![1]).unwrap()), &[]).unwrap(), true);
+        assert_eq!(array(Some(&to_value(h).unwrap()), &[]).unwrap(), false);
+        assert_eq!(array(Some(&to_value("hello").unwrap()), &[]).unwrap(), false);
+    }
+
     #[test]
     fn test_object() {
         let mut h = HashMap::new();
@@ -337,12 +390,16 @@ mod tests {
             ),
         ];
 
+        let regexes = RegexCache::default();
         for (container, needle, expected) in tests {
-            assert_eq!(matching(Some(&container), &[needle]).unwrap(), expected);
+            assert_eq!(matching(&regexes, Some(&container), &[needle]).unwrap(), expected);
         }
 
-        assert!(
-            matching(Some(&to_value("").unwrap()), &[to_value("(Invalid regex").unwrap()]).is_err()
-        );
+        assert!(matching(
+            &regexes,
+            Some(&to_value("").unwrap()),
+            &[to_value("(Invalid regex").unwrap()]
+        )
+        .is_err());
     }
 }
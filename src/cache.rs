@@ -0,0 +1,132 @@
+//! Caching the output of the `{% cache %}` blocks between renders
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Where the output of the `{% cache key %}...{% endcache %}` blocks is kept between renders.
+///
+/// The keys are the value of the key of the tag prefixed by the name of the template, so the
+/// same key can be used in different templates.
+pub trait FragmentCache: Sync + Send {
+    /// Returns the output cached for that key, if it is there and hasn't expired
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Caches the output of a block, for `ttl` if the tag has one or until it is evicted otherwise
+    fn set(&self, key: &str, output: String, ttl: Option<Duration>);
+}
+
+#[derive(Debug)]
+struct Entry {
+    output: String,
+    expires: Option<Instant>,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    entries: HashMap<String, Entry>,
+    // Incremented on every access, to know which entry was used the least recently
+    clock: u64,
+}
+
+/// An in-memory fragment cache keeping at most `capacity` outputs, evicting the least
+/// recently used one when it is full.
+///
+/// ```rust
+/// # use tera::{Context, MemoryCache, Tera};
+/// let mut tera = Tera::default();
+/// tera.set_fragment_cache(MemoryCache::new(100));
+/// tera.add_raw_template("hello", "{% cache 'greeting' %}Hello {{ name }}{% endcache %}").unwrap();
+/// let mut context = Context::new();
+/// context.insert("name", "Bob");
+/// assert_eq!(tera.render("hello", &context).unwrap(), "Hello Bob");
+/// context.insert("name", "Alice");
+/// assert_eq!(tera.render("hello", &context).unwrap(), "Hello Bob");
+/// ```
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl MemoryCache {
+    /// Creates an empty cache keeping at most `capacity` outputs
+    pub fn new(capacity: usize) -> MemoryCache {
+        MemoryCache { capacity, entries: Mutex::new(Entries::default()) }
+    }
+
+    /// Removes every output cached
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().entries.clear();
+    }
+}
+
+impl FragmentCache for MemoryCache {
+    fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        let entry = entries.entries.get_mut(key)?;
+        if entry.expires.filter(|expires| *expires <= Instant::now()).is_some() {
+            entries.entries.remove(key);
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.output.clone())
+    }
+
+    fn set(&self, key: &str, output: String, ttl: Option<Duration>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        if entries.entries.len() >= self.capacity && !entries.entries.contains_key(key) {
+            let lru = entries
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.entries.remove(&lru);
+            }
+        }
+
+        let expires = ttl.map(|ttl| Instant::now() + ttl);
+        entries.entries.insert(key.to_string(), Entry { output, expires, last_used: clock });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.set("a", "1".to_string(), None);
+        cache.set("b", "2".to_string(), None);
+        assert_eq!(cache.get("a"), Some("1".to_string()));
+        cache.set("c", "3".to_string(), None);
+
+        assert_eq!(cache.get("a"), Some("1".to_string()));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("c"), Some("3".to_string()));
+
+        cache.clear();
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn memory_cache_expires_outputs() {
+        let cache = MemoryCache::new(2);
+        cache.set("a", "1".to_string(), Some(Duration::from_secs(0)));
+        cache.set("b", "2".to_string(), Some(Duration::from_secs(3600)));
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some("2".to_string()));
+    }
+}
@@ -23,6 +23,9 @@ pub enum ErrorKind {
     },
     /// A template was missing (more generic version of MissingParent)
     TemplateNotFound(String),
+    /// The name of a template to render is absolute or goes above the template directories,
+    /// like `../secrets.txt`, and isn't the name of a loaded template
+    InvalidTemplateName(String),
     /// A filter wasn't found
     FilterNotFound(String),
     /// A test wasn't found
@@ -39,6 +42,8 @@ pub enum ErrorKind {
     CallFilter(String),
     /// An error occured while executing a test.
     CallTest(String),
+    /// An IO error occured while writing the rendered output
+    Io(std::io::ErrorKind),
     /// This enum may grow additional variants, so this makes sure clients
     /// don't count on exhaustive matching. (Otherwise, adding a new variant
     /// could break existing code.)
@@ -69,6 +74,9 @@ impl fmt::Display for Error {
                 current, parent
             ),
             ErrorKind::TemplateNotFound(ref name) => write!(f, "Template '{}' not found", name),
+            ErrorKind::InvalidTemplateName(ref name) => {
+                write!(f, "Template name '{}' is outside of the template directories", name)
+            }
             ErrorKind::FilterNotFound(ref name) => write!(f, "Filter '{}' not found", name),
             ErrorKind::TestNotFound(ref name) => write!(f, "Test '{}' not found", name),
             ErrorKind::FunctionNotFound(ref name) => write!(f, "Function '{}' not found", name),
@@ -79,6 +87,9 @@ impl fmt::Display for Error {
             ErrorKind::CallFunction(ref name) => write!(f, "Function call '{}' failed", name),
             ErrorKind::CallFilter(ref name) => write!(f, "Filter call '{}' failed", name),
             ErrorKind::CallTest(ref name) => write!(f, "Test call '{}' failed", name),
+            ErrorKind::Io(ref kind) => {
+                write!(f, "Io error while writing the rendered output: {:?}", kind)
+            }
             ErrorKind::__Nonexhaustive => write!(f, "Nonexhaustive"),
         }
     }
@@ -120,6 +131,11 @@ impl Error {
         Self { kind: ErrorKind::TemplateNotFound(tpl.to_string()), source: None }
     }
 
+    /// Creates an invalid template name error
+    pub fn invalid_template_name(tpl: impl ToString) -> Self {
+        Self { kind: ErrorKind::InvalidTemplateName(tpl.to_string()), source: None }
+    }
+
     /// Creates a filter not found error
     pub fn filter_not_found(name: impl ToString) -> Self {
         Self { kind: ErrorKind::FilterNotFound(name.to_string()), source: None }
@@ -169,6 +185,11 @@ impl Error {
         Self { kind: ErrorKind::Json(value), source: None }
     }
 
+    /// Creates an IO error, keeping the original error as the source
+    pub fn io_error(error: std::io::Error) -> Self {
+        Self { kind: ErrorKind::Io(error.kind()), source: Some(Box::new(error)) }
+    }
+
     /// Creates an invalid macro definition error
     pub fn invalid_macro_def(name: impl ToString) -> Self {
         Self { kind: ErrorKind::InvalidMacroDefinition(name.to_string()), source: None }
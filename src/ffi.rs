@@ -0,0 +1,225 @@
+//! A C interface, to embed Tera in programs written in other languages through a `cdylib`.
+//!
+//! Strings are given and returned as NUL-terminated UTF-8. Every function returns a
+//! `TeraStatus` and the message of the last error of the calling thread can be read with
+//! `tera_last_error`.
+use std::cell::RefCell;
+use std::error::Error as StdError;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use serde_json::Value;
+
+use crate::context::Context;
+use crate::tera::Tera;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The result of a call
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TeraStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A pointer given was null
+    NullArgument = 1,
+    /// A string given wasn't valid UTF-8
+    InvalidUtf8 = 2,
+    /// The context isn't a JSON object
+    InvalidContext = 3,
+    /// A template couldn't be parsed or rendered
+    TemplateError = 4,
+    /// Tera panicked, which is a bug
+    Panic = 5,
+}
+
+fn set_last_error(message: String) {
+    // Messages can't contain NUL bytes coming from valid C strings but be safe anyway
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Reads a string argument, the error being already set if it fails
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, TeraStatus> {
+    if s.is_null() {
+        set_last_error(format!("`{}` is null", name));
+        return Err(TeraStatus::NullArgument);
+    }
+    CStr::from_ptr(s).to_str().map_err(|e| {
+        set_last_error(format!("`{}` isn't valid UTF-8: {}", name, e));
+        TeraStatus::InvalidUtf8
+    })
+}
+
+/// Calls `f`, turning its errors and panics into a status
+fn run<F>(f: F) -> TeraStatus
+where
+    F: FnOnce() -> Result<(), TeraStatus>,
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => TeraStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => {
+            set_last_error("Tera panicked".to_string());
+            TeraStatus::Panic
+        }
+    }
+}
+
+/// Sets an error of Tera and its causes as the last error
+fn template_error(e: crate::Error) -> TeraStatus {
+    let mut message = e.to_string();
+    let mut cause = e.source();
+    while let Some(e) = cause {
+        message += &format!("\n{}", e);
+        cause = e.source();
+    }
+    set_last_error(message);
+    TeraStatus::TemplateError
+}
+
+/// Creates an instance without any templates, to free with `tera_free`
+#[no_mangle]
+pub extern "C" fn tera_new() -> *mut Tera {
+    Box::into_raw(Box::new(Tera::default()))
+}
+
+/// Frees an instance created by `tera_new`
+///
+/// # Safety
+/// `tera` must be null or returned by `tera_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tera_free(tera: *mut Tera) {
+    if !tera.is_null() {
+        drop(Box::from_raw(tera));
+    }
+}
+
+/// Adds a template named `name` to the instance
+///
+/// # Safety
+/// `tera` must be returned by `tera_new` and `name` and `content` must be NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn tera_add_template(
+    tera: *mut Tera,
+    name: *const c_char,
+    content: *const c_char,
+) -> TeraStatus {
+    run(|| {
+        let tera = tera.as_mut().ok_or_else(|| {
+            set_last_error("`tera` is null".to_string());
+            TeraStatus::NullArgument
+        })?;
+        let name = read_str(name, "name")?;
+        let content = read_str(content, "content")?;
+        tera.add_raw_template(name, content).map_err(template_error)
+    })
+}
+
+/// Renders the template `name` with the JSON object `context` and sets `output` to the
+/// result, to free with `tera_string_free`. `output` is set to null if there is an error.
+///
+/// # Safety
+/// `tera` must be returned by `tera_new`, `name` and `context` must be NUL-terminated
+/// and `output` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn tera_render_json(
+    tera: *const Tera,
+    name: *const c_char,
+    context: *const c_char,
+    output: *mut *mut c_char,
+) -> TeraStatus {
+    if output.is_null() {
+        set_last_error("`output` is null".to_string());
+        return TeraStatus::NullArgument;
+    }
+    *output = ptr::null_mut();
+
+    run(|| {
+        let tera = tera.as_ref().ok_or_else(|| {
+            set_last_error("`tera` is null".to_string());
+            TeraStatus::NullArgument
+        })?;
+        let name = read_str(name, "name")?;
+        let context = read_str(context, "context")?;
+        let context = serde_json::from_str::<Value>(context)
+            .map_err(crate::Error::json)
+            .and_then(Context::from_value)
+            .map_err(|e| {
+                set_last_error(format!("Invalid context: {}", e));
+                TeraStatus::InvalidContext
+            })?;
+
+        let rendered = tera.render(name, &context).map_err(template_error)?;
+        let rendered = CString::new(rendered).map_err(|_| {
+            set_last_error(format!("The render of `{}` contains a NUL byte", name));
+            TeraStatus::TemplateError
+        })?;
+        *output = rendered.into_raw();
+        Ok(())
+    })
+}
+
+/// Frees a string returned by `tera_render_json`
+///
+/// # Safety
+/// `s` must be null or returned by `tera_render_json` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tera_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The message of the last error of the calling thread, or null if there wasn't any.
+/// It is valid until the next call failing on that thread.
+#[no_mangle]
+pub extern "C" fn tera_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    #[test]
+    fn can_render_through_ffi() {
+        unsafe {
+            let tera = tera_new();
+            let status =
+                tera_add_template(tera, c("hello").as_ptr(), c("Hello {{ name }}").as_ptr());
+            assert_eq!(status, TeraStatus::Ok);
+
+            let mut output = ptr::null_mut();
+            let context = c(r#"{"name": "Bob"}"#);
+            let status = tera_render_json(tera, c("hello").as_ptr(), context.as_ptr(), &mut output);
+            assert_eq!(status, TeraStatus::Ok);
+            assert_eq!(CStr::from_ptr(output).to_str().unwrap(), "Hello Bob");
+            tera_string_free(output);
+
+            let status = tera_render_json(tera, c("hello").as_ptr(), c("[]").as_ptr(), &mut output);
+            assert_eq!(status, TeraStatus::InvalidContext);
+            assert!(output.is_null());
+
+            let status = tera_render_json(tera, c("nope").as_ptr(), c("{}").as_ptr(), &mut output);
+            assert_eq!(status, TeraStatus::TemplateError);
+            let error = CStr::from_ptr(tera_last_error()).to_str().unwrap();
+            assert!(error.contains("Template 'nope' not found"));
+
+            let status = tera_add_template(tera, ptr::null(), c("").as_ptr());
+            assert_eq!(status, TeraStatus::NullArgument);
+            tera_free(tera);
+        }
+    }
+}
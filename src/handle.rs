@@ -0,0 +1,132 @@
+//! Sharing a Tera instance between threads while still being able to reload it.
+use std::sync::{Arc, Mutex};
+
+use arc_swap::ArcSwap;
+
+use crate::context::Context;
+use crate::errors::Result;
+use crate::tera::Tera;
+
+/// A Tera instance that can be reloaded while other threads are rendering with it.
+///
+/// Renders never wait: they use the instance that was current when they started, even if it
+/// gets replaced before they are done. Reloads work on a copy of the current instance that is
+/// only published once it has been built successfully, so a template with an error doesn't
+/// affect the renders.
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use tera::{Context, Tera, TeraHandle};
+/// let mut tera = Tera::default();
+/// tera.add_raw_template("hello", "Hello").unwrap();
+/// let handle = Arc::new(TeraHandle::new(tera));
+///
+/// // Given to every worker thread
+/// let worker_handle = Arc::clone(&handle);
+/// assert_eq!(worker_handle.render("hello", &Context::new()).unwrap(), "Hello");
+///
+/// handle.update(|tera| tera.add_raw_template("hello", "Bonjour")).unwrap();
+/// assert_eq!(worker_handle.render("hello", &Context::new()).unwrap(), "Bonjour");
+/// ```
+pub struct TeraHandle {
+    current: ArcSwap<Tera>,
+    // Updates are made one at a time so none of them is lost
+    update_lock: Mutex<()>,
+}
+
+impl TeraHandle {
+    /// Creates a handle publishing the given instance
+    pub fn new(tera: Tera) -> Self {
+        TeraHandle { current: ArcSwap::from_pointee(tera), update_lock: Mutex::new(()) }
+    }
+
+    /// Returns the current instance. It stays usable as long as needed, even if it gets
+    /// replaced in the meantime: use it when several templates must be rendered with
+    /// the same version.
+    pub fn get(&self) -> Arc<Tera> {
+        self.current.load_full()
+    }
+
+    /// Renders a template with the current instance, see [Tera::render](struct.Tera.html#method.render)
+    pub fn render(&self, template_name: &str, context: &Context) -> Result<String> {
+        self.current.load().render(template_name, context)
+    }
+
+    /// Re-parses all the templates found in the glob of the current instance and publishes the
+    /// result, see [Tera::full_reload](struct.Tera.html#method.full_reload).
+    /// The current instance is kept if there is an error.
+    pub fn full_reload(&self) -> Result<()> {
+        self.update(|tera| tera.full_reload())
+    }
+
+    /// Calls `f` on a copy of the current instance and publishes it if `f` succeeds.
+    /// The current instance is kept if there is an error.
+    pub fn update<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Tera) -> Result<()>,
+    {
+        let _guard = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut tera = Tera::clone(&self.current.load());
+        f(&mut tera)?;
+        self.current.store(Arc::new(tera));
+        Ok(())
+    }
+
+    /// Replaces the current instance
+    pub fn set(&self, tera: Tera) {
+        let _guard = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.current.store(Arc::new(tera));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::thread;
+
+    use tempfile::tempdir;
+
+    use super::TeraHandle;
+    use crate::context::Context;
+    use crate::tera::Tera;
+
+    #[test]
+    fn full_reload_keeps_the_instances_in_use() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("hello.html");
+        File::create(&path).unwrap().write_all(b"Hello").unwrap();
+        let tera = Tera::new(&format!("{}/*.html", dir.path().display())).unwrap();
+        let handle = TeraHandle::new(tera);
+
+        let before = handle.get();
+        File::create(&path).unwrap().write_all(b"Bonjour").unwrap();
+        handle.full_reload().unwrap();
+
+        assert_eq!(before.render("hello.html", &Context::new()).unwrap(), "Hello");
+        assert_eq!(handle.render("hello.html", &Context::new()).unwrap(), "Bonjour");
+
+        // A template with an error is not published
+        File::create(&path).unwrap().write_all(b"{{ hey").unwrap();
+        assert!(handle.full_reload().is_err());
+        assert_eq!(handle.render("hello.html", &Context::new()).unwrap(), "Bonjour");
+    }
+
+    #[test]
+    fn updates_are_not_lost() {
+        let handle = Arc::new(TeraHandle::new(Tera::default()));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let handle = Arc::clone(&handle);
+                thread::spawn(move || {
+                    handle.update(|tera| tera.add_raw_template(&i.to_string(), "a")).unwrap()
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(handle.get().templates.len(), 8);
+    }
+}
@@ -0,0 +1,29 @@
+//! Following the progress of renders, for example to trace them
+use crate::parser::lexer::Span;
+
+/// Whether a hook is called before or after rendering something
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HookPhase {
+    /// Called before it is rendered
+    Enter,
+    /// Called after it is rendered, even if that failed
+    Exit,
+}
+
+/// Callbacks called by the renderer as it goes through templates, set with `Tera::set_render_hooks`.
+///
+/// Every hook is called with `HookPhase::Enter` and then with `HookPhase::Exit` for the same
+/// arguments, with the span of the node being rendered and the name of the template it is in.
+/// All of them do nothing by default.
+pub trait RenderHooks: Sync + Send {
+    /// Called for every node of the templates rendered, `kind` being the name of its tag
+    /// such as `for` or `include`, `variable` for `{{ }}` and `text` for the text in between
+    fn on_node(&self, _phase: HookPhase, _kind: &str, _span: &Span, _template: &str) {}
+
+    /// Called when the template `name` is included, inside the `on_node` calls of the include
+    fn on_include(&self, _phase: HookPhase, _name: &str, _span: &Span, _template: &str) {}
+
+    /// Called when the filter `name` is applied to a value, the span being the one of the node
+    /// containing the filter
+    fn on_filter(&self, _phase: HookPhase, _name: &str, _span: &Span, _template: &str) {}
+}
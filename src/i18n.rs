@@ -0,0 +1,228 @@
+//! Translating the messages of the `{% trans %}` tags
+use std::collections::HashMap;
+
+/// Something that can translate the messages of `{% trans %}` tags.
+///
+/// The message is the content of the tag, with its variables written as `{{ name }}`:
+/// `{% trans %}Hello {{ name }}!{% endtrans %}` is translated as `Hello {{ name }}!`.
+/// Those placeholders can be moved around in the translation and are replaced by the
+/// variable values afterwards.
+///
+/// The language is the `locale` of the `RenderOptions` of the render if it is set, otherwise
+/// the value of the `lang` variable of the context, if any.
+///
+/// Messages with a plural form, `{% trans count=n %}One item{% pluralize %}{{ count }} items{% endtrans %}`,
+/// are translated with `translate_plural` instead.
+pub trait Translator: Sync + Send {
+    /// Returns the translation of the message, or `None` to render the message as is
+    fn translate(&self, lang: Option<&str>, message: &str) -> Option<String>;
+
+    /// Returns the translation of a message with a plural form, picking the right form
+    /// for `count` according to the plural rules of the language.
+    ///
+    /// By default the form is picked with the English rules (singular only for 1) and
+    /// translated with `translate`: implement it for languages with other plural rules.
+    fn translate_plural(
+        &self,
+        lang: Option<&str>,
+        singular: &str,
+        plural: &str,
+        count: u64,
+    ) -> Option<String> {
+        self.translate(lang, if count == 1 { singular } else { plural })
+    }
+}
+
+impl<F> Translator for F
+where
+    F: Fn(Option<&str>, &str) -> Option<String> + Sync + Send,
+{
+    fn translate(&self, lang: Option<&str>, message: &str) -> Option<String> {
+        self(lang, message)
+    }
+}
+
+/// A simple message catalog: `lang -> message -> translation`
+impl Translator for HashMap<String, HashMap<String, String>> {
+    fn translate(&self, lang: Option<&str>, message: &str) -> Option<String> {
+        self.get(lang?)?.get(message).cloned()
+    }
+}
+
+/// Replaces the `{{ name }}` placeholders of a translated message by their value.
+/// Unknown placeholders are kept as they are.
+pub(crate) fn fill_placeholders(message: &str, values: &HashMap<&str, String>) -> String {
+    let mut res = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        res.push_str(&rest[..start]);
+        match values.get(rest[start + 2..end].trim()) {
+            Some(value) => res.push_str(value),
+            None => res.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    res.push_str(rest);
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{fill_placeholders, Translator};
+    use crate::context::Context;
+    use crate::tera::Tera;
+
+    fn catalog() -> HashMap<String, HashMap<String, String>> {
+        let mut fr = HashMap::new();
+        fr.insert("Hello {{ name }}!".to_string(), "Bonjour {{ name }} !".to_string());
+        fr.insert(
+            "{{ count }} messages for {{ name }}".to_string(),
+            "{{name}} a {{count}} messages".to_string(),
+        );
+        let mut catalog = HashMap::new();
+        catalog.insert("fr".to_string(), fr);
+        catalog
+    }
+
+    #[test]
+    fn can_fill_placeholders() {
+        let mut values = HashMap::new();
+        values.insert("name", "Bob".to_string());
+        assert_eq!(fill_placeholders("Hi {{ name }}, {{name}}", &values), "Hi Bob, Bob");
+        assert_eq!(fill_placeholders("{{ other }} {{ name", &values), "{{ other }} {{ name");
+    }
+
+    #[test]
+    fn can_translate_messages() {
+        let mut tera = Tera::default();
+        tera.set_translator(catalog());
+        tera.add_raw_templates(vec![
+            ("hello", "{% trans %}Hello {{ name }}!{% endtrans %}"),
+            ("count", "{%- trans -%} {{ count }} messages for {{ name }} {%- endtrans %}"),
+            ("missing", "{% trans %}Goodbye {{ name }}{% endtrans %}"),
+        ])
+        .unwrap();
+
+        let mut context = Context::new();
+        context.insert("name", "Bob");
+        context.insert("count", &2);
+        assert_eq!(tera.render("hello", &context).unwrap(), "Hello Bob!");
+
+        context.insert("lang", "fr");
+        assert_eq!(tera.render("hello", &context).unwrap(), "Bonjour Bob !");
+        assert_eq!(tera.render("count", &context).unwrap(), "Bob a 2 messages");
+        assert_eq!(tera.render("missing", &context).unwrap(), "Goodbye Bob");
+    }
+
+    #[test]
+    fn can_use_closure_as_translator() {
+        let mut tera = Tera::default();
+        tera.set_translator(|_: Option<&str>, message: &str| Some(message.replace("Hello", "Hi")));
+        tera.add_raw_template("hello.html", "{% trans %}Hello {{ name }}{% endtrans %}").unwrap();
+
+        let mut context = Context::new();
+        context.insert("name", "<b>");
+        // the variables values are escaped like in variable blocks
+        assert_eq!(tera.render("hello.html", &context).unwrap(), "Hi &lt;b&gt;");
+    }
+
+    #[test]
+    fn can_translate_plural_forms() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "items",
+            "{% trans count=items | length %}One {{ kind }}{% pluralize %}{{ count }} {{ kind }}s{% endtrans %}",
+        )
+        .unwrap();
+        let mut context = Context::new();
+        context.insert("kind", "apple");
+        context.insert("items", &[1]);
+        assert_eq!(tera.render("items", &context).unwrap(), "One apple");
+        context.insert("items", &[1, 2, 3]);
+        assert_eq!(tera.render("items", &context).unwrap(), "3 apples");
+
+        // The default rules translate the form picked for the count
+        let mut catalog = catalog();
+        catalog.get_mut("fr").unwrap().insert(
+            "{{ count }} {{ kind }}s".to_string(),
+            "{{ count }} {{ kind }}s (fr)".to_string(),
+        );
+        tera.set_translator(catalog);
+        context.insert("lang", "fr");
+        assert_eq!(tera.render("items", &context).unwrap(), "3 apples (fr)");
+    }
+
+    #[test]
+    fn can_use_custom_plural_rules() {
+        struct Polish;
+        impl Translator for Polish {
+            fn translate(&self, _: Option<&str>, _: &str) -> Option<String> {
+                None
+            }
+
+            fn translate_plural(
+                &self,
+                _: Option<&str>,
+                _: &str,
+                _: &str,
+                count: u64,
+            ) -> Option<String> {
+                let form = match (count % 10, count % 100) {
+                    _ if count == 1 => "plik",
+                    (2..=4, r) if !(12..=14).contains(&r) => "pliki",
+                    _ => "plików",
+                };
+                Some(format!("{{{{ count }}}} {}", form))
+            }
+        }
+
+        let mut tera = Tera::default();
+        tera.set_translator(Polish);
+        tera.add_raw_template(
+            "files",
+            "{% trans count=n %}{{ count }} file{% pluralize %}{{ count }} files{% endtrans %}",
+        )
+        .unwrap();
+        let mut context = Context::new();
+        for (n, expected) in
+            &[(1, "1 plik"), (3, "3 pliki"), (5, "5 plików"), (22, "22 pliki"), (12, "12 plików")]
+        {
+            context.insert("n", n);
+            assert_eq!(tera.render("files", &context).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn errors_on_invalid_count() {
+        let mut tera = Tera::default();
+        assert!(tera.add_raw_template("a", "{% trans %}a{% pluralize %}b{% endtrans %}").is_err());
+        tera.add_raw_template("b", "{% trans count=n %}a{% pluralize %}b{% endtrans %}").unwrap();
+        let mut context = Context::new();
+        context.insert("n", &-1);
+        assert!(tera.render("b", &context).is_err());
+    }
+
+    #[test]
+    fn errors_on_missing_variable() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello", "{% trans %}Hello {{ name }}{% endtrans %}").unwrap();
+        assert!(tera.render("hello", &Context::new()).is_err());
+    }
+
+    #[test]
+    fn only_plain_variables_are_allowed() {
+        let mut tera = Tera::default();
+        assert!(tera.add_raw_template("a", "{% trans %}{{ name | upper }}{% endtrans %}").is_err());
+        assert!(tera
+            .add_raw_template("b", "{% trans %}{% if a %}a{% endif %}{% endtrans %}")
+            .is_err());
+    }
+}
@@ -0,0 +1,1042 @@
+//! Static analysis of templates, without rendering them.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde_json::{to_value, Value};
+
+use crate::context::Context;
+use crate::errors::Result;
+use crate::parser::ast::{Block, CustomTag, Embed, Expr, ExprVal, FunctionCall, MacroCall, Node};
+use crate::parser::lexer::Span;
+use crate::template::Template;
+use crate::tera::Tera;
+use crate::warnings::{Deprecation, Warning};
+
+/// Finds the top-level context variables used when rendering the given template.
+///
+/// Variables defined in the template itself (`set`, forloop values, `loop`) are not included,
+/// and neither are the ones only used with a `default` filter or the `defined`/`undefined` tests
+/// since those are optional.
+pub(crate) fn required_variables(tera: &Tera, template: &Template) -> Result<BTreeSet<String>> {
+    Ok(find_variables(tera, template)?.found)
+}
+
+/// Checks that the context has all the variables required by the given template, and that the
+/// ones accessed with `.` or `[]` are not scalars, returning a message for each problem found
+pub(crate) fn validate_context(
+    tera: &Tera,
+    template: &Template,
+    context: &Context,
+) -> Result<Vec<String>> {
+    let finder = find_variables(tera, template)?;
+    let mut problems = vec![];
+
+    for name in &finder.found {
+        let value = match context.get(name) {
+            Some(value) => value,
+            None => {
+                problems.push(format!("Variable `{}` is missing", name));
+                continue;
+            }
+        };
+        if let Some(access) = finder.accessed.get(name) {
+            let kind = match *value {
+                Value::Null => "null",
+                Value::Bool(_) => "a boolean",
+                Value::Number(_) => "a number",
+                Value::String(_) => "a string",
+                Value::Array(_) | Value::Object(_) => continue,
+            };
+            problems.push(format!(
+                "Variable `{}` is used as an array or an object (`{}`) but is {}",
+                name, access, kind
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+fn find_variables<'a>(tera: &'a Tera, template: &'a Template) -> Result<VariableFinder<'a>> {
+    let mut finder = VariableFinder {
+        tera,
+        template,
+        scopes: vec![HashSet::new()],
+        includes: vec![],
+        found: BTreeSet::new(),
+        accessed: BTreeMap::new(),
+    };
+
+    // With inheritance, only the AST of the top-most parent is rendered
+    let root = match template.parents.last() {
+        Some(name) => tera.get_template(name)?,
+        None => template,
+    };
+    finder.visit_body(&root.ast)?;
+
+    Ok(finder)
+}
+
+struct VariableFinder<'a> {
+    tera: &'a Tera,
+    /// The template being analyzed, used to find the right block definitions
+    template: &'a Template,
+    /// Variables defined in the template, innermost scope last
+    scopes: Vec<HashSet<String>>,
+    /// The templates currently being included, to avoid looping forever
+    includes: Vec<&'a str>,
+    found: BTreeSet<String>,
+    /// The first use of the variables accessed with `.` or `[]`, by name
+    accessed: BTreeMap<String, String>,
+}
+
+impl<'a> VariableFinder<'a> {
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|s| s.contains(name))
+    }
+
+    fn define(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().insert(name.to_string());
+    }
+
+    fn visit_scoped_body(&mut self, body: &'a [Node], locals: &[&str]) -> Result<()> {
+        self.scopes.push(locals.iter().map(|l| l.to_string()).collect());
+        let res = self.visit_body(body);
+        self.scopes.pop();
+        res
+    }
+
+    fn visit_body(&mut self, body: &'a [Node]) -> Result<()> {
+        for node in body {
+            match *node {
+                Node::VariableBlock(_, ref expr, _)
+                | Node::Do(_, ref expr, _)
+                | Node::DynamicExtends(_, ref expr, _) => self.visit_expr(expr),
+                Node::Set(_, ref set, _) => {
+                    self.visit_expr(&set.value);
+                    if set.global {
+                        self.scopes[0].insert(set.key.to_string());
+                    } else if !set.key.contains('.') {
+                        // `ns.attribute` modifies an existing variable
+                        self.define(&set.key);
+                    }
+                }
+                Node::FilterSection(_, ref section, _, _) => {
+                    self.visit_fn_call(&section.filter);
+                    self.visit_body(&section.body)?;
+                }
+                Node::Spaceless(_, ref body, _, _) => self.visit_body(body)?,
+                Node::With(_, ref with, _, _) => {
+                    for (_, expr) in &with.assignments {
+                        self.visit_expr(expr);
+                    }
+                    let locals: Vec<_> = with.assignments.iter().map(|(k, _)| k.as_str()).collect();
+                    self.visit_scoped_body(&with.body, &locals)?;
+                }
+                Node::Cache(_, ref cache, _, _) => {
+                    self.visit_expr(&cache.key);
+                    if let Some(ref ttl) = cache.ttl {
+                        self.visit_expr(ttl);
+                    }
+                    self.visit_body(&cache.body)?;
+                }
+                Node::CallBlock(_, ref call_block, _, _) => {
+                    for arg in call_block.call.args.values().chain(&call_block.call.positional_args)
+                    {
+                        self.visit_expr(arg);
+                    }
+                    self.visit_body(&call_block.body)?;
+                }
+                Node::CustomTag(_, ref tag, _, _) => {
+                    for arg in tag.args.values() {
+                        self.visit_expr(arg);
+                    }
+                    if let Some(ref body) = tag.body {
+                        self.visit_body(body)?;
+                    }
+                }
+                Node::Trans(_, ref trans, _, _) => {
+                    if let Some(ref count) = trans.count {
+                        self.visit_expr(count);
+                    }
+                    for (_, expr) in &trans.variables {
+                        self.visit_expr(expr);
+                    }
+                }
+                Node::Forloop(_, ref forloop, _, _) => {
+                    self.visit_expr(&forloop.container);
+                    let mut locals = vec!["loop", forloop.value.as_str()];
+                    if let Some(ref key) = forloop.key {
+                        locals.push(key);
+                    }
+                    if let Some(ref condition) = forloop.condition {
+                        self.scopes.push(locals.iter().map(|l| l.to_string()).collect());
+                        self.visit_expr(condition);
+                        self.scopes.pop();
+                    }
+                    self.visit_scoped_body(&forloop.body, &locals)?;
+                    if let Some(ref empty_body) = forloop.empty_body {
+                        self.visit_body(empty_body)?;
+                    }
+                }
+                Node::While(_, ref while_loop, _, _) => {
+                    self.visit_expr(&while_loop.condition);
+                    self.visit_body(&while_loop.body)?;
+                }
+                Node::If(ref if_node, _, _) => {
+                    for (_, expr, body) in &if_node.conditions {
+                        self.visit_expr(expr);
+                        self.visit_body(body)?;
+                    }
+                    if let Some((_, ref body)) = if_node.otherwise {
+                        self.visit_body(body)?;
+                    }
+                }
+                Node::Switch(_, ref switch, _, _) => {
+                    self.visit_expr(&switch.value);
+                    for (_, values, body) in &switch.cases {
+                        for value in values {
+                            self.visit_expr(value);
+                        }
+                        self.visit_body(body)?;
+                    }
+                    if let Some((_, ref body)) = switch.default {
+                        self.visit_body(body)?;
+                    }
+                }
+                Node::Debug(_, Some(ref name), _) => self.visit_ident(name),
+                Node::Block(_, ref block, _, _) => self.visit_block(&block.name, 0)?,
+                Node::Embed(_, ref embed, _, _) => {
+                    for node in &embed.blocks {
+                        if let Node::Block(_, ref block, _, _) = *node {
+                            self.visit_scoped_body(&block.body, &[])?;
+                        }
+                    }
+                    if self.includes.contains(&embed.name.as_str()) {
+                        continue;
+                    }
+                    let included = self.tera.get_template(&embed.name)?;
+                    self.includes.push(&embed.name);
+                    let res = self.visit_body(&included.ast);
+                    self.includes.pop();
+                    res?;
+                }
+                Node::Include(_, ref include, _) => {
+                    for (_, expr) in &include.variables {
+                        self.visit_expr(expr);
+                    }
+                    // With `only`, the included template can't use the context
+                    if include.only
+                        || self.includes.contains(&include.name.as_str())
+                        || include.ignore_missing
+                            && !self.tera.templates.contains_key(&include.name)
+                    {
+                        continue;
+                    }
+                    let included = self.tera.get_template(&include.name)?;
+                    let locals: Vec<_> =
+                        include.variables.iter().map(|(k, _)| k.as_str()).collect();
+                    self.includes.push(&include.name);
+                    let res = self.visit_scoped_body(&included.ast, &locals);
+                    self.includes.pop();
+                    res?;
+                }
+                // Macros do not have access to the context, only to their arguments
+                // and are only visited through their calls
+                Node::MacroDefinition(..)
+                | Node::Super(_)
+                | Node::Text(..)
+                | Node::Raw(..)
+                | Node::Extends(..)
+                | Node::ImportMacro(..)
+                | Node::Break(..)
+                | Node::Continue(..)
+                | Node::Debug(_, None, _) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Visits the definition of a block at the given level of the inheritance chain,
+    /// the level being increased for each `super()` call
+    fn visit_block(&mut self, name: &str, level: usize) -> Result<()> {
+        let definition = self.block_definitions(name).into_iter().nth(level);
+        let block = match definition {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        self.scopes.push(HashSet::new());
+        let res = self.visit_block_body(name, &block.body, level);
+        self.scopes.pop();
+        res
+    }
+
+    fn visit_block_body(&mut self, name: &str, body: &'a [Node], level: usize) -> Result<()> {
+        for node in body {
+            if let Node::Super(_) = *node {
+                self.visit_block(name, level + 1)?;
+            } else {
+                // `super()` can only be called directly in the block body
+                self.visit_body(std::slice::from_ref(node))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All the definitions of that block, from the template being analyzed to the top-most parent
+    fn block_definitions(&self, name: &str) -> Vec<&'a Block> {
+        let template = self.template;
+        let mut definitions: Vec<_> = template.blocks.get(name).into_iter().collect();
+        for parent in &template.parents {
+            if let Some(block) =
+                self.tera.get_template(parent).ok().and_then(|t| t.blocks.get(name))
+            {
+                definitions.push(block);
+            }
+        }
+        definitions
+    }
+
+    fn visit_ident(&mut self, ident: &str) {
+        let end = ident.find(&['.', '[', '?'][..]).unwrap_or(ident.len());
+        let name = &ident[..end];
+        if !self.is_local(name) && name != "__tera_context" {
+            self.found.insert(name.to_string());
+            if ident[end..].starts_with(&['.', '['][..]) {
+                self.accessed.entry(name.to_string()).or_insert_with(|| ident.to_string());
+            }
+        }
+
+        // Variables used as indices or slice bounds: `items[index]`, `items[start:]`
+        for part in ident.split('[').skip(1) {
+            let inner = part.split(']').next().unwrap_or("");
+            if inner.starts_with(&['"', '\'', '`'][..]) {
+                continue;
+            }
+            for bound in inner.split(':') {
+                if !bound.is_empty() && bound.parse::<i64>().is_err() {
+                    self.visit_ident(bound);
+                }
+            }
+        }
+    }
+
+    fn visit_fn_call(&mut self, call: &FunctionCall) {
+        for arg in call.args.values() {
+            self.visit_expr(arg);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        for filter in &expr.filters {
+            self.visit_fn_call(filter);
+        }
+        if expr.has_default_filter() {
+            if let ExprVal::Ident(_) = expr.val {
+                return;
+            }
+        }
+        self.visit_expr_val(&expr.val);
+    }
+
+    fn visit_expr_val(&mut self, val: &ExprVal) {
+        match *val {
+            ExprVal::Ident(ref ident) => self.visit_ident(ident),
+            ExprVal::Math(ref e) => {
+                self.visit_expr(&e.lhs);
+                self.visit_expr(&e.rhs);
+            }
+            ExprVal::Logic(ref e) => {
+                self.visit_expr(&e.lhs);
+                self.visit_expr(&e.rhs);
+            }
+            ExprVal::Test(ref test) => {
+                match test.call {
+                    Some(ref call) => self.visit_fn_call(call),
+                    None if test.name != "defined" && test.name != "undefined" => {
+                        self.visit_ident(&test.ident)
+                    }
+                    None => (),
+                }
+                for arg in &test.args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::MacroCall(ref call) => {
+                for arg in call.args.values().chain(&call.positional_args) {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::FunctionCall(ref call) => self.visit_fn_call(call),
+            ExprVal::Array(ref values) => {
+                for value in values {
+                    self.visit_expr(value);
+                }
+            }
+            ExprVal::Map(ref entries) => {
+                for (_, value) in entries {
+                    self.visit_expr(value);
+                }
+            }
+            ExprVal::StringConcat(ref concat) => {
+                for value in &concat.values {
+                    self.visit_expr_val(value);
+                }
+            }
+            ExprVal::In(ref e) => {
+                self.visit_expr(&e.lhs);
+                self.visit_expr(&e.rhs);
+            }
+            // Like with `default`, the variables before the last one can be undefined
+            ExprVal::Coalesce(ref values) => {
+                let (last, rest) = values.split_last().unwrap();
+                for value in rest {
+                    for filter in &value.filters {
+                        self.visit_fn_call(filter);
+                    }
+                    if let ExprVal::Ident(_) = value.val {
+                        continue;
+                    }
+                    self.visit_expr_val(&value.val);
+                }
+                self.visit_expr(last);
+            }
+            ExprVal::String(_)
+            | ExprVal::Int(_)
+            | ExprVal::Float(_)
+            | ExprVal::Bool(_)
+            | ExprVal::Null => (),
+        }
+    }
+}
+
+/// A block defined in a template
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockInfo {
+    /// The block name
+    pub name: String,
+    /// Whether the block calls `{{ super() }}` to render the parent block content
+    pub calls_super: bool,
+}
+
+/// A block of a template once its inheritance chain is resolved
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedBlock {
+    /// The block name
+    pub name: String,
+    /// The template whose definition of the block is rendered: the template itself or the
+    /// closest parent defining it
+    pub template: String,
+    /// The parent templates whose definition is overridden by it, from the closest to the base
+    /// template. They are only rendered through `{{ super() }}`.
+    pub overrides: Vec<String>,
+}
+
+/// An argument of a macro
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacroArgument {
+    /// The argument name
+    pub name: String,
+    /// The default value if there is one, in which case the argument is optional
+    pub default: Option<Value>,
+}
+
+/// The signature of a macro defined in a template
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacroSignature {
+    /// The macro name
+    pub name: String,
+    /// The macro arguments, sorted by name
+    pub args: Vec<MacroArgument>,
+}
+
+fn calls_super(body: &[Node]) -> bool {
+    body.iter().any(|node| match *node {
+        Node::Super(_) => true,
+        // A nested block calling super() refers to its own parent
+        Node::Block(..) => false,
+        _ => node.bodies().into_iter().any(|b| calls_super(b)),
+    })
+}
+
+/// All the blocks defined in that template, including nested ones, sorted by name
+pub(crate) fn blocks(template: &Template) -> Vec<BlockInfo> {
+    let mut blocks: Vec<_> = template
+        .blocks
+        .values()
+        .map(|b| BlockInfo { name: b.name.clone(), calls_super: calls_super(&b.body) })
+        .collect();
+    blocks.sort_by(|a, b| a.name.cmp(&b.name));
+    blocks
+}
+
+/// All the blocks of that template and of the templates it extends, with the template each of
+/// them is rendered from, sorted by name
+pub(crate) fn resolved_blocks(tera: &Tera, template: &Template) -> Result<Vec<ResolvedBlock>> {
+    let mut blocks: BTreeMap<&str, ResolvedBlock> = BTreeMap::new();
+    let mut chain = vec![template];
+    for parent in &template.parents {
+        chain.push(tera.get_template(parent)?);
+    }
+
+    for tpl in chain {
+        for name in tpl.blocks.keys() {
+            match blocks.get_mut(name.as_str()) {
+                Some(block) => block.overrides.push(tpl.name.clone()),
+                None => {
+                    let block = ResolvedBlock {
+                        name: name.clone(),
+                        template: tpl.name.clone(),
+                        overrides: vec![],
+                    };
+                    blocks.insert(name, block);
+                }
+            }
+        }
+    }
+
+    Ok(blocks.into_iter().map(|(_, block)| block).collect())
+}
+
+/// All the macros defined in that template, sorted by name
+pub(crate) fn macros(template: &Template) -> Vec<MacroSignature> {
+    let mut macros: Vec<_> = template
+        .macros
+        .values()
+        .map(|m| {
+            let mut args: Vec<_> = m
+                .args
+                .iter()
+                .map(|(name, default)| MacroArgument {
+                    name: name.clone(),
+                    default: default.as_ref().and_then(|d| literal_value(&d.val)),
+                })
+                .collect();
+            args.sort_by(|a, b| a.name.cmp(&b.name));
+            MacroSignature { name: m.name.clone(), args }
+        })
+        .collect();
+    macros.sort_by(|a, b| a.name.cmp(&b.name));
+    macros
+}
+
+/// Macro default arguments can only be literals
+fn literal_value(val: &ExprVal) -> Option<Value> {
+    match *val {
+        ExprVal::String(ref s) => to_value(s).ok(),
+        ExprVal::Int(i) => to_value(i).ok(),
+        ExprVal::Float(f) => to_value(f).ok(),
+        ExprVal::Bool(b) => to_value(b).ok(),
+        ExprVal::Null => Some(Value::Null),
+        _ => None,
+    }
+}
+
+/// Finds the templates, filters, functions, tests and macros used by that template that don't
+/// exist in the Tera instance, returning a message for each of them
+pub(crate) fn missing_references(tera: &Tera, template: &Template) -> Vec<String> {
+    let mut namespaces = HashMap::new();
+    collect_macro_namespaces(tera, template, &mut namespaces, &mut vec![]);
+    let mut checker = ReferenceChecker::new(tera, Some(namespaces));
+
+    for name in template.direct_dependencies() {
+        if tera.get_template(name).is_err() && !template.optional_includes.iter().any(|i| i == name)
+        {
+            checker.problems.push(format!("template `{}` not found", name));
+        }
+    }
+    checker.visit_body(&template.ast);
+
+    checker.problems
+}
+
+/// Like `missing_references` but only for the filters, functions, tests and tags, which don't
+/// depend on the other templates and can be checked as soon as the template is parsed
+pub(crate) fn missing_callables(tera: &Tera, template: &Template) -> Vec<String> {
+    let mut checker = ReferenceChecker::new(tera, None);
+    checker.visit_body(&template.ast);
+    checker.problems
+}
+
+/// The uses of deprecated filters and templates in that template, in order
+pub(crate) fn deprecations(tera: &Tera, template: &Template) -> Vec<Warning> {
+    let mut checker = ReferenceChecker::new(tera, None);
+    checker.visit_body(&template.ast);
+    checker
+        .deprecations
+        .into_iter()
+        .map(|(span, deprecation)| Warning {
+            template: template.name.clone(),
+            span: Some(span),
+            deprecation,
+        })
+        .collect()
+}
+
+/// The templates the macros of each namespace come from, in the same way the renderer finds
+/// them: imports of the template first, then the ones of the imported files and of the parents
+fn collect_macro_namespaces<'a>(
+    tera: &'a Tera,
+    template: &'a Template,
+    namespaces: &mut HashMap<&'a str, &'a Template>,
+    visited: &mut Vec<&'a str>,
+) {
+    if visited.contains(&template.name.as_str()) {
+        return;
+    }
+    visited.push(&template.name);
+
+    if !template.macros.is_empty() {
+        namespaces.entry("self").or_insert(template);
+    }
+    for (filename, namespace) in &template.imported_macro_files {
+        if let Ok(macro_tpl) = tera.get_template(filename) {
+            namespaces.entry(namespace).or_insert(macro_tpl);
+        }
+    }
+    let others =
+        template.imported_macro_files.iter().map(|(name, _)| name).chain(&template.parents);
+    for name in others {
+        if let Ok(other) = tera.get_template(name) {
+            collect_macro_namespaces(tera, other, namespaces, visited);
+        }
+    }
+}
+
+struct ReferenceChecker<'a> {
+    tera: &'a Tera,
+    // The macro namespaces available, `None` if the macro calls are not checked
+    namespaces: Option<HashMap<&'a str, &'a Template>>,
+    // The span of the node being visited, to locate the problems
+    span: Span,
+    problems: Vec<String>,
+    // The deprecated filters and templates used, where they are used
+    deprecations: Vec<(Span, Deprecation)>,
+}
+
+impl<'a> ReferenceChecker<'a> {
+    fn new(tera: &'a Tera, namespaces: Option<HashMap<&'a str, &'a Template>>) -> Self {
+        ReferenceChecker {
+            tera,
+            namespaces,
+            span: Span::default(),
+            problems: vec![],
+            deprecations: vec![],
+        }
+    }
+
+    fn location(&self) -> String {
+        format!("at line {}, column {}", self.span.start.line, self.span.start.column)
+    }
+
+    fn report(&mut self, problem: String) {
+        let problem = format!("{} {}", problem, self.location());
+        self.problems.push(problem);
+    }
+
+    fn visit_body(&mut self, body: &[Node]) {
+        for node in body {
+            self.span = *node.span();
+            match *node {
+                Node::VariableBlock(_, ref expr, _)
+                | Node::Do(_, ref expr, _)
+                | Node::DynamicExtends(_, ref expr, _) => self.visit_expr(expr),
+                Node::Set(_, ref set, _) => self.visit_expr(&set.value),
+                Node::FilterSection(_, ref section, _, _) => self.visit_filter(&section.filter),
+                Node::With(_, ref with, _, _) => {
+                    for (_, expr) in &with.assignments {
+                        self.visit_expr(expr);
+                    }
+                }
+                Node::Cache(_, ref cache, _, _) => {
+                    self.visit_expr(&cache.key);
+                    if let Some(ref ttl) = cache.ttl {
+                        self.visit_expr(ttl);
+                    }
+                }
+                Node::CallBlock(_, ref call_block, _, _) => self.visit_macro_call(&call_block.call),
+                Node::CustomTag(_, ref tag, _, _) => self.visit_custom_tag(tag),
+                Node::Include(_, ref include, _) => {
+                    for (_, expr) in &include.variables {
+                        self.visit_expr(expr);
+                    }
+                    self.visit_template_use(&include.name);
+                }
+                Node::Extends(_, ref name, _)
+                | Node::ImportMacro(_, ref name, _, _)
+                | Node::Embed(_, Embed { ref name, .. }, _, _) => self.visit_template_use(name),
+                Node::Trans(_, ref trans, _, _) => {
+                    if let Some(ref count) = trans.count {
+                        self.visit_expr(count);
+                    }
+                    for (_, expr) in &trans.variables {
+                        self.visit_expr(expr);
+                    }
+                }
+                Node::Forloop(_, ref forloop, _, _) => {
+                    self.visit_expr(&forloop.container);
+                    if let Some(ref condition) = forloop.condition {
+                        self.visit_expr(condition);
+                    }
+                }
+                Node::While(_, ref while_loop, _, _) => self.visit_expr(&while_loop.condition),
+                Node::If(ref if_node, _, _) => {
+                    for (_, expr, _) in &if_node.conditions {
+                        self.visit_expr(expr);
+                    }
+                }
+                Node::Switch(_, ref switch, _, _) => {
+                    self.visit_expr(&switch.value);
+                    for (_, values, _) in &switch.cases {
+                        for value in values {
+                            self.visit_expr(value);
+                        }
+                    }
+                }
+                _ => (),
+            }
+
+            for body in node.bodies() {
+                self.visit_body(body);
+            }
+        }
+    }
+
+    fn visit_args(&mut self, args: &HashMap<String, Expr>) {
+        for arg in args.values() {
+            self.visit_expr(arg);
+        }
+    }
+
+    fn visit_custom_tag(&mut self, custom_tag: &CustomTag) {
+        match self.tera.tags.get(&custom_tag.name) {
+            Some(tag) => {
+                let arg_names: Vec<_> = custom_tag.args.keys().map(|k| k.as_str()).collect();
+                if let Err(e) = tag.check(&arg_names, custom_tag.body.is_some()) {
+                    let location = self.location();
+                    self.problems
+                        .push(format!("tag `{}` is invalid {}: {}", custom_tag.name, location, e));
+                }
+            }
+            None => self.report(format!("tag `{}` not found", custom_tag.name)),
+        }
+        self.visit_args(&custom_tag.args);
+    }
+
+    fn visit_template_use(&mut self, name: &str) {
+        let reason = self.tera.get_template(name).ok().and_then(|t| t.deprecated.as_ref());
+        if let Some(reason) = reason {
+            let deprecation =
+                Deprecation::Template { name: name.to_string(), reason: reason.to_string() };
+            self.deprecations.push((self.span, deprecation));
+        }
+    }
+
+    fn visit_filter(&mut self, filter: &FunctionCall) {
+        if !self.tera.filters.contains_key(&filter.name) {
+            self.report(format!("filter `{}` not found", filter.name));
+        }
+        if let Some(replacement) = self.tera.deprecated_filters.get(&filter.name) {
+            let deprecation =
+                Deprecation::Filter { name: filter.name.clone(), replacement: replacement.clone() };
+            self.deprecations.push((self.span, deprecation));
+        }
+        self.visit_args(&filter.args);
+    }
+
+    fn visit_fn_call(&mut self, call: &FunctionCall) {
+        // `cycle` and `caller` are provided by the renderer
+        let builtin = call.name == "cycle" || call.name == "caller";
+        if !builtin && !self.tera.functions.contains_key(&call.name) {
+            self.report(format!("function `{}` not found", call.name));
+        }
+        self.visit_args(&call.args);
+    }
+
+    fn visit_macro_call(&mut self, call: &MacroCall) {
+        if let Some(ref namespaces) = self.namespaces {
+            let problem = match namespaces.get(call.namespace.as_str()) {
+                Some(macro_tpl) if !macro_tpl.macros.contains_key(&call.name) => Some(format!(
+                    "macro `{}::{}` not found in `{}`",
+                    call.namespace, call.name, macro_tpl.name
+                )),
+                Some(_) => None,
+                None => Some(format!(
+                    "macro namespace `{}` not found, for `{}::{}`",
+                    call.namespace, call.namespace, call.name
+                )),
+            };
+            if let Some(problem) = problem {
+                self.report(problem);
+            }
+        }
+        self.visit_args(&call.args);
+        for arg in &call.positional_args {
+            self.visit_expr(arg);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        for filter in &expr.filters {
+            self.visit_filter(filter);
+        }
+        self.visit_expr_val(&expr.val);
+    }
+
+    fn visit_expr_val(&mut self, val: &ExprVal) {
+        match *val {
+            ExprVal::Math(ref e) => {
+                self.visit_expr(&e.lhs);
+                self.visit_expr(&e.rhs);
+            }
+            ExprVal::Logic(ref e) => {
+                self.visit_expr(&e.lhs);
+                self.visit_expr(&e.rhs);
+            }
+            ExprVal::In(ref e) => {
+                self.visit_expr(&e.lhs);
+                self.visit_expr(&e.rhs);
+            }
+            ExprVal::Test(ref test) => {
+                if !self.tera.testers.contains_key(&test.name) {
+                    self.report(format!("test `{}` not found", test.name));
+                }
+                if let Some(ref call) = test.call {
+                    self.visit_fn_call(call);
+                }
+                for arg in &test.args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::MacroCall(ref call) => self.visit_macro_call(call),
+            ExprVal::FunctionCall(ref call) => self.visit_fn_call(call),
+            ExprVal::Array(ref values) | ExprVal::Coalesce(ref values) => {
+                for value in values {
+                    self.visit_expr(value);
+                }
+            }
+            ExprVal::Map(ref entries) => {
+                for (_, value) in entries {
+                    self.visit_expr(value);
+                }
+            }
+            ExprVal::StringConcat(ref concat) => {
+                for value in &concat.values {
+                    self.visit_expr_val(value);
+                }
+            }
+            ExprVal::Ident(_)
+            | ExprVal::String(_)
+            | ExprVal::Int(_)
+            | ExprVal::Float(_)
+            | ExprVal::Bool(_)
+            | ExprVal::Null => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{BlockInfo, MacroArgument, MacroSignature, ResolvedBlock};
+    use crate::context::Context;
+    use crate::template::Template;
+    use crate::tera::Tera;
+
+    fn required(tera: &Tera, name: &str) -> Vec<String> {
+        tera.required_variables(name).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn finds_variables_in_expressions() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "tpl",
+            r#"{{ user.name | truncate(length=max) }}{% if a and b is divisibleby(c) %}{{ d ~ "e" }}{% endif %}{{ f[g.h] }}{{ i["j"] }}{{ k[0] }}{{ get_url(path=l) }}{{ m in [n, 1] }}{{ o[p:-1] }}{{ q[-1] }}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            required(&tera, "tpl"),
+            vec![
+                "a", "b", "c", "d", "f", "g", "i", "k", "l", "m", "max", "n", "o", "p", "q", "user"
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_local_and_optional_variables() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "tpl",
+            "{% set a = b %}{{ a }}{% for k, v in items %}{{ k }}{{ v }}{{ loop.index }}{% set c = 1 %}{% endfor %}{{ c }}{{ d | default(value=1) }}{% if e is defined %}{% endif %}{% macro hey(f) %}{{ f }}{{ g }}{% endmacro hey %}{{ h ?? i }}",
+        )
+        .unwrap();
+        assert_eq!(required(&tera, "tpl"), vec!["b", "c", "i", "items"]);
+    }
+
+    #[test]
+    fn follows_includes_with_local_variables() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("item", "{{ item.name }}{{ currency }}{% include \"item\" %}"),
+            ("tpl", "{% for item in items %}{% include \"item\" %}{% endfor %}"),
+        ])
+        .unwrap();
+        assert_eq!(required(&tera, "tpl"), vec!["currency", "items"]);
+    }
+
+    #[test]
+    fn follows_includes_with_variables() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("widget", "{{ items }}{{ title }}"),
+            ("open", "{% include \"widget\" with {\"items\": cart} %}"),
+            ("only", "{% include \"widget\" with {\"items\": cart} only %}"),
+        ])
+        .unwrap();
+        assert_eq!(required(&tera, "open"), vec!["cart", "title"]);
+        assert_eq!(required(&tera, "only"), vec!["cart"]);
+    }
+
+    #[test]
+    fn follows_inheritance() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base", "{{ title }}{% block content %}{{ base_only }}{% endblock content %}{% block footer %}{{ footer }}{% endblock footer %}"),
+            ("child", "{% extends \"base\" %}{{ ignored }}{% block content %}{{ child }}{% endblock content %}{% block footer %}{{ super() }}{{ extra }}{% endblock footer %}"),
+        ])
+        .unwrap();
+        assert_eq!(required(&tera, "child"), vec!["child", "extra", "footer", "title"]);
+        assert_eq!(required(&tera, "base"), vec!["base_only", "footer", "title"]);
+    }
+
+    #[test]
+    fn validates_context() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "tpl",
+            "{{ user.name }}{{ items[0] }}{{ count }}{% for tag in tags %}{{ tag.name }}{% endfor %}{{ a?.b }}",
+        )
+        .unwrap();
+        let mut context = Context::new();
+        context.insert("user", &json!({"name": "Bob"}));
+        context.insert("items", &1);
+        context.insert("tags", &json!([]));
+        context.insert("a", &json!(null));
+
+        assert_eq!(
+            tera.validate_context("tpl", &context).unwrap_err().to_string(),
+            "2 problems found in the context of 'tpl':
+Variable `count` is missing
+Variable `items` is used as an array or an object (`items[0]`) but is a number"
+        );
+
+        context.insert("count", &2);
+        context.insert("items", &[1]);
+        assert!(tera.validate_context("tpl", &context).is_ok());
+    }
+
+    #[test]
+    fn lists_blocks() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base", "{% block content %}{% endblock content %}{% block footer %}{% endblock footer %}"),
+            ("child", "{% extends \"base\" %}{% block content %}{% block inner %}{{ super() }}{% endblock inner %}{% endblock content %}{% block footer %}{% if a %}{{ super() }}{% endif %}{% endblock footer %}"),
+        ])
+        .unwrap();
+        assert_eq!(
+            tera.blocks("child").unwrap(),
+            vec![
+                BlockInfo { name: "content".to_string(), calls_super: false },
+                BlockInfo { name: "footer".to_string(), calls_super: true },
+                BlockInfo { name: "inner".to_string(), calls_super: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_blocks() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base", "{% block content %}{% endblock content %}{% block footer %}{% endblock footer %}{% block title %}{% endblock title %}"),
+            ("layout", "{% extends \"base\" %}{% block content %}{% block sidebar %}{% endblock sidebar %}{% endblock content %}{% block footer %}{% endblock footer %}"),
+            ("page", "{% extends \"layout\" %}{% block footer %}{{ super() }}{% endblock footer %}"),
+        ])
+        .unwrap();
+        let block = |name: &str, template: &str, overrides: Vec<&str>| ResolvedBlock {
+            name: name.to_string(),
+            template: template.to_string(),
+            overrides: overrides.into_iter().map(|t| t.to_string()).collect(),
+        };
+        assert_eq!(
+            tera.resolved_blocks("page").unwrap(),
+            vec![
+                block("content", "layout", vec!["base"]),
+                block("footer", "page", vec!["layout", "base"]),
+                block("sidebar", "layout", vec![]),
+                block("title", "base", vec![]),
+            ]
+        );
+        assert_eq!(
+            tera.resolved_blocks("base").unwrap(),
+            vec![
+                block("content", "base", vec![]),
+                block("footer", "base", vec![]),
+                block("title", "base", vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_missing_references() {
+        let mut tera = Tera::default();
+        // Added directly to skip the inheritance checks of `add_raw_templates`
+        for (name, source) in [
+            ("macros", "{% macro hey() %}{{ self::missing() }}{% endmacro hey %}"),
+            ("page", "{% extends \"nope\" %}{% import \"macros\" as m %}{% block a %}{{ m::hey() }}{{ m::bye() }}{{ u::hey() }}{% endblock a %}"),
+            ("optional", "{% include \"gone\" ignore missing %}"),
+            ("tags", "{% filter shout %}{% for i in items | nope if i is odd %}{{ cycle(values=[1]) }}{{ f(x=[a is weird]) }}{% endfor %}{% endfilter %}"),
+        ] {
+            tera.templates.insert(name.to_string(), Template::new(name, None, source).unwrap());
+        }
+
+        assert_eq!(
+            tera.check().unwrap_err().to_string(),
+            "8 problems found:
+Template 'macros': macro `self::missing` not found in `macros` at line 1, column 18
+Template 'page': template `nope` not found
+Template 'page': macro `m::bye` not found in `macros` at line 1, column 74
+Template 'page': macro namespace `u` not found, for `u::hey` at line 1, column 88
+Template 'tags': filter `shout` not found at line 1, column 1
+Template 'tags': filter `nope` not found at line 1, column 19
+Template 'tags': function `f` not found at line 1, column 81
+Template 'tags': test `weird` not found at line 1, column 81"
+        );
+    }
+
+    #[test]
+    fn lists_macros() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "macros",
+            "{% macro input(name, type=\"text\", size=10) %}{% endmacro input %}{% macro hey() %}{% endmacro hey %}",
+        )
+        .unwrap();
+        assert_eq!(
+            tera.macros("macros").unwrap(),
+            vec![
+                MacroSignature { name: "hey".to_string(), args: vec![] },
+                MacroSignature {
+                    name: "input".to_string(),
+                    args: vec![
+                        MacroArgument { name: "name".to_string(), default: None },
+                        MacroArgument { name: "size".to_string(), default: Some(json!(10)) },
+                        MacroArgument { name: "type".to_string(), default: Some(json!("text")) },
+                    ]
+                },
+            ]
+        );
+    }
+}
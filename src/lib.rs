@@ -10,33 +10,81 @@
 
 #[macro_use]
 mod macros;
+mod builder;
 mod builtins;
+mod cache;
 mod context;
 mod errors;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod filter_utils;
+#[cfg(feature = "handle")]
+mod handle;
+mod hooks;
+mod i18n;
+mod introspection;
+#[cfg(feature = "async")]
+mod loader;
+mod options;
 mod parser;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+mod post_process;
+mod pre_process;
+mod profile;
 mod renderer;
+mod tags;
 mod template;
 mod tera;
+mod urls;
 mod utils;
+mod warnings;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 // Library exports.
 
 // Template is meant to be used internally only but is exported for test/bench.
+pub use crate::builder::TeraBuilder;
 pub use crate::builtins::filters::Filter;
 pub use crate::builtins::functions::Function;
 pub use crate::builtins::testers::Test;
+pub use crate::cache::{FragmentCache, MemoryCache};
 pub use crate::context::Context;
 pub use crate::errors::{Error, ErrorKind, Result};
+#[cfg(feature = "handle")]
+pub use crate::handle::TeraHandle;
+pub use crate::hooks::{HookPhase, RenderHooks};
+pub use crate::i18n::Translator;
+pub use crate::introspection::{BlockInfo, MacroArgument, MacroSignature, ResolvedBlock};
+#[cfg(feature = "async")]
+pub use crate::loader::{AsyncLoader, LoadFuture};
+pub use crate::options::{MapOrder, OutOfBounds, RenderOptions};
+pub use crate::parser::delimiters::Delimiters;
+pub use crate::parser::incremental::ParsedSource;
+pub use crate::parser::lexer::{Lexer, Position, Span, Token, TokenKind};
+pub use crate::post_process::{PostProcessor, TemplateMeta};
+pub use crate::pre_process::PreProcessor;
+pub use crate::profile::{ProfileEntry, ProfileKind, ProfileTotal};
 #[doc(hidden)]
 pub use crate::renderer::Renderer;
+pub use crate::tags::Tag;
 pub use crate::template::Template;
 pub use crate::tera::Tera;
-pub use crate::utils::escape_html;
+pub use crate::urls::{AssetResolver, UrlResolver};
+pub use crate::utils::{escape_csv, escape_html, escape_json_string, escape_latex, escape_xml};
+pub use crate::warnings::{Deprecation, Warning};
+#[cfg(feature = "wasm")]
+pub use crate::wasm::WasmTera;
 /// Re-export Value and other useful things from serde
 /// so apps/tools can encode data in Tera types
 pub use serde_json::value::{from_value, to_value, Map, Number, Value};
 
+// Used by the `embed_templates!` macro
+#[cfg(feature = "embed")]
+#[doc(hidden)]
+pub use include_dir;
+
 // Exposes the AST if one needs it but changing the AST is not considered
 // a breaking change so it isn't public
 #[doc(hidden)]
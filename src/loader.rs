@@ -0,0 +1,281 @@
+//! Loading templates asynchronously, for templates stored in places where fetching them
+//! should not block the current thread, such as a database or an object storage.
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::context::Context;
+use crate::errors::{Error, Result};
+use crate::template::Template;
+use crate::tera::Tera;
+use crate::utils::normalize_template_name;
+
+/// The future returned by `AsyncLoader::load`
+pub type LoadFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Something that can fetch the content of a template from its name.
+///
+/// ```rust,ignore
+/// struct DbLoader { pool: DbPool }
+///
+/// impl AsyncLoader for DbLoader {
+///     fn load<'a>(&'a self, name: &'a str) -> LoadFuture<'a> {
+///         Box::pin(async move {
+///             self.pool.fetch_template(name).await.map_err(|e| Error::msg(e.to_string()))
+///         })
+///     }
+/// }
+/// ```
+pub trait AsyncLoader: Sync {
+    /// Fetches the content of the template with the given name
+    fn load<'a>(&'a self, name: &'a str) -> LoadFuture<'a>;
+}
+
+impl Tera {
+    /// Fetches the given templates with the loader, as well as all the templates they depend on
+    /// (extends, includes and macro imports) that are not already loaded in that Tera instance.
+    ///
+    /// Templates already loaded are not fetched again: use
+    /// [Tera::full_reload_async](struct.Tera.html#method.full_reload_async) to refresh them.
+    pub async fn load_templates_async<L: AsyncLoader>(
+        &mut self,
+        loader: &L,
+        names: &[&str],
+    ) -> Result<()> {
+        let loaded = self.fetch_missing(loader, names).await?;
+        if loaded.is_empty() {
+            return Ok(());
+        }
+        self.add_loaded(loaded)
+    }
+
+    /// Fetches again all the templates that are currently loaded with the given loader.
+    /// Templates added with [Tera::extend](struct.Tera.html#method.extend) are kept as they are.
+    pub async fn full_reload_async<L: AsyncLoader>(&mut self, loader: &L) -> Result<()> {
+        let names: Vec<String> =
+            self.templates.values().filter(|t| !t.from_extend).map(|t| t.name.clone()).collect();
+
+        // Fetch everything first so a failure doesn't leave the instance half reloaded
+        let mut reloaded = Vec::with_capacity(names.len());
+        for name in &names {
+            reloaded.push(fetch(loader, name, self).await?);
+        }
+        self.add_loaded(reloaded)
+    }
+
+    /// Renders a template, fetching it with the loader first if it isn't loaded yet, along with
+    /// the templates it depends on.
+    ///
+    /// The fetched templates are only used for that render and are not kept in the instance:
+    /// call [Tera::load_templates_async](struct.Tera.html#method.load_templates_async)
+    /// beforehand to avoid fetching them on every render.
+    ///
+    /// ```rust,ignore
+    /// let mut tera = Tera::default();
+    /// tera.load_templates_async(&loader, &["index.html"]).await?;
+    /// let html = tera.render_async("index.html", &context, &loader).await?;
+    /// ```
+    pub async fn render_async<L: AsyncLoader>(
+        &self,
+        template_name: &str,
+        context: &Context,
+        loader: &L,
+    ) -> Result<String> {
+        let loaded = self.fetch_missing(loader, &[template_name]).await?;
+        if loaded.is_empty() {
+            return self.render(template_name, context);
+        }
+        let mut tera = self.clone();
+        tera.add_loaded(loaded)?;
+        tera.render(template_name, context)
+    }
+
+    /// Fetches and parses the given templates and their dependencies that are not loaded yet
+    async fn fetch_missing<L: AsyncLoader>(
+        &self,
+        loader: &L,
+        names: &[&str],
+    ) -> Result<Vec<Template>> {
+        // The templates to load and whether they can be missing: `{% include "..." ignore missing %}`
+        let mut to_load: Vec<(String, bool)> =
+            names.iter().map(|n| (n.to_string(), false)).collect();
+        let mut loaded = vec![];
+
+        while let Some((name, optional)) = to_load.pop() {
+            if self.templates.contains_key(&name) {
+                continue;
+            }
+            // The loader could be reading files: never ask it for one outside of its directories.
+            // `./users/x` and `users/../users/x` are both loaded once, as `users/x`
+            let name = match normalize_template_name(&name) {
+                Some(normalized) => normalized,
+                None => return Err(Error::invalid_template_name(&name)),
+            };
+            if self.templates.contains_key(&name)
+                || loaded.iter().any(|t: &Template| t.name == name)
+            {
+                continue;
+            }
+            let content = match loader.load(&name).await {
+                Ok(content) => content,
+                Err(_) if optional => continue,
+                Err(e) => return Err(Error::chain(format!("Failed to load '{}'", name), e)),
+            };
+            let template = parse(&name, &content, self)?;
+            to_load.extend(
+                template
+                    .direct_dependencies()
+                    .map(|d| (d.to_string(), template.optional_includes.iter().any(|i| i == d))),
+            );
+            loaded.push(template);
+        }
+
+        Ok(loaded)
+    }
+
+    fn add_loaded(&mut self, loaded: Vec<Template>) -> Result<()> {
+        for template in loaded {
+            self.templates.insert(template.name.clone(), template);
+        }
+        self.build_inheritance_chains()?;
+        self.check_macro_files()
+    }
+}
+
+async fn fetch<L: AsyncLoader>(loader: &L, name: &str, tera: &Tera) -> Result<Template> {
+    let content = loader
+        .load(name)
+        .await
+        .map_err(|e| Error::chain(format!("Failed to load '{}'", name), e))?;
+    parse(name, &content, tera)
+}
+
+fn parse(name: &str, content: &str, tera: &Tera) -> Result<Template> {
+    tera.parse_template(name, None, content)
+        .map_err(|e| Error::chain(format!("Failed to parse '{}'", name), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use super::{AsyncLoader, LoadFuture};
+    use crate::context::Context;
+    use crate::errors::Error;
+    use crate::tera::Tera;
+
+    struct MemoryLoader {
+        templates: Mutex<HashMap<&'static str, &'static str>>,
+        fetched: Mutex<Vec<String>>,
+    }
+
+    impl MemoryLoader {
+        fn new(templates: Vec<(&'static str, &'static str)>) -> MemoryLoader {
+            MemoryLoader {
+                templates: Mutex::new(templates.into_iter().collect()),
+                fetched: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl AsyncLoader for MemoryLoader {
+        fn load<'a>(&'a self, name: &'a str) -> LoadFuture<'a> {
+            Box::pin(async move {
+                self.fetched.lock().unwrap().push(name.to_string());
+                match self.templates.lock().unwrap().get(name) {
+                    Some(content) => Ok(content.to_string()),
+                    None => Err(Error::template_not_found(name)),
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn render_async_loads_dependencies_lazily() {
+        let loader = MemoryLoader::new(vec![
+            ("base", "{% import \"macros\" as m %}[{% block content %}{% endblock content %}]"),
+            ("macros", "{% macro hey() %}hey{% endmacro hey %}"),
+            ("page", "{% extends \"base\" %}{% block content %}{% include \"part\" %}{% endblock content %}"),
+            ("part", "{{ name }}"),
+            ("other", "other"),
+        ]);
+        let mut tera = Tera::default();
+        let mut context = Context::new();
+        context.insert("name", "Bob");
+
+        let res = pollster::block_on(tera.render_async("page", &context, &loader));
+        assert_eq!(res.unwrap(), "[Bob]");
+        let mut fetched = loader.fetched.lock().unwrap().clone();
+        fetched.sort();
+        assert_eq!(fetched, vec!["base", "macros", "page", "part"]);
+        // Rendering doesn't keep the templates it fetched
+        assert!(tera.templates.is_empty());
+
+        // Already loaded templates are not fetched again
+        pollster::block_on(tera.load_templates_async(&loader, &["part"])).unwrap();
+        pollster::block_on(tera.render_async("part", &context, &loader)).unwrap();
+        assert_eq!(loader.fetched.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn full_reload_async_fetches_templates_again() {
+        let loader = MemoryLoader::new(vec![("hello", "Hello")]);
+        let mut tera = Tera::default();
+        pollster::block_on(tera.load_templates_async(&loader, &["hello"])).unwrap();
+        loader.templates.lock().unwrap().insert("hello", "Bonjour");
+
+        pollster::block_on(tera.full_reload_async(&loader)).unwrap();
+        assert_eq!(tera.render("hello", &Context::new()).unwrap(), "Bonjour");
+    }
+
+    #[test]
+    fn errors_on_missing_template() {
+        let loader = MemoryLoader::new(vec![("page", "{% include \"missing\" %}")]);
+        let mut tera = Tera::default();
+        let err = pollster::block_on(tera.load_templates_async(&loader, &["page"])).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to load 'missing'");
+        assert!(tera.templates.is_empty());
+    }
+
+    #[test]
+    fn skips_missing_optional_includes() {
+        let loader = MemoryLoader::new(vec![
+            (
+                "page",
+                "{% include \"missing\" ignore missing %}{% include \"part\" ignore missing %}",
+            ),
+            ("part", "part"),
+        ]);
+        let tera = Tera::default();
+        let res = pollster::block_on(tera.render_async("page", &Context::new(), &loader));
+        assert_eq!(res.unwrap(), "part");
+    }
+
+    #[test]
+    fn loads_templates_once_whatever_the_way_their_name_is_written() {
+        let loader = MemoryLoader::new(vec![
+            ("base", "[{% block content %}{% endblock content %}]"),
+            ("users/page", "{% extends \"./base\" %}{% block content %}{% include \"users/../users/part\" %}{% endblock content %}"),
+            ("users/part", "part"),
+        ]);
+        let mut tera = Tera::default();
+        pollster::block_on(tera.load_templates_async(&loader, &["./users/page", "users//part"]))
+            .unwrap();
+        let mut fetched = loader.fetched.lock().unwrap().clone();
+        fetched.sort();
+        assert_eq!(fetched, vec!["base", "users/page", "users/part"]);
+        assert_eq!(tera.render("users/page", &Context::new()).unwrap(), "[part]");
+    }
+
+    #[test]
+    fn refuses_names_outside_of_the_loader_directories() {
+        let loader = MemoryLoader::new(vec![("page", "{% include \"../secrets.txt\" %}")]);
+        let mut tera = Tera::default();
+        let err = pollster::block_on(tera.load_templates_async(&loader, &["page"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Template name '../secrets.txt' is outside of the template directories"
+        );
+        assert_eq!(*loader.fetched.lock().unwrap(), vec!["page".to_string()]);
+    }
+}
@@ -33,3 +33,40 @@ macro_rules! try_get_value {
         }
     }};
 }
+
+/// Logs a message with the `log` crate if the `log` feature is enabled, at the level given
+/// first: `debug` or `warn`. The arguments are still type-checked otherwise.
+macro_rules! log {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::$level!(target: "tera", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        {
+            if false {
+                let _ = format_args!($($arg)+);
+            }
+        }
+    }};
+}
+
+/// Embeds all the templates of a directory in the binary and returns a `Result<Tera>` with
+/// all of them loaded, named after their path relative to that directory.
+///
+/// The path is relative to the directory the compiler is running in so you will usually
+/// want to start it with `$CARGO_MANIFEST_DIR`.
+/// Only available with the `embed` feature.
+///
+/// ```rust,ignore
+/// let tera = tera::embed_templates!("$CARGO_MANIFEST_DIR/templates")?;
+/// tera.render("users/profile.html", &context)?;
+/// ```
+#[cfg(feature = "embed")]
+#[macro_export]
+macro_rules! embed_templates {
+    ($path:tt) => {{
+        // The expansion of `include_dir!` refers to `include_dir::...`
+        use $crate::include_dir;
+        static TEMPLATES: include_dir::Dir<'static> = include_dir::include_dir!($path);
+        $crate::Tera::from_embedded_dir(&TEMPLATES)
+    }};
+}
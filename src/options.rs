@@ -0,0 +1,69 @@
+//! Settings of a single render, overriding the ones of the `Tera` instance
+
+/// What a subscript missing from its array or object, like `items[10]` on an array of
+/// 3 elements or `user["nickname"]` on an object without that key, evaluates to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutOfBounds {
+    /// Rendering it is an error, unless it is caught by the `default` filter or the `defined`
+    /// test. The default.
+    #[default]
+    Error,
+    /// It is undefined but renders as an empty string, like a missing value of an optional
+    /// access with `?.`. The array or object and the variables used as index still need to exist.
+    Undefined,
+}
+
+/// The order in which `{% for key, value in map %}` loops iterate on maps
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MapOrder {
+    /// The order of the map: sorted by key, or the insertion order with the `preserve_order`
+    /// feature. The default.
+    #[default]
+    Insertion,
+    /// Sorted by key, even with the `preserve_order` feature, for outputs that need to be
+    /// reproducible whatever map type the context was built from
+    Sorted,
+}
+
+/// Settings overriding the ones of the `Tera` instance for one render, given to
+/// [Tera::render_with_options](struct.Tera.html#method.render_with_options).
+///
+/// Every setting is `None` by default, keeping the one of the instance or the default behaviour,
+/// so a single instance can render the same template differently for each request.
+///
+/// ```rust
+/// # use tera::{Context, OutOfBounds, RenderOptions, Tera};
+/// let mut tera = Tera::default();
+/// tera.add_raw_template("first", "{{ items[0] }}").unwrap();
+/// let options = RenderOptions { out_of_bounds: Some(OutOfBounds::Undefined), ..RenderOptions::default() };
+/// let mut context = Context::new();
+/// context.insert("items", &Vec::<i32>::new());
+/// assert!(tera.render("first", &context).is_err());
+/// assert_eq!(tera.render_with_options("first", &context, &options).unwrap(), "");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderOptions {
+    /// Overrides [Tera::set_out_of_bounds](struct.Tera.html#method.set_out_of_bounds)
+    pub out_of_bounds: Option<OutOfBounds>,
+    /// Overrides [Tera::set_minify_html](struct.Tera.html#method.set_minify_html)
+    pub minify_html: Option<bool>,
+    /// Overrides [Tera::set_map_order](struct.Tera.html#method.set_map_order)
+    pub map_order: Option<MapOrder>,
+    /// Whether a variable missing from the context is an error, the default. If `false`, it
+    /// evaluates to nothing like a missing value of an optional access with `?.`
+    pub strict: Option<bool>,
+    /// Whether the output is escaped, instead of deciding it from the suffix of the template
+    /// name as set with [Tera::autoescape_on](struct.Tera.html#method.autoescape_on)
+    pub autoescape: Option<bool>,
+    /// How many iterations all the for and while loops of the render can do in total before it
+    /// fails. No limit by default, except for each while loop failing after 10,000 iterations
+    pub max_loop_iterations: Option<usize>,
+    /// How deep includes and macro calls can be nested in each other before the render fails,
+    /// no limit by default
+    pub max_depth: Option<usize>,
+    /// The language of the `{% trans %}` tags, instead of the `lang` variable of the context,
+    /// and the default `locale` of the `num_format` and `currency` filters
+    pub locale: Option<String>,
+    /// The default `timezone` of the `date` filter and of the `now` function
+    pub timezone: Option<String>,
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::fmt;
 
+pub use crate::parser::lexer::{Position, Span};
+
 /// Whether to remove the whitespace of a `{% %}` tag
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct WS {
@@ -27,6 +29,8 @@ pub enum MathOperator {
     Mul,
     /// /
     Div,
+    /// //
+    FloorDiv,
     /// %
     Modulo,
 }
@@ -41,6 +45,7 @@ impl fmt::Display for MathOperator {
                 MathOperator::Sub => "-",
                 MathOperator::Mul => "*",
                 MathOperator::Div => "/",
+                MathOperator::FloorDiv => "//",
                 MathOperator::Modulo => "%",
             }
         )
@@ -159,6 +164,8 @@ pub enum ExprVal {
     Int(i64),
     Float(f64),
     Bool(bool),
+    /// The `none` literal
+    Null,
     Ident(String),
     Math(MathExpr),
     Logic(LogicExpr),
@@ -168,8 +175,12 @@ pub enum ExprVal {
     // A vec of Expr, not ExprVal since filters are allowed
     // on values inside arrays
     Array(Vec<Expr>),
+    /// A map literal `{"a": 1}`, with its entries in the order they are written
+    Map(Vec<(String, Expr)>),
     StringConcat(StringConcat),
     In(In),
+    /// `a ?? b ?? c`: the first of those expressions that is defined and not `none`
+    Coalesce(Vec<Expr>),
 }
 
 /// An expression is a value that can be negated and followed by
@@ -219,11 +230,13 @@ impl Expr {
     }
 }
 
-/// A test node `if my_var is odd`
+/// A test node `if my_var is odd` or `if get_count() is odd`
 #[derive(Clone, Debug, PartialEq)]
 pub struct Test {
-    /// Which variable is evaluated
+    /// Which variable is evaluated, empty when testing the result of a function call
     pub ident: String,
+    /// The function call whose result is evaluated instead of a variable
+    pub call: Option<FunctionCall>,
     /// Is it using `not`?
     pub negated: bool,
     /// Name of the test
@@ -241,10 +254,92 @@ pub struct FilterSection {
     pub body: Vec<Node>,
 }
 
+/// A `{% with total = cart | length %}...{% endwith %}` node: the variables only exist in its body
+#[derive(Clone, Debug, PartialEq)]
+pub struct With {
+    /// The variables defined by the tag with their values, in order
+    pub assignments: Vec<(String, Expr)>,
+    /// The body in which the variables are defined
+    pub body: Vec<Node>,
+}
+
+/// A `{% cache "sidebar" ~ user.id 300 %}...{% endcache %}` node: the body is rendered once and
+/// its output reused from the fragment cache of the Tera instance for the same key
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cache {
+    /// The key of the output in the cache
+    pub key: Expr,
+    /// For how many seconds the output is cached, forever if there isn't one
+    pub ttl: Option<Expr>,
+    /// The body whose output is cached
+    pub body: Vec<Node>,
+}
+
+/// A `{% widget name="x" %}` node, or `{% widget %}...{% endwidget %}` with a body: a tag
+/// registered by the application with `Tera::register_tag`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomTag {
+    /// The name of the tag
+    pub name: String,
+    /// The arguments given to the tag
+    pub args: HashMap<String, Expr>,
+    /// What is between the tag and its end tag, if there is one
+    pub body: Option<Vec<Node>>,
+}
+
+/// An `{% include "widget.html" ignore missing with {"items": cart} only %}` node
+#[derive(Clone, Debug, PartialEq)]
+pub struct Include {
+    /// The name of the template included
+    pub name: String,
+    /// The variables given to the included template with their values, in order
+    pub variables: Vec<(String, Expr)>,
+    /// Whether the included template only sees `variables` and not the enclosing context
+    pub only: bool,
+    /// Whether nothing is rendered if the template doesn't exist, instead of an error
+    pub ignore_missing: bool,
+}
+
+/// A `{% embed "card.html" %}{% block title %}Hi{% endblock %}{% endembed %}` node: the template
+/// is included with some of its blocks replaced
+#[derive(Clone, Debug, PartialEq)]
+pub struct Embed {
+    /// The name of the template embedded
+    pub name: String,
+    /// The `Node::Block` replacing the blocks of the template with the same name
+    pub blocks: Vec<Node>,
+}
+
+/// A `{% call macros::card(title="Hi") %}...{% endcall %}` node: the macro is called with the
+/// body, which it renders where it calls `caller()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallBlock {
+    /// The macro called
+    pub call: MacroCall,
+    /// The body given to the macro, rendered in the scope of the caller
+    pub body: Vec<Node>,
+}
+
+/// A translatable message `{% trans %}Hello {{ name }}{% endtrans %}`, optionally with
+/// a plural form `{% trans count=n %}One item{% pluralize %}{{ count }} items{% endtrans %}`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trans {
+    /// The message given to the translator, with the variables written as `{{ name }}`
+    pub message: String,
+    /// The plural form of the message, after `{% pluralize %}`
+    pub plural: Option<String>,
+    /// The `count` argument, choosing between the singular and plural forms.
+    /// It is available as `{{ count }}` in the messages
+    pub count: Option<Expr>,
+    /// The variables used in the message by name, in the order they first appear
+    pub variables: Vec<(String, Expr)>,
+}
+
 /// Set a variable in the context `{% set val = "hey" %}`
 #[derive(Clone, Debug, PartialEq)]
 pub struct Set {
-    /// The name for that value in the context
+    /// The name for that value in the context, or `ns.attribute` to assign
+    /// an attribute of a namespace
     pub key: String,
     /// The value to assign
     pub value: Expr,
@@ -262,6 +357,8 @@ pub struct MacroCall {
     pub name: String,
     /// The args for that macro: name -> value
     pub args: HashMap<String, Expr>,
+    /// The args given without a name, matched in order to the args of the macro definition
+    pub positional_args: Vec<Expr>,
 }
 
 /// A Macro definition
@@ -271,6 +368,8 @@ pub struct MacroDefinition {
     pub name: String,
     /// The args for that macro: name -> optional default value
     pub args: HashMap<String, Option<Expr>>,
+    /// The arg names in the order they are declared, used for positional args
+    pub arg_order: Vec<String>,
     /// The macro content
     pub body: Vec<Node>,
 }
@@ -293,12 +392,28 @@ pub struct Forloop {
     pub value: String,
     /// Expression being iterated on
     pub container: Expr,
+    /// Only the elements for which this condition is truthy are iterated on:
+    /// `{% for x in items if x.published %}`
+    pub condition: Option<Expr>,
+    /// Whether a map is iterated on sorted by key whatever the map order of the render:
+    /// `{% for key, value in map sorted %}`
+    pub sorted: bool,
     /// What's in the forloop itself
     pub body: Vec<Node>,
     /// The body to execute in case of an empty object
     pub empty_body: Option<Vec<Node>>,
 }
 
+/// A `{% while queue %}...{% endwhile %}` loop, rendering its body as long as the condition is
+/// truthy
+#[derive(Clone, Debug, PartialEq)]
+pub struct While {
+    /// The condition evaluated before every iteration
+    pub condition: Expr,
+    /// What's in the loop itself
+    pub body: Vec<Node>,
+}
+
 /// An if/elif/else condition with their respective body
 #[derive(Clone, Debug, PartialEq)]
 pub struct If {
@@ -308,43 +423,239 @@ pub struct If {
     pub otherwise: Option<(WS, Vec<Node>)>,
 }
 
-/// All Tera nodes that can be encountered
+/// A `{% switch %}` block, rendering the body of the first case with a value equal to the one
+/// of the switch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Switch {
+    /// The expression compared to the values of the cases
+    pub value: Expr,
+    /// The `{% case %}` tags with their values and body
+    pub cases: Vec<(WS, Vec<Expr>, Vec<Node>)>,
+    /// The optional `{% default %}` block
+    pub default: Option<(WS, Vec<Node>)>,
+}
+
+/// All Tera nodes that can be encountered.
+///
+/// The last field of every node is the `Span` of its source in the template: from the opening
+/// delimiter of its first tag to the closing one of its last tag.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Node {
     /// A call to `{{ super() }}` in a block
-    Super,
+    Super(Span),
 
     /// Some actual text
-    Text(String),
+    Text(String, Span),
     /// A `{{ }}` block
-    VariableBlock(WS, Expr),
+    VariableBlock(WS, Expr, Span),
     /// A `{% macro hello() %}...{% endmacro %}`
-    MacroDefinition(WS, MacroDefinition, WS),
+    MacroDefinition(WS, MacroDefinition, WS, Span),
 
     /// The `{% extends "blabla.html" %}` node, contains the template name
-    Extends(WS, String),
-    /// The `{% include "blabla.html" %}` node, contains the template name
-    Include(WS, String),
+    Extends(WS, String, Span),
+    /// The `{% extends layout %}` node, contains the expression giving the template name
+    DynamicExtends(WS, Expr, Span),
+    /// The `{% include "blabla.html" %}` node
+    Include(WS, Include, Span),
     /// The `{% import "macros.html" as macros %}`
-    ImportMacro(WS, String, String),
+    ImportMacro(WS, String, String, Span),
     /// The `{% set val = something %}` tag
-    Set(WS, Set),
+    Set(WS, Set, Span),
+    /// The `{% do something() %}` tag, evaluating an expression without rendering it
+    Do(WS, Expr, Span),
 
     /// The text between `{% raw %}` and `{% endraw %}`
-    Raw(WS, String, WS),
+    Raw(WS, String, WS, Span),
 
     /// A filter section node `{{ filter name(param="value") }} content {{ endfilter }}`
-    FilterSection(WS, FilterSection, WS),
+    FilterSection(WS, FilterSection, WS, Span),
+    /// A `{% spaceless %}...{% endspaceless %}` node, removing whitespace between HTML tags
+    Spaceless(WS, Vec<Node>, WS, Span),
+    /// A `{% with a = 1 %}...{% endwith %}` node
+    With(WS, With, WS, Span),
+    /// A `{% cache key %}...{% endcache %}` node
+    Cache(WS, Cache, WS, Span),
+    /// A tag registered with `Tera::register_tag`, the second WS being the one of its end tag
+    CustomTag(WS, CustomTag, WS, Span),
+    /// A `{% call %}` block
+    CallBlock(WS, CallBlock, WS, Span),
+    /// A `{% embed %}` block
+    Embed(WS, Embed, WS, Span),
+    /// A `{% trans %}...{% endtrans %}` node, translated at render time
+    Trans(WS, Trans, WS, Span),
     /// A `{% block name %}...{% endblock %}`
-    Block(WS, Block, WS),
+    Block(WS, Block, WS, Span),
     /// A `{% for i in items %}...{% endfor %}`
-    Forloop(WS, Forloop, WS),
+    Forloop(WS, Forloop, WS, Span),
+    /// A `{% while condition %}...{% endwhile %}`
+    While(WS, While, WS, Span),
 
     /// A if/elif/else block, WS for the if/elif/else is directly in the struct
-    If(If, WS),
+    If(If, WS, Span),
+    /// A `{% switch %}` block, WS for the cases and default is directly in the struct
+    Switch(WS, Switch, WS, Span),
 
     /// The `{% break %}` tag
-    Break(WS),
+    Break(WS, Span),
     /// The `{% continue %}` tag
-    Continue(WS),
+    Continue(WS, Span),
+    /// The `{% debug %}` tag, dumping the variables in scope or only the given one
+    Debug(WS, Option<String>, Span),
+}
+
+impl Node {
+    /// Where that node is in the template source
+    pub fn span(&self) -> &Span {
+        match self {
+            Node::Super(span)
+            | Node::Text(_, span)
+            | Node::VariableBlock(_, _, span)
+            | Node::Extends(_, _, span)
+            | Node::DynamicExtends(_, _, span)
+            | Node::Include(_, _, span)
+            | Node::ImportMacro(_, _, _, span)
+            | Node::Set(_, _, span)
+            | Node::Do(_, _, span)
+            | Node::Raw(_, _, _, span)
+            | Node::Trans(_, _, _, span)
+            | Node::Break(_, span)
+            | Node::Continue(_, span)
+            | Node::Debug(_, _, span)
+            | Node::MacroDefinition(_, _, _, span)
+            | Node::FilterSection(_, _, _, span)
+            | Node::Spaceless(_, _, _, span)
+            | Node::With(_, _, _, span)
+            | Node::Cache(_, _, _, span)
+            | Node::CustomTag(_, _, _, span)
+            | Node::CallBlock(_, _, _, span)
+            | Node::Embed(_, _, _, span)
+            | Node::Block(_, _, _, span)
+            | Node::Forloop(_, _, _, span)
+            | Node::While(_, _, _, span)
+            | Node::Switch(_, _, _, span)
+            | Node::If(_, _, span) => span,
+        }
+    }
+
+    /// The name of the tag of that node, `variable` for `{{ }}` and `text` for the text around tags
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Node::Super(_) => "super",
+            Node::Text(..) => "text",
+            Node::VariableBlock(..) => "variable",
+            Node::MacroDefinition(..) => "macro",
+            Node::Extends(..) | Node::DynamicExtends(..) => "extends",
+            Node::Include(..) => "include",
+            Node::ImportMacro(..) => "import",
+            Node::Set(..) => "set",
+            Node::Do(..) => "do",
+            Node::Raw(..) => "raw",
+            Node::FilterSection(..) => "filter",
+            Node::Spaceless(..) => "spaceless",
+            Node::With(..) => "with",
+            Node::Cache(..) => "cache",
+            Node::CustomTag(..) => "custom",
+            Node::CallBlock(..) => "call",
+            Node::Embed(..) => "embed",
+            Node::Trans(..) => "trans",
+            Node::Block(..) => "block",
+            Node::Forloop(..) => "for",
+            Node::While(..) => "while",
+            Node::If(..) => "if",
+            Node::Switch(..) => "switch",
+            Node::Break(..) => "break",
+            Node::Continue(..) => "continue",
+            Node::Debug(..) => "debug",
+        }
+    }
+
+    /// Returns all the nested bodies of that node, in the order they appear in the template
+    pub(crate) fn bodies(&self) -> Vec<&Vec<Node>> {
+        match self {
+            Node::MacroDefinition(_, MacroDefinition { body, .. }, _, _)
+            | Node::FilterSection(_, FilterSection { body, .. }, _, _)
+            | Node::Spaceless(_, body, _, _)
+            | Node::With(_, With { body, .. }, _, _)
+            | Node::Cache(_, Cache { body, .. }, _, _)
+            | Node::CustomTag(_, CustomTag { body: Some(body), .. }, _, _)
+            | Node::CallBlock(_, CallBlock { body, .. }, _, _)
+            | Node::Embed(_, Embed { blocks: body, .. }, _, _)
+            | Node::While(_, While { body, .. }, _, _)
+            | Node::Block(_, Block { body, .. }, _, _) => vec![body],
+            Node::Forloop(_, Forloop { body, empty_body, .. }, _, _) => {
+                let mut bodies = vec![body];
+                if let Some(b) = empty_body {
+                    bodies.push(b);
+                }
+                bodies
+            }
+            Node::If(If { conditions, otherwise }, _, _) => {
+                let mut bodies: Vec<_> = conditions.iter().map(|c| &c.2).collect();
+                if let Some((_, b)) = otherwise {
+                    bodies.push(b);
+                }
+                bodies
+            }
+            Node::Switch(_, Switch { cases, default, .. }, _, _) => {
+                let mut bodies: Vec<_> = cases.iter().map(|c| &c.2).collect();
+                if let Some((_, b)) = default {
+                    bodies.push(b);
+                }
+                bodies
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Returns the span of the node as well as all the nested bodies it contains
+    pub(crate) fn span_and_bodies_mut(&mut self) -> (&mut Span, Vec<&mut Vec<Node>>) {
+        match self {
+            Node::Super(span)
+            | Node::Text(_, span)
+            | Node::VariableBlock(_, _, span)
+            | Node::Extends(_, _, span)
+            | Node::DynamicExtends(_, _, span)
+            | Node::Include(_, _, span)
+            | Node::ImportMacro(_, _, _, span)
+            | Node::Set(_, _, span)
+            | Node::Do(_, _, span)
+            | Node::Raw(_, _, _, span)
+            | Node::Trans(_, _, _, span)
+            | Node::Break(_, span)
+            | Node::Continue(_, span)
+            | Node::Debug(_, _, span)
+            | Node::CustomTag(_, CustomTag { body: None, .. }, _, span) => (span, vec![]),
+            Node::MacroDefinition(_, MacroDefinition { body, .. }, _, span)
+            | Node::FilterSection(_, FilterSection { body, .. }, _, span)
+            | Node::Spaceless(_, body, _, span)
+            | Node::With(_, With { body, .. }, _, span)
+            | Node::Cache(_, Cache { body, .. }, _, span)
+            | Node::CustomTag(_, CustomTag { body: Some(body), .. }, _, span)
+            | Node::CallBlock(_, CallBlock { body, .. }, _, span)
+            | Node::Embed(_, Embed { blocks: body, .. }, _, span)
+            | Node::While(_, While { body, .. }, _, span)
+            | Node::Block(_, Block { body, .. }, _, span) => (span, vec![body]),
+            Node::Forloop(_, Forloop { body, empty_body, .. }, _, span) => {
+                let mut bodies = vec![body];
+                if let Some(b) = empty_body {
+                    bodies.push(b);
+                }
+                (span, bodies)
+            }
+            Node::If(If { conditions, otherwise }, _, span) => {
+                let mut bodies: Vec<_> = conditions.iter_mut().map(|c| &mut c.2).collect();
+                if let Some((_, b)) = otherwise {
+                    bodies.push(b);
+                }
+                (span, bodies)
+            }
+            Node::Switch(_, Switch { cases, default, .. }, _, span) => {
+                let mut bodies: Vec<_> = cases.iter_mut().map(|c| &mut c.2).collect();
+                if let Some((_, b)) = default {
+                    bodies.push(b);
+                }
+                (span, bodies)
+            }
+        }
+    }
 }
@@ -0,0 +1,250 @@
+//! What the translations of the Django and Liquid syntaxes to the Tera one have in common:
+//! the same delimiters, `{% comment %}` blocks and filter arguments given after a `:`.
+use crate::errors::{Error, Result};
+use crate::parser::delimiters::find_tag_end;
+
+/// An argument given to a filter after a `:`, with its name if it has one
+pub(crate) type FilterArg<'a> = (Option<&'a str>, &'a str);
+
+/// Calls `f` with the start delimiter, content and end delimiter of every variable block and
+/// tag of the template, replacing it by what `f` returns.
+///
+/// Comments and the content of `raw` blocks are kept as they are and `{% comment %}` blocks
+/// become Tera comments. Variable blocks and tags that are not closed are kept as they are so
+/// the parser can point at them.
+pub(crate) fn translate_tags<F>(input: &str, mut f: F) -> Result<String>
+where
+    F: FnMut(&str, &str, &str) -> Result<String>,
+{
+    let mut res = String::with_capacity(input.len());
+    let mut rest = input;
+    // Whether we are in a `raw` block, in which only the `endraw` tag is looked for
+    let mut in_raw = false;
+
+    loop {
+        let next_start = ["{{", "{%", "{#"]
+            .iter()
+            .filter(|start| !in_raw || **start == "{%")
+            .filter_map(|&start| rest.find(start).map(|i| (i, start)))
+            .min();
+        let (i, start) = match next_start {
+            Some(s) => s,
+            None => break,
+        };
+
+        res.push_str(&rest[..i]);
+        let after_start = &rest[i + 2..];
+        let end = match start {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let end_pos =
+            if start == "{#" { after_start.find(end) } else { find_tag_end(after_start, end) };
+        let end_pos = match end_pos {
+            Some(e) => e,
+            None => break,
+        };
+        let content = &after_start[..end_pos];
+        let tag = &rest[i..i + 2 + end_pos + 2];
+        let offset = input.len() - rest.len() + i;
+        rest = &after_start[end_pos + 2..];
+
+        let name = if start == "{%" { tag_name(content) } else { "" };
+        if in_raw || start == "{#" {
+            in_raw = in_raw && name != "endraw";
+            res.push_str(tag);
+            continue;
+        }
+
+        match name {
+            "raw" => {
+                in_raw = true;
+                res.push_str(tag);
+            }
+            "comment" => {
+                let body_end = match find_endcomment(rest) {
+                    Some(e) => e,
+                    None => {
+                        return Err(Error::msg(format!(
+                            "The `{{% comment %}}` at line {} is never closed",
+                            input[..offset].matches('\n').count() + 1
+                        )))
+                    }
+                };
+                // Only the newlines are kept, for the line numbers of errors
+                res.push_str("{#");
+                for _ in rest[..body_end].matches('\n') {
+                    res.push('\n');
+                }
+                res.push_str("#}");
+                rest = &rest[body_end..];
+            }
+            _ => res.push_str(&f(start, content, end)?),
+        }
+    }
+
+    res.push_str(rest);
+    Ok(res)
+}
+
+/// The name of the tag, without the whitespace control and the arguments
+pub(crate) fn tag_name(content: &str) -> &str {
+    let content = content.trim_matches(|c: char| c == '-' || c.is_whitespace());
+    content.split(char::is_whitespace).next().unwrap_or("")
+}
+
+/// Finds the `{% endcomment %}` tag, returning the offset right after it
+fn find_endcomment(input: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(i) = input[offset..].find("{%") {
+        let start = offset + i + 2;
+        let end = start + input[start..].find("%}")?;
+        if tag_name(&input[start..end]) == "endcomment" {
+            return Some(end + 2);
+        }
+        offset = end + 2;
+    }
+    None
+}
+
+pub(crate) fn is_quote(c: char) -> bool {
+    c == '"' || c == '\'' || c == '`'
+}
+
+/// Whether the argument is a string literal
+pub(crate) fn is_string(arg: &str) -> bool {
+    arg.len() >= 2 && arg.starts_with(is_quote) && arg.ends_with(&arg[..1])
+}
+
+/// Returns the offset right after the string literal starting the input, or the length
+/// of the input if it is never closed
+pub(crate) fn string_end(input: &str) -> usize {
+    let quote = input.chars().next().unwrap();
+    let mut escaped = false;
+    for (i, c) in input.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote != '`' {
+            escaped = true;
+        } else if c == quote {
+            return i + 1;
+        }
+    }
+    input.len()
+}
+
+fn leading_whitespace(input: &str) -> usize {
+    input.len() - input.trim_start().len()
+}
+
+fn ident_len(input: &str) -> usize {
+    input.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(input.len())
+}
+
+/// Returns the length of the filter argument starting the input: a string literal, a number
+/// or a variable, with dots and square brackets
+fn arg_len(input: &str) -> usize {
+    match input.chars().next() {
+        Some(c) if is_quote(c) => return string_end(input),
+        Some(_) => (),
+        None => return 0,
+    }
+
+    let mut brackets = 0;
+    let mut len = 0;
+    while len < input.len() {
+        let c = input[len..].chars().next().unwrap();
+        match c {
+            _ if is_quote(c) && brackets > 0 => {
+                len += string_end(&input[len..]);
+                continue;
+            }
+            '[' => brackets += 1,
+            ']' if brackets > 0 => brackets -= 1,
+            '-' if len == 0 => (),
+            _ if c.is_alphanumeric() || c == '_' || c == '.' || brackets > 0 => (),
+            _ => break,
+        }
+        len += c.len_utf8();
+    }
+    len
+}
+
+/// Parses the arguments following a filter name, returning them along with the length they
+/// take in the input. Django filters take a single argument right after the `:` while Liquid
+/// ones take `several` of them, separated by commas and possibly named like `name: value`.
+fn filter_args(input: &str, several: bool) -> (Vec<FilterArg<'_>>, usize) {
+    let mut args = vec![];
+    if !input.starts_with(':') || input.starts_with("::") {
+        return (args, 0);
+    }
+
+    let mut pos = 1;
+    let mut end = 0;
+    loop {
+        let mut name = None;
+        if several {
+            pos += leading_whitespace(&input[pos..]);
+            let name_len = ident_len(&input[pos..]);
+            let after_name = &input[pos + name_len..];
+            if name_len > 0 && after_name.starts_with(':') && !after_name.starts_with("::") {
+                name = Some(&input[pos..pos + name_len]);
+                pos += name_len + 1;
+                pos += leading_whitespace(&input[pos..]);
+            }
+        }
+        let len = arg_len(&input[pos..]);
+        if len == 0 {
+            break;
+        }
+        args.push((name, &input[pos..pos + len]));
+        pos += len;
+        end = pos;
+
+        let whitespace = leading_whitespace(&input[pos..]);
+        if !several || !input[pos + whitespace..].starts_with(',') {
+            break;
+        }
+        pos += whitespace + 1;
+    }
+
+    (args, end)
+}
+
+/// Rewrites the filters of the content of a variable block or tag: `f` is given the name and
+/// arguments of each filter and returns what replaces them.
+pub(crate) fn translate_filters<F>(content: &str, several_args: bool, f: F) -> String
+where
+    F: Fn(&str, &[FilterArg]) -> String,
+{
+    let mut res = String::with_capacity(content.len());
+    let mut i = 0;
+
+    while i < content.len() {
+        let c = content[i..].chars().next().unwrap();
+        if is_quote(c) {
+            let end = i + string_end(&content[i..]);
+            res.push_str(&content[i..end]);
+            i = end;
+            continue;
+        }
+
+        if c == '|' {
+            let name_start = i + 1 + leading_whitespace(&content[i + 1..]);
+            let name_end = name_start + ident_len(&content[name_start..]);
+            if name_end > name_start {
+                let (args, args_len) = filter_args(&content[name_end..], several_args);
+                res.push_str(&content[i..name_start]);
+                res.push_str(&f(&content[name_start..name_end], &args));
+                i = name_end + args_len;
+                continue;
+            }
+        }
+
+        res.push(c);
+        i += c.len_utf8();
+    }
+
+    res
+}
@@ -0,0 +1,277 @@
+//! Custom delimiters are handled by translating the template to the default delimiters
+//! before parsing it, so the grammar doesn't have to know about them.
+use std::cmp::Reverse;
+
+use crate::errors::{Error, Result};
+
+/// The markers used to delimit variables, tags and comments in templates.
+///
+/// ```rust
+/// # use tera::{Context, Delimiters, Tera};
+/// let mut tera = Tera::default();
+/// tera.set_delimiters(Delimiters {
+///     variable_start: "[[".to_string(),
+///     variable_end: "]]".to_string(),
+///     ..Delimiters::default()
+/// }).unwrap();
+/// tera.add_raw_template("hello", "{{ name }}: [[ name ]]").unwrap();
+/// let mut context = Context::new();
+/// context.insert("name", "Bob");
+/// assert_eq!(tera.render("hello", &context).unwrap(), "{{ name }}: Bob");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delimiters {
+    /// Start of a variable block, `{{` by default
+    pub variable_start: String,
+    /// End of a variable block, `}}` by default
+    pub variable_end: String,
+    /// Start of a tag, `{%` by default
+    pub block_start: String,
+    /// End of a tag, `%}` by default
+    pub block_end: String,
+    /// Start of a comment, `{#` by default
+    pub comment_start: String,
+    /// End of a comment, `#}` by default
+    pub comment_end: String,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters {
+            variable_start: "{{".to_string(),
+            variable_end: "}}".to_string(),
+            block_start: "{%".to_string(),
+            block_end: "%}".to_string(),
+            comment_start: "{#".to_string(),
+            comment_end: "#}".to_string(),
+        }
+    }
+}
+
+impl Delimiters {
+    /// Errors if the delimiters are empty or if two start delimiters are identical.
+    /// A start delimiter can be a prefix of another one, like `<%` and `<%=`: the longest
+    /// one is used when both match.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let all = [
+            &self.variable_start,
+            &self.variable_end,
+            &self.block_start,
+            &self.block_end,
+            &self.comment_start,
+            &self.comment_end,
+        ];
+        if all.iter().any(|d| d.trim().is_empty() || d.chars().any(char::is_whitespace)) {
+            return Err(Error::msg("Delimiters cannot be empty or contain whitespace"));
+        }
+
+        let starts = [&self.variable_start, &self.block_start, &self.comment_start];
+        for (i, a) in starts.iter().enumerate() {
+            for b in starts.iter().skip(i + 1) {
+                if a == b {
+                    return Err(Error::msg(format!("Start delimiter `{}` is used twice", a)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Delimiters::default()
+    }
+}
+
+/// Finds the end delimiter of a tag, skipping the content of string literals and comments
+pub(crate) fn find_tag_end(input: &str, end: &str) -> Option<usize> {
+    scan_tag(input, end).ok()
+}
+
+/// Same as `find_tag_end` but, if the end delimiter is missing, tells which string
+/// literal or comment is never closed with its offset, if any
+fn scan_tag(input: &str, end: &str) -> std::result::Result<usize, Option<(&'static str, usize)>> {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut comment_end = 0;
+    for (i, c) in input.char_indices() {
+        if i < comment_end {
+            continue;
+        }
+        match quote {
+            Some((_, _)) if escaped => escaped = false,
+            Some((q, _)) if c == '\\' && q != '`' => escaped = true,
+            Some((q, _)) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' || c == '`' => quote = Some((c, i)),
+            None if input[i..].starts_with("{#") => match input[i..].find("#}") {
+                Some(e) => comment_end = i + e + 2,
+                None => return Err(Some(("`{#`", i))),
+            },
+            None if input[i..].starts_with(end) => return Ok(i),
+            None => (),
+        }
+    }
+
+    Err(quote.map(|(q, i)| {
+        let kind = match q {
+            '"' => "string started by `\"`",
+            '\'' => "string started by `'`",
+            _ => "string started by `` ` ``",
+        };
+        (kind, i)
+    }))
+}
+
+/// Finds the first variable block, tag, comment or string literal that is never closed,
+/// returning what it is and its offset. Newlines are allowed inside of them so a missing
+/// end delimiter is only noticed at the end of the template: this points at where the
+/// problem actually is.
+pub(crate) fn find_unterminated(input: &str) -> Option<(&'static str, usize)> {
+    let mut offset = 0;
+    let mut in_raw = false;
+
+    loop {
+        let rest = &input[offset..];
+        let (i, start, end, name) =
+            [("{{", "}}", "`{{`"), ("{%", "%}", "`{%`"), ("{#", "#}", "`{#`")]
+                .iter()
+                .filter(|(start, ..)| !in_raw || *start == "{%")
+                .filter_map(|&(start, end, name)| rest.find(start).map(|i| (i, start, end, name)))
+                .min_by_key(|&(i, ..)| i)?;
+
+        let after_start = &rest[i + start.len()..];
+        let end_pos = if start == "{#" {
+            after_start.find(end).ok_or(None)
+        } else {
+            scan_tag(after_start, end)
+        };
+        let end_pos = match end_pos {
+            Ok(e) => e,
+            Err(_) if in_raw => return None,
+            Err(Some((inner, j))) => return Some((inner, offset + i + start.len() + j)),
+            Err(None) => return Some((name, offset + i)),
+        };
+
+        if start == "{%" {
+            let content =
+                after_start[..end_pos].trim_matches(|c: char| c == '-' || c.is_whitespace());
+            if content == "raw" {
+                in_raw = true;
+            } else if content == "endraw" {
+                in_raw = false;
+            }
+        }
+        offset += i + start.len() + end_pos + end.len();
+    }
+}
+
+/// Rewrites a template using custom delimiters into one using the default ones.
+/// Default delimiters appearing in the text are output as string literals so they
+/// are rendered as is.
+pub(crate) fn translate(input: &str, delimiters: &Delimiters) -> Result<String> {
+    let default = Delimiters::default();
+    let mut res = String::with_capacity(input.len());
+    let mut rest = input;
+    // Whether we are in a `raw` block, in which only the `endraw` tag is looked for
+    let mut in_raw = false;
+
+    while !rest.is_empty() {
+        let next_start = [
+            (
+                &delimiters.variable_start,
+                &delimiters.variable_end,
+                &default.variable_start,
+                &default.variable_end,
+            ),
+            (
+                &delimiters.block_start,
+                &delimiters.block_end,
+                &default.block_start,
+                &default.block_end,
+            ),
+            (
+                &delimiters.comment_start,
+                &delimiters.comment_end,
+                &default.comment_start,
+                &default.comment_end,
+            ),
+        ]
+        .iter()
+        .filter(|(start, ..)| !in_raw || *start == &delimiters.block_start)
+        .filter_map(|&(start, end, default_start, default_end)| {
+            rest.find(start.as_str()).map(|i| (i, start, end, default_start, default_end))
+        })
+        .min_by_key(|&(i, start, ..)| (i, Reverse(start.len())));
+
+        let (i, start, end, default_start, default_end) = match next_start {
+            Some(s) => s,
+            None => {
+                push_text(&mut res, rest, in_raw);
+                break;
+            }
+        };
+
+        push_text(&mut res, &rest[..i], in_raw);
+        let after_start = &rest[i + start.len()..];
+        let is_comment = start == &delimiters.comment_start;
+        let end_pos = if is_comment {
+            after_start.find(end.as_str())
+        } else {
+            find_tag_end(after_start, end)
+        };
+        let end_pos = match end_pos {
+            Some(e) => e,
+            None => {
+                return Err(Error::msg(format!(
+                    "Missing `{}` to close the `{}` opened at byte {}",
+                    end,
+                    start,
+                    input.len() - rest.len() + i
+                )))
+            }
+        };
+        let content = &after_start[..end_pos];
+
+        if in_raw {
+            if content.trim_matches(|c: char| c == '-' || c.is_whitespace()) != "endraw" {
+                // Not the end of the raw block, this is just text
+                push_text(&mut res, &rest[..i + start.len()], true);
+                rest = after_start;
+                continue;
+            }
+            in_raw = false;
+        } else if start == &delimiters.block_start
+            && content.trim_matches(|c: char| c == '-' || c.is_whitespace()) == "raw"
+        {
+            in_raw = true;
+        }
+
+        res.push_str(default_start);
+        if is_comment {
+            res.push_str(&content.replace(default_end.as_str(), "# }"));
+        } else {
+            res.push_str(content);
+        }
+        res.push_str(default_end);
+        rest = &after_start[end_pos + end.len()..];
+    }
+
+    Ok(res)
+}
+
+/// Text containing the default start delimiters is output as a string literal
+/// so it's not interpreted. Text in raw blocks is never interpreted.
+fn push_text(res: &mut String, text: &str, in_raw: bool) {
+    if in_raw {
+        res.push_str(text);
+        return;
+    }
+
+    let mut rest = text;
+    while let Some(i) = ["{{", "{%", "{#"].iter().filter_map(|d| rest.find(d)).min() {
+        res.push_str(&rest[..i]);
+        res.push_str(&format!("{{{{ \"{}\" }}}}", &rest[i..i + 2]));
+        rest = &rest[i + 2..];
+    }
+    res.push_str(rest);
+}
@@ -0,0 +1,72 @@
+//! Some of the syntax of Django templates, to ease porting them. Like custom delimiters,
+//! it is handled by translating the template to the Tera syntax before parsing it:
+//!
+//! - `{% empty %}` in `for` loops becomes `{% else %}`
+//! - `{% comment %}...{% endcomment %}` becomes a `{# #}` comment
+//! - `value|filter:arg` becomes `value|filter(name=arg)`, `name` being the argument of the
+//!   Tera filter with the same name or `arg` for other filters
+use crate::errors::Result;
+use crate::parser::compat::{is_string, tag_name, translate_filters, translate_tags, FilterArg};
+
+/// The argument a Django positional filter argument is passed as, for the built-in filters
+/// taking one. Other filters get it as `arg`.
+const FILTER_ARGS: [(&str, &str); 20] = [
+    ("concat", "with"),
+    ("date", "format"),
+    ("default", "value"),
+    ("filter", "attribute"),
+    ("float", "default"),
+    ("format", "spec"),
+    ("get", "key"),
+    ("group_by", "attribute"),
+    ("int", "default"),
+    ("join", "sep"),
+    ("json_encode", "pretty"),
+    ("map", "attribute"),
+    ("nth", "n"),
+    ("round", "precision"),
+    ("sort", "attribute"),
+    ("split", "pat"),
+    ("trim_end_matches", "pat"),
+    ("trim_start_matches", "pat"),
+    ("truncate", "length"),
+    ("unique", "attribute"),
+];
+
+/// Rewrites a template using the Django syntax into one using the Tera one
+pub(crate) fn translate(input: &str) -> Result<String> {
+    translate_tags(input, |start, content, end| {
+        if start == "{%" && tag_name(content) == "empty" {
+            return Ok(format!("{}{}{}", start, content.replacen("empty", "else", 1), end));
+        }
+        Ok(format!("{}{}{}", start, translate_filters(content, false, filter_call), end))
+    })
+}
+
+/// The Tera call of a filter called with a Django positional argument
+fn filter_call(name: &str, args: &[FilterArg]) -> String {
+    let arg = match args.first() {
+        Some((_, arg)) => arg,
+        None => return name.to_string(),
+    };
+    // `pluralize:"y,ies"` gives the singular and plural suffixes
+    if name == "pluralize" && is_string(arg) && arg.contains(',') {
+        let quote = &arg[..1];
+        let inner = &arg[1..arg.len() - 1];
+        let comma = inner.find(',').unwrap();
+        return format!(
+            "{}(singular={q}{}{q}, plural={q}{}{q})",
+            name,
+            &inner[..comma],
+            &inner[comma + 1..],
+            q = quote
+        );
+    }
+
+    let arg_name = match FILTER_ARGS.iter().find(|(filter, _)| *filter == name) {
+        Some((_, arg_name)) => arg_name,
+        None if name == "pluralize" => "plural",
+        None => "arg",
+    };
+    format!("{}({}={})", name, arg_name, arg)
+}
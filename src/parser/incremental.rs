@@ -0,0 +1,159 @@
+//! Parsing a template again after an edit without going through all of it, for editors
+//! that need up to date diagnostics on every keystroke.
+//!
+//! Only the top-level nodes touched by the edit are parsed again, along with the text and
+//! top imports next to them since those can merge with what the edit adds. The nodes before
+//! are kept as they are and the ones after only get their span moved.
+use std::ops::Range;
+
+use crate::errors::{Error, Result};
+use crate::parser::ast::Node;
+use crate::parser::lexer::LineIndex;
+use crate::parser::{parse, set_line_columns};
+
+/// A template source along with the result of parsing it, kept up to date as the source
+/// is edited.
+///
+/// The nodes are the ones of the template as parsed, before whitespace control is applied.
+///
+/// ```rust
+/// # use tera::ParsedSource;
+/// let mut parsed = ParsedSource::new("Hello {{ name }}!");
+/// assert_eq!(parsed.nodes().unwrap().len(), 3);
+///
+/// // Replaces `name` by `user.name | upper`
+/// assert!(parsed.edit(9..13, "user.name | upper").is_ok());
+/// assert_eq!(parsed.source(), "Hello {{ user.name | upper }}!");
+///
+/// // The error is the same one as when parsing the whole template
+/// let err = parsed.edit(29..29, "{{").unwrap_err();
+/// assert!(err.to_string().contains("the `{{` at 1:30 is never closed"));
+/// ```
+#[derive(Debug)]
+pub struct ParsedSource {
+    source: String,
+    result: Result<Vec<Node>>,
+}
+
+impl ParsedSource {
+    /// Parses the given template source
+    pub fn new(source: &str) -> Self {
+        ParsedSource { source: source.to_string(), result: parse(source) }
+    }
+
+    /// The current source of the template
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The top-level nodes of the template, or the syntax error if it isn't valid
+    pub fn nodes(&self) -> std::result::Result<&[Node], &Error> {
+        match self.result {
+            Ok(ref nodes) => Ok(nodes),
+            Err(ref e) => Err(e),
+        }
+    }
+
+    /// Replaces the given byte range of the source by `text` and parses the template again,
+    /// reusing the nodes the edit doesn't touch if the template was valid before it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or doesn't lie on char boundaries, like
+    /// `String::replace_range`.
+    pub fn edit(
+        &mut self,
+        range: Range<usize>,
+        text: &str,
+    ) -> std::result::Result<&[Node], &Error> {
+        self.source.replace_range(range.clone(), text);
+        let previous = std::mem::replace(&mut self.result, Ok(vec![]));
+        self.result = match previous {
+            Ok(nodes) => reparse(nodes, &self.source, range, text.len()),
+            Err(_) => parse(&self.source),
+        };
+        self.nodes()
+    }
+}
+
+/// Whether the node can be different when parsed with what is next to it in the template:
+/// text merges with adjacent text and top imports eat the whitespace around them
+fn depends_on_neighbours(node: &Node) -> bool {
+    match node {
+        Node::Text(..) => true,
+        _ => is_top_import(node),
+    }
+}
+
+fn is_top_import(node: &Node) -> bool {
+    match node {
+        Node::Extends(..) | Node::DynamicExtends(..) | Node::ImportMacro(..) => true,
+        _ => false,
+    }
+}
+
+fn shift_offsets(nodes: &mut [Node], delta: isize) {
+    for node in nodes {
+        let (span, bodies) = node.span_and_bodies_mut();
+        span.start.offset = (span.start.offset as isize + delta) as usize;
+        span.end.offset = (span.end.offset as isize + delta) as usize;
+        for body in bodies {
+            shift_offsets(body, delta);
+        }
+    }
+}
+
+/// Parses `source` again, `range` of the previous source, whose nodes are given, having
+/// been replaced by `inserted_len` bytes.
+fn reparse(
+    mut nodes: Vec<Node>,
+    source: &str,
+    range: Range<usize>,
+    inserted_len: usize,
+) -> Result<Vec<Node>> {
+    let delta = inserted_len as isize - range.len() as isize;
+
+    let mut prefix_len = nodes.iter().take_while(|n| n.span().end.offset < range.start).count();
+    while prefix_len > 0 && depends_on_neighbours(&nodes[prefix_len - 1]) {
+        prefix_len -= 1;
+    }
+    let mut suffix_start = nodes
+        .iter()
+        .position(|n| n.span().start.offset > range.end)
+        .unwrap_or(nodes.len())
+        .max(prefix_len);
+    while suffix_start < nodes.len() && depends_on_neighbours(&nodes[suffix_start]) {
+        suffix_start += 1;
+    }
+
+    let region_start = if prefix_len > 0 { nodes[prefix_len - 1].span().end.offset } else { 0 };
+    let region_end = match nodes.get(suffix_start) {
+        Some(n) => (n.span().start.offset as isize + delta) as usize,
+        None => source.len(),
+    };
+    let region = &source[region_start..region_end];
+    // A `{` at the end of the region would make a start delimiter with the next node
+    if suffix_start < nodes.len() && region.ends_with('{') {
+        return parse(source);
+    }
+
+    // Errors are reported by a full parse so their message is about the whole template
+    let mut region_nodes = match parse(region) {
+        Ok(n) => n,
+        Err(_) => return parse(source),
+    };
+    // Top imports are only valid at the start of the template
+    if region_start > 0 && region_nodes.iter().any(is_top_import) {
+        return parse(source);
+    }
+
+    let mut suffix = nodes.split_off(suffix_start);
+    nodes.truncate(prefix_len);
+    shift_offsets(&mut region_nodes, region_start as isize);
+    shift_offsets(&mut suffix, delta);
+    nodes.extend(region_nodes);
+    nodes.extend(suffix);
+    set_line_columns(&mut nodes[prefix_len..], source, &LineIndex::new(source));
+
+    Ok(nodes)
+}
@@ -0,0 +1,281 @@
+//! A token-level view of a template.
+//!
+//! Tools like syntax highlighters or editor plugins often only need to know what each
+//! part of a template is, not what it means. The `Lexer` gives them a flat list of tokens
+//! with their kind and exact location without exposing the parser internals.
+use pest::iterators::Pair;
+
+use crate::errors::Result;
+use crate::parser::{parse_template_pairs, Rule};
+
+/// A location in the template source
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Position {
+    /// Byte offset from the start of the template
+    pub offset: usize,
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column number in characters, starting at 1
+    pub column: usize,
+}
+
+/// A range in the template source. `end` is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Where the range starts
+    pub start: Position,
+    /// Where the range ends (exclusive)
+    pub end: Position,
+}
+
+/// The kind of a token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Text outside of any tag, including the content of `{% raw %}` blocks
+    Text,
+    /// `{{` or `{{-`
+    VariableStart,
+    /// `}}` or `-}}`
+    VariableEnd,
+    /// `{%` or `{%-`
+    TagStart,
+    /// `%}` or `-%}`
+    TagEnd,
+    /// A whole `{# ... #}` comment
+    Comment,
+    /// A tag name or a reserved word such as `for`, `in`, `is`, `endif` or `super`
+    Keyword,
+    /// An identifier, which can be dotted or use square brackets: `user.name`, `items[0]`
+    Ident,
+    /// A string literal, including its quotes
+    String,
+    /// An integer literal
+    Integer,
+    /// A float literal
+    Float,
+    /// `true` or `false`
+    Bool,
+    /// `none`
+    Null,
+    /// A math, comparison, concatenation or logic operator: `+`, `==`, `~`, `and`...
+    Operator,
+    /// Any other symbol found in a tag: `(`, `)`, `,`, `=`, `|`, `::`, `[`, `]`...
+    Punctuation,
+}
+
+/// A single token of a template
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token<'a> {
+    /// What the token is
+    pub kind: TokenKind,
+    /// The source text of the token
+    pub value: &'a str,
+    /// Where the token is in the template
+    pub span: Span,
+}
+
+/// Maps byte offsets to line/column positions without rescanning the input every time
+#[derive(Debug)]
+pub(crate) struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(input: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in input.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        LineIndex { line_starts }
+    }
+
+    pub fn position(&self, input: &str, offset: usize) -> Position {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(l) => l,
+            Err(l) => l - 1,
+        };
+        let column = input[self.line_starts[line]..offset].chars().count() + 1;
+        Position { offset, line: line + 1, column }
+    }
+
+    pub fn span(&self, input: &str, start: usize, end: usize) -> Span {
+        Span { start: self.position(input, start), end: self.position(input, end) }
+    }
+}
+
+/// Turns a template into a list of tokens.
+///
+/// ```rust
+/// # use tera::{Lexer, TokenKind};
+/// let tokens = Lexer::new("Hello {{ name | upper }}").tokenize().unwrap();
+/// let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+/// assert_eq!(kinds, vec![
+///     TokenKind::Text,
+///     TokenKind::VariableStart,
+///     TokenKind::Ident,
+///     TokenKind::Punctuation,
+///     TokenKind::Ident,
+///     TokenKind::VariableEnd,
+/// ]);
+/// ```
+#[derive(Debug)]
+pub struct Lexer<'a> {
+    input: &'a str,
+}
+
+impl<'a> Lexer<'a> {
+    /// Creates a lexer for the given template source
+    pub fn new(input: &'a str) -> Self {
+        Lexer { input }
+    }
+
+    /// Tokenizes the whole template.
+    ///
+    /// This errors if the template is not syntactically valid, with the same
+    /// error message as the one you would get when adding the template to Tera.
+    pub fn tokenize(&self) -> Result<Vec<Token<'a>>> {
+        let mut raw_tokens = vec![];
+        for pair in parse_template_pairs(self.input)? {
+            collect_raw_tokens(pair, &mut raw_tokens);
+        }
+
+        let lines = LineIndex::new(self.input);
+        let mut tokens = Vec::with_capacity(raw_tokens.len());
+        let mut in_tag = false;
+        let mut last_end = 0;
+
+        for (kind, start, end) in raw_tokens {
+            self.fill_gap(last_end, start, in_tag, &lines, &mut tokens);
+            match kind {
+                TokenKind::VariableStart | TokenKind::TagStart => in_tag = true,
+                TokenKind::VariableEnd | TokenKind::TagEnd => in_tag = false,
+                _ => (),
+            }
+            tokens.push(self.token(kind, start, end, &lines));
+            last_end = end;
+        }
+        self.fill_gap(last_end, self.input.len(), in_tag, &lines, &mut tokens);
+
+        Ok(tokens)
+    }
+
+    fn token(&self, kind: TokenKind, start: usize, end: usize, lines: &LineIndex) -> Token<'a> {
+        Token { kind, value: &self.input[start..end], span: lines.span(self.input, start, end) }
+    }
+
+    /// Some parts of the templates are not captured by the grammar, such as tag names or
+    /// punctuation inside tags and whitespace around `extends`/`import` tags.
+    fn fill_gap(
+        &self,
+        start: usize,
+        end: usize,
+        in_tag: bool,
+        lines: &LineIndex,
+        tokens: &mut Vec<Token<'a>>,
+    ) {
+        if start >= end {
+            return;
+        }
+
+        if !in_tag {
+            tokens.push(self.token(TokenKind::Text, start, end, lines));
+            return;
+        }
+
+        let gap = &self.input[start..end];
+        let mut chars = gap.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            let mut token_end = i + c.len_utf8();
+            let kind = if gap[i..].starts_with("{#") {
+                // A comment inside of a tag, the grammar ensures it is closed
+                token_end = i + gap[i..].find("#}").unwrap() + 2;
+                while let Some(&(j, _)) = chars.peek() {
+                    if j >= token_end {
+                        break;
+                    }
+                    chars.next();
+                }
+                TokenKind::Comment
+            } else if c.is_alphanumeric() || c == '_' {
+                while let Some(&(j, c2)) = chars.peek() {
+                    if !(c2.is_alphanumeric() || c2 == '_') {
+                        break;
+                    }
+                    token_end = j + c2.len_utf8();
+                    chars.next();
+                }
+                match &gap[i..token_end] {
+                    "and" | "or" | "not" => TokenKind::Operator,
+                    _ => TokenKind::Keyword,
+                }
+            } else if c == ':' && chars.peek().map(|&(_, c2)| c2) == Some(':') {
+                chars.next();
+                token_end += 1;
+                TokenKind::Punctuation
+            } else if c == '~' {
+                TokenKind::Operator
+            } else {
+                TokenKind::Punctuation
+            };
+
+            tokens.push(self.token(kind, start + i, start + token_end, lines));
+        }
+    }
+}
+
+/// Walks the pest pairs and collects the ones that map directly to a token.
+/// Tokens are stored as (kind, start, end) byte offsets.
+fn collect_raw_tokens(pair: Pair<Rule>, tokens: &mut Vec<(TokenKind, usize, usize)>) {
+    let kind = match pair.as_rule() {
+        Rule::text | Rule::raw_text => TokenKind::Text,
+        Rule::comment_tag => TokenKind::Comment,
+        Rule::variable_start => TokenKind::VariableStart,
+        Rule::variable_end => TokenKind::VariableEnd,
+        Rule::tag_start => TokenKind::TagStart,
+        Rule::tag_end => TokenKind::TagEnd,
+        Rule::string => TokenKind::String,
+        Rule::int => TokenKind::Integer,
+        Rule::float => TokenKind::Float,
+        Rule::boolean => TokenKind::Bool,
+        Rule::none => TokenKind::Null,
+        Rule::ident | Rule::dotted_ident | Rule::dotted_square_bracket_ident => TokenKind::Ident,
+        Rule::op_or
+        | Rule::op_and
+        | Rule::op_not
+        | Rule::op_lte
+        | Rule::op_gte
+        | Rule::op_lt
+        | Rule::op_gt
+        | Rule::op_eq
+        | Rule::op_ineq
+        | Rule::op_plus
+        | Rule::op_minus
+        | Rule::op_times
+        | Rule::op_floor_div
+        | Rule::op_slash
+        | Rule::op_modulo => TokenKind::Operator,
+        _ => {
+            for p in pair.into_inner() {
+                collect_raw_tokens(p, tokens);
+            }
+            return;
+        }
+    };
+
+    let span = pair.as_span();
+    let end = if kind == TokenKind::Operator {
+        // Keyword operators like `and` include the whitespace following them in the grammar
+        span.start() + span.as_str().trim_end().len()
+    } else {
+        span.end()
+    };
+    if end > span.start() {
+        tokens.push((kind, span.start(), end));
+    }
+}
@@ -0,0 +1,311 @@
+//! The syntax of Liquid templates, as used by Shopify and Jekyll, for the templates Tera is
+//! told to parse with it. Like the Django one, it is handled by translating the template to
+//! the Tera syntax before parsing it:
+//!
+//! - `{% assign a = b %}` becomes `{% set a = b %}` and `{% elsif %}` becomes `{% elif %}`
+//! - `{% unless c %}...{% endunless %}` becomes an `if` with the negated condition
+//! - `{% case %}` and `{% when %}` become an `if` with an `elif` for each value
+//! - `(a..b)` ranges and the `reversed`, `limit:` and `offset:` of `for` loops become
+//!   the `range` function and the `slice` and `reverse` filters
+//! - `a contains b` becomes `b in a`, `nil` becomes `none` and `forloop` becomes `loop`
+//! - filters with a Tera equivalent are renamed and get their arguments by name
+//! - `{% comment %}...{% endcomment %}` becomes a `{# #}` comment
+use crate::errors::{Error, Result};
+use crate::parser::compat::{
+    is_quote, string_end, tag_name, translate_filters, translate_tags, FilterArg,
+};
+
+/// Liquid filter -> (Tera filter, names of its positional arguments)
+const FILTERS: [(&str, &str, &[&str]); 25] = [
+    ("capitalize", "capitalize", &[]),
+    ("concat", "concat", &["with"]),
+    ("date", "date", &["format"]),
+    ("default", "default", &["value"]),
+    ("downcase", "lower", &[]),
+    ("escape", "escape", &[]),
+    ("first", "first", &[]),
+    ("join", "join", &["sep"]),
+    ("last", "last", &[]),
+    ("lstrip", "trim_start", &[]),
+    ("map", "map", &["attribute"]),
+    ("replace", "replace", &["from", "to"]),
+    ("reverse", "reverse", &[]),
+    ("round", "round", &["precision"]),
+    ("rstrip", "trim_end", &[]),
+    ("size", "length", &[]),
+    ("sort", "sort", &["attribute"]),
+    ("split", "split", &["pat"]),
+    ("strip", "trim", &[]),
+    ("strip_html", "striptags", &[]),
+    ("truncate", "truncate", &["length", "end"]),
+    ("uniq", "unique", &["attribute"]),
+    ("upcase", "upper", &[]),
+    ("url_encode", "urlencode", &[]),
+    ("where", "filter", &["attribute", "value"]),
+];
+
+/// Rewrites a template using the Liquid syntax into one using the Tera one
+pub(crate) fn translate(input: &str) -> Result<String> {
+    // The value of the `case` tags we are in and whether a `when` was found for them
+    let mut cases: Vec<(String, bool)> = vec![];
+
+    translate_tags(input, |start, content, end| {
+        let content = translate_idents(content);
+        if start == "{{" {
+            return Ok(format!(
+                "{}{}{}",
+                start,
+                translate_filters(&content, true, filter_call),
+                end
+            ));
+        }
+
+        let ws_start = if content.starts_with('-') { "-" } else { "" };
+        let ws_end =
+            if content.len() > ws_start.len() && content.ends_with('-') { "-" } else { "" };
+        let inner = content[ws_start.len()..content.len() - ws_end.len()].trim();
+        let name = tag_name(inner);
+        let args = inner[name.len()..].trim();
+        let tag = |inner: &str| format!("{}{} {} {}{}", start, ws_start, inner, ws_end, end);
+
+        let translated = match name {
+            "assign" => format!("set {}", translate_filters(args, true, filter_call)),
+            "if" => format!("if {}", translate_condition(args, false)?),
+            "elsif" => format!("elif {}", translate_condition(args, false)?),
+            "unless" => format!("if {}", translate_condition(args, true)?),
+            "endunless" => "endif".to_string(),
+            "for" => format!("for {}", translate_for(args)),
+            "case" => {
+                cases.push((translate_filters(args, true, filter_call), false));
+                return Ok(String::new());
+            }
+            "when" => match cases.last_mut() {
+                Some((value, seen_when)) => {
+                    let condition = split_tokens(args)
+                        .into_iter()
+                        .map(|t| t.trim_end_matches(','))
+                        .filter(|t| !t.is_empty() && *t != "or")
+                        .map(|t| format!("{} == {}", value, t))
+                        .collect::<Vec<_>>()
+                        .join(" or ");
+                    let keyword = if *seen_when { "elif" } else { "if" };
+                    *seen_when = true;
+                    format!("{} {}", keyword, condition)
+                }
+                None => inner.to_string(),
+            },
+            "endcase" => match cases.pop() {
+                Some((_, true)) => "endif".to_string(),
+                Some((_, false)) => return Ok(String::new()),
+                None => inner.to_string(),
+            },
+            _ => translate_filters(inner, true, filter_call),
+        };
+        Ok(tag(&translated))
+    })
+}
+
+/// The Tera call of a Liquid filter
+fn filter_call(name: &str, args: &[FilterArg]) -> String {
+    let (tera_name, arg_names) = match FILTERS.iter().find(|(liquid, ..)| *liquid == name) {
+        Some((_, tera_name, arg_names)) => (*tera_name, *arg_names),
+        None => (name, &[][..]),
+    };
+    if args.is_empty() {
+        return tera_name.to_string();
+    }
+
+    let args: Vec<_> = args
+        .iter()
+        .enumerate()
+        .map(|(i, (arg_name, value))| match (arg_name, arg_names.get(i)) {
+            (Some(arg_name), _) | (None, Some(arg_name)) => format!("{}={}", arg_name, value),
+            (None, None) if i == 0 => format!("arg={}", value),
+            (None, None) => format!("arg{}={}", i + 1, value),
+        })
+        .collect();
+    format!("{}({})", tera_name, args.join(", "))
+}
+
+/// Replaces `nil` by `none` and `forloop` by `loop`
+fn translate_idents(content: &str) -> String {
+    let mut res = String::with_capacity(content.len());
+    let mut i = 0;
+    let mut previous = ' ';
+
+    while i < content.len() {
+        let c = content[i..].chars().next().unwrap();
+        if is_quote(c) {
+            let end = i + string_end(&content[i..]);
+            res.push_str(&content[i..end]);
+            i = end;
+            previous = c;
+            continue;
+        }
+
+        let starts_ident = (c.is_alphabetic() || c == '_')
+            && !(previous.is_alphanumeric() || previous == '_' || previous == '.');
+        if starts_ident {
+            let len = content[i..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(content.len() - i);
+            match &content[i..i + len] {
+                "nil" => res.push_str("none"),
+                "forloop" => res.push_str("loop"),
+                ident => res.push_str(ident),
+            }
+            i += len;
+            previous = 'a';
+            continue;
+        }
+
+        res.push(c);
+        i += c.len_utf8();
+        previous = c;
+    }
+
+    res
+}
+
+/// Splits the content of a tag on whitespace, keeping string literals whole
+fn split_tokens(content: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = None;
+    let mut i = 0;
+
+    while i < content.len() {
+        let c = content[i..].chars().next().unwrap();
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                tokens.push(&content[s..i]);
+            }
+            i += c.len_utf8();
+            continue;
+        }
+        if start.is_none() {
+            start = Some(i);
+        }
+        i += if is_quote(c) { string_end(&content[i..]) } else { c.len_utf8() };
+    }
+    if let Some(s) = start {
+        tokens.push(&content[s..]);
+    }
+
+    tokens
+}
+
+/// Translates the condition of a `if`, `elsif` or `unless` tag, negating it for the latter.
+///
+/// Liquid evaluates `and` and `or` from right to left, `a and b or c` being `a and (b or c)`,
+/// which can't be written in Tera where `and` comes first: those conditions are an error.
+fn translate_condition(condition: &str, negated: bool) -> Result<String> {
+    let mut clauses = vec![vec![]];
+    let mut operators = vec![];
+    for token in split_tokens(condition) {
+        if token == "and" || token == "or" {
+            operators.push(token);
+            clauses.push(vec![]);
+        } else {
+            clauses.last_mut().unwrap().push(token);
+        }
+    }
+
+    let first_and = operators.iter().position(|o| *o == "and");
+    let last_or = operators.iter().rposition(|o| *o == "or");
+    let unsupported = match (first_and, last_or) {
+        // Negating the condition swaps the operators
+        (Some(_), Some(_)) if negated => true,
+        (Some(a), Some(o)) => a < o,
+        _ => false,
+    };
+    if unsupported {
+        return Err(Error::msg(format!(
+            "The Liquid condition `{}` can't be translated: Liquid evaluates `and` and `or` from \
+             right to left, add a tag to split it",
+            condition
+        )));
+    }
+
+    let mut res = String::with_capacity(condition.len());
+    for (i, clause) in clauses.iter().enumerate() {
+        if i > 0 {
+            let operator = match operators[i - 1] {
+                "and" if negated => "or",
+                "or" if negated => "and",
+                operator => operator,
+            };
+            res.push_str(&format!(" {} ", operator));
+        }
+        if negated {
+            res.push_str("not ");
+        }
+        let clause = match clause.iter().position(|t| *t == "contains") {
+            Some(k) if k > 0 && k + 1 < clause.len() => {
+                format!("{} in {}", clause[k + 1..].join(" "), clause[..k].join(" "))
+            }
+            _ => clause.join(" "),
+        };
+        res.push_str(&translate_filters(&clause, true, filter_call));
+    }
+
+    Ok(res)
+}
+
+/// Translates the arguments of a `for` tag
+fn translate_for(args: &str) -> String {
+    let tokens = split_tokens(args);
+    let in_pos = match tokens.iter().position(|t| *t == "in") {
+        Some(p) => p,
+        None => return args.to_string(),
+    };
+
+    let mut collection = vec![];
+    let mut reversed = false;
+    let mut limit = None;
+    let mut offset = None;
+    let mut rest = tokens[in_pos + 1..].iter();
+    while let Some(token) = rest.next() {
+        if *token == "reversed" {
+            reversed = true;
+            continue;
+        }
+        let modifier = if token.starts_with("limit:") {
+            &mut limit
+        } else if token.starts_with("offset:") {
+            &mut offset
+        } else {
+            collection.push(*token);
+            continue;
+        };
+        let value = &token[token.find(':').unwrap() + 1..];
+        *modifier = if value.is_empty() { rest.next().cloned() } else { Some(value) };
+    }
+
+    let collection = collection.join(" ");
+    let range = if collection.len() > 1 && collection.starts_with('(') && collection.ends_with(')')
+    {
+        Some(&collection[1..collection.len() - 1])
+    } else {
+        None
+    };
+    let mut collection = match range {
+        Some(range) if range.contains("..") => {
+            let dots = range.find("..").unwrap();
+            format!("range(start={}, end={} + 1)", range[..dots].trim(), range[dots + 2..].trim())
+        }
+        _ => translate_filters(&collection, true, filter_call),
+    };
+    match (offset, limit) {
+        (Some(o), Some(l)) => {
+            collection.push_str(&format!(" | slice(start={}, end={} + {})", o, o, l))
+        }
+        (Some(o), None) => collection.push_str(&format!(" | slice(start={})", o)),
+        (None, Some(l)) => collection.push_str(&format!(" | slice(end={})", l)),
+        (None, None) => (),
+    }
+    if reversed {
+        collection.push_str(" | reverse");
+    }
+
+    format!("{} in {}", tokens[..in_pos].join(" "), collection)
+}
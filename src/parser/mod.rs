@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use lazy_static::lazy_static;
-use pest::iterators::Pair;
+use pest::error::{Error as PestError, InputLocation};
+use pest::iterators::{Pair, Pairs};
 use pest::prec_climber::{Assoc, Operator, PrecClimber};
 use pest::Parser;
 use pest_derive::Parser;
@@ -18,20 +19,30 @@ pub struct TeraParser;
 
 /// The AST of Tera
 pub mod ast;
+pub(crate) mod compat;
+pub(crate) mod delimiters;
+pub(crate) mod django;
+pub(crate) mod incremental;
+pub mod lexer;
+pub(crate) mod liquid;
+pub(crate) mod recovery;
 mod whitespace;
 
 #[cfg(test)]
 mod tests;
 
 use self::ast::*;
-pub use self::whitespace::remove_whitespace;
+use self::delimiters::find_unterminated;
+use self::lexer::LineIndex;
+pub use self::whitespace::{remove_whitespace, remove_whitespace_between_tags};
 
 lazy_static! {
     static ref MATH_CLIMBER: PrecClimber<Rule> = PrecClimber::new(vec![
         // +, -
         Operator::new(Rule::op_plus, Assoc::Left) | Operator::new(Rule::op_minus, Assoc::Left),
-        // *, /, %
+        // *, //, /, %
         Operator::new(Rule::op_times, Assoc::Left) |
+        Operator::new(Rule::op_floor_div, Assoc::Left) |
         Operator::new(Rule::op_slash, Assoc::Left) |
         Operator::new(Rule::op_modulo, Assoc::Left),
     ]);
@@ -49,13 +60,100 @@ lazy_static! {
 
 /// Strings are delimited by double quotes, single quotes and backticks
 /// We need to remove those before putting them in the AST
-fn replace_string_markers(input: &str) -> String {
-    match input.chars().next().unwrap() {
-        '"' => input.replace('"', ""),
-        '\'' => input.replace('\'', ""),
-        '`' => input.replace('`', ""),
-        _ => unreachable!("How did you even get there"),
+/// Removes the quotes around a string literal and decodes its escape sequences.
+/// Backquoted strings are raw: their content is kept as is.
+fn parse_string(pair: &Pair<Rule>) -> TeraResult<String> {
+    let literal = pair.as_str();
+    let content = &literal[1..literal.len() - 1];
+    if literal.starts_with('`') || !content.contains('\\') {
+        return Ok(content.to_string());
     }
+
+    let mut res = String::with_capacity(content.len());
+    let mut chars = content.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        // The grammar ensures there is always a character after a backslash
+        let decoded = match chars.next().unwrap().1 {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            'u' if chars.peek().map(|&(_, c)| c) == Some('{') => {
+                let mut code = String::new();
+                let mut closed = false;
+                chars.next();
+                for (_, c) in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    code.push(c);
+                }
+                if closed && code.len() <= 6 {
+                    u32::from_str_radix(&code, 16).ok().and_then(std::char::from_u32)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        match decoded {
+            Some(c) => res.push(c),
+            None => {
+                let end = chars.peek().map(|&(i, _)| i).unwrap_or_else(|| content.len());
+                // The position of the backslash in the template, the literal starting with a quote
+                let before = &literal[..=start];
+                let (mut line, mut column) = pair.as_span().start_pos().line_col();
+                match before.rfind('\n') {
+                    Some(i) => {
+                        line += before.matches('\n').count();
+                        column = before[i + 1..].chars().count() + 1;
+                    }
+                    None => column += before.chars().count(),
+                }
+                return Err(Error::msg(format!(
+                    "Invalid escape sequence `{}` in string literal at line {}, column {}",
+                    &content[start..end],
+                    line,
+                    column
+                )));
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+/// Integer literals can have underscores between their digits and be written in
+/// hexadecimal (`0xFF`) or binary (`0b1010`)
+fn parse_int(pair: &Pair<Rule>) -> TeraResult<i64> {
+    let literal = pair.as_str().replace('_', "");
+    let (sign, digits) =
+        if literal.starts_with('-') { ("-", &literal[1..]) } else { ("", literal.as_str()) };
+    let res = if digits.starts_with("0x") {
+        i64::from_str_radix(&format!("{}{}", sign, &digits[2..]), 16)
+    } else if digits.starts_with("0b") {
+        i64::from_str_radix(&format!("{}{}", sign, &digits[2..]), 2)
+    } else {
+        literal.parse()
+    };
+    res.map_err(|_| Error::msg(format!("Integer out of bounds: `{}`", pair.as_str())))
+}
+
+/// Float literals can have underscores between their digits
+fn parse_float(pair: &Pair<Rule>) -> TeraResult<f64> {
+    pair.as_str()
+        .replace('_', "")
+        .parse()
+        .map_err(|_| Error::msg(format!("Float out of bounds: `{}`", pair.as_str())))
 }
 
 fn parse_kwarg(pair: Pair<Rule>) -> TeraResult<(String, Expr)> {
@@ -142,13 +240,15 @@ fn parse_test_call(pair: Pair<Rule>) -> TeraResult<(String, Vec<Expr>)> {
 }
 
 fn parse_test(pair: Pair<Rule>) -> TeraResult<Test> {
-    let mut ident = None;
+    let mut ident = String::new();
+    let mut call = None;
     let mut name = None;
     let mut args = vec![];
 
     for p in pair.into_inner() {
         match p.as_rule() {
-            Rule::dotted_ident => ident = Some(p.as_str().to_string()),
+            Rule::dotted_ident => ident = p.as_str().to_string(),
+            Rule::fn_call => call = Some(parse_fn_call(p)?),
             Rule::test_call => {
                 let (_name, _args) = parse_test_call(p)?;
                 name = Some(_name);
@@ -158,7 +258,7 @@ fn parse_test(pair: Pair<Rule>) -> TeraResult<Test> {
         };
     }
 
-    Ok(Test { ident: ident.unwrap(), negated: false, name: name.unwrap(), args })
+    Ok(Test { ident, call, negated: false, name: name.unwrap(), args })
 }
 
 fn parse_string_concat(pair: Pair<Rule>) -> TeraResult<ExprVal> {
@@ -169,27 +269,21 @@ fn parse_string_concat(pair: Pair<Rule>) -> TeraResult<ExprVal> {
     for p in pair.into_inner() {
         match p.as_rule() {
             Rule::string => {
-                current_str.push_str(&replace_string_markers(p.as_str()));
+                current_str.push_str(&parse_string(&p)?);
             }
             Rule::int => {
                 if !current_str.is_empty() {
                     values.push(ExprVal::String(current_str));
                     current_str = String::new();
                 }
-                values.push(ExprVal::Int(p.as_str().parse().map_err(|_| {
-                    Error::msg(format!("Integer out of bounds: `{}`", p.as_str()))
-                })?));
+                values.push(ExprVal::Int(parse_int(&p)?));
             }
             Rule::float => {
                 if !current_str.is_empty() {
                     values.push(ExprVal::String(current_str));
                     current_str = String::new();
                 }
-                values.push(ExprVal::Float(
-                    p.as_str().parse().map_err(|_| {
-                        Error::msg(format!("Float out of bounds: `{}`", p.as_str()))
-                    })?,
-                ));
+                values.push(ExprVal::Float(parse_float(&p)?));
             }
             Rule::dotted_square_bracket_ident => {
                 if !current_str.is_empty() {
@@ -231,6 +325,7 @@ fn parse_basic_expression(pair: Pair<Rule>) -> TeraResult<ExprVal> {
                 Rule::op_plus => MathOperator::Add,
                 Rule::op_minus => MathOperator::Sub,
                 Rule::op_times => MathOperator::Mul,
+                Rule::op_floor_div => MathOperator::FloorDiv,
                 Rule::op_slash => MathOperator::Div,
                 Rule::op_modulo => MathOperator::Modulo,
                 _ => unreachable!(),
@@ -240,16 +335,8 @@ fn parse_basic_expression(pair: Pair<Rule>) -> TeraResult<ExprVal> {
     };
 
     let expr = match pair.as_rule() {
-        Rule::int => ExprVal::Int(
-            pair.as_str()
-                .parse()
-                .map_err(|_| Error::msg(format!("Integer out of bounds: `{}`", pair.as_str())))?,
-        ),
-        Rule::float => ExprVal::Float(
-            pair.as_str()
-                .parse()
-                .map_err(|_| Error::msg(format!("Float out of bounds: `{}`", pair.as_str())))?,
-        ),
+        Rule::int => ExprVal::Int(parse_int(&pair)?),
+        Rule::float => ExprVal::Float(parse_float(&pair)?),
         Rule::boolean => match pair.as_str() {
             "true" => ExprVal::Bool(true),
             "True" => ExprVal::Bool(true),
@@ -257,6 +344,7 @@ fn parse_basic_expression(pair: Pair<Rule>) -> TeraResult<ExprVal> {
             "False" => ExprVal::Bool(false),
             _ => unreachable!(),
         },
+        Rule::none => ExprVal::Null,
         Rule::test => ExprVal::Test(parse_test(pair)?),
         Rule::test_not => {
             let mut test = parse_test(pair)?;
@@ -266,6 +354,14 @@ fn parse_basic_expression(pair: Pair<Rule>) -> TeraResult<ExprVal> {
         Rule::fn_call => ExprVal::FunctionCall(parse_fn_call(pair)?),
         Rule::macro_call => ExprVal::MacroCall(parse_macro_call(pair)?),
         Rule::dotted_square_bracket_ident => ExprVal::Ident(pair.as_str().to_string()),
+        Rule::array => parse_array(pair)?,
+        Rule::map => parse_map(pair)?,
+        // `-x` is evaluated as `0 - x`
+        Rule::negative => ExprVal::Math(MathExpr {
+            lhs: Box::new(Expr::new(ExprVal::Int(0))),
+            operator: MathOperator::Sub,
+            rhs: Box::new(Expr::new(parse_basic_expression(pair.into_inner().next().unwrap())?)),
+        }),
         Rule::basic_expr => MATH_CLIMBER.climb(pair.into_inner(), primary, infix)?,
         _ => unreachable!("Got {:?} in parse_basic_expression: {}", pair.as_rule(), pair.as_str()),
     };
@@ -295,7 +391,7 @@ fn parse_string_expr_with_filters(pair: Pair<Rule>) -> TeraResult<Expr> {
 
     for p in pair.into_inner() {
         match p.as_rule() {
-            Rule::string => expr_val = Some(ExprVal::String(replace_string_markers(p.as_str()))),
+            Rule::string => expr_val = Some(ExprVal::String(parse_string(&p)?)),
             Rule::string_concat => expr_val = Some(parse_string_concat(p)?),
             Rule::filter => filters.push(parse_filter(p)?),
             _ => unreachable!("Got {:?}", p),
@@ -330,6 +426,7 @@ fn parse_in_condition_container(pair: Pair<Rule>) -> TeraResult<Expr> {
                 expr = Some(Expr::new(ExprVal::Ident(p.as_str().to_string())))
             }
             Rule::string_expr_filter => expr = Some(parse_string_expr_with_filters(p)?),
+            Rule::fn_call => expr = Some(Expr::new(ExprVal::FunctionCall(parse_fn_call(p)?))),
             _ => unreachable!("Got {:?} in parse_in_condition_container", p),
         };
     }
@@ -371,6 +468,7 @@ fn parse_comparison_val(pair: Pair<Rule>) -> TeraResult<Expr> {
                 Rule::op_plus => MathOperator::Add,
                 Rule::op_minus => MathOperator::Sub,
                 Rule::op_times => MathOperator::Mul,
+                Rule::op_floor_div => MathOperator::FloorDiv,
                 Rule::op_slash => MathOperator::Div,
                 Rule::op_modulo => MathOperator::Modulo,
                 _ => unreachable!(),
@@ -409,6 +507,7 @@ fn parse_comparison_expression(pair: Pair<Rule>) -> TeraResult<Expr> {
     let expr = match pair.as_rule() {
         Rule::comparison_val => parse_comparison_val(pair)?,
         Rule::string_expr_filter => parse_string_expr_with_filters(pair)?,
+        Rule::coalesce_expr => parse_coalesce_expression(pair)?,
         Rule::comparison_expr => {
             COMPARISON_EXPR_CLIMBER.climb(pair.into_inner(), primary, infix)?
         }
@@ -417,6 +516,19 @@ fn parse_comparison_expression(pair: Pair<Rule>) -> TeraResult<Expr> {
     Ok(expr)
 }
 
+fn parse_coalesce_expression(pair: Pair<Rule>) -> TeraResult<Expr> {
+    let mut values = vec![];
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::basic_expr_filter => values.push(parse_basic_expr_with_filters(p)?),
+            Rule::string_expr_filter => values.push(parse_string_expr_with_filters(p)?),
+            _ => unreachable!("Got {:?} in parse_coalesce_expression", p),
+        };
+    }
+
+    Ok(Expr::new(ExprVal::Coalesce(values)))
+}
+
 /// An expression that can be negated
 fn parse_logic_val(pair: Pair<Rule>) -> TeraResult<Expr> {
     let mut negated = false;
@@ -480,10 +592,31 @@ fn parse_array(pair: Pair<Rule>) -> TeraResult<ExprVal> {
     Ok(ExprVal::Array(vals))
 }
 
+fn parse_map(pair: Pair<Rule>) -> TeraResult<ExprVal> {
+    let mut entries = vec![];
+
+    for p in pair.into_inner() {
+        let mut key = None;
+        let mut val = None;
+        for p2 in p.into_inner() {
+            match p2.as_rule() {
+                Rule::string => key = Some(parse_string(&p2)?),
+                Rule::ident => key = Some(p2.as_str().to_string()),
+                Rule::logic_val => val = Some(parse_logic_val(p2)?),
+                _ => unreachable!("Got {:?} in parse_map", p2.as_rule()),
+            }
+        }
+        entries.push((key.unwrap(), val.unwrap()));
+    }
+
+    Ok(ExprVal::Map(entries))
+}
+
 fn parse_macro_call(pair: Pair<Rule>) -> TeraResult<MacroCall> {
     let mut namespace = None;
     let mut name = None;
     let mut args = HashMap::new();
+    let mut positional_args = vec![];
 
     for p in pair.into_inner() {
         match p.as_rule() {
@@ -499,14 +632,23 @@ fn parse_macro_call(pair: Pair<Rule>) -> TeraResult<MacroCall> {
                 let (key, val) = parse_kwarg(p)?;
                 args.insert(key, val);
             }
+            Rule::macro_arg => {
+                let p2 = p.into_inner().next().unwrap();
+                match p2.as_rule() {
+                    Rule::logic_expr => positional_args.push(parse_logic_expr(p2)?),
+                    Rule::array_filter => positional_args.push(parse_array_with_filters(p2)?),
+                    _ => unreachable!("Got {:?} in parse_macro_call", p2.as_rule()),
+                }
+            }
             _ => unreachable!("Got {:?} in parse_macro_call", p.as_rule()),
         }
     }
 
-    Ok(MacroCall { namespace: namespace.unwrap(), name: name.unwrap(), args })
+    Ok(MacroCall { namespace: namespace.unwrap(), name: name.unwrap(), args, positional_args })
 }
 
 fn parse_variable_tag(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut ws = WS::default();
     let mut expr = None;
 
@@ -523,10 +665,11 @@ fn parse_variable_tag(pair: Pair<Rule>) -> TeraResult<Node> {
             _ => unreachable!("unexpected {:?} rule in parse_variable_tag", p.as_rule()),
         }
     }
-    Ok(Node::VariableBlock(ws, expr.unwrap()))
+    Ok(Node::VariableBlock(ws, expr.unwrap(), span))
 }
 
-fn parse_import_macro(pair: Pair<Rule>) -> Node {
+fn parse_import_macro(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut ws = WS::default();
     let mut file = None;
     let mut ident = None;
@@ -536,7 +679,7 @@ fn parse_import_macro(pair: Pair<Rule>) -> Node {
             Rule::tag_start => {
                 ws.left = p.as_span().as_str() == "{%-";
             }
-            Rule::string => file = Some(replace_string_markers(p.as_span().as_str())),
+            Rule::string => file = Some(parse_string(&p)?),
             Rule::ident => ident = Some(p.as_span().as_str().to_string()),
             Rule::tag_end => {
                 ws.right = p.as_span().as_str() == "-%}";
@@ -545,20 +688,74 @@ fn parse_import_macro(pair: Pair<Rule>) -> Node {
         };
     }
 
-    Node::ImportMacro(ws, file.unwrap(), ident.unwrap())
+    Ok(Node::ImportMacro(ws, file.unwrap(), ident.unwrap(), span))
 }
 
-/// `extends` and `include` have the same structure so only way fn to parse them both
-fn parse_extends_include(pair: Pair<Rule>) -> (WS, String) {
+/// The parent of an `extends` tag is either a template name or an expression evaluated when
+/// rendering
+fn parse_extends(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut ws = WS::default();
     let mut file = None;
+    let mut expr = None;
 
     for p in pair.into_inner() {
         match p.as_rule() {
             Rule::tag_start => {
                 ws.left = p.as_span().as_str() == "{%-";
             }
-            Rule::string => file = Some(replace_string_markers(p.as_span().as_str())),
+            Rule::string => file = Some(parse_string(&p)?),
+            Rule::logic_expr => expr = Some(parse_logic_expr(p)?),
+            Rule::tag_end => {
+                ws.right = p.as_span().as_str() == "-%}";
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_extends", p.as_rule()),
+        };
+    }
+
+    match expr {
+        Some(expr) => Ok(Node::DynamicExtends(ws, expr, span)),
+        None => Ok(Node::Extends(ws, file.unwrap(), span)),
+    }
+}
+
+/// `include` and `embed` tags have the same structure so only one fn to parse them both,
+/// only `include` accepting variables
+fn parse_include(pair: Pair<Rule>) -> TeraResult<(WS, Include, Span)> {
+    let span = span_of(&pair);
+    let mut ws = WS::default();
+    let mut file = None;
+    let mut variables = vec![];
+    let mut only = false;
+    let mut ignore_missing = false;
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::tag_start => {
+                ws.left = p.as_span().as_str() == "{%-";
+            }
+            Rule::string => file = Some(parse_string(&p)?),
+            Rule::include_variables => {
+                for p2 in p.into_inner() {
+                    let mut key = None;
+                    let mut value = None;
+                    for p3 in p2.into_inner() {
+                        match p3.as_rule() {
+                            Rule::string => key = Some(parse_string(&p3)?),
+                            Rule::ident => key = Some(p3.as_str().to_string()),
+                            Rule::logic_expr => value = Some(parse_logic_expr(p3)?),
+                            Rule::array_filter => value = Some(parse_array_with_filters(p3)?),
+                            _ => unreachable!(
+                                "unexpected {:?} rule in include_variable",
+                                p3.as_rule()
+                            ),
+                        }
+                    }
+                    variables.push((key.unwrap(), value.unwrap()));
+                }
+            }
+            Rule::include_only => only = true,
+            Rule::include_ignore_missing => ignore_missing = true,
             Rule::tag_end => {
                 ws.right = p.as_span().as_str() == "-%}";
             }
@@ -566,13 +763,15 @@ fn parse_extends_include(pair: Pair<Rule>) -> (WS, String) {
         };
     }
 
-    (ws, file.unwrap())
+    Ok((ws, Include { name: file.unwrap(), variables, only, ignore_missing }, span))
 }
 
 fn parse_set_tag(pair: Pair<Rule>, global: bool) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut ws = WS::default();
     let mut key = None;
     let mut expr = None;
+    let mut math_op = None;
 
     for p in pair.into_inner() {
         match p.as_rule() {
@@ -582,17 +781,42 @@ fn parse_set_tag(pair: Pair<Rule>, global: bool) -> TeraResult<Node> {
             Rule::tag_end => {
                 ws.right = p.as_span().as_str() == "-%}";
             }
-            Rule::ident => key = Some(p.as_str().to_string()),
+            Rule::ident | Rule::set_key => key = Some(p.as_str().to_string()),
             Rule::logic_expr => expr = Some(parse_logic_expr(p)?),
             Rule::array_filter => expr = Some(parse_array_with_filters(p)?),
+            Rule::set_math_op => {
+                math_op = Some(match p.as_str() {
+                    "+=" => MathOperator::Add,
+                    _ => MathOperator::Sub,
+                })
+            }
+            // `key += value` is desugared to `key = key + value`
+            Rule::basic_expr_filter => {
+                let lhs = Expr::new(ExprVal::Ident(key.clone().unwrap()));
+                expr = Some(Expr::new(ExprVal::Math(MathExpr {
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(parse_basic_expr_with_filters(p)?),
+                    operator: math_op.unwrap(),
+                })));
+            }
+            // and `key ~= value` to `key = key ~ value`
+            Rule::set_concat_values => {
+                let mut values = vec![ExprVal::Ident(key.clone().unwrap())];
+                match parse_string_concat(p)? {
+                    ExprVal::StringConcat(concat) => values.extend(concat.values),
+                    val => values.push(val),
+                }
+                expr = Some(Expr::new(ExprVal::StringConcat(StringConcat { values })));
+            }
             _ => unreachable!("unexpected {:?} rule in parse_set_tag", p.as_rule()),
         }
     }
 
-    Ok(Node::Set(ws, Set { key: key.unwrap(), value: expr.unwrap(), global }))
+    Ok(Node::Set(ws, Set { key: key.unwrap(), value: expr.unwrap(), global }, span))
 }
 
 fn parse_raw_tag(pair: Pair<Rule>) -> Node {
+    let span = span_of(&pair);
     let mut start_ws = WS::default();
     let mut end_ws = WS::default();
     let mut text = None;
@@ -622,10 +846,11 @@ fn parse_raw_tag(pair: Pair<Rule>) -> Node {
         };
     }
 
-    Node::Raw(start_ws, text.unwrap(), end_ws)
+    Node::Raw(start_ws, text.unwrap(), end_ws, span)
 }
 
 fn parse_filter_section(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut start_ws = WS::default();
     let mut end_ws = WS::default();
     let mut filter = None;
@@ -668,10 +893,341 @@ fn parse_filter_section(pair: Pair<Rule>) -> TeraResult<Node> {
             _ => unreachable!("unexpected {:?} rule in parse_filter_section", p.as_rule()),
         };
     }
-    Ok(Node::FilterSection(start_ws, FilterSection { filter: filter.unwrap(), body }, end_ws))
+    Ok(Node::FilterSection(start_ws, FilterSection { filter: filter.unwrap(), body }, end_ws, span))
+}
+
+fn parse_spaceless(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut body = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::spaceless_tag | Rule::endspaceless_tag => {
+                let ws =
+                    if p.as_rule() == Rule::spaceless_tag { &mut start_ws } else { &mut end_ws };
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            Rule::filter_section_content => body.extend(parse_content(p)?),
+            _ => unreachable!("unexpected {:?} rule in parse_spaceless", p.as_rule()),
+        };
+    }
+
+    Ok(Node::Spaceless(start_ws, body, end_ws, span))
+}
+
+fn parse_with(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut assignments = vec![];
+    let mut body = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::with_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => start_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => start_ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::with_assignment => {
+                            let mut key = None;
+                            let mut value = None;
+                            for p3 in p2.into_inner() {
+                                match p3.as_rule() {
+                                    Rule::ident => key = Some(p3.as_str().to_string()),
+                                    Rule::logic_expr => value = Some(parse_logic_expr(p3)?),
+                                    Rule::array_filter => {
+                                        value = Some(parse_array_with_filters(p3)?)
+                                    }
+                                    _ => unreachable!(
+                                        "unexpected {:?} rule in with_assignment",
+                                        p3.as_rule()
+                                    ),
+                                }
+                            }
+                            assignments.push((key.unwrap(), value.unwrap()));
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            Rule::filter_section_content => body.extend(parse_content(p)?),
+            Rule::endwith_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_with", p.as_rule()),
+        };
+    }
+
+    Ok(Node::With(start_ws, With { assignments, body }, end_ws, span))
+}
+
+fn parse_cache(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut key = None;
+    let mut ttl = None;
+    let mut body = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::cache_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => start_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => start_ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::logic_expr => key = Some(parse_logic_expr(p2)?),
+                        Rule::basic_expr_filter => ttl = Some(parse_basic_expr_with_filters(p2)?),
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            Rule::filter_section_content => body.extend(parse_content(p)?),
+            Rule::endcache_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_cache", p.as_rule()),
+        };
+    }
+
+    Ok(Node::Cache(start_ws, Cache { key: key.unwrap(), ttl, body }, end_ws, span))
+}
+
+/// Parses a `custom_tag` or `custom_start_tag`: its whitespace control, name and arguments
+fn parse_custom_tag_start(pair: Pair<Rule>) -> TeraResult<(WS, String, HashMap<String, Expr>)> {
+    let mut ws = WS::default();
+    let mut name = None;
+    let mut args = HashMap::new();
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::tag_start => ws.left = p.as_span().as_str() == "{%-",
+            Rule::tag_end => ws.right = p.as_span().as_str() == "-%}",
+            Rule::custom_tag_name => name = Some(p.as_str().to_string()),
+            Rule::custom_tag_args => {
+                for p2 in p.into_inner() {
+                    let (key, val) = parse_kwarg(p2)?;
+                    args.insert(key, val);
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_custom_tag_start", p.as_rule()),
+        };
+    }
+
+    Ok((ws, name.unwrap(), args))
+}
+
+fn parse_custom_tag(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let (ws, name, args) = parse_custom_tag_start(pair)?;
+    Ok(Node::CustomTag(ws, CustomTag { name, args, body: None }, WS::default(), span))
+}
+
+fn parse_custom_section(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start = None;
+    let mut end_ws = WS::default();
+    let mut body = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::custom_start_tag => start = Some(parse_custom_tag_start(p)?),
+            Rule::filter_section_content => body.extend(parse_content(p)?),
+            Rule::endcustom_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_custom_section", p.as_rule()),
+        };
+    }
+
+    let (start_ws, name, args) = start.unwrap();
+    Ok(Node::CustomTag(start_ws, CustomTag { name, args, body: Some(body) }, end_ws, span))
+}
+
+fn parse_embed(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut name = None;
+    let mut blocks = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::embed_tag => {
+                let (ws, include, _) = parse_include(p)?;
+                start_ws = ws;
+                name = Some(include.name);
+            }
+            Rule::block => {
+                let block = parse_block(p)?;
+                if contains_super(block.bodies()[0]) {
+                    return Err(Error::msg(
+                        "`super()` can't be used in the blocks of an `{% embed %}`",
+                    ));
+                }
+                blocks.push(block);
+            }
+            Rule::endembed_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_embed", p.as_rule()),
+        };
+    }
+
+    Ok(Node::Embed(start_ws, Embed { name: name.unwrap(), blocks }, end_ws, span))
+}
+
+fn contains_super(body: &[Node]) -> bool {
+    body.iter().any(|node| match *node {
+        Node::Super(_) => true,
+        _ => node.bodies().into_iter().any(|b| contains_super(b)),
+    })
+}
+
+fn parse_call_block(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut call = None;
+    let mut body = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::call_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => start_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => start_ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::macro_call_wrapper => {
+                            call = Some(parse_macro_call(p2.into_inner().next().unwrap())?)
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            Rule::filter_section_content => body.extend(parse_content(p)?),
+            Rule::endcall_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_call_block", p.as_rule()),
+        };
+    }
+
+    Ok(Node::CallBlock(start_ws, CallBlock { call: call.unwrap(), body }, end_ws, span))
+}
+
+fn parse_trans(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut count = None;
+    let mut message = String::new();
+    let mut plural: Option<String> = None;
+    let mut trim_plural_start = false;
+    let mut variables: Vec<(String, Expr)> = vec![];
+
+    for p in pair.into_inner() {
+        // Text goes in the plural form once we have seen the `pluralize` tag
+        let current = plural.as_mut().unwrap_or(&mut message);
+        match p.as_rule() {
+            Rule::trans_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => start_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => start_ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::logic_expr => count = Some(parse_logic_expr(p2)?),
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            Rule::pluralize_tag => {
+                let mut ws = WS::default();
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+                if ws.left {
+                    message = message.trim_end().to_string();
+                }
+                trim_plural_start = ws.right;
+                plural = Some(String::new());
+            }
+            Rule::endtrans_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    }
+                }
+            }
+            Rule::trans_variable => {
+                let name = p.into_inner().find(|p2| p2.as_rule() == Rule::ident).unwrap().as_str();
+                current.push_str(&format!("{{{{ {} }}}}", name));
+                let is_count = count.is_some() && name == "count";
+                if !is_count && !variables.iter().any(|(n, _)| n == name) {
+                    variables.push((name.to_string(), Expr::new(ExprVal::Ident(name.to_string()))));
+                }
+            }
+            Rule::text => current.push_str(p.as_str()),
+            _ => unreachable!("unexpected {:?} rule in parse_trans", p.as_rule()),
+        };
+    }
+
+    if plural.is_some() && count.is_none() {
+        return Err(Error::msg("A `trans` tag with a `pluralize` tag needs a `count` argument"));
+    }
+    if trim_plural_start {
+        plural = plural.map(|p| p.trim_start().to_string());
+    }
+
+    Ok(Node::Trans(start_ws, Trans { message, plural, count, variables }, end_ws, span))
 }
 
 fn parse_block(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut start_ws = WS::default();
     let mut end_ws = WS::default();
     let mut name = None;
@@ -704,21 +1260,13 @@ fn parse_block(pair: Pair<Rule>) -> TeraResult<Node> {
         };
     }
 
-    Ok(Node::Block(start_ws, Block { name: name.unwrap(), body }, end_ws))
+    Ok(Node::Block(start_ws, Block { name: name.unwrap(), body }, end_ws, span))
 }
 
 fn parse_macro_arg(p: Pair<Rule>) -> TeraResult<ExprVal> {
     let val = match p.as_rule() {
-        Rule::int => Some(ExprVal::Int(
-            p.as_str()
-                .parse()
-                .map_err(|_| Error::msg(format!("Integer out of bounds: `{}`", p.as_str())))?,
-        )),
-        Rule::float => Some(ExprVal::Float(
-            p.as_str()
-                .parse()
-                .map_err(|_| Error::msg(format!("Float out of bounds: `{}`", p.as_str())))?,
-        )),
+        Rule::int => Some(ExprVal::Int(parse_int(&p)?)),
+        Rule::float => Some(ExprVal::Float(parse_float(&p)?)),
         Rule::boolean => match p.as_str() {
             "true" => Some(ExprVal::Bool(true)),
             "True" => Some(ExprVal::Bool(true)),
@@ -726,16 +1274,20 @@ fn parse_macro_arg(p: Pair<Rule>) -> TeraResult<ExprVal> {
             "False" => Some(ExprVal::Bool(false)),
             _ => unreachable!(),
         },
-        Rule::string => Some(ExprVal::String(replace_string_markers(&p.as_str()))),
+        Rule::none => Some(ExprVal::Null),
+        Rule::string => Some(ExprVal::String(parse_string(&p)?)),
         _ => unreachable!("Got {:?} in parse_macro_arg: {}", p.as_rule(), p.as_str()),
     };
 
     Ok(val.unwrap())
 }
 
-fn parse_macro_fn(pair: Pair<Rule>) -> TeraResult<(String, HashMap<String, Option<Expr>>)> {
+type MacroSignature = (String, HashMap<String, Option<Expr>>, Vec<String>);
+
+fn parse_macro_fn(pair: Pair<Rule>) -> TeraResult<MacroSignature> {
     let mut name = String::new();
     let mut args = HashMap::new();
+    let mut arg_order = vec![];
 
     for p2 in pair.into_inner() {
         match p2.as_rule() {
@@ -749,20 +1301,24 @@ fn parse_macro_fn(pair: Pair<Rule>) -> TeraResult<(String, HashMap<String, Optio
                         _ => default_val = Some(Expr::new(parse_macro_arg(p3)?)),
                     };
                 }
-                args.insert(arg_name.unwrap(), default_val);
+                let arg_name = arg_name.unwrap();
+                arg_order.push(arg_name.clone());
+                args.insert(arg_name, default_val);
             }
             _ => continue,
         }
     }
 
-    Ok((name, args))
+    Ok((name, args, arg_order))
 }
 
 fn parse_macro_definition(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut start_ws = WS::default();
     let mut end_ws = WS::default();
     let mut name = String::new();
     let mut args = HashMap::new();
+    let mut arg_order = vec![];
     let mut body = vec![];
 
     for p in pair.into_inner() {
@@ -776,6 +1332,7 @@ fn parse_macro_definition(pair: Pair<Rule>) -> TeraResult<Node> {
                             let macro_fn = parse_macro_fn(p2)?;
                             name = macro_fn.0;
                             args = macro_fn.1;
+                            arg_order = macro_fn.2;
                         }
                         _ => continue,
                     };
@@ -796,16 +1353,24 @@ fn parse_macro_definition(pair: Pair<Rule>) -> TeraResult<Node> {
         }
     }
 
-    Ok(Node::MacroDefinition(start_ws, MacroDefinition { name, args, body }, end_ws))
+    Ok(Node::MacroDefinition(
+        start_ws,
+        MacroDefinition { name, args, arg_order, body },
+        end_ws,
+        span,
+    ))
 }
 
 fn parse_forloop(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     let mut start_ws = WS::default();
     let mut end_ws = WS::default();
 
     let mut key = None;
     let mut value = None;
     let mut container = None;
+    let mut condition = None;
+    let mut sorted = false;
     let mut body = vec![];
     let mut empty_body: Option<Vec<Node>> = None;
 
@@ -822,6 +1387,8 @@ fn parse_forloop(pair: Pair<Rule>) -> TeraResult<Node> {
                             container = Some(parse_basic_expr_with_filters(p2)?);
                         }
                         Rule::array_filter => container = Some(parse_array_with_filters(p2)?),
+                        Rule::for_sorted => sorted = true,
+                        Rule::logic_expr => condition = Some(parse_logic_expr(p2)?),
                         _ => unreachable!(),
                     };
                 }
@@ -862,12 +1429,58 @@ fn parse_forloop(pair: Pair<Rule>) -> TeraResult<Node> {
 
     Ok(Node::Forloop(
         start_ws,
-        Forloop { key, value: value.unwrap(), container: container.unwrap(), body, empty_body },
+        Forloop {
+            key,
+            value: value.unwrap(),
+            container: container.unwrap(),
+            condition,
+            sorted,
+            body,
+            empty_body,
+        },
         end_ws,
+        span,
     ))
 }
 
+fn parse_while(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut condition = None;
+    let mut body = vec![];
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::while_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => start_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => start_ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::logic_expr => condition = Some(parse_logic_expr(p2)?),
+                        _ => unreachable!(),
+                    };
+                }
+            }
+            Rule::filter_section_content => body.extend(parse_content(p)?),
+            Rule::endwhile_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    };
+                }
+            }
+            _ => unreachable!("unexpected {:?} rule in parse_while", p.as_rule()),
+        };
+    }
+
+    Ok(Node::While(start_ws, While { condition: condition.unwrap(), body }, end_ws, span))
+}
+
 fn parse_break_tag(pair: Pair<Rule>) -> Node {
+    let span = span_of(&pair);
     let mut ws = WS::default();
 
     for p in pair.into_inner() {
@@ -882,10 +1495,11 @@ fn parse_break_tag(pair: Pair<Rule>) -> Node {
         };
     }
 
-    Node::Break(ws)
+    Node::Break(ws, span)
 }
 
 fn parse_continue_tag(pair: Pair<Rule>) -> Node {
+    let span = span_of(&pair);
     let mut ws = WS::default();
 
     for p in pair.into_inner() {
@@ -900,10 +1514,54 @@ fn parse_continue_tag(pair: Pair<Rule>) -> Node {
         };
     }
 
-    Node::Continue(ws)
+    Node::Continue(ws, span)
+}
+
+fn parse_do_tag(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut ws = WS::default();
+    let mut expr = None;
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::tag_start => {
+                ws.left = p.as_span().as_str() == "{%-";
+            }
+            Rule::tag_end => {
+                ws.right = p.as_span().as_str() == "-%}";
+            }
+            Rule::logic_expr => expr = Some(parse_logic_expr(p)?),
+            Rule::array_filter => expr = Some(parse_array_with_filters(p)?),
+            _ => unreachable!("unexpected {:?} rule in parse_do_tag", p.as_rule()),
+        };
+    }
+
+    Ok(Node::Do(ws, expr.unwrap(), span))
+}
+
+fn parse_debug_tag(pair: Pair<Rule>) -> Node {
+    let span = span_of(&pair);
+    let mut ws = WS::default();
+    let mut name = None;
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::tag_start => {
+                ws.left = p.as_span().as_str() == "{%-";
+            }
+            Rule::tag_end => {
+                ws.right = p.as_span().as_str() == "-%}";
+            }
+            Rule::dotted_ident => name = Some(p.as_str().to_string()),
+            _ => unreachable!(),
+        };
+    }
+
+    Node::Debug(ws, name, span)
 }
 
 fn parse_if(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
     // the `endif` tag ws handling
     let mut end_ws = WS::default();
     let mut conditions = vec![];
@@ -978,7 +1636,81 @@ fn parse_if(pair: Pair<Rule>) -> TeraResult<Node> {
         }
     }
 
-    Ok(Node::If(If { conditions, otherwise }, end_ws))
+    Ok(Node::If(If { conditions, otherwise }, end_ws, span))
+}
+
+fn parse_switch(pair: Pair<Rule>) -> TeraResult<Node> {
+    let span = span_of(&pair);
+    let mut start_ws = WS::default();
+    let mut end_ws = WS::default();
+    let mut value = None;
+    let mut cases = vec![];
+    let mut default = None;
+
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::switch_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => start_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => start_ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::logic_expr => value = Some(parse_logic_expr(p2)?),
+                        _ => unreachable!(),
+                    };
+                }
+            }
+            Rule::case_tag => {
+                let mut ws = WS::default();
+                let mut values = vec![];
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => ws.right = p2.as_span().as_str() == "-%}",
+                        Rule::logic_expr => values.push(parse_logic_expr(p2)?),
+                        _ => unreachable!(),
+                    };
+                }
+                cases.push((ws, values, vec![]));
+            }
+            Rule::default_tag => {
+                let mut ws = WS::default();
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    };
+                }
+                default = Some((ws, vec![]));
+            }
+            Rule::content
+            | Rule::macro_content
+            | Rule::block_content
+            | Rule::for_content
+            | Rule::filter_section_content => {
+                // The content always comes after a case or the default
+                let body = match default {
+                    Some((_, ref mut body)) => body,
+                    None => &mut cases.last_mut().unwrap().2,
+                };
+                body.extend(parse_content(p)?);
+            }
+            // Comments are allowed before the first case
+            Rule::comment_tag => (),
+            Rule::endswitch_tag => {
+                for p2 in p.into_inner() {
+                    match p2.as_rule() {
+                        Rule::tag_start => end_ws.left = p2.as_span().as_str() == "{%-",
+                        Rule::tag_end => end_ws.right = p2.as_span().as_str() == "-%}",
+                        _ => unreachable!(),
+                    };
+                }
+            }
+            _ => unreachable!("unreachable rule in parse_switch: {:?}", p.as_rule()),
+        }
+    }
+
+    Ok(Node::Switch(start_ws, Switch { value: value.unwrap(), cases, default }, end_ws, span))
 }
 
 fn parse_content(pair: Pair<Rule>) -> TeraResult<Vec<Node>> {
@@ -987,27 +1719,43 @@ fn parse_content(pair: Pair<Rule>) -> TeraResult<Vec<Node>> {
     for p in pair.into_inner() {
         match p.as_rule() {
             Rule::include_tag => {
-                let (ws, file) = parse_extends_include(p);
-                nodes.push(Node::Include(ws, file));
+                let (ws, include, span) = parse_include(p)?;
+                nodes.push(Node::Include(ws, include, span));
             }
             // Ignore comments
             Rule::comment_tag => (),
-            Rule::super_tag => nodes.push(Node::Super),
+            Rule::super_tag => nodes.push(Node::Super(span_of(&p))),
             Rule::set_tag => nodes.push(parse_set_tag(p, false)?),
             Rule::set_global_tag => nodes.push(parse_set_tag(p, true)?),
             Rule::raw => nodes.push(parse_raw_tag(p)),
             Rule::variable_tag => nodes.push(parse_variable_tag(p)?),
             Rule::macro_definition => nodes.push(parse_macro_definition(p)?),
             Rule::forloop => nodes.push(parse_forloop(p)?),
+            Rule::while_loop => nodes.push(parse_while(p)?),
             Rule::break_tag => nodes.push(parse_break_tag(p)),
             Rule::continue_tag => nodes.push(parse_continue_tag(p)),
+            Rule::debug_tag => nodes.push(parse_debug_tag(p)),
+            Rule::do_tag => nodes.push(parse_do_tag(p)?),
             Rule::content_if
             | Rule::macro_if
             | Rule::block_if
             | Rule::for_if
             | Rule::filter_section_if => nodes.push(parse_if(p)?),
+            Rule::content_switch
+            | Rule::macro_switch
+            | Rule::block_switch
+            | Rule::for_switch
+            | Rule::filter_section_switch => nodes.push(parse_switch(p)?),
             Rule::filter_section => nodes.push(parse_filter_section(p)?),
-            Rule::text => nodes.push(Node::Text(p.as_span().as_str().to_string())),
+            Rule::spaceless => nodes.push(parse_spaceless(p)?),
+            Rule::trans => nodes.push(parse_trans(p)?),
+            Rule::with_section => nodes.push(parse_with(p)?),
+            Rule::cache_section => nodes.push(parse_cache(p)?),
+            Rule::custom_section => nodes.push(parse_custom_section(p)?),
+            Rule::custom_tag => nodes.push(parse_custom_tag(p)?),
+            Rule::call_section => nodes.push(parse_call_block(p)?),
+            Rule::embed => nodes.push(parse_embed(p)?),
+            Rule::text => nodes.push(Node::Text(p.as_span().as_str().to_string(), span_of(&p))),
             Rule::block => nodes.push(parse_block(p)?),
             _ => unreachable!("unreachable content rule: {:?}", p.as_rule()),
         };
@@ -1016,145 +1764,275 @@ fn parse_content(pair: Pair<Rule>) -> TeraResult<Vec<Node>> {
     Ok(nodes)
 }
 
-pub fn parse(input: &str) -> TeraResult<Vec<Node>> {
-    let mut pairs = match TeraParser::parse(Rule::template, input) {
-        Ok(p) => p,
-        Err(e) => {
-            let fancy_e = e.renamed_rules(|rule| {
-                match *rule {
-                    Rule::EOI => "end of input".to_string(),
-                    Rule::int => "an integer".to_string(),
-                    Rule::float => "a float".to_string(),
-                    Rule::string
-                    | Rule::double_quoted_string
-                    | Rule::single_quoted_string
-                    | Rule::backquoted_quoted_string => {
-                        "a string".to_string()
-                    }
-                    Rule::string_concat => "a concatenation of strings".to_string(),
-                    Rule::string_expr_filter => "a string or a concatenation of strings".to_string(),
-                    Rule::all_chars => "a character".to_string(),
-                    Rule::array => "an array of values".to_string(),
-                    Rule::array_filter => "an array of values with an optional filter".to_string(),
-                    Rule::basic_val => "a value".to_string(),
-                    Rule::basic_op => "a mathematical operator".to_string(),
-                    Rule::comparison_op => "a comparison operator".to_string(),
-                    Rule::boolean => "`true` or `false`".to_string(),
-                    Rule::ident => "an identifier (must start with a-z)".to_string(),
-                    Rule::dotted_ident => "a dotted identifier (identifiers separated by `.`)".to_string(),
-                    Rule::dotted_square_bracket_ident => "a square bracketed identifier (identifiers separated by `.` or `[]`s)".to_string(),
-                    Rule::square_brackets => "an identifier, string or integer inside `[]`s".to_string(),
-                    Rule::basic_expr_filter => "an expression with an optional filter".to_string(),
-                    Rule::comparison_val => "a comparison value".to_string(),
-                    Rule::basic_expr | Rule::comparison_expr => "an expression".to_string(),
-                    Rule::logic_val => "a value that can be negated".to_string(),
-                    Rule::logic_expr => "any expressions".to_string(),
-                    Rule::fn_call => "a function call".to_string(),
-                    Rule::kwarg => "a keyword argument: `key=value` where `value` can be any expressions".to_string(),
-                    Rule::kwargs => "a list of keyword arguments: `key=value` where `value` can be any expressions and separated by `,`".to_string(),
-                    Rule::op_or => "`or`".to_string(),
-                    Rule::op_and => "`and`".to_string(),
-                    Rule::op_not => "`not`".to_string(),
-                    Rule::op_lte => "`<=`".to_string(),
-                    Rule::op_gte => "`>=`".to_string(),
-                    Rule::op_lt => "`<`".to_string(),
-                    Rule::op_gt => "`>`".to_string(),
-                    Rule::op_ineq => "`!=`".to_string(),
-                    Rule::op_eq => "`==`".to_string(),
-                    Rule::op_plus => "`+`".to_string(),
-                    Rule::op_minus => "`-`".to_string(),
-                    Rule::op_times => "`*`".to_string(),
-                    Rule::op_slash => "`/`".to_string(),
-                    Rule::op_modulo => "`%`".to_string(),
-                    Rule::filter => "a filter".to_string(),
-                    Rule::test => "a test".to_string(),
-                    Rule::test_not => "a negated test".to_string(),
-                    Rule::test_call => "a test call".to_string(),
-                    Rule::test_arg => "a test argument (any expressions including arrays)".to_string(),
-                    Rule::test_args => "a list of test arguments (any expression including arrayss)".to_string(),
-                    Rule::macro_fn | Rule::macro_fn_wrapper => "a macro function".to_string(),
-                    Rule::macro_call => "a macro function call".to_string(),
-                    Rule::macro_def_arg => {
-                        "an argument name with an optional default literal value: `id`, `key=1`".to_string()
-                    }
-                    Rule::macro_def_args => {
-                        "a list of argument names with an optional default literal value: `id`, `key=1`".to_string()
-                    }
-                    Rule::endmacro_tag => "`{% endmacro %}`".to_string(),
-                    Rule::macro_content => "the macro content".to_string(),
-                    Rule::filter_section_content => "the filter section content".to_string(),
-                    Rule::set_tag => "a `set` tag`".to_string(),
-                    Rule::set_global_tag => "a `set_global` tag`".to_string(),
-                    Rule::block_content | Rule::content | Rule::for_content => {
-                        "some content".to_string()
-                    },
-                    Rule::text => "some text".to_string(),
-                    // Pest will error an unexpected tag as Rule::tag_start
-                    // and just showing `{%` is not clear as some other valid
-                    // tags will also start with `{%`
-                    Rule::tag_start => "tag".to_string(),
-                    Rule::tag_end => "`%}` or `-%}`".to_string(),
-                    Rule::super_tag => "`{{ super() }}`".to_string(),
-                    Rule::raw_tag => "`{% raw %}`".to_string(),
-                    Rule::raw_text => "some raw text".to_string(),
-                    Rule::raw => "a raw block (`{% raw %}...{% endraw %}`".to_string(),
-                    Rule::endraw_tag => "`{% endraw %}`".to_string(),
-                    Rule::include_tag => r#"an include tag (`{% include "..." %}`)"#.to_string(),
-                    Rule::comment_tag => "a comment tag (`{#...#}`)".to_string(),
-                    Rule::variable_tag => "a variable tag (`{{ ... }}`)".to_string(),
-                    Rule::filter_tag | Rule::filter_section => {
-                        "a filter section (`{% filter something %}...{% endfilter %}`)".to_string()
-                    }
-                    Rule::for_tag | Rule::forloop => {
-                        "a forloop (`{% for i in something %}...{% endfor %}".to_string()
-                    },
-                    Rule::endfilter_tag => "an endfilter tag (`{% endfilter %}`)".to_string(),
-                    Rule::endfor_tag => "an endfor tag (`{% endfor %}`)".to_string(),
-                    Rule::if_tag
-                    | Rule::content_if
-                    | Rule::block_if
-                    | Rule::macro_if
-                    | Rule::for_if
-                    | Rule::filter_section_if => {
-                        "a `if` tag".to_string()
-                    }
-                    Rule::elif_tag => "an `elif` tag".to_string(),
-                    Rule::else_tag => "an `else` tag".to_string(),
-                    Rule::endif_tag => "an endif tag (`{% endif %}`)".to_string(),
-                    Rule::WHITESPACE => "whitespace".to_string(),
-                    Rule::variable_start => "a variable start (`{{`)".to_string(),
-                    Rule::variable_end => "a variable end (`}}`)".to_string(),
-                    Rule::comment_start => "a comment start (`{#`)".to_string(),
-                    Rule::comment_end => "a comment end (`#}`)".to_string(),
-                    Rule::block_start => "`{{`, `{%` or `{#`".to_string(),
-                    Rule::import_macro_tag => r#"an import macro tag (`{% import "filename" as namespace %}`"#.to_string(),
-                    Rule::block | Rule::block_tag => r#"a block tag (`{% block block_name %}`"#.to_string(),
-                    Rule::endblock_tag => r#"an endblock tag (`{% endblock block_name %}`"#.to_string(),
-                    Rule::macro_definition
-                    | Rule::macro_tag => r#"a macro definition tag (`{% macro my_macro() %}`"#.to_string(),
-                    Rule::extends_tag => r#"an extends tag (`{% extends "myfile" %}`"#.to_string(),
-                    Rule::template => "a template".to_string(),
-                    Rule::break_tag => "a break tag".to_string(),
-                    Rule::continue_tag => "a continue tag".to_string(),
-                    Rule::top_imports => "top imports".to_string(),
-                    Rule::in_cond => "a `in` condition".to_string(),
-                    Rule::in_cond_container => "a `in` condition container: a string, an array or an ident".to_string(),
-                }
-            });
-            return Err(Error::msg(fancy_e));
+/// The span of a pair with only the byte offsets set: lines and columns are filled
+/// for the whole AST at once by `set_line_columns` at the end of parsing
+fn span_of(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+    Span {
+        start: Position { offset: span.start(), ..Position::default() },
+        end: Position { offset: span.end(), ..Position::default() },
+    }
+}
+
+pub(crate) fn set_line_columns(nodes: &mut [Node], input: &str, lines: &LineIndex) {
+    for node in nodes {
+        let (span, bodies) = node.span_and_bodies_mut();
+        *span = lines.span(input, span.start.offset, span.end.offset);
+        for body in bodies {
+            set_line_columns(body, input, lines);
         }
+    }
+}
+
+/// Runs the pest grammar on the whole template, turning pest errors into readable Tera ones
+pub(crate) fn parse_template_pairs(input: &str) -> TeraResult<Pairs<'_, Rule>> {
+    TeraParser::parse(Rule::template, input).map_err(|e| syntax_error(input, e))
+}
+
+/// Turns an error of the grammar into a readable one
+pub(crate) fn syntax_error(input: &str, e: PestError<Rule>) -> Error {
+    // Only errors at a position have something to show
+    let found_at = match e.location {
+        InputLocation::Pos(pos) => Some(pos),
+        InputLocation::Span(_) => None,
     };
+    let fancy_e = e.renamed_rules(|rule| {
+        match *rule {
+            Rule::EOI => "end of input".to_string(),
+            Rule::int => "an integer".to_string(),
+            Rule::float => "a float".to_string(),
+            Rule::string
+            | Rule::double_quoted_string
+            | Rule::single_quoted_string
+            | Rule::backquoted_quoted_string => {
+                "a string".to_string()
+            }
+            Rule::string_concat => "a concatenation of strings".to_string(),
+            Rule::string_expr_filter => "a string or a concatenation of strings".to_string(),
+            Rule::all_chars => "a character".to_string(),
+            Rule::array => "an array of values".to_string(),
+            Rule::array_filter => "an array of values with an optional filter".to_string(),
+            Rule::basic_val => "a value".to_string(),
+            Rule::basic_op => "a mathematical operator".to_string(),
+            Rule::comparison_op => "a comparison operator".to_string(),
+            Rule::boolean => "`true` or `false`".to_string(),
+            Rule::none => "`none`".to_string(),
+            Rule::ident => "an identifier (must start with a letter or `_`)".to_string(),
+            Rule::dotted_ident => "a dotted identifier (identifiers separated by `.`)".to_string(),
+            Rule::dotted_square_bracket_ident => "a square bracketed identifier (identifiers separated by `.` or `[]`s)".to_string(),
+            Rule::square_brackets => "an identifier, string, integer or slice inside `[]`s".to_string(),
+            Rule::slice => "a slice (`[start:end]`)".to_string(),
+            Rule::slice_bound => "an integer or an identifier".to_string(),
+            Rule::basic_expr_filter => "an expression with an optional filter".to_string(),
+            Rule::comparison_val => "a comparison value".to_string(),
+            Rule::basic_expr | Rule::comparison_expr => "an expression".to_string(),
+            Rule::logic_val => "a value that can be negated".to_string(),
+            Rule::logic_expr => "any expressions".to_string(),
+            Rule::fn_call => "a function call".to_string(),
+            Rule::kwarg => "a keyword argument: `key=value` where `value` can be any expressions".to_string(),
+            Rule::kwargs => "a list of keyword arguments: `key=value` where `value` can be any expressions and separated by `,`".to_string(),
+            Rule::op_or => "`or`".to_string(),
+            Rule::op_and => "`and`".to_string(),
+            Rule::op_not => "`not`".to_string(),
+            Rule::op_lte => "`<=`".to_string(),
+            Rule::op_gte => "`>=`".to_string(),
+            Rule::op_lt => "`<`".to_string(),
+            Rule::op_gt => "`>`".to_string(),
+            Rule::op_ineq => "`!=`".to_string(),
+            Rule::op_eq => "`==`".to_string(),
+            Rule::op_plus => "`+`".to_string(),
+            Rule::op_minus => "`-`".to_string(),
+            Rule::op_times => "`*`".to_string(),
+            Rule::op_floor_div => "`//`".to_string(),
+            Rule::op_slash => "`/`".to_string(),
+            Rule::op_modulo => "`%`".to_string(),
+            Rule::filter => "a filter".to_string(),
+            Rule::test => "a test".to_string(),
+            Rule::test_not => "a negated test".to_string(),
+            Rule::test_call => "a test call".to_string(),
+            Rule::test_arg => "a test argument (any expressions including arrays)".to_string(),
+            Rule::test_args => "a list of test arguments (any expression including arrayss)".to_string(),
+            Rule::macro_fn | Rule::macro_fn_wrapper => "a macro function".to_string(),
+            Rule::macro_call => "a macro function call".to_string(),
+            Rule::macro_def_arg => {
+                "an argument name with an optional default literal value: `id`, `key=1`".to_string()
+            }
+            Rule::macro_def_args => {
+                "a list of argument names with an optional default literal value: `id`, `key=1`".to_string()
+            }
+            Rule::endmacro_tag => "`{% endmacro %}`".to_string(),
+            Rule::macro_content => "the macro content".to_string(),
+            Rule::filter_section_content => "the filter section content".to_string(),
+            Rule::set_tag => "a `set` tag`".to_string(),
+            Rule::set_key => "a variable name or a namespace attribute (`ns.total`)".to_string(),
+            Rule::coalesce_val => "a value".to_string(),
+            Rule::coalesce_expr => "a `??` expression".to_string(),
+            Rule::set_value => "an assignment (`= value`, `+= 1`, `-= 1` or `~= \"a\"`)".to_string(),
+            Rule::set_math_op => "`+=` or `-=`".to_string(),
+            Rule::set_concat_values => "a value to concatenate".to_string(),
+            Rule::set_global_tag => "a `set_global` tag`".to_string(),
+            Rule::block_content | Rule::content | Rule::for_content => {
+                "some content".to_string()
+            },
+            Rule::text => "some text".to_string(),
+            // Pest will error an unexpected tag as Rule::tag_start
+            // and just showing `{%` is not clear as some other valid
+            // tags will also start with `{%`
+            Rule::tag_start => "tag".to_string(),
+            Rule::tag_end => "`%}` or `-%}`".to_string(),
+            Rule::super_tag => "`{{ super() }}`".to_string(),
+            Rule::raw_tag => "`{% raw %}`".to_string(),
+            Rule::raw_text => "some raw text".to_string(),
+            Rule::raw => "a raw block (`{% raw %}...{% endraw %}`".to_string(),
+            Rule::endraw_tag => "`{% endraw %}`".to_string(),
+            Rule::include_tag => r#"an include tag (`{% include "..." %}`)"#.to_string(),
+            Rule::include_variables => r#"the variables of an include (`{"name": value}`)"#.to_string(),
+            Rule::include_variable => r#"a variable of an include (`"name": value`)"#.to_string(),
+            Rule::include_only => "`only`".to_string(),
+            Rule::include_ignore_missing => "`ignore missing`".to_string(),
+            Rule::comment_tag => "a comment tag (`{#...#}`)".to_string(),
+            Rule::variable_tag => "a variable tag (`{{ ... }}`)".to_string(),
+            Rule::filter_tag | Rule::filter_section => {
+                "a filter section (`{% filter something %}...{% endfilter %}`)".to_string()
+            }
+            Rule::for_tag | Rule::forloop => {
+                "a forloop (`{% for i in something %}...{% endfor %}".to_string()
+            },
+            Rule::endfilter_tag => "an endfilter tag (`{% endfilter %}`)".to_string(),
+            Rule::spaceless_tag | Rule::spaceless => {
+                "a spaceless block (`{% spaceless %}...{% endspaceless %}`)".to_string()
+            }
+            Rule::endspaceless_tag => "an endspaceless tag (`{% endspaceless %}`)".to_string(),
+            Rule::trans_tag | Rule::trans => {
+                "a translated message (`{% trans %}...{% endtrans %}`)".to_string()
+            }
+            Rule::trans_variable => "a variable without filters (`{{ name }}`)".to_string(),
+            Rule::endtrans_tag => "an endtrans tag (`{% endtrans %}`)".to_string(),
+            Rule::pluralize_tag => "a pluralize tag (`{% pluralize %}`)".to_string(),
+            Rule::with_tag | Rule::with_section => {
+                "a with block (`{% with name = value %}...{% endwith %}`)".to_string()
+            }
+            Rule::with_assignment => "an assignment (`name = value`)".to_string(),
+            Rule::endwith_tag => "an endwith tag (`{% endwith %}`)".to_string(),
+            Rule::cache_tag | Rule::cache_section => {
+                "a cache block (`{% cache key %}...{% endcache %}`)".to_string()
+            }
+            Rule::endcache_tag => "an endcache tag (`{% endcache %}`)".to_string(),
+            Rule::custom_tag | Rule::custom_start_tag | Rule::custom_section => {
+                "a tag registered with `Tera::register_tag` (`{% name key=value %}`)".to_string()
+            }
+            Rule::tag_keyword | Rule::tera_tag_start => "a Tera tag".to_string(),
+            Rule::custom_tag_name => "the name of a tag".to_string(),
+            Rule::custom_tag_args => "the arguments of a tag (`key=value, ...`)".to_string(),
+            Rule::endcustom_tag => "the end tag of a registered tag (`{% endname %}`)".to_string(),
+            Rule::call_tag | Rule::call_section => {
+                "a call block (`{% call namespace::macro() %}...{% endcall %}`)".to_string()
+            }
+            Rule::macro_call_wrapper => "a macro call".to_string(),
+            Rule::endcall_tag => "an endcall tag (`{% endcall %}`)".to_string(),
+            Rule::embed_tag | Rule::embed => {
+                r#"an embed block (`{% embed "..." %}...{% endembed %}`)"#.to_string()
+            }
+            Rule::endembed_tag => "an endembed tag (`{% endembed %}`)".to_string(),
+            Rule::endfor_tag => "an endfor tag (`{% endfor %}`)".to_string(),
+            Rule::for_sorted => "`sorted`".to_string(),
+            Rule::negative => "a negative value".to_string(),
+            Rule::macro_arg => "a macro argument".to_string(),
+            Rule::map => "a map of values".to_string(),
+            Rule::map_entry => "a map entry: `key: value`".to_string(),
+            Rule::macro_args => "a list of macro arguments: values followed by `key=value` arguments, separated by `,`".to_string(),
+            Rule::while_tag | Rule::while_loop => {
+                "a while loop (`{% while condition %}...{% endwhile %}`)".to_string()
+            }
+            Rule::endwhile_tag => "an endwhile tag (`{% endwhile %}`)".to_string(),
+            Rule::if_tag
+            | Rule::content_if
+            | Rule::block_if
+            | Rule::macro_if
+            | Rule::for_if
+            | Rule::filter_section_if => {
+                "a `if` tag".to_string()
+            }
+            Rule::elif_tag => "an `elif` tag".to_string(),
+            Rule::else_tag => "an `else` tag".to_string(),
+            Rule::endif_tag => "an endif tag (`{% endif %}`)".to_string(),
+            Rule::switch_tag
+            | Rule::content_switch
+            | Rule::block_switch
+            | Rule::macro_switch
+            | Rule::for_switch
+            | Rule::filter_section_switch => {
+                "a switch block (`{% switch value %}...{% endswitch %}`)".to_string()
+            }
+            Rule::case_tag => "a `case` tag (`{% case value %}`)".to_string(),
+            Rule::default_tag => "a `default` tag".to_string(),
+            Rule::endswitch_tag => "an endswitch tag (`{% endswitch %}`)".to_string(),
+            Rule::WHITESPACE => "whitespace".to_string(),
+            Rule::variable_start => "a variable start (`{{`)".to_string(),
+            Rule::variable_end => "a variable end (`}}`)".to_string(),
+            Rule::comment_start => "a comment start (`{#`)".to_string(),
+            Rule::comment_end => "a comment end (`#}`)".to_string(),
+            Rule::block_start => "`{{`, `{%` or `{#`".to_string(),
+            Rule::import_macro_tag => r#"an import macro tag (`{% import "filename" as namespace %}`"#.to_string(),
+            Rule::block | Rule::block_tag => r#"a block tag (`{% block block_name %}`"#.to_string(),
+            Rule::endblock_tag => r#"an endblock tag (`{% endblock block_name %}`"#.to_string(),
+            Rule::macro_definition
+            | Rule::macro_tag => r#"a macro definition tag (`{% macro my_macro() %}`"#.to_string(),
+            Rule::extends_tag => r#"an extends tag (`{% extends "myfile" %}`"#.to_string(),
+            Rule::template => "a template".to_string(),
+            Rule::break_tag => "a break tag".to_string(),
+            Rule::continue_tag => "a continue tag".to_string(),
+            Rule::debug_tag => "a debug tag (`{% debug %}`)".to_string(),
+            Rule::do_tag => "a do tag (`{% do expression %}`)".to_string(),
+            Rule::top_imports => "top imports".to_string(),
+            Rule::in_cond => "a `in` condition".to_string(),
+            Rule::in_cond_container => "a `in` condition container: a string, an array, an ident or a function call".to_string(),
+        }
+    });
+    let mut message = fancy_e.to_string();
+    if let Some(pos) = found_at {
+        message.push_str(&format!("\n  = found {}", found_token(&input[pos..])));
+    }
+    if let Some((unterminated, offset)) = find_unterminated(input) {
+        let position = LineIndex::new(input).position(input, offset);
+        message.push_str(&format!(
+            "\n  = the {} at {}:{} is never closed",
+            unterminated, position.line, position.column
+        ));
+    }
+    Error::msg(message)
+}
+
+/// Describes the token starting the given input, for parse errors
+fn found_token(input: &str) -> String {
+    const SYMBOLS: [&str; 16] = [
+        "{{-", "-}}", "{%-", "-%}", "{{", "}}", "{%", "%}", "{#", "#}", "==", "!=", "<=", ">=",
+        "//", "::",
+    ];
 
+    let first = match input.chars().next() {
+        Some(c) => c,
+        None => return "the end of the template".to_string(),
+    };
+    let len = if first.is_alphanumeric() || first == '_' {
+        input.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(input.len())
+    } else if first.is_whitespace() {
+        return "a whitespace".to_string();
+    } else {
+        SYMBOLS.iter().find(|s| input.starts_with(*s)).map_or(first.len_utf8(), |s| s.len())
+    };
+    format!("`{}`", &input[..len])
+}
+
+pub fn parse(input: &str) -> TeraResult<Vec<Node>> {
+    let mut pairs = parse_template_pairs(input)?;
     let mut nodes = vec![];
 
     // We must have at least a `template` pair if we got there
     for p in pairs.next().unwrap().into_inner() {
         match p.as_rule() {
-            Rule::extends_tag => {
-                let (ws, file) = parse_extends_include(p);
-                nodes.push(Node::Extends(ws, file));
-            }
-            Rule::import_macro_tag => nodes.push(parse_import_macro(p)),
+            Rule::extends_tag => nodes.push(parse_extends(p)?),
+            Rule::import_macro_tag => nodes.push(parse_import_macro(p)?),
             Rule::content => nodes.extend(parse_content(p)?),
             Rule::comment_tag => (),
             Rule::EOI => (),
@@ -1162,5 +2040,7 @@ pub fn parse(input: &str) -> TeraResult<Vec<Node>> {
         }
     }
 
+    set_line_columns(&mut nodes, input, &LineIndex::new(input));
+
     Ok(nodes)
 }
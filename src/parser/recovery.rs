@@ -0,0 +1,105 @@
+//! Collecting all the syntax errors of a template instead of stopping at the first one.
+//!
+//! The grammar can't recover from an error by itself so the template is parsed again
+//! after each error, with the tag or variable block causing it replaced by a valid
+//! one of the same length. Positions in the following errors are still the right ones.
+use pest::error::{Error as PestError, InputLocation};
+use pest::{Parser, Position, Span};
+
+use crate::parser::delimiters::find_tag_end;
+use crate::parser::{parse, syntax_error, Rule, TeraParser};
+
+/// Above that, the errors are likely to be caused by the previous ones anyway
+const MAX_ERRORS: usize = 20;
+
+/// Returns the messages of all the syntax errors of the template, in order.
+/// The list is empty if the template is valid.
+pub(crate) fn collect_syntax_errors(input: &str) -> Vec<String> {
+    let mut errors = vec![];
+    let mut current = input.to_string();
+    let mut last_pos = None;
+
+    while errors.len() < MAX_ERRORS {
+        let e = match TeraParser::parse(Rule::template, &current) {
+            Ok(_) => {
+                // The grammar is happy but building the AST can still fail
+                if let Err(e) = parse(&current) {
+                    errors.push(e.to_string());
+                }
+                break;
+            }
+            Err(e) => e,
+        };
+        // The error is shown with the original template, the replaced tags having the same length
+        let (pos, original) = match e.location {
+            InputLocation::Pos(pos) => {
+                (pos, PestError::new_from_pos(e.variant, Position::new(input, pos).unwrap()))
+            }
+            InputLocation::Span((start, end)) => {
+                (start, PestError::new_from_span(e.variant, Span::new(input, start, end).unwrap()))
+            }
+        };
+        errors.push(syntax_error(input, original).to_string());
+
+        // Stop if we are not making progress or if the error is not inside of a tag
+        if let Some(last) = last_pos {
+            if pos <= last {
+                break;
+            }
+        }
+        last_pos = Some(pos);
+        match enclosing_tag(&current, pos) {
+            Some((start, end)) => {
+                let replacement = replacement_tag(&current[start..end]);
+                current.replace_range(start..end, &replacement);
+            }
+            None => break,
+        }
+    }
+
+    errors
+}
+
+/// Finds the variable block or tag containing the given position, returning its range
+fn enclosing_tag(input: &str, pos: usize) -> Option<(usize, usize)> {
+    // The error can be on the start delimiter itself
+    let search_end = (pos + 2).min(input.len());
+    let start = ["{{", "{%"].iter().filter_map(|d| input[..search_end].rfind(d)).max()?;
+    let end_delimiter = if input[start..].starts_with("{{") { "}}" } else { "%}" };
+    let end = start + 2 + find_tag_end(&input[start + 2..], end_delimiter)? + 2;
+
+    if end > pos {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/// Builds a valid tag of the same length as the invalid one so the positions in the template
+/// don't change. Tags opening a section are kept as such so their end tag doesn't cause another
+/// error, as long as the minimal version fits.
+fn replacement_tag(tag: &str) -> String {
+    let blank = || tag.chars().map(|c| if c == '\n' { '\n' } else { ' ' }).collect::<String>();
+    if !tag.starts_with("{%") {
+        return blank();
+    }
+
+    let inner = tag[2..tag.len() - 2].trim_matches('-').trim_start();
+    let keyword = inner.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+    let valid = match keyword {
+        "if" => "if 1",
+        "elif" => "elif 1",
+        "for" => "for a in b",
+        "while" => "while 1",
+        "block" => "block a",
+        "macro" => "macro a()",
+        "filter" => "filter a",
+        "with" => "with a=1",
+        "trans" => "trans",
+        _ => return blank(),
+    };
+    if valid.len() + 4 > tag.len() || tag[2..tag.len() - 2].contains('\n') {
+        return blank();
+    }
+    format!("{{%{:width$}%}}", valid, width = tag.len() - 4)
+}
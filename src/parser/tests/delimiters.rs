@@ -0,0 +1,79 @@
+use crate::parser::delimiters::{translate, Delimiters};
+
+fn erb() -> Delimiters {
+    Delimiters {
+        variable_start: "<%=".to_string(),
+        variable_end: "%>".to_string(),
+        block_start: "<%".to_string(),
+        block_end: "%>".to_string(),
+        comment_start: "<%#".to_string(),
+        comment_end: "%>".to_string(),
+    }
+}
+
+fn brackets() -> Delimiters {
+    Delimiters {
+        variable_start: "[[".to_string(),
+        variable_end: "]]".to_string(),
+        block_start: "[%".to_string(),
+        block_end: "%]".to_string(),
+        comment_start: "[#".to_string(),
+        comment_end: "#]".to_string(),
+    }
+}
+
+#[test]
+fn translates_custom_delimiters() {
+    let input = "[# hey #][% if a -%] [[- name | upper ]] [%- endif %]";
+    assert_eq!(
+        translate(input, &brackets()).unwrap(),
+        "{# hey #}{% if a -%} {{- name | upper }} {%- endif %}"
+    );
+}
+
+#[test]
+fn escapes_default_delimiters_in_text() {
+    assert_eq!(
+        translate("{{ a }} {% b %} {# c #} [[ d ]]", &brackets()).unwrap(),
+        "{{ \"{{\" }} a }} {{ \"{%\" }} b %} {{ \"{#\" }} c #} {{ d }}"
+    );
+}
+
+#[test]
+fn ignores_end_delimiters_in_strings() {
+    assert_eq!(translate("[[ \"]]\" ~ ']]' ]]", &brackets()).unwrap(), "{{ \"]]\" ~ ']]' }}");
+    assert_eq!(translate(r#"[[ "\"]]" ~ `\` ]]"#, &brackets()).unwrap(), r#"{{ "\"]]" ~ `\` }}"#);
+}
+
+#[test]
+fn keeps_raw_content_as_is() {
+    assert_eq!(
+        translate("[% raw %][[ a ]]{{ b }}[% endraw %][[ c ]]", &brackets()).unwrap(),
+        "{% raw %}[[ a ]]{{ b }}{% endraw %}{{ c }}"
+    );
+}
+
+#[test]
+fn picks_longest_matching_start_delimiter() {
+    assert_eq!(
+        translate("<%= name %><% if a %><%# hey %><% endif %>", &erb()).unwrap(),
+        "{{ name }}{% if a %}{# hey #}{% endif %}"
+    );
+}
+
+#[test]
+fn errors_on_unclosed_tag() {
+    let err = translate("hello [[ name", &brackets()).unwrap_err();
+    assert_eq!(err.to_string(), "Missing `]]` to close the `[[` opened at byte 6");
+}
+
+#[test]
+fn validates_delimiters() {
+    assert!(Delimiters::default().validate().is_ok());
+    assert!(brackets().validate().is_ok());
+    let empty = Delimiters { block_end: "".to_string(), ..brackets() };
+    assert!(empty.validate().is_err());
+    assert!(erb().validate().is_ok());
+    let duplicated = Delimiters { comment_start: "[[".to_string(), ..brackets() };
+    assert!(duplicated.validate().is_err());
+}
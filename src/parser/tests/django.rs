@@ -0,0 +1,55 @@
+use crate::parser::django::translate;
+
+#[test]
+fn can_translate_filter_args() {
+    let inputs = vec![
+        (r#"{{ a|default:"b" }}"#, r#"{{ a|default(value="b") }}"#),
+        (r#"{{ a | join:", " | upper }}"#, r#"{{ a | join(sep=", ") | upper }}"#),
+        ("{{ a|truncate:10|nth:-1 }}", "{{ a|truncate(length=10)|nth(n=-1) }}"),
+        ("{{ a|default:user.names[0] }}", "{{ a|default(value=user.names[0]) }}"),
+        ("{{ a|default:b['c|d:e'] }}", "{{ a|default(value=b['c|d:e']) }}"),
+        ("{{ a|custom:1.5 }}", "{{ a|custom(arg=1.5) }}"),
+        (r#"{{ n|pluralize:"es" }}"#, r#"{{ n|pluralize(plural="es") }}"#),
+        (r#"{{ n|pluralize:"y,ies" }}"#, r#"{{ n|pluralize(singular="y", plural="ies") }}"#),
+        (
+            "{% for a in b|sort:'name' %}{{ a }}{% endfor %}",
+            "{% for a in b|sort(attribute='name') %}{{ a }}{% endfor %}",
+        ),
+        // Strings, comments and Tera calls are kept as they are
+        (r#"{{ "a|default:b" }}"#, r#"{{ "a|default:b" }}"#),
+        ("{# a|default:b #}", "{# a|default:b #}"),
+        (r#"{{ a|default(value="b") }}"#, r#"{{ a|default(value="b") }}"#),
+        ("{% raw %}{{ a|default:b }}{% endraw %}", "{% raw %}{{ a|default:b }}{% endraw %}"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(translate(input).unwrap(), expected);
+    }
+}
+
+#[test]
+fn can_translate_tags() {
+    assert_eq!(
+        translate("{% for a in b %}{{ a }}{%- empty -%}none{% endfor %}").unwrap(),
+        "{% for a in b %}{{ a }}{%- else -%}none{% endfor %}"
+    );
+    assert_eq!(
+        translate("a{% comment \"why\" %}\n{{ b }} #}\n{% endcomment %}c").unwrap(),
+        "a{#\n\n#}c"
+    );
+}
+
+#[test]
+fn errors_on_unclosed_comment_blocks() {
+    let err = translate("a\n{% comment %}{{ b }}").unwrap_err();
+    assert_eq!(err.to_string(), "The `{% comment %}` at line 2 is never closed");
+}
+
+#[test]
+fn keeps_unclosed_tags_for_the_parser() {
+    assert_eq!(
+        translate("{{ a|default:b }}{{ c|default:d").unwrap(),
+        "{{ a|default(value=b) }}{{ c|default:d"
+    );
+}
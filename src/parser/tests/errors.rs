@@ -19,7 +19,7 @@ fn invalid_number() {
         "{{ 1.2.2 }}",
         &[
             "1:7",
-            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `/`, `%`, a filter, or a variable end (`}}`)"
+            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `//`, `/`, `%`, a filter, or a variable end (`}}`)"
         ],
     );
 }
@@ -35,7 +35,7 @@ fn wrong_start_block() {
         "{{ if true %}",
         &[
             "1:7",
-            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `/`, `%`, a filter, or a variable end (`}}`)"
+            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `//`, `/`, `%`, a filter, or a variable end (`}}`)"
         ],
     );
 }
@@ -46,7 +46,7 @@ fn wrong_end_block() {
         "{{ hey %}",
         &[
             "1:9",
-            "expected an integer, a float, `true` or `false`, an identifier (must start with a-z), a dotted identifier (identifiers separated by `.`), a square bracketed identifier (identifiers separated by `.` or `[]`s), or an expression"
+            "expected an integer, a float, `true` or `false`, `none`, an identifier (must start with a letter or `_`), a square bracketed identifier (identifiers separated by `.` or `[]`s), a negative value, an expression, an array of values, a map of values, a negated test, or a test"
         ],
     );
 }
@@ -57,14 +57,45 @@ fn unterminated_variable_block() {
         "{{ hey",
         &[
             "1:7",
-            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `/`, `%`, a filter, or a variable end (`}}`)"
+            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `//`, `/`, `%`, a filter, or a variable end (`}}`)"
         ],
     );
 }
 
+#[test]
+fn unterminated_multiline_blocks() {
+    assert_err_msg(
+        "{% if a %}\n{{ name\n  | upper\n{% endif %}",
+        &["4:1", "the `{{` at 2:1 is never closed"],
+    );
+    assert_err_msg("{# a #}\n{% set a = 1\n", &["the `{%` at 2:1 is never closed"]);
+    // Raw blocks and strings can contain start and end delimiters
+    assert_err_msg(
+        "{% raw %}{{{% endraw %}{{ '}}' ~ a }}\n{% if",
+        &["the `{%` at 2:1 is never closed"],
+    );
+}
+
 #[test]
 fn unterminated_string() {
-    assert_err_msg(r#"{{ "hey }}"#, &["1:4", "expected a value that can be negated"]);
+    assert_err_msg(
+        r#"{{ "hey }}"#,
+        &[
+            "1:4",
+            "expected a value that can be negated",
+            "the string started by `\"` at 1:4 is never closed",
+        ],
+    );
+    assert_err_msg(
+        "{% set a = 1 %}\n{{ 'hey ~ a }}\n",
+        &["the string started by `'` at 2:4 is never closed"],
+    );
+}
+
+#[test]
+fn unterminated_comment() {
+    assert_err_msg("{{ a }}\n{# hey", &["the `{#` at 2:1 is never closed"]);
+    assert_err_msg("{{ a {# hey }}", &["the `{#` at 1:6 is never closed"]);
 }
 
 #[test]
@@ -84,7 +115,7 @@ fn unterminated_filter_section() {
 fn invalid_filter_section_missing_name() {
     assert_err_msg(
         r#"{% filter %}sd{% endfilter %}"#,
-        &["1:11", "expected an identifier (must start with a-z)"],
+        &["1:11", "expected an identifier (must start with a letter or `_`)"],
     );
 }
 
@@ -108,7 +139,7 @@ fn invalid_macro_default_arg_value() {
 {% macro input(label=something) %}
 {% endmacro input %}
     "#,
-        &["2:22", "expected an integer, a float, a string, or `true` or `false`"],
+        &["2:22", "expected an integer, a float, a string, `true` or `false`, or `none`"],
     );
 }
 
@@ -155,7 +186,7 @@ fn invalid_operator() {
         "{{ hey =! }}",
         &[
             "1:8",
-            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `/`, `%`, a filter, or a variable end (`}}`)"
+            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `//`, `/`, `%`, a filter, or a variable end (`}}`)"
         ],
     );
 }
@@ -177,7 +208,10 @@ fn missing_container_name_in_forloop() {
 
 #[test]
 fn missing_variable_name_in_set() {
-    assert_err_msg("{% set = 1 %}", &["1:8", "expected an identifier (must start with a-z)"]);
+    assert_err_msg(
+        "{% set = 1 %}",
+        &["1:8", "expected an identifier (must start with a letter or `_`)"],
+    );
 }
 
 #[test]
@@ -190,7 +224,10 @@ fn missing_value_in_set() {
 
 #[test]
 fn unterminated_fn_call() {
-    assert_err_msg("{{ a | slice( }}", &["1:15", "expected an identifier (must start with a-z)"]);
+    assert_err_msg(
+        "{{ a | slice( }}",
+        &["1:15", "expected an identifier (must start with a letter or `_`)"],
+    );
 }
 
 #[test]
@@ -203,7 +240,10 @@ fn invalid_fn_call_missing_value() {
 
 #[test]
 fn unterminated_macro_call() {
-    assert_err_msg("{{ my::macro( }}", &["1:15", "expected an identifier (must start with a-z)"]);
+    assert_err_msg(
+        "{{ my::macro( }}",
+        &["1:15", "expected an identifier (must start with a letter or `_`)"],
+    );
 }
 
 #[test]
@@ -212,7 +252,7 @@ fn invalid_macro_call() {
         "{{ my:macro() }}",
         &[
             "1:6",
-            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `/`, `%`, a filter, or a variable end (`}}`)"
+            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `//`, `/`, `%`, a filter, or a variable end (`}}`)"
         ],
     );
 }
@@ -233,8 +273,8 @@ fn unterminated_extends() {
 }
 
 #[test]
-fn invalid_extends_no_string() {
-    assert_err_msg("{% extends 1 %}", &["1:12", "expected a string"]);
+fn invalid_extends_no_parent() {
+    assert_err_msg("{% extends %}", &["1:12", "expected a string"]);
 }
 
 #[test]
@@ -246,13 +286,16 @@ fn invalid_import_macros_missing_filename() {
 fn invalid_import_macros_missing_namespace() {
     assert_err_msg(
         r#"{% import "hello" as %}"#,
-        &["1:22", "expected an identifier (must start with a-z)"],
+        &["1:22", "expected an identifier (must start with a letter or `_`)"],
     );
 }
 
 #[test]
 fn invalid_block_missing_name() {
-    assert_err_msg(r#"{% block %}"#, &["1:10", "expected an identifier (must start with a-z)"]);
+    assert_err_msg(
+        r#"{% block %}"#,
+        &["1:10", "expected an identifier (must start with a letter or `_`)"],
+    );
 }
 
 #[test]
@@ -269,7 +312,7 @@ fn invalid_test_argument() {
         r#"{% if a is odd(key=1) %}"#,
         &[
             "1:19",
-            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `/`, `%`, or a filter"
+            "expected `or`, `and`, `not`, `<=`, `>=`, `<`, `>`, `==`, `!=`, `+`, `-`, `*`, `//`, `/`, `%`, or a filter"
         ],
     );
 }
@@ -288,3 +331,49 @@ fn invalid_break_outside_loop() {
 fn invalid_continue_outside_loop() {
     assert_err_msg(r#"{% continue %}"#, &["1:1", "{% continue %}", "expected a template"]);
 }
+
+#[test]
+fn invalid_string_escape() {
+    assert_err_msg(
+        r#"{{ "C:\dir" }}"#,
+        &["Invalid escape sequence `\\d` in string literal at line 1, column 7"],
+    );
+    assert_err_msg(
+        "{% set a = 1 %}\n{{ 'a' ~ \"b\\u{110000}\" }}",
+        &["Invalid escape sequence `\\u{110000}` in string literal at line 2, column 12"],
+    );
+    assert_err_msg(r#"{{ "\u{zz}" }}"#, &["Invalid escape sequence `\\u{zz}`"]);
+}
+
+#[test]
+fn errors_show_the_found_token() {
+    assert_err_msg(
+        "{{ a | slice(start=1 end=2) }}",
+        &["1:22", "expected `or`, `and`", "found `end`"],
+    );
+    assert_err_msg("{% if a == %}", &["1:12", "found `%}`"]);
+    assert_err_msg("{{ a ) }}", &["1:6", "found `)`"]);
+    assert_err_msg("{{ a + ", &["1:8", "found the end of the template"]);
+}
+
+#[test]
+fn invalid_identifier_start() {
+    // Only letters and `_` can start an identifier, in any language
+    assert_err_msg("{% set 1a = 1 %}", &["1:8", "expected an identifier"]);
+    assert_err_msg("{% set ·a = 1 %}", &["1:8", "expected an identifier"]);
+}
+
+#[test]
+fn content_before_first_switch_case() {
+    assert_err_msg("{% switch a %}hello{% case 1 %}{% endswitch %}", &["1:15", "found `hello`"]);
+}
+
+#[test]
+fn break_in_while_loop() {
+    assert_err_msg("{% while true %}{% break %}{% endwhile %}", &["1:17"]);
+}
+
+#[test]
+fn invalid_positional_arg_after_kwarg_in_macro_call() {
+    assert_err_msg("{{ forms::input(name='email', 'text') }}", &["1:31"]);
+}
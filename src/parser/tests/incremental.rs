@@ -0,0 +1,85 @@
+use crate::parser::incremental::ParsedSource;
+use crate::parser::parse;
+
+/// Applies the edits one after the other, checking each time that the result is the same
+/// as when parsing the whole template
+fn assert_same_as_full_parse(source: &str, edits: &[(usize, usize, &str)]) {
+    let mut parsed = ParsedSource::new(source);
+    for &(start, end, text) in edits {
+        let result = parsed.edit(start..end, text).map(|n| n.to_vec()).map_err(|e| e.to_string());
+        let expected = parse(parsed.source()).map_err(|e| e.to_string());
+        println!("{:?}", parsed.source());
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn can_edit_inside_nodes() {
+    let source =
+        "Hello {{ name }}\n{% if a %}\n  {{ a }}\n{% endif %}\n{% for x in b %}{{ x }}{% endfor %}";
+    assert_same_as_full_parse(
+        source,
+        &[(9, 13, "user.name"), (28, 29, "a and b\n"), (0, 5, "Bonjour\n\n"), (78, 79, "items")],
+    );
+}
+
+#[test]
+fn can_edit_text() {
+    assert_same_as_full_parse(
+        "a{{ b }}c{% set d = 1 %}e",
+        &[(0, 1, "aa"), (10, 10, "c"), (11, 26, ""), (0, 0, "{{ x }}"), (19, 19, "f")],
+    );
+}
+
+#[test]
+fn can_add_and_remove_nodes() {
+    assert_same_as_full_parse(
+        "{{ a }} {{ b }}",
+        &[
+            (7, 7, "{% if c %}"),
+            (17, 17, "{% endif %}"),
+            (7, 28, " "),
+            (0, 0, "{% block x %}"),
+            (21, 21, "{% endblock %}"),
+        ],
+    );
+}
+
+#[test]
+fn can_recover_from_errors() {
+    let mut parsed = ParsedSource::new("{{ a }} {{ b }}");
+    assert!(parsed.edit(12..12, "+").is_err());
+    assert!(parsed.edit(13..13, " 1").is_ok());
+    assert_eq!(parsed.source(), "{{ a }} {{ b+ 1 }}");
+
+    let mut parsed = ParsedSource::new("{% if %}");
+    assert!(parsed.nodes().is_err());
+    assert!(parsed.edit(6..6, "a ").is_err());
+    assert!(parsed.edit(10..10, "{% endif %}").is_ok());
+}
+
+#[test]
+fn errors_are_about_the_whole_template() {
+    let mut parsed = ParsedSource::new("{{ a }}\n{{ b }}\n{{ c }}");
+    let err = parsed.edit(12..12, " +").unwrap_err().to_string();
+    assert!(err.contains("2:8"));
+    assert!(err.contains("{{ b + }}"));
+}
+
+#[test]
+fn can_edit_around_top_imports() {
+    assert_same_as_full_parse(
+        "{% extends \"a\" %}\n{% import \"m\" as m %}\n{% block b %}{% endblock %}",
+        &[(18, 18, "{# hey #}\n"), (0, 0, "  "), (29, 29, " "), (2, 2, "x")],
+    );
+    assert_same_as_full_parse(
+        "{% block b %}{% endblock %}",
+        &[(0, 0, "{% extends \"a\" %}"), (0, 0, "{% block c %}{% endblock %}")],
+    );
+}
+
+#[test]
+fn delimiters_can_be_made_across_nodes() {
+    assert_same_as_full_parse("a{{ b }}", &[(1, 1, "{"), (0, 1, "")]);
+    assert_same_as_full_parse("{{ a }}}", &[(7, 7, "}")]);
+}
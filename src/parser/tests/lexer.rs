@@ -26,7 +26,18 @@ fn lex_boolean() {
 
 #[test]
 fn lex_int() {
-    let inputs = vec!["-10", "0", "100", "250000"];
+    let inputs = vec![
+        "-10",
+        "0",
+        "100",
+        "250000",
+        "1_000_000",
+        "-1_0",
+        "0xFF",
+        "0x1f_ff",
+        "-0b1010",
+        "0b1_0",
+    ];
     for i in inputs {
         assert_lex_rule!(Rule::int, i);
     }
@@ -34,12 +45,20 @@ fn lex_int() {
 
 #[test]
 fn lex_float() {
-    let inputs = vec!["123.5", "123.5", "0.1", "-1.1"];
+    let inputs = vec!["123.5", "123.5", "0.1", "-1.1", "1_000.000_5"];
     for i in inputs {
         assert_lex_rule!(Rule::float, i);
     }
 }
 
+#[test]
+fn lex_numbers_with_invalid_underscores() {
+    for i in &["_1", "1__0", "1_", "0_1", "1_.5", "1._5", "0x", "0xG", "0b2", "0x_1"] {
+        let res = TeraParser::parse(Rule::int, i).or_else(|_| TeraParser::parse(Rule::float, i));
+        assert!(res.map(|mut p| p.next().unwrap().as_str() != *i).unwrap_or(true), "{}", i);
+    }
+}
+
 #[test]
 fn lex_string() {
     let inputs = vec![
@@ -427,7 +446,16 @@ fn lex_test() {
 
 #[test]
 fn lex_include_tag() {
-    assert!(TeraParser::parse(Rule::include_tag, "{% include \"index.html\" %}").is_ok());
+    let inputs = vec![
+        "{% include \"index.html\" %}",
+        "{% include \"index.html\" only %}",
+        "{% include \"index.html\" ignore missing %}",
+        "{% include \"index.html\" with {} %}",
+        "{% include \"index.html\" with {\"a\": b | upper, c: [1, 2]} only %}",
+    ];
+    for i in inputs {
+        assert!(TeraParser::parse(Rule::include_tag, i).is_ok());
+    }
 }
 
 #[test]
@@ -644,7 +672,8 @@ fn lex_requires_whitespace_between_things() {
         "{% for a,bin c %}{{a}}{% endfor %}",
         "{% for a,b inc %}{{a}}{% endfor %}",
         "{% ifi18n %}世界{% else %}world{% endif %}",
-        "{% if i18n %}世界{% eliftrue %}world{% endif %}",
+        // `{% eliftrue %}` alone would be a tag registered with `Tera::register_tag`
+        "{% if i18n %}世界{% eliftrue and false %}world{% endif %}",
         "{% include'base.html' %}",
     ];
 
@@ -0,0 +1,64 @@
+use crate::parser::liquid::translate;
+
+#[test]
+fn can_translate_filters() {
+    let inputs = vec![
+        ("{{ a | upcase }}", "{{ a | upper }}"),
+        (r#"{{ a | default: "b" | size }}"#, r#"{{ a | default(value="b") | length }}"#),
+        (r#"{{ a | replace: "b", "c" }}"#, r#"{{ a | replace(from="b", to="c") }}"#),
+        (r#"{{ a | truncate: 10, end: "…" }}"#, r#"{{ a | truncate(length=10, end="…") }}"#),
+        (r#"{{ a | where: "ok", true }}"#, r#"{{ a | filter(attribute="ok", value=true) }}"#),
+        ("{{ a | custom: 1, b.c }}", "{{ a | custom(arg=1, arg2=b.c) }}"),
+        ("{{ a | default: nil }}", "{{ a | default(value=none) }}"),
+        ("{{ forloop.index }}", "{{ loop.index }}"),
+        // Strings and raw blocks are kept as they are
+        (r#"{{ "nil | upcase" }}"#, r#"{{ "nil | upcase" }}"#),
+        ("{% raw %}{{ a | upcase }}{% endraw %}", "{% raw %}{{ a | upcase }}{% endraw %}"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(translate(input).unwrap(), expected);
+    }
+}
+
+#[test]
+fn can_translate_tags() {
+    let inputs = vec![
+        ("{% assign a = b | downcase %}", "{% set a = b | lower %}"),
+        (
+            "{% if a contains 'b' and c %}1{% elsif d == nil %}2{% endif %}",
+            "{% if 'b' in a and c %}1{% elif d == none %}2{% endif %}",
+        ),
+        ("{%- unless a or b > 1 -%}1{% endunless %}", "{%- if not a and not b > 1 -%}1{% endif %}"),
+        (
+            "{% case a %}{% when 1, 2 %}x{% when 'b' or 'c' %}y{% else %}z{% endcase %}",
+            "{% if a == 1 or a == 2 %}x{% elif a == 'b' or a == 'c' %}y{% else %}z{% endif %}",
+        ),
+        ("{% case a %}{% endcase %}", ""),
+        (
+            "{% for a in b reversed limit: 2 offset:1 %}{% endfor %}",
+            "{% for a in b | slice(start=1, end=1 + 2) | reverse %}{% endfor %}",
+        ),
+        (
+            "{% for i in (1..n) %}{% endfor %}",
+            "{% for i in range(start=1, end=n + 1) %}{% endfor %}",
+        ),
+        ("a{% comment %}\n{{ b }}{% endcomment %}c", "a{#\n#}c"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(translate(input).unwrap(), expected);
+    }
+}
+
+#[test]
+fn errors_on_conditions_grouped_differently() {
+    for input in &["{% if a and b or c %}{% endif %}", "{% unless a or b and c %}{% endunless %}"] {
+        let err = translate(input).unwrap_err();
+        assert!(err.to_string().contains("Liquid evaluates `and` and `or` from right to left"));
+    }
+    // Those mean the same in Tera
+    assert!(translate("{% if a or b and c %}{% endif %}").is_ok());
+}
@@ -1,4 +1,11 @@
+mod delimiters;
+mod django;
 mod errors;
+mod incremental;
 mod lexer;
+mod liquid;
 mod parser;
+mod recovery;
+mod spans;
+mod tokens;
 mod whitespace;
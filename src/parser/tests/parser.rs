@@ -1,7 +1,25 @@
 use std::collections::HashMap;
 
+use crate::errors::Result;
 use crate::parser::ast::*;
-use crate::parser::parse;
+
+/// Parses the template and resets all the node spans so the expected ASTs stay readable.
+/// Spans themselves are tested in `spans.rs`
+fn parse(input: &str) -> Result<Vec<Node>> {
+    fn reset_spans(nodes: &mut [Node]) {
+        for node in nodes {
+            let (span, bodies) = node.span_and_bodies_mut();
+            *span = Span::default();
+            for body in bodies {
+                reset_spans(body);
+            }
+        }
+    }
+
+    let mut nodes = crate::parser::parse(input)?;
+    reset_spans(&mut nodes);
+    Ok(nodes)
+}
 
 #[test]
 fn parse_empty_template() {
@@ -12,31 +30,117 @@ fn parse_empty_template() {
 #[test]
 fn parse_text() {
     let ast = parse("hello world").unwrap();
-    assert_eq!(ast[0], Node::Text("hello world".to_string()));
+    assert_eq!(ast[0], Node::Text("hello world".to_string(), Span::default()));
 }
 
 #[test]
 fn parse_text_with_whitespace() {
     let ast = parse(" hello world ").unwrap();
-    assert_eq!(ast[0], Node::Text(" hello world ".to_string()));
+    assert_eq!(ast[0], Node::Text(" hello world ".to_string(), Span::default()));
 }
 
 #[test]
 fn parse_include_tag() {
     let ast = parse("{% include \"index.html\" -%}").unwrap();
-    assert_eq!(ast[0], Node::Include(WS { left: false, right: true }, "index.html".to_string(),),);
+    assert_eq!(
+        ast[0],
+        Node::Include(
+            WS { left: false, right: true },
+            Include {
+                name: "index.html".to_string(),
+                variables: vec![],
+                only: false,
+                ignore_missing: false,
+            },
+            Span::default(),
+        ),
+    );
+}
+
+#[test]
+fn parse_include_tag_with_variables() {
+    let ast = parse(
+        "{% include \"widget.html\" ignore missing with {\"items\": cart, total: 1,} only %}",
+    )
+    .unwrap();
+    assert_eq!(
+        ast[0],
+        Node::Include(
+            WS::default(),
+            Include {
+                name: "widget.html".to_string(),
+                variables: vec![
+                    ("items".to_string(), Expr::new(ExprVal::Ident("cart".to_string()))),
+                    ("total".to_string(), Expr::new(ExprVal::Int(1))),
+                ],
+                only: true,
+                ignore_missing: true,
+            },
+            Span::default(),
+        ),
+    );
+}
+
+#[test]
+fn parse_embed() {
+    let ast =
+        parse("{% embed \"card.html\" %}\n  {% block title %}Hi{% endblock %}\n{% endembed %}")
+            .unwrap();
+    assert_eq!(
+        ast[0],
+        Node::Embed(
+            WS::default(),
+            Embed {
+                name: "card.html".to_string(),
+                blocks: vec![Node::Block(
+                    WS::default(),
+                    Block {
+                        name: "title".to_string(),
+                        body: vec![Node::Text("Hi".to_string(), Span::default())],
+                    },
+                    WS::default(),
+                    Span::default(),
+                )],
+            },
+            WS::default(),
+            Span::default(),
+        ),
+    );
+
+    assert!(parse("{% embed \"card.html\" %}text{% endembed %}").is_err());
+    let err = parse("{% embed \"a\" %}{% block b %}{{ super() }}{% endblock %}{% endembed %}");
+    assert!(err.unwrap_err().to_string().contains("`super()` can't be used"));
 }
 
 #[test]
 fn parse_extends() {
     let ast = parse("{% extends \"index.html\" -%}").unwrap();
-    assert_eq!(ast[0], Node::Extends(WS { left: false, right: true }, "index.html".to_string(),),);
+    assert_eq!(
+        ast[0],
+        Node::Extends(WS { left: false, right: true }, "index.html".to_string(), Span::default(),),
+    );
+}
+
+#[test]
+fn parse_dynamic_extends() {
+    let ast = parse("{% extends layout %}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::DynamicExtends(
+            WS::default(),
+            Expr::new(ExprVal::Ident("layout".to_string())),
+            Span::default(),
+        ),
+    );
 }
 
 #[test]
 fn parse_comments_before_extends() {
     let ast = parse("{# A comment #}{% extends \"index.html\" -%}").unwrap();
-    assert_eq!(ast[0], Node::Extends(WS { left: false, right: true }, "index.html".to_string(),),);
+    assert_eq!(
+        ast[0],
+        Node::Extends(WS { left: false, right: true }, "index.html".to_string(), Span::default(),),
+    );
 }
 
 #[test]
@@ -48,6 +152,7 @@ fn parse_import_macro() {
             WS { left: false, right: true },
             "macros.html".to_string(),
             "macros".to_string(),
+            Span::default(),
         ),
     );
 }
@@ -59,7 +164,8 @@ fn parse_variable_with_whitespace_trimming() {
         ast[0],
         Node::VariableBlock(
             WS { left: true, right: false },
-            Expr::new(ExprVal::Ident("id".to_string()))
+            Expr::new(ExprVal::Ident("id".to_string())),
+            Span::default()
         ),
     );
 }
@@ -69,7 +175,11 @@ fn parse_variable_tag_ident() {
     let ast = parse("{{ id }}").unwrap();
     assert_eq!(
         ast[0],
-        Node::VariableBlock(WS::default(), Expr::new(ExprVal::Ident("id".to_string()))),
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Ident("id".to_string())),
+            Span::default()
+        ),
     );
 }
 
@@ -89,21 +199,106 @@ fn parse_variable_tag_ident_with_simple_filters() {
                     FunctionCall { name: "first".to_string(), args: HashMap::new() },
                     FunctionCall { name: "join".to_string(), args: join_args },
                 ],
-            )
+            ),
+            Span::default()
         )
     );
 }
 
 #[test]
 fn parse_variable_tag_lit() {
-    let ast = parse("{{ 2 }}{{ 3.14 }}{{ \"hey\" }}{{ true }}").unwrap();
-    assert_eq!(ast[0], Node::VariableBlock(WS::default(), Expr::new(ExprVal::Int(2))));
-    assert_eq!(ast[1], Node::VariableBlock(WS::default(), Expr::new(ExprVal::Float(3.14))));
+    let ast = parse("{{ 2 }}{{ 3.14 }}{{ \"hey\" }}{{ true }}{{ none }}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(WS::default(), Expr::new(ExprVal::Int(2)), Span::default())
+    );
+    assert_eq!(
+        ast[1],
+        Node::VariableBlock(WS::default(), Expr::new(ExprVal::Float(3.14)), Span::default())
+    );
     assert_eq!(
         ast[2],
-        Node::VariableBlock(WS::default(), Expr::new(ExprVal::String("hey".to_string()))),
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::String("hey".to_string())),
+            Span::default()
+        ),
+    );
+    assert_eq!(
+        ast[3],
+        Node::VariableBlock(WS::default(), Expr::new(ExprVal::Bool(true)), Span::default())
+    );
+    assert_eq!(
+        ast[4],
+        Node::VariableBlock(WS::default(), Expr::new(ExprVal::Null), Span::default())
+    );
+}
+
+#[test]
+fn parse_none_prefixed_ident() {
+    let ast = parse("{{ none_left }}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Ident("none_left".to_string())),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_numbers_with_underscores() {
+    let ast = parse("{{ 1_000_000 }}{{ -1_000.000_5 }}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(WS::default(), Expr::new(ExprVal::Int(1_000_000)), Span::default())
     );
-    assert_eq!(ast[3], Node::VariableBlock(WS::default(), Expr::new(ExprVal::Bool(true))));
+    assert_eq!(
+        ast[1],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Float(-1_000.000_5)),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_hex_and_binary_numbers() {
+    let ast = parse("{{ 0xFF }}{{ 0x00ff_00ff }}{{ -0b1010 }}").unwrap();
+    let expected = vec![255, 0x00ff_00ff, -10];
+    for (node, value) in ast.iter().zip(expected) {
+        assert_eq!(
+            *node,
+            Node::VariableBlock(WS::default(), Expr::new(ExprVal::Int(value)), Span::default())
+        );
+    }
+    assert!(parse("{{ 0x1_0000_0000_0000_0000 }}").is_err());
+}
+
+#[test]
+fn parse_string_escapes() {
+    let inputs = vec![
+        (r#"{{ "a\"b\\c" }}"#, "a\"b\\c"),
+        (r#"{{ 'it\'s' }}"#, "it's"),
+        (r#"{{ "tab\tnew\nline\r\0" }}"#, "tab\tnew\nline\r\0"),
+        (r#"{{ "\u{e9}\u{1F600}" }}"#, "\u{e9}\u{1F600}"),
+        (r#"{{ `C:\dir\n` }}"#, "C:\\dir\\n"),
+        (r#"{{ "it's " ~ 'a "quote"' }}"#, "it's a \"quote\""),
+    ];
+
+    for (input, expected) in inputs {
+        let ast = parse(input).unwrap();
+        assert_eq!(
+            ast[0],
+            Node::VariableBlock(
+                WS::default(),
+                Expr::new(ExprVal::String(expected.to_string())),
+                Span::default()
+            )
+        );
+    }
 }
 
 #[test]
@@ -120,7 +315,32 @@ fn parse_variable_tag_array_lit() {
                 Expr::new(ExprVal::Int(1)),
                 Expr::new(ExprVal::Int(2)),
                 Expr::new(ExprVal::Int(3))
-            ]),)
+            ]),),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_variable_tag_map_lit() {
+    let ast = parse("{{ {'a': 1, b: [2], c: d | upper,} }}").unwrap();
+
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Map(vec![
+                ("a".to_string(), Expr::new(ExprVal::Int(1))),
+                ("b".to_string(), Expr::new(ExprVal::Array(vec![Expr::new(ExprVal::Int(2))]))),
+                (
+                    "c".to_string(),
+                    Expr::with_filters(
+                        ExprVal::Ident("d".to_string()),
+                        vec![FunctionCall { name: "upper".to_string(), args: HashMap::new() }],
+                    )
+                ),
+            ])),
+            Span::default()
         )
     );
 }
@@ -142,7 +362,8 @@ fn parse_variable_tag_array_lit_with_filter() {
                     Expr::new(ExprVal::Int(3))
                 ]),
                 vec![FunctionCall { name: "length".to_string(), args: HashMap::new() },],
-            )
+            ),
+            Span::default()
         )
     );
 }
@@ -163,7 +384,8 @@ fn parse_variable_tag_lit_math_expression() {
                     operator: MathOperator::Mul,
                     rhs: Box::new(Expr::new(ExprVal::Float(2.5))),
                 },))),
-            },))
+            },)),
+            Span::default()
         ),
     );
 }
@@ -183,7 +405,29 @@ fn parse_variable_tag_lit_math_expression_with_parentheses() {
                 },))),
                 operator: MathOperator::Mul,
                 rhs: Box::new(Expr::new(ExprVal::Float(2.5))),
-            },))
+            },)),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_variable_tag_negated_math_expression() {
+    let ast = parse("{{ -(count + 1) }}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Math(MathExpr {
+                lhs: Box::new(Expr::new(ExprVal::Int(0))),
+                operator: MathOperator::Sub,
+                rhs: Box::new(Expr::new(ExprVal::Math(MathExpr {
+                    lhs: Box::new(Expr::new(ExprVal::Ident("count".to_string()))),
+                    operator: MathOperator::Add,
+                    rhs: Box::new(Expr::new(ExprVal::Int(1))),
+                },))),
+            },)),
+            Span::default()
         )
     );
 }
@@ -206,7 +450,28 @@ fn parse_variable_tag_lit_math_expression_with_parentheses_and_filter() {
                     rhs: Box::new(Expr::new(ExprVal::Float(2.5))),
                 },),
                 vec![FunctionCall { name: "round".to_string(), args: HashMap::new() },],
-            )
+            ),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_variable_tag_coalesce() {
+    let ast = parse("{{ user.nickname ?? user.name | upper ?? 'anonymous' }}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Coalesce(vec![
+                Expr::new(ExprVal::Ident("user.nickname".to_string())),
+                Expr::with_filters(
+                    ExprVal::Ident("user.name".to_string()),
+                    vec![FunctionCall { name: "upper".to_string(), args: HashMap::new() },],
+                ),
+                Expr::new(ExprVal::String("anonymous".to_string())),
+            ])),
+            Span::default()
         )
     );
 }
@@ -225,7 +490,8 @@ fn parse_variable_math_on_filter() {
                 )),
                 operator: MathOperator::Sub,
                 rhs: Box::new(Expr::new(ExprVal::Int(1))),
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -241,7 +507,8 @@ fn parse_variable_tag_simple_logic_expression() {
                 lhs: Box::new(Expr::new(ExprVal::Int(1))),
                 operator: LogicOperator::Gt,
                 rhs: Box::new(Expr::new(ExprVal::Int(2))),
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -265,7 +532,8 @@ fn parse_variable_tag_math_and_logic_expression() {
                 },))),
                 operator: LogicOperator::And,
                 rhs: Box::new(Expr::new(ExprVal::Ident("admin".to_string()))),
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -292,7 +560,8 @@ fn parse_variable_tag_math_with_filters_and_logic_expression() {
                 )),
                 operator: LogicOperator::And,
                 rhs: Box::new(Expr::new(ExprVal::Ident("admin".to_string()))),
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -302,7 +571,11 @@ fn parse_variable_tag_simple_negated_expr() {
     let ast = parse("{{ not id }}").unwrap();
     assert_eq!(
         ast[0],
-        Node::VariableBlock(WS::default(), Expr::new_negated(ExprVal::Ident("id".to_string())))
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new_negated(ExprVal::Ident("id".to_string())),
+            Span::default()
+        )
     );
 }
 
@@ -315,10 +588,53 @@ fn parse_test() {
             WS::default(),
             Expr::new(ExprVal::Test(Test {
                 ident: "a".to_string(),
+                call: None,
                 negated: false,
                 name: "divisibleby".to_string(),
                 args: vec![Expr::new(ExprVal::Int(2))]
-            }))
+            })),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_test_on_function_call() {
+    let ast = parse("{{ now() is not none }}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::Test(Test {
+                ident: String::new(),
+                call: Some(FunctionCall { name: "now".to_string(), args: HashMap::new() }),
+                negated: true,
+                name: "none".to_string(),
+                args: vec![],
+            })),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_in_function_call() {
+    let ast = parse("{{ 1 in range(end=3) }}").unwrap();
+    let mut args = HashMap::new();
+    args.insert("end".to_string(), Expr::new(ExprVal::Int(3)));
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::In(In {
+                lhs: Box::new(Expr::new(ExprVal::Int(1))),
+                rhs: Box::new(Expr::new(ExprVal::FunctionCall(FunctionCall {
+                    name: "range".to_string(),
+                    args
+                }))),
+                negated: false,
+            })),
+            Span::default()
         )
     );
 }
@@ -342,7 +658,8 @@ fn parse_variable_tag_negated_expr() {
                     operator: MathOperator::Add,
                     rhs: Box::new(Expr::new(ExprVal::Int(1))),
                 },))),
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -356,10 +673,12 @@ fn parse_variable_tag_simple_test() {
             WS::default(),
             Expr::new(ExprVal::Test(Test {
                 ident: "id".to_string(),
+                call: None,
                 negated: false,
                 name: "defined".to_string(),
                 args: vec![],
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -373,10 +692,12 @@ fn parse_variable_tag_simple_negated_test() {
             WS::default(),
             Expr::new(ExprVal::Test(Test {
                 ident: "id".to_string(),
+                call: None,
                 negated: true,
                 name: "defined".to_string(),
                 args: vec![],
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -391,13 +712,15 @@ fn parse_variable_tag_test_as_expression() {
             Expr::new(ExprVal::Logic(LogicExpr {
                 lhs: Box::new(Expr::new(ExprVal::Test(Test {
                     ident: "user".to_string(),
+                    call: None,
                     negated: false,
                     name: "defined".to_string(),
                     args: vec![],
                 },))),
                 operator: LogicOperator::And,
                 rhs: Box::new(Expr::new(ExprVal::Ident("user.admin".to_string()))),
-            },))
+            },)),
+            Span::default()
         )
     );
 }
@@ -416,7 +739,9 @@ fn parse_variable_tag_macro_call() {
                 namespace: "macros".to_string(),
                 name: "get_time".to_string(),
                 args,
+                positional_args: vec![],
             },)),
+            Span::default(),
         )
     );
 }
@@ -436,12 +761,14 @@ fn parse_allow_block_in_filter_section() {
                     WS::default(),
                     Block {
                         name: "content".to_owned(),
-                        body: vec![Node::Text("Hello".to_owned())]
+                        body: vec![Node::Text("Hello".to_owned(), Span::default())]
                     },
                     WS::default(),
+                    Span::default(),
                 )],
             },
             WS::default(),
+            Span::default(),
         )
     );
 }
@@ -464,7 +791,33 @@ fn parse_variable_tag_macro_call_with_array() {
                 namespace: "macros".to_string(),
                 name: "get_time".to_string(),
                 args,
-            },))
+                positional_args: vec![],
+            },)),
+            Span::default()
+        )
+    );
+}
+
+#[test]
+fn parse_variable_tag_macro_call_with_positional_args() {
+    let ast = parse("{{ forms::input('email', 'text', size=4) }}").unwrap();
+    let mut args = HashMap::new();
+    args.insert("size".to_string(), Expr::new(ExprVal::Int(4)));
+
+    assert_eq!(
+        ast[0],
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::MacroCall(MacroCall {
+                namespace: "forms".to_string(),
+                name: "input".to_string(),
+                args,
+                positional_args: vec![
+                    Expr::new(ExprVal::String("email".to_string())),
+                    Expr::new(ExprVal::String("text".to_string())),
+                ],
+            },)),
+            Span::default()
         )
     );
 }
@@ -490,7 +843,9 @@ fn parse_variable_tag_macro_call_with_array_with_filters() {
                 namespace: "macros".to_string(),
                 name: "get_time".to_string(),
                 args,
-            },))
+                positional_args: vec![],
+            },)),
+            Span::default()
         )
     );
 }
@@ -510,9 +865,11 @@ fn parse_variable_tag_macro_call_with_filter() {
                     namespace: "macros".to_string(),
                     name: "get_time".to_string(),
                     args,
+                    positional_args: vec![],
                 },),
                 vec![FunctionCall { name: "round".to_string(), args: HashMap::new() },],
-            )
+            ),
+            Span::default()
         )
     );
 }
@@ -527,7 +884,8 @@ fn parse_variable_tag_global_function() {
         ast[0],
         Node::VariableBlock(
             WS::default(),
-            Expr::new(ExprVal::FunctionCall(FunctionCall { name: "get_time".to_string(), args },))
+            Expr::new(ExprVal::FunctionCall(FunctionCall { name: "get_time".to_string(), args },)),
+            Span::default()
         )
     );
 }
@@ -546,7 +904,8 @@ fn parse_in_condition() {
                 lhs: Box::new(Expr::new(ExprVal::Ident("b".to_string()))),
                 rhs: Box::new(Expr::new(ExprVal::Ident("c".to_string()))),
                 negated: false,
-            }))
+            })),
+            Span::default()
         )
     );
 }
@@ -565,7 +924,8 @@ fn parse_negated_in_condition() {
                 lhs: Box::new(Expr::new(ExprVal::Ident("b".to_string()))),
                 rhs: Box::new(Expr::new(ExprVal::Ident("c".to_string()))),
                 negated: true,
-            }))
+            })),
+            Span::default()
         )
     );
 }
@@ -586,7 +946,8 @@ fn parse_variable_tag_global_function_with_filter() {
                     FunctionCall { name: "round".to_string(), args: HashMap::new() },
                     FunctionCall { name: "upper".to_string(), args: HashMap::new() },
                 ],
-            )
+            ),
+            Span::default()
         )
     );
 }
@@ -609,6 +970,7 @@ fn parse_set_tag_lit() {
                 value: Expr::new(ExprVal::String("hi".to_string())),
                 global: false,
             },
+            Span::default(),
         )
     );
 }
@@ -626,9 +988,11 @@ fn parse_set_tag_macro_call() {
                     namespace: "macros".to_string(),
                     name: "something".to_string(),
                     args: HashMap::new(),
+                    positional_args: vec![],
                 },)),
                 global: false,
             },
+            Span::default(),
         )
     );
 }
@@ -648,6 +1012,7 @@ fn parse_set_tag_fn_call() {
                 },)),
                 global: false,
             },
+            Span::default(),
         )
     );
 }
@@ -668,6 +1033,7 @@ fn parse_set_array() {
                 ])),
                 global: false,
             },
+            Span::default(),
         )
     );
 }
@@ -691,6 +1057,7 @@ fn parse_set_array_with_filter() {
                 ),
                 global: false,
             },
+            Span::default(),
         )
     );
 }
@@ -710,10 +1077,53 @@ fn parse_set_global_tag() {
                 },)),
                 global: true,
             },
+            Span::default(),
         )
     );
 }
 
+#[test]
+fn parse_set_tag_augmented_assignment() {
+    let ast =
+        parse("{% set total += price | round %}{% set_global names ~= ', ' ~ name %}").unwrap();
+    assert_eq!(
+        ast,
+        vec![
+            Node::Set(
+                WS::default(),
+                Set {
+                    key: "total".to_string(),
+                    value: Expr::new(ExprVal::Math(MathExpr {
+                        lhs: Box::new(Expr::new(ExprVal::Ident("total".to_string()))),
+                        rhs: Box::new(Expr::with_filters(
+                            ExprVal::Ident("price".to_string()),
+                            vec![FunctionCall { name: "round".to_string(), args: HashMap::new() },],
+                        )),
+                        operator: MathOperator::Add,
+                    })),
+                    global: false,
+                },
+                Span::default(),
+            ),
+            Node::Set(
+                WS::default(),
+                Set {
+                    key: "names".to_string(),
+                    value: Expr::new(ExprVal::StringConcat(StringConcat {
+                        values: vec![
+                            ExprVal::Ident("names".to_string()),
+                            ExprVal::String(", ".to_string()),
+                            ExprVal::Ident("name".to_string()),
+                        ]
+                    })),
+                    global: true,
+                },
+                Span::default(),
+            ),
+        ]
+    );
+}
+
 #[test]
 fn parse_raw_tag() {
     let ast = parse("{% raw -%}{{hey}}{%- endraw %}").unwrap();
@@ -722,7 +1132,7 @@ fn parse_raw_tag() {
     let mut end_ws = WS::default();
     end_ws.left = true;
 
-    assert_eq!(ast[0], Node::Raw(start_ws, "{{hey}}".to_string(), end_ws));
+    assert_eq!(ast[0], Node::Raw(start_ws, "{{hey}}".to_string(), end_ws, Span::default()));
 }
 
 // https://github.com/Keats/tera/issues/513
@@ -733,7 +1143,10 @@ fn parse_raw_tag_with_ws() {
     let start_ws = WS::default();
     let end_ws = WS::default();
 
-    assert_eq!(ast[0], Node::Raw(start_ws, "    yaml_test:     ".to_string(), end_ws));
+    assert_eq!(
+        ast[0],
+        Node::Raw(start_ws, "    yaml_test:     ".to_string(), end_ws, Span::default())
+    );
 }
 
 #[test]
@@ -750,9 +1163,221 @@ fn parse_filter_section_without_args() {
             start_ws,
             FilterSection {
                 filter: FunctionCall { name: "upper".to_string(), args: HashMap::new() },
-                body: vec![Node::Text("A".to_string())],
+                body: vec![Node::Text("A".to_string(), Span::default())],
+            },
+            end_ws,
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_spaceless() {
+    let ast = parse("{% spaceless -%}<p> {{ a }} </p>{% endspaceless %}").unwrap();
+    let mut start_ws = WS::default();
+    start_ws.right = true;
+
+    assert_eq!(
+        ast[0],
+        Node::Spaceless(
+            start_ws,
+            vec![
+                Node::Text("<p> ".to_string(), Span::default()),
+                Node::VariableBlock(
+                    WS::default(),
+                    Expr::new(ExprVal::Ident("a".to_string())),
+                    Span::default()
+                ),
+                Node::Text(" </p>".to_string(), Span::default()),
+            ],
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_with() {
+    let ast =
+        parse("{% with total = cart | length, empty = [] -%}{{ total }}{% endwith %}").unwrap();
+    let mut start_ws = WS::default();
+    start_ws.right = true;
+    let mut total = Expr::new(ExprVal::Ident("cart".to_string()));
+    total.filters.push(FunctionCall { name: "length".to_string(), args: HashMap::new() });
+
+    assert_eq!(
+        ast[0],
+        Node::With(
+            start_ws,
+            With {
+                assignments: vec![
+                    ("total".to_string(), total),
+                    ("empty".to_string(), Expr::new(ExprVal::Array(vec![]))),
+                ],
+                body: vec![Node::VariableBlock(
+                    WS::default(),
+                    Expr::new(ExprVal::Ident("total".to_string())),
+                    Span::default()
+                )],
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_cache() {
+    let ast = parse("{% cache 'menu' ~ lang 60 -%}{{ lang }}{% endcache %}").unwrap();
+    let key = Expr::new(ExprVal::StringConcat(StringConcat {
+        values: vec![ExprVal::String("menu".to_string()), ExprVal::Ident("lang".to_string())],
+    }));
+
+    assert_eq!(
+        ast[0],
+        Node::Cache(
+            WS { left: false, right: true },
+            Cache {
+                key,
+                ttl: Some(Expr::new(ExprVal::Int(60))),
+                body: vec![Node::VariableBlock(
+                    WS::default(),
+                    Expr::new(ExprVal::Ident("lang".to_string())),
+                    Span::default()
+                )],
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+
+    let ast = parse("{% cache key %}{% endcache %}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::Cache(
+            WS::default(),
+            Cache { key: Expr::new(ExprVal::Ident("key".to_string())), ttl: None, body: vec![] },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_custom_tags() {
+    let ast = parse("{% widget name='side', size=2 %}{%- card -%}{{ a }}{% endcard %}").unwrap();
+    let mut args = HashMap::new();
+    args.insert("name".to_string(), Expr::new(ExprVal::String("side".to_string())));
+    args.insert("size".to_string(), Expr::new(ExprVal::Int(2)));
+
+    assert_eq!(
+        ast[0],
+        Node::CustomTag(
+            WS::default(),
+            CustomTag { name: "widget".to_string(), args, body: None },
+            WS::default(),
+            Span::default(),
+        )
+    );
+    assert_eq!(
+        ast[1],
+        Node::CustomTag(
+            WS { left: true, right: true },
+            CustomTag {
+                name: "card".to_string(),
+                args: HashMap::new(),
+                body: Some(vec![Node::VariableBlock(
+                    WS::default(),
+                    Expr::new(ExprVal::Ident("a".to_string())),
+                    Span::default()
+                )]),
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_call_block() {
+    let ast = parse("{% call macros::card(title = 'Hi') -%}{{ name }}{% endcall %}").unwrap();
+    let mut args = HashMap::new();
+    args.insert("title".to_string(), Expr::new(ExprVal::String("Hi".to_string())));
+
+    assert_eq!(
+        ast[0],
+        Node::CallBlock(
+            WS { left: false, right: true },
+            CallBlock {
+                call: MacroCall {
+                    namespace: "macros".to_string(),
+                    name: "card".to_string(),
+                    args,
+                    positional_args: vec![],
+                },
+                body: vec![Node::VariableBlock(
+                    WS::default(),
+                    Expr::new(ExprVal::Ident("name".to_string())),
+                    Span::default()
+                )],
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_trans() {
+    let ast =
+        parse("{% trans %}Hello {{name}}, {{ name }} and {{- other }}{% endtrans %}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::Trans(
+            WS::default(),
+            Trans {
+                message: "Hello {{ name }}, {{ name }} and {{ other }}".to_string(),
+                plural: None,
+                count: None,
+                variables: vec![
+                    ("name".to_string(), Expr::new(ExprVal::Ident("name".to_string()))),
+                    ("other".to_string(), Expr::new(ExprVal::Ident("other".to_string()))),
+                ],
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_trans_with_plural() {
+    let ast = parse(
+        "{% trans count=items | length -%} One {{ kind }} {%- pluralize -%} {{ count }} {{ kind }}s {%- endtrans %}",
+    )
+    .unwrap();
+    let mut start_ws = WS::default();
+    start_ws.right = true;
+    let mut end_ws = WS::default();
+    end_ws.left = true;
+    let mut count = Expr::new(ExprVal::Ident("items".to_string()));
+    count.filters.push(FunctionCall { name: "length".to_string(), args: HashMap::new() });
+
+    assert_eq!(
+        ast[0],
+        Node::Trans(
+            start_ws,
+            Trans {
+                message: " One {{ kind }}".to_string(),
+                plural: Some("{{ count }} {{ kind }}s ".to_string()),
+                count: Some(count),
+                variables: vec![(
+                    "kind".to_string(),
+                    Expr::new(ExprVal::Ident("kind".to_string()))
+                )],
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -774,9 +1399,10 @@ fn parse_filter_section_with_args() {
             start_ws,
             FilterSection {
                 filter: FunctionCall { name: "upper".to_string(), args },
-                body: vec![Node::Text("A".to_string())],
+                body: vec![Node::Text("A".to_string(), Span::default())],
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -792,12 +1418,17 @@ fn parse_filter_section_preserves_ws() {
             FilterSection {
                 filter: FunctionCall { name: "upper".to_string(), args: HashMap::new() },
                 body: vec![
-                    Node::Text("  ".to_string()),
-                    Node::VariableBlock(WS::default(), Expr::new(ExprVal::Ident("a".to_string()))),
-                    Node::Text("  B  ".to_string())
+                    Node::Text("  ".to_string(), Span::default()),
+                    Node::VariableBlock(
+                        WS::default(),
+                        Expr::new(ExprVal::Ident("a".to_string())),
+                        Span::default()
+                    ),
+                    Node::Text("  B  ".to_string(), Span::default())
                 ]
             },
             WS::default(),
+            Span::default(),
         )
     );
 }
@@ -815,9 +1446,13 @@ fn parse_block() {
             start_ws,
             Block {
                 name: "hello".to_string(),
-                body: vec![Node::Super, Node::Text(" hey".to_string())],
+                body: vec![
+                    Node::Super(Span::default()),
+                    Node::Text(" hey".to_string(), Span::default())
+                ],
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -837,12 +1472,18 @@ fn parse_simple_macro_definition() {
             MacroDefinition {
                 name: "hello".to_string(),
                 args,
+                arg_order: vec!["a".to_string(), "b".to_string(), "c".to_string()],
                 body: vec![
-                    Node::Text("A: ".to_string()),
-                    Node::VariableBlock(WS::default(), Expr::new(ExprVal::Ident("a".to_string()))),
+                    Node::Text("A: ".to_string(), Span::default()),
+                    Node::VariableBlock(
+                        WS::default(),
+                        Expr::new(ExprVal::Ident("a".to_string())),
+                        Span::default()
+                    ),
                 ],
             },
             WS::default(),
+            Span::default(),
         )
     );
 }
@@ -865,10 +1506,62 @@ fn parse_value_forloop() {
                     ExprVal::Ident("items".to_string()),
                     vec![FunctionCall { name: "reverse".to_string(), args: HashMap::new() },],
                 ),
-                body: vec![Node::Text("A".to_string())],
+                condition: None,
+                sorted: false,
+                body: vec![Node::Text("A".to_string(), Span::default())],
                 empty_body: None,
             },
             end_ws,
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_forloop_with_condition() {
+    let ast = parse("{% for item in items | reverse if item.published %}A{% endfor %}").unwrap();
+
+    assert_eq!(
+        ast[0],
+        Node::Forloop(
+            WS::default(),
+            Forloop {
+                key: None,
+                value: "item".to_string(),
+                container: Expr::with_filters(
+                    ExprVal::Ident("items".to_string()),
+                    vec![FunctionCall { name: "reverse".to_string(), args: HashMap::new() },],
+                ),
+                condition: Some(Expr::new(ExprVal::Ident("item.published".to_string()))),
+                sorted: false,
+                body: vec![Node::Text("A".to_string(), Span::default())],
+                empty_body: None,
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_sorted_forloop() {
+    let ast = parse("{% for key, value in map sorted %}A{% endfor %}").unwrap();
+
+    assert_eq!(
+        ast[0],
+        Node::Forloop(
+            WS::default(),
+            Forloop {
+                key: Some("key".to_string()),
+                value: "value".to_string(),
+                container: Expr::new(ExprVal::Ident("map".to_string())),
+                condition: None,
+                sorted: true,
+                body: vec![Node::Text("A".to_string(), Span::default())],
+                empty_body: None,
+            },
+            WS::default(),
+            Span::default(),
         )
     );
 }
@@ -891,10 +1584,13 @@ fn parse_key_value_forloop() {
                     name: "get_map".to_string(),
                     args: HashMap::new(),
                 },)),
-                body: vec![Node::Text("A".to_string())],
+                condition: None,
+                sorted: false,
+                body: vec![Node::Text("A".to_string(), Span::default())],
                 empty_body: None,
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -917,10 +1613,13 @@ fn parse_value_forloop_array() {
                     Expr::new(ExprVal::Int(1)),
                     Expr::new(ExprVal::Int(2)),
                 ])),
-                body: vec![Node::Text("A".to_string())],
+                condition: None,
+                sorted: false,
+                body: vec![Node::Text("A".to_string(), Span::default())],
                 empty_body: None,
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -943,10 +1642,13 @@ fn parse_value_forloop_array_with_filter() {
                     ExprVal::Array(vec![Expr::new(ExprVal::Int(1)), Expr::new(ExprVal::Int(2)),]),
                     vec![FunctionCall { name: "reverse".to_string(), args: HashMap::new() },],
                 ),
-                body: vec![Node::Text("A".to_string())],
+                condition: None,
+                sorted: false,
+                body: vec![Node::Text("A".to_string(), Span::default())],
                 empty_body: None,
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -969,10 +1671,13 @@ fn parse_value_forloop_empty() {
                     Expr::new(ExprVal::Int(1)),
                     Expr::new(ExprVal::Int(2)),
                 ])),
-                body: vec![Node::Text("A".to_string())],
-                empty_body: Some(vec![Node::Text("B".to_string())]),
+                condition: None,
+                sorted: false,
+                body: vec![Node::Text("A".to_string(), Span::default())],
+                empty_body: Some(vec![Node::Text("B".to_string(), Span::default())]),
             },
             end_ws,
+            Span::default(),
         )
     );
 }
@@ -998,7 +1703,7 @@ fn parse_if() {
                             operator: LogicOperator::Or,
                             rhs: Box::new(Expr::new(ExprVal::Ident("admin".to_string()))),
                         })),
-                        vec![Node::Text("A ".to_string())],
+                        vec![Node::Text("A ".to_string(), Span::default())],
                     ),
                     (
                         end_ws.clone(),
@@ -1007,12 +1712,91 @@ fn parse_if() {
                             operator: LogicOperator::Gt,
                             rhs: Box::new(Expr::new(ExprVal::Int(2))),
                         })),
-                        vec![Node::Text("B".to_string())],
+                        vec![Node::Text("B".to_string(), Span::default())],
                     ),
                 ],
-                otherwise: Some((else_ws, vec![Node::Text(" C".to_string())])),
+                otherwise: Some((else_ws, vec![Node::Text(" C".to_string(), Span::default())])),
             },
             end_ws,
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_switch() {
+    let ast = parse(
+        "{% switch status %} {% case 1, 2 %}A{%- case 'b' %}B{% default -%} C{% endswitch %}",
+    )
+    .unwrap();
+    assert_eq!(
+        ast[0],
+        Node::Switch(
+            WS::default(),
+            Switch {
+                value: Expr::new(ExprVal::Ident("status".to_string())),
+                cases: vec![
+                    (
+                        WS::default(),
+                        vec![Expr::new(ExprVal::Int(1)), Expr::new(ExprVal::Int(2))],
+                        vec![Node::Text("A".to_string(), Span::default())],
+                    ),
+                    (
+                        WS { left: true, right: false },
+                        vec![Expr::new(ExprVal::String("b".to_string()))],
+                        vec![Node::Text("B".to_string(), Span::default())],
+                    ),
+                ],
+                default: Some((
+                    WS { left: false, right: true },
+                    vec![Node::Text(" C".to_string(), Span::default())]
+                )),
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_switch_without_cases() {
+    let ast = parse("{% switch a %}{% endswitch %}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::Switch(
+            WS::default(),
+            Switch {
+                value: Expr::new(ExprVal::Ident("a".to_string())),
+                cases: vec![],
+                default: None
+            },
+            WS::default(),
+            Span::default(),
+        )
+    );
+}
+
+#[test]
+fn parse_while() {
+    let ast = parse("{% while page < 3 -%}{{ page }}{%- endwhile %}").unwrap();
+    assert_eq!(
+        ast[0],
+        Node::While(
+            WS { left: false, right: true },
+            While {
+                condition: Expr::new(ExprVal::Logic(LogicExpr {
+                    lhs: Box::new(Expr::new(ExprVal::Ident("page".to_string()))),
+                    operator: LogicOperator::Lt,
+                    rhs: Box::new(Expr::new(ExprVal::Int(3))),
+                })),
+                body: vec![Node::VariableBlock(
+                    WS::default(),
+                    Expr::new(ExprVal::Ident("page".to_string())),
+                    Span::default()
+                )],
+            },
+            WS { left: true, right: false },
+            Span::default(),
         )
     );
 }
@@ -1029,10 +1813,13 @@ fn parse_break() {
                 key: None,
                 value: "item".to_string(),
                 container: Expr::new(ExprVal::Ident("items".to_string())),
-                body: vec![Node::Break(WS { left: false, right: true }),],
+                condition: None,
+                sorted: false,
+                body: vec![Node::Break(WS { left: false, right: true }, Span::default()),],
                 empty_body: None,
             },
             for_ws,
+            Span::default(),
         )
     );
 }
@@ -1049,20 +1836,59 @@ fn parse_continue() {
                 key: None,
                 value: "item".to_string(),
                 container: Expr::new(ExprVal::Ident("items".to_string())),
-                body: vec![Node::Continue(WS { left: false, right: true }),],
+                condition: None,
+                sorted: false,
+                body: vec![Node::Continue(WS { left: false, right: true }, Span::default()),],
                 empty_body: None,
             },
             for_ws,
+            Span::default(),
         )
     );
 }
 
+#[test]
+fn parse_debug() {
+    let ast = parse("{% debug %}{%- debug user.name %}").unwrap();
+    assert_eq!(
+        ast,
+        vec![
+            Node::Debug(WS::default(), None, Span::default()),
+            Node::Debug(
+                WS { left: true, right: false },
+                Some("user.name".to_string()),
+                Span::default()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn parse_do_tag() {
+    let ast = parse("{% do a %}{%- do b -%}").unwrap();
+    assert_eq!(
+        ast,
+        vec![
+            Node::Do(WS::default(), Expr::new(ExprVal::Ident("a".to_string())), Span::default()),
+            Node::Do(
+                WS { left: true, right: true },
+                Expr::new(ExprVal::Ident("b".to_string())),
+                Span::default()
+            ),
+        ]
+    );
+}
+
 #[test]
 fn parse_string_concat_can_merge() {
     let ast = parse("{{ `hello` ~ 'hey' }}").unwrap();
     assert_eq!(
         ast[0],
-        Node::VariableBlock(WS::default(), Expr::new(ExprVal::String("hellohey".to_string()))),
+        Node::VariableBlock(
+            WS::default(),
+            Expr::new(ExprVal::String("hellohey".to_string())),
+            Span::default()
+        ),
     );
 }
 #[test]
@@ -1077,7 +1903,8 @@ fn parse_string_concat() {
                     ExprVal::String("hello".to_string()),
                     ExprVal::Ident("ident".to_string()),
                 ]
-            }))
+            })),
+            Span::default()
         ),
     );
 }
@@ -1095,7 +1922,8 @@ fn parse_string_concat_multiple() {
                     ExprVal::Ident("ident".to_string()),
                     ExprVal::String("ho".to_string()),
                 ]
-            }))
+            })),
+            Span::default()
         ),
     );
 }
@@ -0,0 +1,33 @@
+use crate::parser::recovery::collect_syntax_errors;
+
+#[test]
+fn valid_template_has_no_errors() {
+    assert!(collect_syntax_errors("{% if a %}{{ a | upper }}{% endif %}").is_empty());
+}
+
+#[test]
+fn collects_errors_after_the_first_one() {
+    let errors = collect_syntax_errors("{{ a + }}\n{% if %}{% endif %}\n{{ b | }}");
+    assert_eq!(errors.len(), 3);
+    assert!(errors[0].contains("1:8"));
+    assert!(errors[1].contains("2:7"));
+    assert!(errors[2].contains("3:8"));
+    // The errors show the original template
+    assert!(errors[2].contains("{{ b | }}"));
+    assert!(errors[1].contains("{% if %}{% endif %}"));
+}
+
+#[test]
+fn keeps_sections_opened_by_invalid_tags() {
+    // The `endif` and `endfor` don't cause errors of their own
+    let errors =
+        collect_syntax_errors("{% if a == %}a{% endif %}{% for x in %}{{ x }}{% endfor %}");
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn stops_at_errors_outside_of_tags() {
+    let errors = collect_syntax_errors("{{ a + }}{% if a %}");
+    assert_eq!(errors.len(), 2);
+    assert!(errors[1].contains("expected tag or some content"));
+}
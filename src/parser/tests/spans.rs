@@ -0,0 +1,52 @@
+use crate::parser::ast::*;
+use crate::parser::parse;
+
+fn source<'a>(input: &'a str, node: &Node) -> &'a str {
+    &input[node.span().start.offset..node.span().end.offset]
+}
+
+#[test]
+fn spans_cover_whole_tags() {
+    let input = "Hello {{ name | upper }}{% include \"a.html\" %}{# hey #}{% set a = 1 -%}";
+    let ast = parse(input).unwrap();
+    assert_eq!(ast.len(), 4);
+    assert_eq!(source(input, &ast[0]), "Hello ");
+    assert_eq!(source(input, &ast[1]), "{{ name | upper }}");
+    assert_eq!(source(input, &ast[2]), "{% include \"a.html\" %}");
+    assert_eq!(source(input, &ast[3]), "{% set a = 1 -%}");
+}
+
+#[test]
+fn spans_of_nested_nodes() {
+    let input = "{% for i in items %}{% if i %}{{ i }}{% else %}-{% endif %}{% endfor %}";
+    let ast = parse(input).unwrap();
+    assert_eq!(source(input, &ast[0]), input);
+
+    let forloop = match ast[0] {
+        Node::Forloop(_, ref forloop, _, _) => forloop,
+        _ => unreachable!(),
+    };
+    assert_eq!(source(input, &forloop.body[0]), "{% if i %}{{ i }}{% else %}-{% endif %}");
+
+    let if_node = match forloop.body[0] {
+        Node::If(ref if_node, _, _) => if_node,
+        _ => unreachable!(),
+    };
+    assert_eq!(source(input, &if_node.conditions[0].2[0]), "{{ i }}");
+    assert_eq!(source(input, &if_node.otherwise.as_ref().unwrap().1[0]), "-");
+}
+
+#[test]
+fn spans_have_lines_and_columns() {
+    let input = "{% block content %}\n  héllo {{ name }}\n{% endblock content %}";
+    let ast = parse(input).unwrap();
+
+    let block = match ast[0] {
+        Node::Block(_, ref block, _, _) => block,
+        _ => unreachable!(),
+    };
+    assert_eq!(ast[0].span().start, Position { offset: 0, line: 1, column: 1 });
+    assert_eq!(ast[0].span().end, Position { offset: input.len(), line: 3, column: 23 });
+    assert_eq!(block.body[1].span().start, Position { offset: 29, line: 2, column: 9 });
+    assert_eq!(block.body[1].span().end, Position { offset: 39, line: 2, column: 19 });
+}
@@ -0,0 +1,157 @@
+use crate::parser::lexer::{Lexer, Position, TokenKind};
+
+fn tokenize(input: &str) -> Vec<(TokenKind, &str)> {
+    Lexer::new(input).tokenize().unwrap().into_iter().map(|t| (t.kind, t.value)).collect()
+}
+
+#[test]
+fn tokenize_text_only() {
+    assert_eq!(tokenize("Hello world"), vec![(TokenKind::Text, "Hello world")]);
+    assert_eq!(tokenize(""), vec![]);
+}
+
+#[test]
+fn tokenize_variable_block() {
+    assert_eq!(
+        tokenize("Hi {{- user.name | truncate(length=10) -}}!"),
+        vec![
+            (TokenKind::Text, "Hi "),
+            (TokenKind::VariableStart, "{{-"),
+            (TokenKind::Ident, "user.name"),
+            (TokenKind::Punctuation, "|"),
+            (TokenKind::Ident, "truncate"),
+            (TokenKind::Punctuation, "("),
+            (TokenKind::Ident, "length"),
+            (TokenKind::Punctuation, "="),
+            (TokenKind::Integer, "10"),
+            (TokenKind::Punctuation, ")"),
+            (TokenKind::VariableEnd, "-}}"),
+            (TokenKind::Text, "!"),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_comments_inside_tags() {
+    assert_eq!(
+        tokenize("{% set a = 1 {# one #} %}"),
+        vec![
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "set"),
+            (TokenKind::Ident, "a"),
+            (TokenKind::Punctuation, "="),
+            (TokenKind::Integer, "1"),
+            (TokenKind::Comment, "{# one #}"),
+            (TokenKind::TagEnd, "%}"),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_tags() {
+    assert_eq!(
+        tokenize(
+            "{% for i in items %}{% if i and not loop.last %}{{ i * 2.5 }}{% endif %}{% endfor %}"
+        ),
+        vec![
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "for"),
+            (TokenKind::Ident, "i"),
+            (TokenKind::Keyword, "in"),
+            (TokenKind::Ident, "items"),
+            (TokenKind::TagEnd, "%}"),
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "if"),
+            (TokenKind::Ident, "i"),
+            (TokenKind::Operator, "and"),
+            (TokenKind::Operator, "not"),
+            (TokenKind::Ident, "loop.last"),
+            (TokenKind::TagEnd, "%}"),
+            (TokenKind::VariableStart, "{{"),
+            (TokenKind::Ident, "i"),
+            (TokenKind::Operator, "*"),
+            (TokenKind::Float, "2.5"),
+            (TokenKind::VariableEnd, "}}"),
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "endif"),
+            (TokenKind::TagEnd, "%}"),
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "endfor"),
+            (TokenKind::TagEnd, "%}"),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_literals_and_macros() {
+    assert_eq!(
+        tokenize(r#"{{ "a" ~ 'b' }}{{ macros::hey(ok=true) }}"#),
+        vec![
+            (TokenKind::VariableStart, "{{"),
+            (TokenKind::String, "\"a\""),
+            (TokenKind::Operator, "~"),
+            (TokenKind::String, "'b'"),
+            (TokenKind::VariableEnd, "}}"),
+            (TokenKind::VariableStart, "{{"),
+            (TokenKind::Ident, "macros"),
+            (TokenKind::Punctuation, "::"),
+            (TokenKind::Ident, "hey"),
+            (TokenKind::Punctuation, "("),
+            (TokenKind::Ident, "ok"),
+            (TokenKind::Punctuation, "="),
+            (TokenKind::Bool, "true"),
+            (TokenKind::Punctuation, ")"),
+            (TokenKind::VariableEnd, "}}"),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_comments_raw_and_extends() {
+    assert_eq!(
+        tokenize("{# hey #}\n{% extends \"base.html\" %}\n{% raw %}{{ hello }}{% endraw %}"),
+        vec![
+            (TokenKind::Comment, "{# hey #}"),
+            (TokenKind::Text, "\n"),
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "extends"),
+            (TokenKind::String, "\"base.html\""),
+            (TokenKind::TagEnd, "%}"),
+            (TokenKind::Text, "\n"),
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "raw"),
+            (TokenKind::TagEnd, "%}"),
+            (TokenKind::Text, "{{ hello }}"),
+            (TokenKind::TagStart, "{%"),
+            (TokenKind::Keyword, "endraw"),
+            (TokenKind::TagEnd, "%}"),
+        ]
+    );
+}
+
+#[test]
+fn tokens_cover_the_whole_input_in_order() {
+    let input = "{% extends \"base\" %}\n{% block hey %}\n  {{ super() }} é {{ a is defined }}\n{% endblock hey %}";
+    let tokens = Lexer::new(input).tokenize().unwrap();
+    let mut last_end = 0;
+    for token in &tokens {
+        assert!(token.span.start.offset >= last_end);
+        assert!(input[last_end..token.span.start.offset].trim().is_empty());
+        assert_eq!(&input[token.span.start.offset..token.span.end.offset], token.value);
+        last_end = token.span.end.offset;
+    }
+    assert_eq!(last_end, input.len());
+}
+
+#[test]
+fn tokens_have_line_and_column() {
+    let tokens = Lexer::new("é\n  {{ hello }}").tokenize().unwrap();
+    let ident = tokens.iter().find(|t| t.kind == TokenKind::Ident).unwrap();
+    assert_eq!(ident.span.start, Position { offset: 8, line: 2, column: 6 });
+    assert_eq!(ident.span.end, Position { offset: 13, line: 2, column: 11 });
+}
+
+#[test]
+fn tokenize_errors_on_invalid_template() {
+    assert!(Lexer::new("{{ hello").tokenize().is_err());
+}
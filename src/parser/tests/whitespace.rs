@@ -4,7 +4,7 @@ use std::collections::HashMap;
 
 #[test]
 fn do_nothing_if_unneeded() {
-    let ast = vec![Node::Text("hey ".to_string())];
+    let ast = vec![Node::Text("hey ".to_string(), Span::default())];
     assert_eq!(remove_whitespace(ast.clone(), None), ast);
 }
 
@@ -12,15 +12,15 @@ fn do_nothing_if_unneeded() {
 fn remove_previous_ws_if_single_opening_tag_requires_it() {
     let ws = WS { left: true, right: false };
     let ast = vec![
-        Node::Text("hey ".to_string()),
-        Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string()),
+        Node::Text("hey ".to_string(), Span::default()),
+        Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string(), Span::default()),
     ];
 
     assert_eq!(
         remove_whitespace(ast.clone(), None),
         vec![
-            Node::Text("hey".to_string()), // it removed the trailing space
-            Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string()),
+            Node::Text("hey".to_string(), Span::default()), // it removed the trailing space
+            Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string(), Span::default()),
         ]
     );
 }
@@ -29,15 +29,15 @@ fn remove_previous_ws_if_single_opening_tag_requires_it() {
 fn remove_next_ws_if_single_opening_tag_requires_it() {
     let ws = WS { left: true, right: true };
     let ast = vec![
-        Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string()),
-        Node::Text("  hey".to_string()),
+        Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string(), Span::default()),
+        Node::Text("  hey".to_string(), Span::default()),
     ];
 
     assert_eq!(
         remove_whitespace(ast.clone(), None),
         vec![
-            Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string()),
-            Node::Text("hey".to_string()), // it removed the leading space
+            Node::ImportMacro(ws, "hey ".to_string(), "ho".to_string(), Span::default()),
+            Node::Text("hey".to_string(), Span::default()), // it removed the leading space
         ]
     );
 }
@@ -46,15 +46,17 @@ fn remove_next_ws_if_single_opening_tag_requires_it() {
 fn handle_ws_both_sides_for_raw_tag() {
     let start_ws = WS { left: true, right: false };
     let end_ws = WS { left: true, right: true };
-    let ast =
-        vec![Node::Raw(start_ws, "  hey ".to_string(), end_ws), Node::Text("  hey".to_string())];
+    let ast = vec![
+        Node::Raw(start_ws, "  hey ".to_string(), end_ws, Span::default()),
+        Node::Text("  hey".to_string(), Span::default()),
+    ];
 
     assert_eq!(
         remove_whitespace(ast.clone(), None),
         vec![
             // it removed only the space at the end
-            Node::Raw(start_ws, "  hey".to_string(), end_ws),
-            Node::Text("hey".to_string()),
+            Node::Raw(start_ws, "  hey".to_string(), end_ws, Span::default()),
+            Node::Text("hey".to_string(), Span::default()),
         ]
     );
 }
@@ -68,13 +70,15 @@ fn handle_ws_both_sides_for_macro_definitions() {
         MacroDefinition {
             name: "something".to_string(),
             args: HashMap::new(),
+            arg_order: vec![],
             body: vec![
-                Node::Text("\n  ".to_string()),
-                Node::Text("hey".to_string()),
-                Node::Text("  ".to_string()),
+                Node::Text("\n  ".to_string(), Span::default()),
+                Node::Text("hey".to_string(), Span::default()),
+                Node::Text("  ".to_string(), Span::default()),
             ],
         },
         end_ws,
+        Span::default(),
     )];
 
     assert_eq!(
@@ -84,9 +88,11 @@ fn handle_ws_both_sides_for_macro_definitions() {
             MacroDefinition {
                 name: "something".to_string(),
                 args: HashMap::new(),
-                body: vec![Node::Text("hey".to_string())],
+                arg_order: vec![],
+                body: vec![Node::Text("hey".to_string(), Span::default())],
             },
             end_ws,
+            Span::default(),
         ),]
     );
 }
@@ -102,13 +108,19 @@ fn handle_ws_both_sides_for_forloop_tag_and_remove_empty_node() {
                 key: None,
                 value: "item".to_string(),
                 container: Expr::new(ExprVal::Int(1)),
+                condition: None,
+                sorted: false,
                 // not valid but we don't care about it here
-                body: vec![Node::Text("   ".to_string()), Node::Text("hey   ".to_string())],
+                body: vec![
+                    Node::Text("   ".to_string(), Span::default()),
+                    Node::Text("hey   ".to_string(), Span::default()),
+                ],
                 empty_body: None,
             },
             end_ws,
+            Span::default(),
         ),
-        Node::Text("  hey".to_string()),
+        Node::Text("  hey".to_string(), Span::default()),
     ];
 
     assert_eq!(
@@ -120,13 +132,16 @@ fn handle_ws_both_sides_for_forloop_tag_and_remove_empty_node() {
                     key: None,
                     value: "item".to_string(),
                     container: Expr::new(ExprVal::Int(1)),
+                    condition: None,
+                    sorted: false,
                     // not valid but we don't care about it here
-                    body: vec![Node::Text("hey".to_string())],
+                    body: vec![Node::Text("hey".to_string(), Span::default())],
                     empty_body: None,
                 },
                 end_ws,
+                Span::default(),
             ),
-            Node::Text("hey".to_string()),
+            Node::Text("hey".to_string(), Span::default()),
         ]
     );
 }
@@ -135,61 +150,63 @@ fn handle_ws_both_sides_for_forloop_tag_and_remove_empty_node() {
 fn handle_ws_for_if_nodes() {
     let end_ws = WS { left: false, right: true };
     let ast = vec![
-        Node::Text("C ".to_string()),
+        Node::Text("C ".to_string(), Span::default()),
         Node::If(
             If {
                 conditions: vec![
                     (
                         WS { left: true, right: true },
                         Expr::new(ExprVal::Int(1)),
-                        vec![Node::Text(" a ".to_string())],
+                        vec![Node::Text(" a ".to_string(), Span::default())],
                     ),
                     (
                         WS { left: true, right: false },
                         Expr::new(ExprVal::Int(1)),
-                        vec![Node::Text(" a ".to_string())],
+                        vec![Node::Text(" a ".to_string(), Span::default())],
                     ),
                     (
                         WS { left: true, right: true },
                         Expr::new(ExprVal::Int(1)),
-                        vec![Node::Text(" a ".to_string())],
+                        vec![Node::Text(" a ".to_string(), Span::default())],
                     ),
                 ],
                 otherwise: None,
             },
             end_ws,
+            Span::default(),
         ),
-        Node::Text("  hey".to_string()),
+        Node::Text("  hey".to_string(), Span::default()),
     ];
 
     assert_eq!(
         remove_whitespace(ast.clone(), None),
         vec![
-            Node::Text("C".to_string()),
+            Node::Text("C".to_string(), Span::default()),
             Node::If(
                 If {
                     conditions: vec![
                         (
                             WS { left: true, right: true },
                             Expr::new(ExprVal::Int(1)),
-                            vec![Node::Text("a".to_string())],
+                            vec![Node::Text("a".to_string(), Span::default())],
                         ),
                         (
                             WS { left: true, right: false },
                             Expr::new(ExprVal::Int(1)),
-                            vec![Node::Text(" a".to_string())],
+                            vec![Node::Text(" a".to_string(), Span::default())],
                         ),
                         (
                             WS { left: true, right: true },
                             Expr::new(ExprVal::Int(1)),
-                            vec![Node::Text("a ".to_string())],
+                            vec![Node::Text("a ".to_string(), Span::default())],
                         ),
                     ],
                     otherwise: None,
                 },
                 end_ws,
+                Span::default(),
             ),
-            Node::Text("hey".to_string()),
+            Node::Text("hey".to_string(), Span::default()),
         ]
     );
 }
@@ -198,67 +215,69 @@ fn handle_ws_for_if_nodes() {
 fn handle_ws_for_if_nodes_with_else() {
     let end_ws = WS { left: true, right: true };
     let ast = vec![
-        Node::Text("C ".to_string()),
+        Node::Text("C ".to_string(), Span::default()),
         Node::If(
             If {
                 conditions: vec![
                     (
                         WS { left: true, right: true },
                         Expr::new(ExprVal::Int(1)),
-                        vec![Node::Text(" a ".to_string())],
+                        vec![Node::Text(" a ".to_string(), Span::default())],
                     ),
                     (
                         WS { left: true, right: false },
                         Expr::new(ExprVal::Int(1)),
-                        vec![Node::Text(" a ".to_string())],
+                        vec![Node::Text(" a ".to_string(), Span::default())],
                     ),
                     (
                         WS { left: true, right: true },
                         Expr::new(ExprVal::Int(1)),
-                        vec![Node::Text(" a ".to_string())],
+                        vec![Node::Text(" a ".to_string(), Span::default())],
                     ),
                 ],
                 otherwise: Some((
                     WS { left: true, right: true },
-                    vec![Node::Text(" a ".to_string())],
+                    vec![Node::Text(" a ".to_string(), Span::default())],
                 )),
             },
             end_ws,
+            Span::default(),
         ),
-        Node::Text("  hey".to_string()),
+        Node::Text("  hey".to_string(), Span::default()),
     ];
 
     assert_eq!(
         remove_whitespace(ast.clone(), None),
         vec![
-            Node::Text("C".to_string()),
+            Node::Text("C".to_string(), Span::default()),
             Node::If(
                 If {
                     conditions: vec![
                         (
                             WS { left: true, right: true },
                             Expr::new(ExprVal::Int(1)),
-                            vec![Node::Text("a".to_string())],
+                            vec![Node::Text("a".to_string(), Span::default())],
                         ),
                         (
                             WS { left: true, right: false },
                             Expr::new(ExprVal::Int(1)),
-                            vec![Node::Text(" a".to_string())],
+                            vec![Node::Text(" a".to_string(), Span::default())],
                         ),
                         (
                             WS { left: true, right: true },
                             Expr::new(ExprVal::Int(1)),
-                            vec![Node::Text("a".to_string())],
+                            vec![Node::Text("a".to_string(), Span::default())],
                         ),
                     ],
                     otherwise: Some((
                         WS { left: true, right: true },
-                        vec![Node::Text("a".to_string())],
+                        vec![Node::Text("a".to_string(), Span::default())],
                     )),
                 },
                 end_ws,
+                Span::default(),
             ),
-            Node::Text("hey".to_string()),
+            Node::Text("hey".to_string(), Span::default()),
         ]
     );
 }
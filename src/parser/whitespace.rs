@@ -3,10 +3,10 @@ use crate::parser::ast::*;
 macro_rules! trim_right_previous {
     ($vec: expr) => {
         if let Some(last) = $vec.pop() {
-            if let Node::Text(mut s) = last {
+            if let Node::Text(mut s, span) = last {
                 s = s.trim_end().to_string();
                 if !s.is_empty() {
-                    $vec.push(Node::Text(s));
+                    $vec.push(Node::Text(s, span));
                 }
             } else {
                 $vec.push(last);
@@ -37,33 +37,36 @@ pub fn remove_whitespace(nodes: Vec<Node>, body_ws: Option<WS>) -> Vec<Node> {
 
     for n in nodes {
         match n {
-            Node::Text(s) => {
+            Node::Text(s, span) => {
                 previous_was_text = true;
 
                 if !trim_left_next {
-                    res.push(Node::Text(s));
+                    res.push(Node::Text(s, span));
                     continue;
                 }
                 trim_left_next = false;
 
                 let new_val = s.trim_start();
                 if !new_val.is_empty() {
-                    res.push(Node::Text(new_val.to_string()));
+                    res.push(Node::Text(new_val.to_string(), span));
                 }
                 // empty text nodes will be skipped
                 continue;
             }
-            Node::VariableBlock(ws, _)
-            | Node::ImportMacro(ws, _, _)
-            | Node::Extends(ws, _)
-            | Node::Include(ws, _)
-            | Node::Set(ws, _)
-            | Node::Break(ws)
-            | Node::Continue(ws) => {
+            Node::VariableBlock(ws, _, _)
+            | Node::ImportMacro(ws, _, _, _)
+            | Node::Extends(ws, _, _)
+            | Node::DynamicExtends(ws, _, _)
+            | Node::Include(ws, _, _)
+            | Node::Set(ws, _, _)
+            | Node::Do(ws, _, _)
+            | Node::Break(ws, _)
+            | Node::Continue(ws, _)
+            | Node::Debug(ws, _, _) => {
                 trim_right_previous!(previous_was_text && ws.left, res);
                 trim_left_next = ws.right;
             }
-            Node::Raw(start_ws, ref s, end_ws) => {
+            Node::Raw(start_ws, ref s, end_ws, span) => {
                 trim_right_previous!(previous_was_text && start_ws.left, res);
                 previous_was_text = false;
                 trim_left_next = end_ws.right;
@@ -77,15 +80,53 @@ pub fn remove_whitespace(nodes: Vec<Node>, body_ws: Option<WS>) -> Vec<Node> {
                         s.trim_end()
                     };
 
-                    res.push(Node::Raw(start_ws, val.to_string(), end_ws));
+                    res.push(Node::Raw(start_ws, val.to_string(), end_ws, span));
                     continue;
                 }
             }
+            Node::Trans(start_ws, mut trans, end_ws, span) => {
+                trim_right_previous!(previous_was_text && start_ws.left, res);
+                previous_was_text = false;
+                trim_left_next = end_ws.right;
+
+                if start_ws.right {
+                    trans.message = trans.message.trim_start().to_string();
+                }
+                if end_ws.left {
+                    match trans.plural {
+                        Some(ref mut plural) => *plural = plural.trim_end().to_string(),
+                        None => trans.message = trans.message.trim_end().to_string(),
+                    }
+                }
+                res.push(Node::Trans(start_ws, trans, end_ws, span));
+                continue;
+            }
+            Node::CustomTag(start_ws, mut tag, end_ws, span) => {
+                trim_right_previous!(previous_was_text && start_ws.left, res);
+                previous_was_text = false;
+
+                match tag.body {
+                    Some(body) => {
+                        trim_left_next = end_ws.right;
+                        let body_ws = WS { left: start_ws.right, right: end_ws.left };
+                        tag.body = Some(remove_whitespace(body, Some(body_ws)));
+                    }
+                    None => trim_left_next = start_ws.right,
+                }
+                res.push(Node::CustomTag(start_ws, tag, end_ws, span));
+                continue;
+            }
             // Those nodes have a body surrounded by 2 tags
-            Node::Forloop(start_ws, _, end_ws)
-            | Node::MacroDefinition(start_ws, _, end_ws)
-            | Node::FilterSection(start_ws, _, end_ws)
-            | Node::Block(start_ws, _, end_ws) => {
+            Node::Forloop(start_ws, _, end_ws, span)
+            | Node::While(start_ws, _, end_ws, span)
+            | Node::MacroDefinition(start_ws, _, end_ws, span)
+            | Node::FilterSection(start_ws, _, end_ws, span)
+            | Node::Spaceless(start_ws, _, end_ws, span)
+            | Node::With(start_ws, _, end_ws, span)
+            | Node::Cache(start_ws, _, end_ws, span)
+            | Node::CallBlock(start_ws, _, end_ws, span)
+            | Node::Embed(start_ws, _, end_ws, span)
+            | Node::Block(start_ws, _, end_ws, span) => {
                 trim_right_previous!(previous_was_text && start_ws.left, res);
                 previous_was_text = false;
                 trim_left_next = end_ws.right;
@@ -93,28 +134,78 @@ pub fn remove_whitespace(nodes: Vec<Node>, body_ws: Option<WS>) -> Vec<Node> {
                 // let's remove ws from the bodies now and append the cleaned up node
                 let body_ws = WS { left: start_ws.right, right: end_ws.left };
                 match n {
-                    Node::Forloop(_, mut forloop, _) => {
+                    Node::Forloop(_, mut forloop, _, _) => {
                         forloop.body = remove_whitespace(forloop.body, Some(body_ws));
-                        res.push(Node::Forloop(start_ws, forloop, end_ws));
+                        res.push(Node::Forloop(start_ws, forloop, end_ws, span));
+                    }
+                    Node::While(_, mut while_loop, _, _) => {
+                        while_loop.body = remove_whitespace(while_loop.body, Some(body_ws));
+                        res.push(Node::While(start_ws, while_loop, end_ws, span));
                     }
-                    Node::MacroDefinition(_, mut macro_def, _) => {
+                    Node::MacroDefinition(_, mut macro_def, _, _) => {
                         macro_def.body = remove_whitespace(macro_def.body, Some(body_ws));
-                        res.push(Node::MacroDefinition(start_ws, macro_def, end_ws));
+                        res.push(Node::MacroDefinition(start_ws, macro_def, end_ws, span));
                     }
-                    Node::FilterSection(_, mut filter_section, _) => {
+                    Node::FilterSection(_, mut filter_section, _, _) => {
                         filter_section.body = remove_whitespace(filter_section.body, Some(body_ws));
-                        res.push(Node::FilterSection(start_ws, filter_section, end_ws));
+                        res.push(Node::FilterSection(start_ws, filter_section, end_ws, span));
                     }
-                    Node::Block(_, mut block, _) => {
+                    Node::Spaceless(_, body, _, _) => {
+                        let body = remove_whitespace(body, Some(body_ws));
+                        res.push(Node::Spaceless(start_ws, body, end_ws, span));
+                    }
+                    Node::With(_, mut with, _, _) => {
+                        with.body = remove_whitespace(with.body, Some(body_ws));
+                        res.push(Node::With(start_ws, with, end_ws, span));
+                    }
+                    Node::Cache(_, mut cache, _, _) => {
+                        cache.body = remove_whitespace(cache.body, Some(body_ws));
+                        res.push(Node::Cache(start_ws, cache, end_ws, span));
+                    }
+                    Node::CallBlock(_, mut call_block, _, _) => {
+                        call_block.body = remove_whitespace(call_block.body, Some(body_ws));
+                        res.push(Node::CallBlock(start_ws, call_block, end_ws, span));
+                    }
+                    Node::Embed(_, mut embed, _, _) => {
+                        embed.blocks = remove_whitespace(embed.blocks, Some(body_ws));
+                        res.push(Node::Embed(start_ws, embed, end_ws, span));
+                    }
+                    Node::Block(_, mut block, _, _) => {
                         block.body = remove_whitespace(block.body, Some(body_ws));
-                        res.push(Node::Block(start_ws, block, end_ws));
+                        res.push(Node::Block(start_ws, block, end_ws, span));
                     }
                     _ => unreachable!(),
                 };
                 continue;
             }
+            Node::Switch(start_ws, mut switch, end_ws, span) => {
+                trim_right_previous!(previous_was_text && start_ws.left, res);
+                previous_was_text = false;
+                trim_left_next = end_ws.right;
+
+                // The whitespace before the first case is not kept so each body is only
+                // trimmed by its own tag and the one after it
+                let mut bodies: Vec<_> = switch
+                    .cases
+                    .iter_mut()
+                    .map(|(ws, _, body)| (*ws, body))
+                    .chain(switch.default.iter_mut().map(|(ws, body)| (*ws, body)))
+                    .collect();
+                let next_lefts: Vec<_> =
+                    bodies.iter().skip(1).map(|(ws, _)| ws.left).chain(Some(end_ws.left)).collect();
+                for ((ws, body), right) in bodies.iter_mut().zip(next_lefts) {
+                    let body_ws = WS { left: ws.right, right };
+                    **body = remove_whitespace(
+                        std::mem::replace(&mut **body, Vec::new()),
+                        Some(body_ws),
+                    );
+                }
+
+                res.push(Node::Switch(start_ws, switch, end_ws, span));
+                continue;
+            }
             // The ugly one
-            Node::If(If { conditions, otherwise }, end_ws) => {
+            Node::If(If { conditions, otherwise }, end_ws, span) => {
                 trim_left_next = end_ws.right;
                 let mut new_conditions: Vec<(_, _, Vec<_>)> = Vec::with_capacity(conditions.len());
 
@@ -157,6 +248,7 @@ pub fn remove_whitespace(nodes: Vec<Node>, body_ws: Option<WS>) -> Vec<Node> {
                     res.push(Node::If(
                         If { conditions: new_conditions, otherwise: Some((else_ws, else_body)) },
                         end_ws,
+                        span,
                     ));
                     continue;
                 }
@@ -168,10 +260,10 @@ pub fn remove_whitespace(nodes: Vec<Node>, body_ws: Option<WS>) -> Vec<Node> {
                     }
                 }
 
-                res.push(Node::If(If { conditions: new_conditions, otherwise }, end_ws));
+                res.push(Node::If(If { conditions: new_conditions, otherwise }, end_ws, span));
                 continue;
             }
-            Node::Super => (),
+            Node::Super(_) => (),
         };
 
         // If we are there, that means it's not a text node and we didn't have to modify the node
@@ -185,3 +277,51 @@ pub fn remove_whitespace(nodes: Vec<Node>, body_ws: Option<WS>) -> Vec<Node> {
 
     res
 }
+
+/// Whether the node is a tag which doesn't render text directly, unlike `{{ }}` or `{% raw %}`
+fn is_tag(node: &Node) -> bool {
+    match node {
+        Node::Text(..) | Node::VariableBlock(..) | Node::Super(_) | Node::Raw(..) => false,
+        _ => true,
+    }
+}
+
+fn is_blank(node: &Node) -> bool {
+    match node {
+        Node::Text(s, _) => s.trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// Removes the text nodes made only of whitespace, like the indentation and newlines, found
+/// between two tags or between a tag and the start or end of the body it is in.
+/// The ones at the start and end of the template are kept.
+pub fn remove_whitespace_between_tags(nodes: &mut Vec<Node>, in_body: bool) {
+    let mut keep = vec![true; nodes.len()];
+    let mut start = 0;
+    while start < nodes.len() {
+        if !is_blank(&nodes[start]) {
+            start += 1;
+            continue;
+        }
+
+        let mut end = start;
+        while end < nodes.len() && is_blank(&nodes[end]) {
+            end += 1;
+        }
+        let after_tag = if start == 0 { in_body } else { is_tag(&nodes[start - 1]) };
+        let before_tag = nodes.get(end).map_or(in_body, is_tag);
+        if after_tag && before_tag {
+            keep[start..end].iter_mut().for_each(|k| *k = false);
+        }
+        start = end;
+    }
+
+    let mut keep = keep.into_iter();
+    nodes.retain(|_| keep.next().unwrap());
+    for node in nodes.iter_mut() {
+        for body in node.span_and_bodies_mut().1 {
+            remove_whitespace_between_tags(body, true);
+        }
+    }
+}
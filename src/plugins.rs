@@ -0,0 +1,356 @@
+//! Loading filters and functions from plugins: dynamic libraries found at runtime, so a
+//! deployment can add its own filters and functions without recompiling Tera.
+//!
+//! A plugin exports two C functions:
+//!
+//! - `uint32_t tera_plugin_abi_version(void)` returning `PLUGIN_ABI_VERSION`, checked first
+//! - `const PluginDeclaration *tera_plugin_declaration(void)` listing its filters and functions
+//!
+//! Values are exchanged as NUL-terminated JSON. A filter receives `{"value": ..., "args": {...}}`
+//! and a function `{"args": {...}}`. They return `true` with the JSON of their result in `output`,
+//! or `false` with an error message in `output`, which Tera frees with `free_string`.
+//!
+//! ```rust,ignore
+//! unsafe extern "C" fn shout(input: *const c_char, output: *mut *mut c_char) -> bool {
+//!     let input: Value = serde_json::from_str(CStr::from_ptr(input).to_str().unwrap()).unwrap();
+//!     let result = Value::String(input["value"].as_str().unwrap_or("").to_uppercase() + "!");
+//!     *output = CString::new(result.to_string()).unwrap().into_raw();
+//!     true
+//! }
+//!
+//! unsafe extern "C" fn free_string(s: *mut c_char) {
+//!     drop(CString::from_raw(s));
+//! }
+//!
+//! #[no_mangle]
+//! pub extern "C" fn tera_plugin_abi_version() -> u32 {
+//!     PLUGIN_ABI_VERSION
+//! }
+//!
+//! #[no_mangle]
+//! pub extern "C" fn tera_plugin_declaration() -> *const PluginDeclaration {
+//!     let filters = Box::leak(Box::new([PluginCallable {
+//!         name: b"shout\0".as_ptr() as *const c_char,
+//!         call: shout,
+//!     }]));
+//!     Box::leak(Box::new(PluginDeclaration {
+//!         filters: filters.as_ptr(),
+//!         filters_len: 1,
+//!         functions: std::ptr::null(),
+//!         functions_len: 0,
+//!         free_string,
+//!     }))
+//! }
+//! ```
+use std::collections::HashMap;
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::{CStr, CString, OsStr};
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+use libloading::{Library, Symbol};
+use serde_json::{json, Value};
+
+use crate::builtins::filters::Filter;
+use crate::builtins::functions::Function;
+use crate::errors::{Error, Result};
+use crate::tera::Tera;
+
+/// The version of the plugin interface, changed every time `PluginDeclaration` or the way
+/// filters and functions are called changes
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// A filter or function of a plugin, `input` being JSON and `output` JSON or an error message
+/// depending on what it returns
+pub type PluginCall = unsafe extern "C" fn(input: *const c_char, output: *mut *mut c_char) -> bool;
+
+/// A named filter or function of a plugin
+#[repr(C)]
+pub struct PluginCallable {
+    /// The name it is registered under, as a NUL-terminated UTF-8 string
+    pub name: *const c_char,
+    /// Called every time the filter or function is
+    pub call: PluginCall,
+}
+
+/// What a plugin adds to Tera, returned by its `tera_plugin_declaration` function.
+/// It has to stay valid as long as the plugin is loaded.
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// The filters, `filters_len` of them
+    pub filters: *const PluginCallable,
+    /// How many filters `filters` points to
+    pub filters_len: usize,
+    /// The functions, `functions_len` of them
+    pub functions: *const PluginCallable,
+    /// How many functions `functions` points to
+    pub functions_len: usize,
+    /// Frees the strings the filters and functions write in `output`
+    pub free_string: unsafe extern "C" fn(*mut c_char),
+}
+
+/// A filter or function of a loaded plugin
+struct PluginFn {
+    name: String,
+    call: PluginCall,
+    free_string: unsafe extern "C" fn(*mut c_char),
+    // Keeps the library loaded as long as one of its filters or functions is registered
+    _library: Option<Arc<Library>>,
+}
+
+impl PluginFn {
+    fn call(&self, kind: &str, input: &Value) -> Result<Value> {
+        // Unwrap is safe since we are dealing with a Value
+        let input = CString::new(input.to_string()).unwrap();
+        let mut output: *mut c_char = ptr::null_mut();
+        let ok = unsafe { (self.call)(input.as_ptr(), &mut output) };
+        if output.is_null() {
+            return Err(Error::msg(format!(
+                "{} `{}` of a plugin didn't return anything",
+                kind, self.name
+            )));
+        }
+
+        let res = unsafe { CStr::from_ptr(output) }.to_str().map(|s| s.to_string());
+        unsafe { (self.free_string)(output) };
+        let output = res.map_err(|_| {
+            Error::msg(format!("{} `{}` of a plugin returned invalid UTF-8", kind, self.name))
+        })?;
+
+        if !ok {
+            return Err(Error::msg(output));
+        }
+        serde_json::from_str(&output).map_err(|e| {
+            Error::chain(format!("{} `{}` of a plugin returned invalid JSON", kind, self.name), e)
+        })
+    }
+}
+
+struct PluginFilter(PluginFn);
+
+impl Filter for PluginFilter {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+        self.0.call("Filter", &json!({ "value": value, "args": args }))
+    }
+}
+
+struct PluginFunction(PluginFn);
+
+impl Function for PluginFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        self.0.call("Function", &json!({ "args": args }))
+    }
+}
+
+/// Reads the filters or functions of a declaration
+unsafe fn read_callables(
+    callables: *const PluginCallable,
+    len: usize,
+) -> Result<Vec<(String, PluginCall)>> {
+    if len == 0 {
+        return Ok(vec![]);
+    }
+    if callables.is_null() {
+        return Err(Error::msg(
+            "The plugin declares filters or functions but gives a null pointer",
+        ));
+    }
+
+    let mut res = Vec::with_capacity(len);
+    for callable in std::slice::from_raw_parts(callables, len) {
+        if callable.name.is_null() {
+            return Err(Error::msg("The plugin declares a filter or function without a name"));
+        }
+        let name = CStr::from_ptr(callable.name).to_str().map_err(|_| {
+            Error::msg("The plugin declares a filter or function whose name isn't valid UTF-8")
+        })?;
+        res.push((name.to_string(), callable.call));
+    }
+    Ok(res)
+}
+
+impl Tera {
+    /// Loads a plugin and registers its filters and functions, overwriting the existing ones
+    /// with the same names. See the [plugins](plugins/index.html) module for how to write one.
+    ///
+    /// ```rust,ignore
+    /// unsafe { tera.load_plugin("plugins/libcompany_filters.so")? };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialisation code and Tera trusts the plugin to follow the
+    /// interface it declares: only load plugins you trust.
+    pub unsafe fn load_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let err_wrap = |e| Error::chain(format!("Couldn't load plugin `{}`", path.display()), e);
+
+        let library = Library::new(path).map_err(|e| err_wrap(Error::msg(e)))?;
+        let version: Symbol<unsafe extern "C" fn() -> u32> = library
+            .get(b"tera_plugin_abi_version\0")
+            .map_err(|_| err_wrap(Error::msg("It isn't a Tera plugin")))?;
+        let version = version();
+        if version != PLUGIN_ABI_VERSION {
+            return Err(err_wrap(Error::msg(format!(
+                "It was built for version {} of the plugin interface but Tera uses version {}",
+                version, PLUGIN_ABI_VERSION
+            ))));
+        }
+        let declaration: Symbol<unsafe extern "C" fn() -> *const PluginDeclaration> = library
+            .get(b"tera_plugin_declaration\0")
+            .map_err(|_| err_wrap(Error::msg("It doesn't export `tera_plugin_declaration`")))?;
+        let declaration = declaration();
+        if declaration.is_null() {
+            return Err(err_wrap(Error::msg("Its declaration is a null pointer")));
+        }
+
+        self.register_plugin(&*declaration, Some(Arc::new(library))).map_err(err_wrap)
+    }
+
+    /// Loads all the plugins of a directory, the files with the extension of dynamic libraries
+    /// on this platform (`so`, `dylib` or `dll`), in alphabetical order. Returns how many were
+    /// loaded.
+    ///
+    /// # Safety
+    ///
+    /// See [Tera::load_plugin](struct.Tera.html#method.load_plugin).
+    pub unsafe fn load_plugins<P: AsRef<Path>>(&mut self, dir: P) -> Result<usize> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir).map_err(|e| {
+            Error::chain(format!("Couldn't read the plugins directory `{}`", dir.display()), e)
+        })?;
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension() == Some(OsStr::new(DLL_EXTENSION)))
+            .collect();
+        paths.sort();
+
+        for path in &paths {
+            self.load_plugin(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Registers the filters and functions of a declaration, `library` being the one it comes from
+    unsafe fn register_plugin(
+        &mut self,
+        declaration: &PluginDeclaration,
+        library: Option<Arc<Library>>,
+    ) -> Result<()> {
+        // Read everything first so an invalid declaration doesn't register half of the plugin
+        let filters = read_callables(declaration.filters, declaration.filters_len)?;
+        let functions = read_callables(declaration.functions, declaration.functions_len)?;
+
+        let plugin_fn = |name: String, call| PluginFn {
+            name,
+            call,
+            free_string: declaration.free_string,
+            _library: library.clone(),
+        };
+        for (name, call) in filters {
+            self.register_filter(&name.clone(), PluginFilter(plugin_fn(name, call)));
+        }
+        for (name, call) in functions {
+            self.register_function(&name.clone(), PluginFunction(plugin_fn(name, call)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use serde_json::{json, Value};
+
+    use super::{PluginCallable, PluginDeclaration};
+    use crate::context::Context;
+    use crate::tera::Tera;
+
+    unsafe fn read_input(input: *const c_char) -> Value {
+        serde_json::from_str(CStr::from_ptr(input).to_str().unwrap()).unwrap()
+    }
+
+    unsafe fn write_output(output: *mut *mut c_char, s: String) {
+        *output = CString::new(s).unwrap().into_raw();
+    }
+
+    unsafe extern "C" fn shout(input: *const c_char, output: *mut *mut c_char) -> bool {
+        let input = read_input(input);
+        let value = input["value"].as_str().unwrap().to_uppercase();
+        let times = input["args"]["times"].as_u64().unwrap_or(1) as usize;
+        write_output(output, json!(value + &"!".repeat(times)).to_string());
+        true
+    }
+
+    unsafe extern "C" fn answer(_input: *const c_char, output: *mut *mut c_char) -> bool {
+        write_output(output, "42".to_string());
+        true
+    }
+
+    unsafe extern "C" fn fail(_input: *const c_char, output: *mut *mut c_char) -> bool {
+        write_output(output, "No luck".to_string());
+        false
+    }
+
+    unsafe extern "C" fn free_string(s: *mut c_char) {
+        drop(CString::from_raw(s));
+    }
+
+    #[test]
+    fn can_register_plugin_filters_and_functions() {
+        let filters = [PluginCallable { name: b"shout\0".as_ptr() as *const c_char, call: shout }];
+        let functions = [
+            PluginCallable { name: b"answer\0".as_ptr() as *const c_char, call: answer },
+            PluginCallable { name: b"fail\0".as_ptr() as *const c_char, call: fail },
+        ];
+        let declaration = PluginDeclaration {
+            filters: filters.as_ptr(),
+            filters_len: filters.len(),
+            functions: functions.as_ptr(),
+            functions_len: functions.len(),
+            free_string,
+        };
+
+        let mut tera = Tera::default();
+        unsafe { tera.register_plugin(&declaration, None).unwrap() };
+        tera.add_raw_template("ok", "{{ 'hey' | shout(times=2) }} {{ answer() }}").unwrap();
+        tera.add_raw_template("fail", "{{ fail() }}").unwrap();
+
+        assert_eq!(tera.render("ok", &Context::new()).unwrap(), "HEY!! 42");
+        let err = tera.render("fail", &Context::new()).unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(std::error::Error::source(source).unwrap().to_string(), "No luck");
+    }
+
+    #[test]
+    fn errors_on_invalid_declarations() {
+        let declaration = PluginDeclaration {
+            filters: ptr::null(),
+            filters_len: 1,
+            functions: ptr::null(),
+            functions_len: 0,
+            free_string,
+        };
+        let mut tera = Tera::default();
+        assert!(unsafe { tera.register_plugin(&declaration, None) }.is_err());
+    }
+
+    #[test]
+    fn errors_on_libraries_that_arent_plugins() {
+        let mut tera = Tera::default();
+        let err = unsafe { tera.load_plugin("plugins/nope.so") }.unwrap_err();
+        assert_eq!(err.to_string(), "Couldn't load plugin `plugins/nope.so`");
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(unsafe { tera.load_plugins(dir.path()) }.unwrap(), 0);
+        assert!(unsafe { tera.load_plugins(dir.path().join("nope")) }.is_err());
+    }
+}
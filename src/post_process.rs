@@ -0,0 +1,28 @@
+//! Transforms applied to the output of every render, registered with
+//! `Tera::register_post_processor`
+
+/// What a post-processor is told about the template whose output it is given
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TemplateMeta<'a> {
+    /// The name of the template rendered
+    pub name: &'a str,
+    /// The path of the file of the template, if it was loaded from one
+    pub path: Option<&'a str>,
+    /// Whether the output of the template was escaped, as for the HTML templates by default
+    pub autoescape: bool,
+}
+
+/// A transform of the output of renders, such as injecting nonces or rewriting URLs
+pub trait PostProcessor: Sync + Send {
+    /// Returns the new output of the render
+    fn process(&self, output: &str, template: &TemplateMeta) -> String;
+}
+
+impl<F> PostProcessor for F
+where
+    F: Fn(&str, &TemplateMeta) -> String + Sync + Send,
+{
+    fn process(&self, output: &str, template: &TemplateMeta) -> String {
+        self(output, template)
+    }
+}
@@ -0,0 +1,19 @@
+//! Transforms applied to the source of every template before it is parsed, registered with
+//! `Tera::register_pre_processor`
+use crate::errors::Result;
+
+/// A transform of the source of templates, such as stripping a front-matter or adding
+/// imports to every template
+pub trait PreProcessor: Sync + Send {
+    /// Returns the new source of the template `name`, or an error preventing it from being added
+    fn process(&self, name: &str, source: &str) -> Result<String>;
+}
+
+impl<F> PreProcessor for F
+where
+    F: Fn(&str, &str) -> Result<String> + Sync + Send,
+{
+    fn process(&self, name: &str, source: &str) -> Result<String> {
+        self(name, source)
+    }
+}
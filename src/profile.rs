@@ -0,0 +1,172 @@
+//! Measuring where the time of a render goes, see `Tera::render_profiled`.
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+/// What a `ProfileEntry` measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProfileKind {
+    /// The template being rendered
+    Template,
+    /// An `{% include %}`
+    Include,
+    /// A `{% block %}`, including the parent definitions it renders with `super()`
+    Block,
+    /// A macro call
+    Macro,
+}
+
+/// The wall time and output of a template, include, block or macro call, along with the
+/// ones of what it rendered in turn.
+///
+/// The duration and bytes of an entry include the ones of its children.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileEntry {
+    /// What was rendered
+    pub kind: ProfileKind,
+    /// The name of the template, of the block or of the macro as `namespace::name`
+    pub name: String,
+    /// How long it took to render
+    pub duration: Duration,
+    /// How many bytes it output
+    pub bytes: usize,
+    /// The includes, blocks and macro calls it rendered, in order
+    pub children: Vec<ProfileEntry>,
+}
+
+/// The sum of the measures of all the entries of a profile with the same kind and name
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileTotal {
+    /// What was rendered
+    pub kind: ProfileKind,
+    /// The name of the template, of the block or of the macro as `namespace::name`
+    pub name: String,
+    /// How many times it was rendered
+    pub calls: usize,
+    /// How long it took to render, excluding its children
+    pub self_duration: Duration,
+    /// How long it took to render, including its children
+    pub duration: Duration,
+    /// How many bytes it output
+    pub bytes: usize,
+}
+
+impl ProfileEntry {
+    /// How long it took to render, excluding the time spent in its children
+    pub fn self_duration(&self) -> Duration {
+        let children: Duration = self.children.iter().map(|c| c.duration).sum();
+        self.duration.checked_sub(children).unwrap_or_default()
+    }
+
+    /// The entries of the profile summed by kind and name, the ones that took the longest
+    /// to render themselves first. Recursive calls are counted in the duration of each call.
+    pub fn totals(&self) -> Vec<ProfileTotal> {
+        let mut totals: Vec<ProfileTotal> = vec![];
+        let mut entries = vec![self];
+
+        while let Some(entry) = entries.pop() {
+            entries.extend(entry.children.iter().rev());
+            let total =
+                match totals.iter_mut().find(|t| t.kind == entry.kind && t.name == entry.name) {
+                    Some(t) => t,
+                    None => {
+                        totals.push(ProfileTotal {
+                            kind: entry.kind,
+                            name: entry.name.clone(),
+                            calls: 0,
+                            self_duration: Duration::default(),
+                            duration: Duration::default(),
+                            bytes: 0,
+                        });
+                        totals.last_mut().unwrap()
+                    }
+                };
+            total.calls += 1;
+            total.self_duration += entry.self_duration();
+            total.duration += entry.duration;
+            total.bytes += entry.bytes;
+        }
+
+        totals.sort_by_key(|t| Reverse(t.self_duration));
+        totals
+    }
+}
+
+/// Builds the profile of a render as its parts are entered and exited
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    /// The entries being rendered, with when they started
+    stack: Vec<(ProfileEntry, Instant)>,
+    root: Option<ProfileEntry>,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, kind: ProfileKind, name: &str) {
+        let entry = ProfileEntry {
+            kind,
+            name: name.to_string(),
+            duration: Duration::default(),
+            bytes: 0,
+            children: vec![],
+        };
+        self.stack.push((entry, Instant::now()));
+    }
+
+    pub fn exit(&mut self, bytes: usize) {
+        let (mut entry, start) = self.stack.pop().expect("exiting a profile entry never entered");
+        entry.duration = start.elapsed();
+        entry.bytes = bytes;
+        match self.stack.last_mut() {
+            Some((parent, _)) => parent.children.push(entry),
+            None => self.root = Some(entry),
+        }
+    }
+
+    /// The profile of the render, once the template has been exited
+    pub fn into_profile(self) -> Option<ProfileEntry> {
+        self.root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ProfileEntry, ProfileKind};
+
+    fn entry(kind: ProfileKind, name: &str, ms: u64, children: Vec<ProfileEntry>) -> ProfileEntry {
+        ProfileEntry {
+            kind,
+            name: name.to_string(),
+            duration: Duration::from_millis(ms),
+            bytes: 10,
+            children,
+        }
+    }
+
+    #[test]
+    fn can_sum_entries() {
+        let profile = entry(
+            ProfileKind::Template,
+            "page.html",
+            100,
+            vec![
+                entry(ProfileKind::Include, "nav.html", 30, vec![]),
+                entry(
+                    ProfileKind::Block,
+                    "content",
+                    60,
+                    vec![entry(ProfileKind::Include, "nav.html", 20, vec![])],
+                ),
+            ],
+        );
+        assert_eq!(profile.self_duration(), Duration::from_millis(10));
+
+        let totals = profile.totals();
+        let summary: Vec<_> = totals
+            .iter()
+            .map(|t| (t.name.as_str(), t.calls, t.self_duration.as_millis()))
+            .collect();
+        assert_eq!(summary, vec![("nav.html", 2, 50), ("content", 1, 40), ("page.html", 1, 10)]);
+        assert_eq!(totals[0].bytes, 20);
+    }
+}
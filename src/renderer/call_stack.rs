@@ -68,17 +68,31 @@ impl<'a> CallStack<'a> {
         self.stack.push(StackFrame::new_macro(name, tpl, namespace, context));
     }
 
-    pub fn push_include_frame(&mut self, name: &'a str, tpl: &'a Template) {
-        self.stack.push(StackFrame::new_include(name, tpl));
+    /// The included template doesn't see the enclosing frames nor the user context if
+    /// `isolated` is set, only the variables of `context`
+    pub fn push_include_frame(
+        &mut self,
+        name: &'a str,
+        tpl: &'a Template,
+        context: FrameContext<'a>,
+        isolated: bool,
+    ) {
+        self.stack.push(StackFrame::new_include(name, tpl, context, isolated));
+    }
+
+    pub fn push_with_frame(&mut self, context: FrameContext<'a>) {
+        let tpl = self.stack.last().expect("Stack frame").active_template;
+        self.stack.push(StackFrame::new_with(tpl, context));
     }
 
     /// Returns mutable reference to global `StackFrame`
-    /// i.e gets first stack outside current for loops
+    /// i.e gets first stack outside current for loops and with blocks
     pub fn global_frame_mut(&mut self) -> &mut StackFrame<'a> {
-        if self.current_frame().kind == FrameType::ForLoop {
+        let kind = self.current_frame().kind;
+        if kind == FrameType::ForLoop || kind == FrameType::With {
             for stack_frame in self.stack.iter_mut().rev() {
                 // walk up the parent stacks until we meet the current template
-                if stack_frame.kind != FrameType::ForLoop {
+                if stack_frame.kind != FrameType::ForLoop && stack_frame.kind != FrameType::With {
                     return stack_frame;
                 }
             }
@@ -104,7 +118,16 @@ impl<'a> CallStack<'a> {
         self.stack.pop().expect("Mistakenly popped Origin frame");
     }
 
+    /// Pops the current frame, which has to be a for loop one, and returns its loop
+    pub fn pop_for_loop(&mut self) -> ForLoop<'a> {
+        self.stack.pop().and_then(|frame| frame.for_loop).expect("Popped a non for loop frame")
+    }
+
     pub fn lookup(&self, key: &str) -> Option<Val<'a>> {
+        if key.starts_with("loop.parent.") {
+            return self.lookup_parent_loop(key);
+        }
+
         for stack_frame in self.stack.iter().rev() {
             let found = stack_frame.find_value(key);
             if found.is_some() {
@@ -116,6 +139,10 @@ impl<'a> CallStack<'a> {
             if stack_frame.kind == FrameType::Macro || stack_frame.kind == FrameType::Origin {
                 break;
             }
+            // An isolated include doesn't have access to the user context either
+            if stack_frame.kind == FrameType::IsolatedInclude {
+                return None;
+            }
         }
 
         // Not in stack frame, look in user supplied context
@@ -131,7 +158,44 @@ impl<'a> CallStack<'a> {
         None
     }
 
+    /// Looks up `loop.parent.index`-like variables in the enclosing for loops, one level up
+    /// for each `parent`
+    fn lookup_parent_loop(&self, key: &str) -> Option<Val<'a>> {
+        let mut depth = 0;
+        let mut tail = &key["loop.".len()..];
+        while tail.starts_with("parent.") {
+            depth += 1;
+            tail = &tail["parent.".len()..];
+        }
+        let key = format!("loop.{}", tail);
+
+        for stack_frame in self.stack.iter().rev() {
+            if stack_frame.for_loop.is_some() {
+                if depth == 0 {
+                    return stack_frame.find_value_in_for_loop(&key);
+                }
+                depth -= 1;
+            }
+            if stack_frame.kind == FrameType::Macro
+                || stack_frame.kind == FrameType::Origin
+                || stack_frame.kind == FrameType::IsolatedInclude
+            {
+                break;
+            }
+        }
+
+        None
+    }
+
     /// Add an assignment value (via {% set ... %} and {% set_global ... %} )
+    ///
+    /// `set` assigns in the current frame: the variable is scoped to the current iteration of
+    /// a for loop, to a `with` block, a macro call or an included template and is visible in
+    /// the rest of the template otherwise. `if` blocks don't have a frame so a `set` in it is
+    /// visible after the `endif`.
+    /// `set_global` assigns in the first frame that is not a for loop or a `with` block.
+    /// In both cases it shadows any variable with the same name from the outer frames
+    /// and the user context, without modifying them.
     pub fn add_assignment(&mut self, key: &'a str, global: bool, value: Val<'a>) {
         if global {
             self.global_frame_mut().insert(key, value);
@@ -140,6 +204,55 @@ impl<'a> CallStack<'a> {
         }
     }
 
+    /// Sets an attribute of an object assigned in the template, such as one created by the
+    /// `namespace` function (via {% set ns.attribute = ... %}).
+    /// The object is modified in the frame it was assigned in so the change outlives the
+    /// current for loop iteration.
+    pub fn set_attribute(&mut self, key: &str, attribute: &str, value: Val<'a>) -> Result<()> {
+        for stack_frame in self.stack.iter_mut().rev() {
+            if let Some(object) = stack_frame.find_value_mut(key) {
+                return match object.to_mut() {
+                    Value::Object(ref mut map) => {
+                        map.insert(attribute.to_string(), value.into_owned());
+                        Ok(())
+                    }
+                    _ => Err(Error::msg(format!(
+                        "Tried to set `{}.{}` but `{}` is not an object",
+                        key, attribute, key
+                    ))),
+                };
+            }
+
+            if stack_frame.kind == FrameType::Macro
+                || stack_frame.kind == FrameType::Origin
+                || stack_frame.kind == FrameType::IsolatedInclude
+            {
+                break;
+            }
+        }
+
+        Err(Error::msg(format!(
+            "Tried to set `{}.{}` but `{}` isn't assigned in the template: use `{{% set {} = namespace() %}}` first",
+            key, attribute, key, key
+        )))
+    }
+
+    /// The innermost for loop visible from the current frame, if any
+    pub fn current_for_loop(&self) -> Option<&ForLoop<'a>> {
+        for stack_frame in self.stack.iter().rev() {
+            if let Some(ref for_loop) = stack_frame.for_loop {
+                return Some(for_loop);
+            }
+            if stack_frame.kind == FrameType::Macro
+                || stack_frame.kind == FrameType::Origin
+                || stack_frame.kind == FrameType::IsolatedInclude
+            {
+                break;
+            }
+        }
+        None
+    }
+
     /// Breaks current for loop
     pub fn break_for_loop(&mut self) -> Result<()> {
         match self.current_frame_mut().for_loop {
@@ -201,23 +314,24 @@ impl<'a> CallStack<'a> {
     pub fn current_context_cloned(&self) -> Value {
         let mut context = HashMap::new();
 
-        // Go back the stack in reverse to see what we have access to
+        // Go back the stack in reverse to see what we have access to.
+        // Like in `lookup`, the first value found for a key shadows the ones of the outer frames
         for frame in self.stack.iter().rev() {
-            context.extend(frame.context_owned());
+            for (key, val) in frame.context_owned() {
+                context.entry(key).or_insert(val);
+            }
             if let Some(ref for_loop) = frame.for_loop {
-                context.insert(
-                    for_loop.value_name.to_string(),
-                    for_loop.get_current_value().into_owned(),
-                );
+                context
+                    .entry(for_loop.value_name.to_string())
+                    .or_insert_with(|| for_loop.get_current_value().into_owned());
                 if for_loop.is_key_value() {
-                    context.insert(
-                        for_loop.key_name.clone().unwrap(),
-                        Value::String(for_loop.get_current_key()),
-                    );
+                    context
+                        .entry(for_loop.key_name.clone().unwrap())
+                        .or_insert_with(|| for_loop.get_current_key().into_owned());
                 }
             }
-            // Macros don't have access to the user context, we're done
-            if frame.kind == FrameType::Macro {
+            // Macros and isolated includes don't have access to the user context, we're done
+            if frame.kind == FrameType::Macro || frame.kind == FrameType::IsolatedInclude {
                 return to_value(&context).unwrap();
             }
         }
@@ -29,12 +29,12 @@ pub enum ForLoopState {
 pub enum ForLoopValues<'a> {
     /// Values for an array style iteration
     Array(Val<'a>),
-    /// Values for an object style iteration
-    Object(Vec<(String, Val<'a>)>),
+    /// Values for an object style iteration, or for an array of pairs
+    Object(Vec<(Val<'a>, Val<'a>)>),
 }
 
 impl<'a> ForLoopValues<'a> {
-    pub fn current_key(&self, i: usize) -> String {
+    pub fn current_key(&self, i: usize) -> Val<'a> {
         match *self {
             ForLoopValues::Array(_) => unreachable!("No key in array list"),
             ForLoopValues::Object(ref values) => {
@@ -92,7 +92,7 @@ impl<'a> ForLoop<'a> {
         let object_values = object.as_object().unwrap();
         let mut values = Vec::with_capacity(object_values.len());
         for (k, v) in object_values {
-            values.push((k.to_string(), Cow::Borrowed(v)));
+            values.push((Cow::Owned(Value::String(k.to_string())), Cow::Borrowed(v)));
         }
 
         ForLoop {
@@ -114,7 +114,7 @@ impl<'a> ForLoop<'a> {
         };
         let mut values = Vec::with_capacity(object_values.len());
         for (k, v) in object_values {
-            values.push((k.to_string(), Cow::Owned(v)));
+            values.push((Cow::Owned(Value::String(k)), Cow::Owned(v)));
         }
 
         ForLoop {
@@ -127,12 +127,83 @@ impl<'a> ForLoop<'a> {
         }
     }
 
+    /// Destructures an array of 2 elements arrays, like `[[1, "a"], [2, "b"]]`, into
+    /// key/value pairs. Returns `None` if one of the elements isn't such an array.
+    pub fn from_pairs(key_name: &str, value_name: &str, pairs: Val<'a>) -> Option<Self> {
+        let mut values = vec![];
+        match pairs {
+            Cow::Borrowed(v) => {
+                for pair in v.as_array().expect("Is array") {
+                    match pair.as_array() {
+                        Some(p) if p.len() == 2 => {
+                            values.push((Cow::Borrowed(&p[0]), Cow::Borrowed(&p[1])))
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            Cow::Owned(Value::Array(v)) => {
+                for pair in v {
+                    match pair {
+                        Value::Array(mut p) if p.len() == 2 => {
+                            let value = p.pop().unwrap();
+                            values.push((Cow::Owned(p.pop().unwrap()), Cow::Owned(value)));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            Cow::Owned(_) => {
+                unreachable!("Tried to create a Forloop from pairs but it wasn't an array")
+            }
+        }
+
+        Some(ForLoop {
+            key_name: Some(key_name.to_string()),
+            value_name: value_name.to_string(),
+            current: 0,
+            values: ForLoopValues::Object(values),
+            kind: ForLoopKind::KeyValue,
+            state: ForLoopState::Normal,
+        })
+    }
+
+    /// Sorts the key/value pairs of an object by key, the keys being strings
+    pub fn sort_by_key(&mut self) {
+        if let ForLoopValues::Object(ref mut values) = self.values {
+            values.sort_by(|(a, _), (b, _)| a.as_str().cmp(&b.as_str()));
+        }
+    }
+
     #[inline]
     pub fn increment(&mut self) {
         self.current += 1;
         self.state = ForLoopState::Normal;
     }
 
+    /// Only keeps the values whose index is `true` in `keep` and goes back to the first one
+    pub fn retain(&mut self, keep: &[bool]) {
+        match self.values {
+            ForLoopValues::Array(ref mut values) => {
+                let kept = values
+                    .as_array()
+                    .expect("Value is array")
+                    .iter()
+                    .zip(keep)
+                    .filter(|(_, &k)| k)
+                    .map(|(v, _)| v.clone())
+                    .collect();
+                *values = Cow::Owned(Value::Array(kept));
+            }
+            ForLoopValues::Object(ref mut values) => {
+                let mut keep = keep.iter();
+                values.retain(|_| *keep.next().unwrap_or(&false));
+            }
+        }
+        self.current = 0;
+        self.state = ForLoopState::Normal;
+    }
+
     pub fn is_key_value(&self) -> bool {
         self.kind == ForLoopKind::KeyValue
     }
@@ -154,7 +225,7 @@ impl<'a> ForLoop<'a> {
 
     /// Only called in `ForLoopKind::KeyValue`
     #[inline]
-    pub fn get_current_key(&self) -> String {
+    pub fn get_current_key(&self) -> Val<'a> {
         self.values.current_key(self.current)
     }
 
@@ -8,10 +8,18 @@ mod macros;
 mod processor;
 mod stack_frame;
 
+use std::borrow::Cow;
+use std::io::Write;
+
 use self::processor::Processor;
-use crate::errors::Result;
+use crate::errors::{Error, Result};
+use crate::options::RenderOptions;
+use crate::post_process::TemplateMeta;
+use crate::profile::ProfileEntry;
 use crate::template::Template;
-use crate::tera::Tera;
+use crate::tera::{EscapeFn, Tera};
+use crate::utils::minify_html;
+use crate::warnings::Warning;
 use crate::Context;
 
 /// Given a `Tera` and reference to `Template` and a `Context`, renders text
@@ -25,34 +33,163 @@ pub struct Renderer<'a> {
     context: &'a Context,
     /// If set rendering should be escaped
     should_escape: bool,
+    /// The function escaping the output if it should be escaped
+    escape_fn: EscapeFn,
+    /// The settings of the `Tera` instance overridden for this render
+    options: RenderOptions,
 }
 
 impl<'a> Renderer<'a> {
     /// Create a new `Renderer`
     #[inline]
     pub fn new(template: &'a Template, tera: &'a Tera, context: &'a Context) -> Renderer<'a> {
-        let should_escape = tera.autoescape_suffixes.iter().any(|ext| {
-            // We prefer a `path` if set, otherwise use the `name`
-            if let Some(ref p) = template.path {
-                return p.ends_with(ext);
-            }
-            template.name.ends_with(ext)
-        });
+        // We prefer a `path` if set, otherwise use the `name`
+        let name = template.path.as_ref().unwrap_or(&template.name);
+        // The longest suffix is the most specific one
+        let suffix_escape_fn = tera
+            .suffix_escape_fns
+            .iter()
+            .filter(|(suffix, _)| name.ends_with(suffix))
+            .max_by_key(|(suffix, _)| suffix.len());
+        let should_escape = tera.autoescape_suffixes.iter().any(|ext| name.ends_with(ext));
+        let escape_fn = suffix_escape_fn.map(|(_, f)| *f).unwrap_or(*tera.get_escape_fn());
 
-        Renderer { template, tera, context, should_escape }
+        Renderer {
+            template,
+            tera,
+            context,
+            should_escape,
+            escape_fn,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Overrides some of the settings of the `Tera` instance for this render
+    pub fn with_options(mut self, options: &RenderOptions) -> Renderer<'a> {
+        if let Some(autoescape) = options.autoescape {
+            self.should_escape = autoescape;
+        }
+        self.options = options.clone();
+        self
     }
 
     /// Combines the context with the Template to generate the end result
     pub fn render(&self) -> Result<String> {
-        let output;
+        let template = self.resolve_parents(self.template, &mut vec![])?;
+        let mut processor =
+            Processor::new(&template, self.tera, self.context, self.should_escape, self.escape_fn);
+        processor.set_options(&self.options);
+
+        let output = processor.render()?;
+        Ok(self.post_process(output))
+    }
+
+    /// Combines the context with the Template and writes the end result to `write`, flushing
+    /// the output of each top-level node unless it has to be post-processed as a whole.
+    pub fn render_to(&self, mut write: impl Write) -> Result<()> {
+        let template = self.resolve_parents(self.template, &mut vec![])?;
+        let mut processor =
+            Processor::new(&template, self.tera, self.context, self.should_escape, self.escape_fn);
+        processor.set_options(&self.options);
+
+        if !self.minifies() && self.tera.post_processors.is_empty() {
+            return processor.render_to(&mut write);
+        }
+
+        let output = self.post_process(processor.render()?);
+        write.write_all(output.as_bytes()).map_err(Error::io_error)
+    }
+
+    /// Renders only the block `name` of the template
+    pub fn render_block(&self, name: &str) -> Result<String> {
+        let template = self.resolve_parents(self.template, &mut vec![])?;
+        let mut processor =
+            Processor::new(&template, self.tera, self.context, self.should_escape, self.escape_fn);
+        processor.set_options(&self.options);
+
+        let output = processor.render_named_block(name)?;
+        Ok(self.post_process(output))
+    }
+
+    /// Renders the template like `render`, also returning how long its parts took to render
+    pub fn render_profiled(&self) -> Result<(String, ProfileEntry)> {
+        let template = self.resolve_parents(self.template, &mut vec![])?;
+        let mut processor =
+            Processor::new(&template, self.tera, self.context, self.should_escape, self.escape_fn);
+        processor.set_options(&self.options);
+        processor.enable_profiling();
+
+        let output = processor.render()?;
+        // The template entry is always exited once rendered
+        Ok((self.post_process(output), processor.take_profile().unwrap()))
+    }
+
+    /// Renders the template like `render`, also returning the uses of deprecated filters and
+    /// templates met
+    pub fn render_with_warnings(&self) -> Result<(String, Vec<Warning>)> {
+        let template = self.resolve_parents(self.template, &mut vec![])?;
+        let mut processor =
+            Processor::new(&template, self.tera, self.context, self.should_escape, self.escape_fn);
+        processor.set_options(&self.options);
+        processor.enable_warnings();
+
+        let output = processor.render()?;
+        Ok((self.post_process(output), processor.take_warnings().unwrap()))
+    }
 
-        {
-            let mut processor =
-                Processor::new(self.template, self.tera, &self.context, self.should_escape);
+    /// The template with its inheritance chain completed if it, or one of its parents, extends
+    /// a template given by an expression: `{% extends layout %}`.
+    /// `children` are the templates already resolved below it, to detect circular extends.
+    fn resolve_parents(
+        &self,
+        template: &'a Template,
+        children: &mut Vec<String>,
+    ) -> Result<Cow<'a, Template>> {
+        let expr = match template.dynamic_parent {
+            Some(ref expr) => expr,
+            None => return Ok(Cow::Borrowed(template)),
+        };
 
-            output = processor.render()?;
+        let mut processor =
+            Processor::new(template, self.tera, self.context, self.should_escape, self.escape_fn);
+        processor.set_options(&self.options);
+        let name = processor.eval_parent_name(expr)?;
+
+        children.push(template.name.clone());
+        if let Some(pos) = children.iter().position(|child| child == &name) {
+            let mut chain = children.split_off(pos + 1);
+            chain.push(name.clone());
+            return Err(Error::circular_extend(name, chain));
         }
+        let parent = match self.tera.templates.get(&name) {
+            Some(parent) => parent,
+            None => return Err(Error::missing_parent(&template.name, &name)),
+        };
+        let parent = self.resolve_parents(parent, children)?;
+        Ok(Cow::Owned(self.tera.with_dynamic_parent(template, &parent)?))
+    }
+
+    /// Minifies the output of HTML templates if it is enabled and applies the post-processors
+    /// Whether the output is minified, only done for HTML templates
+    fn minifies(&self) -> bool {
+        let name = self.template.path.as_ref().unwrap_or(&self.template.name);
+        let is_html = name.ends_with(".html") || name.ends_with(".htm");
+        is_html && self.options.minify_html.unwrap_or(self.tera.minify_html)
+    }
 
-        Ok(output)
+    fn post_process(&self, mut output: String) -> String {
+        if self.minifies() {
+            output = minify_html(&output);
+        }
+
+        let meta = TemplateMeta {
+            name: &self.template.name,
+            path: self.template.path.as_ref().map(String::as_str),
+            autoescape: self.should_escape,
+        };
+        for processor in &self.tera.post_processors {
+            output = processor.process(&output, &meta);
+        }
+        output
     }
 }
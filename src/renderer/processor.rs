@@ -1,23 +1,72 @@
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Write;
+use std::time::Duration;
 
-use serde_json::{to_string_pretty, to_value, Number, Value};
+use serde_json::{to_string_pretty, to_value, Map, Number, Value};
 
 use crate::context::{ValueRender, ValueTruthy};
 use crate::errors::{Error, Result};
+use crate::hooks::HookPhase;
+use crate::i18n::fill_placeholders;
+use crate::options::{MapOrder, OutOfBounds, RenderOptions};
 use crate::parser::ast::*;
+use crate::profile::{ProfileEntry, ProfileKind, Profiler};
 use crate::renderer::call_stack::CallStack;
 use crate::renderer::for_loop::ForLoop;
 use crate::renderer::macros::MacroCollection;
-use crate::renderer::square_brackets::pull_out_square_bracket;
-use crate::renderer::stack_frame::{FrameContext, FrameType, Val};
+use crate::renderer::square_brackets::{pull_out_square_bracket, split_at_slice};
+use crate::renderer::stack_frame::{value_by_pointer, FrameContext, FrameType, Val};
 use crate::template::Template;
-use crate::tera::Tera;
+use crate::tera::{EscapeFn, Tera};
+use crate::utils::remove_spaces_between_tags;
+use crate::warnings::{Deprecation, Warning};
 use crate::Context;
 
 /// Special string indicating request to dump context
 static MAGICAL_DUMP_VAR: &str = "__tera_context";
 
+/// The function cycling through its `values` with the index of the current for loop.
+/// It needs the renderer state so it is not a regular function, but it can be overridden
+/// by registering a function with the same name.
+static CYCLE_FN: &str = "cycle";
+
+/// The function rendering the body of the `{% call %}` block calling the current macro.
+/// It can be overridden by registering a function with the same name, like `cycle`.
+static CALLER_FN: &str = "caller";
+
+/// The built-in filters taking a `locale` argument, given the one of the render options if it
+/// isn't set
+static LOCALE_FILTERS: &[&str] = &["num_format", "currency"];
+
+/// The built-in filters and functions taking a `timezone` argument, given the one of the render
+/// options if it isn't set
+static TIMEZONE_FILTERS: &[&str] = &["date"];
+static TIMEZONE_FUNCTIONS: &[&str] = &["now"];
+
+/// How many iterations a single `{% while %}` loop can do when the render options don't set
+/// `max_loop_iterations`, so a condition that never becomes falsy can't hang the render
+const MAX_WHILE_ITERATIONS: usize = 10_000;
+
+/// Sets the argument `name` to `value` if it is set and the argument is not
+fn set_default_arg(args: &mut HashMap<String, Value>, name: &str, value: &Option<String>) {
+    if let Some(ref value) = *value {
+        args.entry(name.to_string()).or_insert_with(|| Value::String(value.clone()));
+    }
+}
+
+/// Whether 2 values are equal for `==` and `{% switch %}`. Numbers are compared as floats and
+/// are never equal to a value of another type: we're not implementing JS.
+fn values_equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64() == r.as_f64(),
+        (Value::Number(_), _) | (_, Value::Number(_)) => false,
+        _ => lhs == rhs,
+    }
+}
+
 /// This will convert a Tera variable to a json pointer if it is possible by replacing
 /// the index with their evaluated stringified value
 fn evaluate_sub_variables<'a>(key: &str, call_stack: &CallStack<'a>) -> Result<String> {
@@ -69,8 +118,113 @@ fn evaluate_sub_variables<'a>(key: &str, call_stack: &CallStack<'a>) -> Result<S
         .replace("]", ""))
 }
 
+/// Applies a slice, `[1:3]`, or a negative index, `[-1]`, to an array or a string. Slices work
+/// like in Python: negative bounds count from the end and the ones out of bounds are clamped.
+/// Strings are sliced by characters and can't be indexed. `None` if the index is out of bounds.
+fn slice_value<'a>(
+    value: Val<'a>,
+    subscript: &str,
+    call_stack: &CallStack<'a>,
+) -> Result<Option<Val<'a>>> {
+    let bound = |bound: &str| -> Result<Option<i64>> {
+        if bound.is_empty() {
+            return Ok(None);
+        }
+        if let Ok(i) = bound.parse::<i64>() {
+            return Ok(Some(i));
+        }
+        match *process_path(bound, call_stack)? {
+            Value::Number(ref n) if n.is_i64() => Ok(n.as_i64()),
+            _ => Err(Error::msg(format!(
+                "The bounds of a slice have to be integers (`{}` of `[{}]`)",
+                bound, subscript
+            ))),
+        }
+    };
+
+    let (start, end) = match subscript.find(':') {
+        Some(i) => (bound(&subscript[..i])?, bound(&subscript[i + 1..])?),
+        None => {
+            let index = bound(subscript)?.unwrap();
+            let position = |len: usize| usize::try_from(len as i64 + index).ok();
+            return Ok(match value {
+                Cow::Borrowed(Value::Array(arr)) => {
+                    position(arr.len()).map(|i| Cow::Borrowed(&arr[i]))
+                }
+                Cow::Owned(Value::Array(mut arr)) => {
+                    position(arr.len()).map(|i| Cow::Owned(arr.swap_remove(i)))
+                }
+                _ => None,
+            });
+        }
+    };
+    let clamp = |bound: Option<i64>, default: usize, len: usize| match bound {
+        None => default,
+        Some(i) if i < 0 => usize::try_from(len as i64 + i).unwrap_or(0),
+        Some(i) => usize::try_from(i).unwrap_or(len).min(len),
+    };
+
+    let sliced = match *value {
+        Value::Array(ref arr) => {
+            let start = clamp(start, 0, arr.len());
+            let end = clamp(end, arr.len(), arr.len());
+            Value::Array(if start < end { arr[start..end].to_vec() } else { vec![] })
+        }
+        Value::String(ref s) => {
+            let len = s.chars().count();
+            let start = clamp(start, 0, len);
+            let end = clamp(end, len, len);
+            Value::String(s.chars().skip(start).take(end.saturating_sub(start)).collect())
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "Only arrays and strings can be sliced (tried to use `[{}]` on `{}`)",
+                subscript, value
+            )))
+        }
+    };
+    Ok(Some(Cow::Owned(sliced)))
+}
+
+/// Looks up the rest of a path, like `.name` or `[0][1:]`, in a value that was sliced
+fn process_path_in<'a>(
+    value: Val<'a>,
+    rest: &str,
+    call_stack: &CallStack<'a>,
+) -> Result<Option<Val<'a>>> {
+    let (before, slice) = match split_at_slice(rest) {
+        Some((before, subscript, after)) => (before, Some((subscript, after))),
+        None => (rest, None),
+    };
+
+    let pointer = evaluate_sub_variables(before, call_stack)?;
+    let found = match pointer.trim_start_matches('.') {
+        "" => Some(value),
+        pointer => value_by_pointer(pointer, &value),
+    };
+    match (found, slice) {
+        (Some(found), Some((subscript, after))) => match slice_value(found, subscript, call_stack)?
+        {
+            Some(sliced) => process_path_in(sliced, after, call_stack),
+            None => Ok(None),
+        },
+        (found, _) => Ok(found),
+    }
+}
+
 fn process_path<'a>(path: &str, call_stack: &CallStack<'a>) -> Result<Val<'a>> {
-    if !path.contains('[') {
+    if let Some((before, _, _)) = split_at_slice(path) {
+        let value = process_path(before, call_stack)?;
+        match process_path_in(value, &path[before.len()..], call_stack)? {
+            Some(v) => Ok(v),
+            None => Err(Error::msg(format!(
+                "Variable `{}` not found in context while rendering '{}'. \
+                 Maybe the index is out of bounds?",
+                path,
+                call_stack.active_template().name,
+            ))),
+        }
+    } else if !path.contains('[') {
         match call_stack.lookup(path) {
             Some(v) => Ok(v),
             None => Err(Error::msg(format!(
@@ -95,6 +249,83 @@ fn process_path<'a>(path: &str, call_stack: &CallStack<'a>) -> Result<Val<'a>> {
     }
 }
 
+/// Does the math between 2 numbers, `None` meaning the result is NaN
+fn number_math(l: &Number, operator: MathOperator, r: &Number) -> Result<Option<Number>> {
+    let res = match operator {
+        // Dividing integers still gives a float
+        MathOperator::Div => {
+            let ll = l.as_f64().unwrap();
+            let rr = r.as_f64().unwrap();
+            if rr == 0.0 {
+                return Err(Error::msg(format!("Tried to divide {} by zero", l)));
+            }
+            let res = ll / rr;
+            if res.is_nan() {
+                None
+            } else {
+                Number::from_f64(res)
+            }
+        }
+        _ if !l.is_f64() && !r.is_f64() => Some(int_math(l, operator, r)?),
+        _ => {
+            let ll = l.as_f64().unwrap();
+            let rr = r.as_f64().unwrap();
+            let res = match operator {
+                MathOperator::Add => ll + rr,
+                MathOperator::Sub => ll - rr,
+                MathOperator::Mul => ll * rr,
+                MathOperator::FloorDiv | MathOperator::Modulo if rr == 0.0 => {
+                    return Err(Error::msg(format!("Tried to divide {} by zero", l)));
+                }
+                MathOperator::FloorDiv => (ll / rr).floor(),
+                MathOperator::Modulo => ll % rr,
+                MathOperator::Div => unreachable!(),
+            };
+            if res.is_infinite() {
+                return Err(Error::msg(format!(
+                    "{} {} {} results in an out of bounds float",
+                    l, operator, r
+                )));
+            }
+            Number::from_f64(res)
+        }
+    };
+    Ok(res)
+}
+
+/// Does the math between 2 integers, erroring instead of overflowing.
+/// The operands can be any mix of i64 and u64 and the result is either of them.
+fn int_math(l: &Number, operator: MathOperator, r: &Number) -> Result<Number> {
+    let as_i128 = |n: &Number| match n.as_i64() {
+        Some(i) => i128::from(i),
+        None => i128::from(n.as_u64().unwrap()),
+    };
+    let (ll, rr) = (as_i128(l), as_i128(r));
+    if rr == 0 && (operator == MathOperator::FloorDiv || operator == MathOperator::Modulo) {
+        return Err(Error::msg(format!("Tried to divide {} by zero", ll)));
+    }
+
+    let res = match operator {
+        MathOperator::Add => ll.checked_add(rr),
+        MathOperator::Sub => ll.checked_sub(rr),
+        MathOperator::Mul => ll.checked_mul(rr),
+        // Rounds towards negative infinity like the float version
+        MathOperator::FloorDiv if ll % rr != 0 && (ll < 0) != (rr < 0) => Some(ll / rr - 1),
+        MathOperator::FloorDiv => Some(ll / rr),
+        MathOperator::Modulo => Some(ll % rr),
+        MathOperator::Div => unreachable!("Divisions always give a float"),
+    };
+
+    match res {
+        Some(res) if i64::try_from(res).is_ok() => Ok(Number::from(res as i64)),
+        Some(res) if u64::try_from(res).is_ok() => Ok(Number::from(res as u64)),
+        _ => Err(Error::msg(format!(
+            "{} {} {} results in an out of bounds integer",
+            ll, operator, rr
+        ))),
+    }
+}
+
 /// Processes the ast and renders the output
 pub struct Processor<'a> {
     /// The template we're trying to render
@@ -110,10 +341,35 @@ pub struct Processor<'a> {
     macros: MacroCollection<'a>,
     /// If set, rendering should be escaped
     should_escape: bool,
+    /// The function escaping the output if it should be escaped
+    escape_fn: EscapeFn,
     /// Used when super() is used in a block, to know where we are in our stack of
     /// definitions and for which block
     /// Vec<(block name, tpl_name, level)>
     blocks: Vec<(&'a str, &'a str, usize)>,
+    /// The blocks replacing the ones of the templates being included by `{% embed %}`,
+    /// with the name of those templates
+    embedded_blocks: Vec<(&'a str, &'a [Node])>,
+    /// The bodies of the `{% call %}` blocks of the macros being rendered, rendered beforehand.
+    /// `None` for the macros called in expressions
+    callers: Vec<Option<String>>,
+    /// Set when profiling the render
+    profiler: Option<Profiler>,
+    /// Set when collecting the uses of deprecated filters and templates
+    warnings: Option<Vec<Warning>>,
+    /// The span of the node being rendered, given to the render hooks and used by the warnings
+    span: Span,
+    /// What missing subscripts evaluate to
+    out_of_bounds: OutOfBounds,
+    /// The order in which the for loops iterate on maps
+    map_order: MapOrder,
+    /// The settings of this render
+    options: RenderOptions,
+    /// How many for and while loop iterations were rendered, checked against
+    /// `options.max_loop_iterations`
+    loop_iterations: usize,
+    /// How many includes and macro calls are being rendered, checked against `options.max_depth`
+    depth: usize,
 }
 
 impl<'a> Processor<'a> {
@@ -123,6 +379,7 @@ impl<'a> Processor<'a> {
         tera: &'a Tera,
         context: &'a Context,
         should_escape: bool,
+        escape_fn: EscapeFn,
     ) -> Self {
         // Gets the root template if we are rendering something with inheritance or just return
         // the template we're dealing with otherwise
@@ -141,10 +398,132 @@ impl<'a> Processor<'a> {
             call_stack,
             macros: MacroCollection::from_original_template(&template, &tera),
             should_escape,
+            escape_fn,
             blocks: Vec::new(),
+            embedded_blocks: Vec::new(),
+            callers: Vec::new(),
+            profiler: None,
+            warnings: None,
+            span: Span::default(),
+            out_of_bounds: tera.out_of_bounds,
+            map_order: tera.map_order,
+            options: RenderOptions::default(),
+            loop_iterations: 0,
+            depth: 0,
+        }
+    }
+
+    /// Overrides the settings of the `Tera` instance set in `options`
+    pub fn set_options(&mut self, options: &RenderOptions) {
+        if let Some(out_of_bounds) = options.out_of_bounds {
+            self.out_of_bounds = out_of_bounds;
+        }
+        if let Some(map_order) = options.map_order {
+            self.map_order = map_order;
+        }
+        self.options = options.clone();
+    }
+
+    /// Counts an iteration of a for or while loop, erroring if there were too many
+    fn count_loop_iteration(&mut self) -> Result<()> {
+        self.loop_iterations += 1;
+        match self.options.max_loop_iterations {
+            Some(max) if self.loop_iterations > max => Err(Error::msg(format!(
+                "Reached the maximum of {} loop iterations of the render",
+                max
+            ))),
+            _ => Ok(()),
         }
     }
 
+    /// Enters an include or a macro call, erroring if they are nested too deep.
+    /// `depth` needs to be decremented once it is rendered.
+    fn enter_nested(&mut self) -> Result<()> {
+        self.depth += 1;
+        match self.options.max_depth {
+            Some(max) if self.depth > max => Err(Error::msg(format!(
+                "Reached the maximum depth of {} nested includes and macro calls",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the time spent and the output of the templates, includes, blocks and macro
+    /// calls rendered from now on, see `take_profile`
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler::default());
+    }
+
+    /// The profile of the render, if profiling was enabled and the render succeeded
+    pub fn take_profile(&mut self) -> Option<ProfileEntry> {
+        self.profiler.take().and_then(Profiler::into_profile)
+    }
+
+    /// Collects the uses of deprecated filters and templates met from now on, see
+    /// `take_warnings`
+    pub fn enable_warnings(&mut self) {
+        self.warnings = Some(Vec::new());
+    }
+
+    /// The warnings collected, if they were enabled
+    pub fn take_warnings(&mut self) -> Option<Vec<Warning>> {
+        self.warnings.take()
+    }
+
+    /// Records a warning if they are collected and it wasn't already recorded
+    fn warn(&mut self, template: &str, span: Option<Span>, deprecation: Deprecation) {
+        if let Some(ref mut warnings) = self.warnings {
+            let warning = Warning { template: template.to_string(), span, deprecation };
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+
+    /// Warns about the template being rendered and the ones it extends if they are deprecated
+    fn warn_deprecated_templates(&mut self) {
+        if self.warnings.is_none() {
+            return;
+        }
+        let tera = self.tera;
+        let mut child = self.template;
+        if let Some(ref reason) = child.deprecated {
+            let deprecation =
+                Deprecation::Template { name: child.name.clone(), reason: reason.clone() };
+            self.warn(&child.name, None, deprecation);
+        }
+        for parent in &self.template.parents {
+            let parent = tera.get_template(parent).unwrap();
+            if let Some(ref reason) = parent.deprecated {
+                let span = child.ast.iter().find_map(|node| match *node {
+                    Node::Extends(_, _, span) | Node::DynamicExtends(_, _, span) => Some(span),
+                    _ => None,
+                });
+                let deprecation =
+                    Deprecation::Template { name: parent.name.clone(), reason: reason.clone() };
+                self.warn(&child.name, span, deprecation);
+            }
+            child = parent;
+        }
+    }
+
+    /// Calls `f`, measuring it as `name` when profiling
+    fn profiled<F>(&mut self, kind: ProfileKind, name: &str, f: F) -> Result<String>
+    where
+        F: FnOnce(&mut Self) -> Result<String>,
+    {
+        match self.profiler {
+            Some(ref mut profiler) => profiler.enter(kind, name),
+            None => return f(self),
+        }
+        let res = f(self);
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.exit(res.as_ref().map(|s| s.len()).unwrap_or(0));
+        }
+        res
+    }
+
     fn render_body(&mut self, body: &'a [Node]) -> Result<String> {
         let mut output = String::with_capacity(body.len() * 20);
 
@@ -164,6 +543,8 @@ impl<'a> Processor<'a> {
             ExprVal::Ident(ref ident) => ident,
             ExprVal::FunctionCall(FunctionCall { ref name, .. }) => name,
             ExprVal::Array(_) => "an array literal",
+            ExprVal::Map(_) => "a map literal",
+            ExprVal::Math(MathExpr { operator: MathOperator::Add, .. }) => "a sum",
             _ => return Err(Error::msg(format!(
                 "Forloop containers have to be an ident or a function call (tried to iterate on '{:?}')",
                 for_loop.container.val,
@@ -173,19 +554,21 @@ impl<'a> Processor<'a> {
         let for_loop_name = &for_loop.value;
         let for_loop_body = &for_loop.body;
         let for_loop_empty_body = &for_loop.empty_body;
+        let for_loop_condition = &for_loop.condition;
 
         let container_val = self.safe_eval_expression(&for_loop.container)?;
 
         let for_loop = match *container_val {
-            Value::Array(_) => {
-                if for_loop.key.is_some() {
-                    return Err(Error::msg(format!(
-                        "Tried to iterate using key value on variable `{}`, but it isn't an object/map",
-                        container_name,
-                    )));
-                }
-                ForLoop::from_array(&for_loop.value, container_val)
-            }
+            Value::Array(_) => match for_loop.key {
+                Some(ref key) => ForLoop::from_pairs(key, &for_loop.value, container_val)
+                    .ok_or_else(|| {
+                        Error::msg(format!(
+                            "Tried to iterate using key value on variable `{}`, but its elements aren't all arrays of 2 values",
+                            container_name,
+                        ))
+                    })?,
+                None => ForLoop::from_array(&for_loop.value, container_val),
+            },
             Value::Object(_) => {
                 if for_loop.key.is_none() {
                     return Err(Error::msg(format!(
@@ -193,7 +576,7 @@ impl<'a> Processor<'a> {
                         container_name,
                     )));
                 }
-                match container_val {
+                let mut object_loop = match container_val {
                     Cow::Borrowed(c) => {
                         ForLoop::from_object(&for_loop.key.as_ref().unwrap(), &for_loop.value, c)
                     }
@@ -202,7 +585,17 @@ impl<'a> Processor<'a> {
                         &for_loop.value,
                         c,
                     ),
+                };
+                if for_loop.sorted || self.map_order == MapOrder::Sorted {
+                    object_loop.sort_by_key();
                 }
+                object_loop
+            }
+            _ if for_loop.key.is_some() => {
+                return Err(Error::msg(format!(
+                    "Tried to iterate using key value on variable `{}`, but it isn't an object/map",
+                    container_name,
+                )));
             }
             _ => {
                 return Err(Error::msg(format!(
@@ -211,6 +604,10 @@ impl<'a> Processor<'a> {
                 )));
             }
         };
+        let for_loop = match for_loop_condition {
+            Some(condition) => self.filter_for_loop(for_loop_name, for_loop, condition)?,
+            None => for_loop,
+        };
 
         let len = for_loop.len();
         match (len, for_loop_empty_body) {
@@ -221,6 +618,7 @@ impl<'a> Processor<'a> {
 
                 let mut output = String::with_capacity(len * 20);
                 for _ in 0..len {
+                    self.count_loop_iteration()?;
                     output.push_str(&self.render_body(&for_loop_body)?);
 
                     if self.call_stack.should_break_for_loop() {
@@ -237,6 +635,47 @@ impl<'a> Processor<'a> {
         }
     }
 
+    /// The body is rendered in the current scope so the assignments it makes can change the
+    /// condition. Without `max_loop_iterations`, a loop still fails after `MAX_WHILE_ITERATIONS`.
+    fn render_while(&mut self, while_loop: &'a While) -> Result<String> {
+        let mut output = String::new();
+        let mut iterations = 0;
+        while self.eval_as_bool(&while_loop.condition)? {
+            iterations += 1;
+            if self.options.max_loop_iterations.is_none() && iterations > MAX_WHILE_ITERATIONS {
+                return Err(Error::msg(format!(
+                    "Reached the maximum of {} iterations of a while loop, its condition is probably never false",
+                    MAX_WHILE_ITERATIONS
+                )));
+            }
+            self.count_loop_iteration()?;
+            output.push_str(&self.render_body(&while_loop.body)?);
+        }
+
+        Ok(output)
+    }
+
+    /// Only keeps the elements of the loop matching its `if` condition, which is evaluated
+    /// with the loop variables of each element
+    fn filter_for_loop(
+        &mut self,
+        name: &'a str,
+        for_loop: ForLoop<'a>,
+        condition: &'a Expr,
+    ) -> Result<ForLoop<'a>> {
+        let len = for_loop.len();
+        let mut keep = Vec::with_capacity(len);
+        self.call_stack.push_for_loop_frame(name, for_loop);
+        for _ in 0..len {
+            keep.push(self.eval_as_bool(condition)?);
+            self.call_stack.increment_for_loop()?;
+        }
+
+        let mut for_loop = self.call_stack.pop_for_loop();
+        for_loop.retain(&keep);
+        Ok(for_loop)
+    }
+
     fn render_if_node(&mut self, if_node: &'a If) -> Result<String> {
         for &(_, ref expr, ref body) in &if_node.conditions {
             if self.eval_as_bool(expr)? {
@@ -251,6 +690,25 @@ impl<'a> Processor<'a> {
         Ok(String::new())
     }
 
+    /// Renders the body of the first case with a value equal to the one of the switch, or the
+    /// default one. The values of the cases are only evaluated until one matches.
+    fn render_switch(&mut self, switch: &'a Switch) -> Result<String> {
+        let value = self.safe_eval_expression(&switch.value)?.into_owned();
+        for (_, values, body) in &switch.cases {
+            for case in values {
+                if values_equal(&value, &*self.safe_eval_expression(case)?) {
+                    return self.render_body(body);
+                }
+            }
+        }
+
+        if let Some((_, ref body)) = switch.default {
+            return self.render_body(body);
+        }
+
+        Ok(String::new())
+    }
+
     /// The way inheritance work is that the top parent will be rendered by the renderer so for blocks
     /// we want to look from the bottom (`level = 0`, the template the user is actually rendering)
     /// to the top (the base template).
@@ -263,6 +721,20 @@ impl<'a> Processor<'a> {
                 .unwrap(),
         };
 
+        // Embedded templates have their blocks replaced by the ones of the `{% embed %}`
+        if let Some(&(embedded, blocks)) = self.embedded_blocks.last() {
+            if level == 0 && embedded == level_template.name {
+                for node in blocks {
+                    match *node {
+                        Node::Block(_, ref b, _, _) if b.name == block.name => {
+                            return self.render_body(&b.body);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+
         let blocks_definitions = &level_template.blocks_definitions;
 
         // Can we find this one block in these definitions? If so render it
@@ -334,7 +806,15 @@ impl<'a> Processor<'a> {
                 }
                 Cow::Owned(Value::Array(values))
             }
+            ExprVal::Map(ref entries) => {
+                let mut map = Map::with_capacity(entries.len());
+                for (key, v) in entries {
+                    map.insert(key.clone(), self.eval_expression(v)?.into_owned());
+                }
+                Cow::Owned(Value::Object(map))
+            }
             ExprVal::In(ref in_cond) => Cow::Owned(Value::Bool(self.eval_in_condition(in_cond)?)),
+            ExprVal::Coalesce(ref values) => self.eval_coalesce(values)?,
             ExprVal::String(ref val) => {
                 needs_escape = true;
                 Cow::Owned(Value::String(val.to_string()))
@@ -371,6 +851,13 @@ impl<'a> Processor<'a> {
             ExprVal::Int(val) => Cow::Owned(Value::Number(val.into())),
             ExprVal::Float(val) => Cow::Owned(Value::Number(Number::from_f64(val).unwrap())),
             ExprVal::Bool(val) => Cow::Owned(Value::Bool(val)),
+            ExprVal::Null => {
+                if expr.has_default_filter() {
+                    self.get_default_value(expr)?
+                } else {
+                    Cow::Owned(Value::Null)
+                }
+            }
             ExprVal::Ident(ref ident) => {
                 needs_escape = ident != MAGICAL_DUMP_VAR;
                 // Negated idents are special cased as `not undefined_ident` should not
@@ -386,10 +873,16 @@ impl<'a> Processor<'a> {
                     Err(e) => {
                         if expr.has_default_filter() {
                             self.get_default_value(expr)?
+                        } else if self.is_missing_optional(ident)
+                            || self.is_missing_subscript(ident)
+                            || self.options.strict == Some(false)
+                        {
+                            Cow::Owned(Value::Null)
                         } else {
                             if !expr.negated {
                                 return Err(e);
                             }
+                            log!(debug, "Undefined `not {}` evaluated as true: {}", ident, e);
                             // A negative undefined ident is !false so truthy
                             return Ok(Cow::Owned(Value::Bool(true)));
                         }
@@ -400,10 +893,21 @@ impl<'a> Processor<'a> {
                 self.eval_tera_fn_call(fn_call, &mut needs_escape)?
             }
             ExprVal::MacroCall(ref macro_call) => {
-                Cow::Owned(Value::String(self.eval_macro_call(macro_call)?))
+                Cow::Owned(Value::String(self.eval_macro_call(macro_call, None)?))
             }
             ExprVal::Test(ref test) => Cow::Owned(Value::Bool(self.eval_test(test)?)),
             ExprVal::Logic(_) => Cow::Owned(Value::Bool(self.eval_as_bool(expr)?)),
+            ExprVal::Math(MathExpr { ref lhs, ref rhs, operator: MathOperator::Add }) => {
+                match self.eval_add(lhs, rhs) {
+                    Ok(Some(value)) => Cow::Owned(value),
+                    Ok(None) => match self.eval_as_number(&expr.val) {
+                        Ok(Some(n)) => Cow::Owned(Value::Number(n)),
+                        Ok(None) => Cow::Owned(Value::String("NaN".to_owned())),
+                        Err(e) => return Err(Error::msg(e)),
+                    },
+                    Err(e) => return Err(Error::msg(e)),
+                }
+            }
             ExprVal::Math(_) => match self.eval_as_number(&expr.val) {
                 Ok(Some(n)) => Cow::Owned(Value::Number(n)),
                 Ok(None) => Cow::Owned(Value::String("NaN".to_owned())),
@@ -425,14 +929,61 @@ impl<'a> Processor<'a> {
 
         // Checks if it's a string and we need to escape it (if the last filter is `safe` we don't)
         if self.should_escape && needs_escape && res.is_string() && !expr.is_marked_safe() {
-            res = Cow::Owned(
-                to_value(self.tera.get_escape_fn()(res.as_str().unwrap())).map_err(Error::json)?,
-            );
+            res =
+                Cow::Owned(to_value((self.escape_fn)(res.as_str().unwrap())).map_err(Error::json)?);
         }
 
         Ok(res)
     }
 
+    /// Evaluates `a ?? b ?? c` to the first operand that is defined and not null.
+    /// The operands are evaluated lazily and undefined variables are skipped without erroring,
+    /// except for the last one.
+    fn eval_coalesce(&mut self, values: &'a [Expr]) -> Result<Val<'a>> {
+        let (last, rest) = values.split_last().unwrap();
+        for value in rest {
+            if let ExprVal::Ident(ref ident) = value.val {
+                match self.lookup_ident(ident) {
+                    Ok(val) if !val.is_null() => (),
+                    _ => continue,
+                }
+            }
+            let res = self.eval_expression(value)?;
+            if !res.is_null() {
+                return Ok(res);
+            }
+        }
+
+        self.eval_expression(last)
+    }
+
+    /// `+` on arrays and objects: concatenates or merges them, `None` for other operands
+    fn eval_add(&mut self, lhs: &'a Expr, rhs: &'a Expr) -> Result<Option<Value>> {
+        let l = self.safe_eval_expression(lhs)?;
+        let r = self.safe_eval_expression(rhs)?;
+        let res = match (l.into_owned(), &*r) {
+            (Value::Number(ref l), Value::Number(ref r)) => {
+                number_math(l, MathOperator::Add, r)?.map(Value::Number)
+            }
+            (Value::Array(mut l), Value::Array(ref r)) => {
+                l.extend(r.iter().cloned());
+                Some(Value::Array(l))
+            }
+            (Value::Object(mut l), Value::Object(ref r)) => {
+                l.extend(r.iter().map(|(k, v)| (k.clone(), v.clone())));
+                Some(Value::Object(l))
+            }
+            (l @ Value::Array(_), _) | (l @ Value::Object(_), _) => {
+                return Err(Error::msg(format!(
+                    "Tried to add {} to {}: arrays can only be added to arrays and objects to objects",
+                    r, l
+                )))
+            }
+            _ => None,
+        };
+        Ok(res)
+    }
+
     /// Render an expression and never escape its result
     fn safe_eval_expression(&mut self, expr: &'a Expr) -> Result<Val<'a>> {
         let should_escape = self.should_escape;
@@ -445,8 +996,15 @@ impl<'a> Processor<'a> {
     /// Evaluate a set tag and add the value to the right context
     fn eval_set(&mut self, set: &'a Set) -> Result<()> {
         let assigned_value = self.safe_eval_expression(&set.value)?;
-        self.call_stack.add_assignment(&set.key[..], set.global, assigned_value);
-        Ok(())
+        match set.key.find('.') {
+            Some(dot) => {
+                self.call_stack.set_attribute(&set.key[..dot], &set.key[dot + 1..], assigned_value)
+            }
+            None => {
+                self.call_stack.add_assignment(&set.key[..], set.global, assigned_value);
+                Ok(())
+            }
+        }
     }
 
     fn eval_test(&mut self, test: &'a Test) -> Result<bool> {
@@ -459,7 +1017,10 @@ impl<'a> Processor<'a> {
                 .push(self.safe_eval_expression(arg).map_err(err_wrap)?.clone().into_owned());
         }
 
-        let found = self.lookup_ident(&test.ident).map(|found| found.clone().into_owned()).ok();
+        let found = match test.call {
+            Some(ref call) => Some(self.eval_tera_fn_call(call, &mut false)?.into_owned()),
+            None => self.lookup_ident(&test.ident).map(|found| found.clone().into_owned()).ok(),
+        };
 
         let result = tester_fn.test(found.as_ref(), &tester_args).map_err(err_wrap)?;
         if test.negated {
@@ -474,6 +1035,23 @@ impl<'a> Processor<'a> {
         function_call: &'a FunctionCall,
         needs_escape: &mut bool,
     ) -> Result<Val<'a>> {
+        if function_call.name == CYCLE_FN && !self.tera.functions.contains_key(CYCLE_FN) {
+            return self.eval_cycle(function_call);
+        }
+        if function_call.name == CALLER_FN && !self.tera.functions.contains_key(CALLER_FN) {
+            return match self.callers.last() {
+                Some(Some(body)) => {
+                    // The body was escaped when rendered
+                    *needs_escape = false;
+                    Ok(Cow::Owned(Value::String(body.clone())))
+                }
+                _ => Err(Error::call_function(
+                    CALLER_FN,
+                    Error::msg("Function `caller` can only be used in a macro called by a `{% call %}` block"),
+                )),
+            };
+        }
+
         let tera_fn = self.tera.get_function(&function_call.name)?;
         *needs_escape = !tera_fn.is_safe();
 
@@ -486,11 +1064,48 @@ impl<'a> Processor<'a> {
                 self.safe_eval_expression(expr).map_err(err_wrap)?.clone().into_owned(),
             );
         }
+        if TIMEZONE_FUNCTIONS.contains(&function_call.name.as_str()) {
+            set_default_arg(&mut args, "timezone", &self.options.timezone);
+        }
 
         Ok(Cow::Owned(tera_fn.call(&args).map_err(err_wrap)?))
     }
 
-    fn eval_macro_call(&mut self, macro_call: &'a MacroCall) -> Result<String> {
+    fn eval_cycle(&mut self, function_call: &'a FunctionCall) -> Result<Val<'a>> {
+        let err_wrap = |e| Error::call_function(CYCLE_FN, e);
+        let values = match function_call.args.get("values") {
+            Some(expr) => self.safe_eval_expression(expr).map_err(err_wrap)?,
+            None => {
+                return Err(err_wrap(Error::msg(
+                    "Function `cycle` didn't receive a `values` argument",
+                )))
+            }
+        };
+        let values = match values.as_array() {
+            Some(values) if !values.is_empty() => values,
+            _ => {
+                return Err(err_wrap(Error::msg(format!(
+                "Function `cycle` received values={} but `values` can only be a non-empty array",
+                values
+            ))))
+            }
+        };
+        let index = match self.call_stack.current_for_loop() {
+            Some(for_loop) => for_loop.current,
+            None => {
+                return Err(err_wrap(Error::msg("Function `cycle` can only be used in a for loop")))
+            }
+        };
+
+        Ok(Cow::Owned(values[index % values.len()].clone()))
+    }
+
+    /// Renders a macro, `caller` being the rendered body of the `{% call %}` block calling it
+    fn eval_macro_call(
+        &mut self,
+        macro_call: &'a MacroCall,
+        caller: Option<String>,
+    ) -> Result<String> {
         let active_template_name = if let Some(block) = self.blocks.last() {
             block.1
         } else if self.template.name != self.template_root.name {
@@ -505,11 +1120,32 @@ impl<'a> Processor<'a> {
             &macro_call.name[..],
         )?;
 
+        if macro_call.positional_args.len() > macro_definition.arg_order.len() {
+            return Err(Error::msg(format!(
+                "Macro `{}` takes {} argument(s) but {} positional argument(s) were given",
+                macro_call.name,
+                macro_definition.arg_order.len(),
+                macro_call.positional_args.len()
+            )));
+        }
+
+        // Positional arguments are matched in order to the declared arguments
+        let mut args: HashMap<&str, &'a Expr> =
+            macro_call.args.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        for (arg_name, expr) in macro_definition.arg_order.iter().zip(&macro_call.positional_args) {
+            if args.insert(arg_name.as_str(), expr).is_some() {
+                return Err(Error::msg(format!(
+                    "Macro `{}` got the argument `{}` both by position and by name",
+                    macro_call.name, arg_name
+                )));
+            }
+        }
+
         let mut frame_context = FrameContext::with_capacity(macro_definition.args.len());
 
         // First the default arguments
         for (arg_name, default_value) in &macro_definition.args {
-            let value = match macro_call.args.get(arg_name) {
+            let value = match args.get(arg_name.as_str()) {
                 Some(val) => self.safe_eval_expression(val)?,
                 None => match *default_value {
                     Some(ref val) => self.safe_eval_expression(val)?,
@@ -524,6 +1160,7 @@ impl<'a> Processor<'a> {
             frame_context.insert(&arg_name, value);
         }
 
+        self.enter_nested()?;
         self.call_stack.push_macro_frame(
             &macro_call.namespace,
             &macro_call.name,
@@ -531,9 +1168,14 @@ impl<'a> Processor<'a> {
             self.tera.get_template(macro_template_name)?,
         );
 
-        let output = self.render_body(&macro_definition.body)?;
+        let name = format!("{}::{}", macro_call.namespace, macro_call.name);
+        self.callers.push(caller);
+        let output =
+            self.profiled(ProfileKind::Macro, &name, |p| p.render_body(&macro_definition.body))?;
+        self.callers.pop();
 
         self.call_stack.pop();
+        self.depth -= 1;
 
         Ok(output)
     }
@@ -556,67 +1198,69 @@ impl<'a> Processor<'a> {
                 self.safe_eval_expression(expr).map_err(err_wrap)?.clone().into_owned(),
             );
         }
+        if LOCALE_FILTERS.contains(&fn_call.name.as_str()) {
+            set_default_arg(&mut args, "locale", &self.options.locale);
+        } else if TIMEZONE_FILTERS.contains(&fn_call.name.as_str()) {
+            set_default_arg(&mut args, "timezone", &self.options.timezone);
+        }
 
-        Ok(Cow::Owned(filter_fn.filter(&value, &args).map_err(err_wrap)?))
+        let hooks = self.tera.render_hooks.as_ref().map(|h| &**h);
+        let template = &self.call_stack.active_template().name;
+        if self.warnings.is_some() {
+            if let Some(replacement) = self.tera.deprecated_filters.get(&fn_call.name) {
+                let deprecation = Deprecation::Filter {
+                    name: fn_call.name.clone(),
+                    replacement: replacement.clone(),
+                };
+                self.warn(template, Some(self.span), deprecation);
+            }
+        }
+        if let Some(hooks) = hooks {
+            hooks.on_filter(HookPhase::Enter, &fn_call.name, &self.span, template);
+        }
+        let res = filter_fn.filter(value, &args);
+        if let Some(hooks) = hooks {
+            hooks.on_filter(HookPhase::Exit, &fn_call.name, &self.span, template);
+        }
+
+        Ok(Cow::Owned(res.map_err(err_wrap)?))
     }
 
     fn eval_as_bool(&mut self, bool_expr: &'a Expr) -> Result<bool> {
         let res = match bool_expr.val {
-            ExprVal::Logic(LogicExpr { ref lhs, ref rhs, ref operator }) => {
-                match *operator {
-                    LogicOperator::Or => self.eval_as_bool(lhs)? || self.eval_as_bool(rhs)?,
-                    LogicOperator::And => self.eval_as_bool(lhs)? && self.eval_as_bool(rhs)?,
-                    LogicOperator::Gt
-                    | LogicOperator::Gte
-                    | LogicOperator::Lt
-                    | LogicOperator::Lte => {
-                        let l = self.eval_expr_as_number(lhs)?;
-                        let r = self.eval_expr_as_number(rhs)?;
-                        let (ll, rr) = match (l, r) {
-                            (Some(nl), Some(nr)) => (nl, nr),
-                            _ => return Err(Error::msg("Comparison to NaN")),
-                        };
-
-                        match *operator {
-                            LogicOperator::Gte => ll.as_f64().unwrap() >= rr.as_f64().unwrap(),
-                            LogicOperator::Gt => ll.as_f64().unwrap() > rr.as_f64().unwrap(),
-                            LogicOperator::Lte => ll.as_f64().unwrap() <= rr.as_f64().unwrap(),
-                            LogicOperator::Lt => ll.as_f64().unwrap() < rr.as_f64().unwrap(),
-                            _ => unreachable!(),
-                        }
+            ExprVal::Logic(LogicExpr { ref lhs, ref rhs, ref operator }) => match *operator {
+                LogicOperator::Or => self.eval_as_bool(lhs)? || self.eval_as_bool(rhs)?,
+                LogicOperator::And => self.eval_as_bool(lhs)? && self.eval_as_bool(rhs)?,
+                LogicOperator::Gt | LogicOperator::Gte | LogicOperator::Lt | LogicOperator::Lte => {
+                    let ordering = self.eval_ordering(lhs, rhs)?;
+
+                    match *operator {
+                        LogicOperator::Gte => ordering != Ordering::Less,
+                        LogicOperator::Gt => ordering == Ordering::Greater,
+                        LogicOperator::Lte => ordering != Ordering::Greater,
+                        LogicOperator::Lt => ordering == Ordering::Less,
+                        _ => unreachable!(),
                     }
-                    LogicOperator::Eq | LogicOperator::NotEq => {
-                        let mut lhs_val = self.eval_expression(lhs)?;
-                        let mut rhs_val = self.eval_expression(rhs)?;
-
-                        // Monomorphize number vals.
-                        if lhs_val.is_number() || rhs_val.is_number() {
-                            // We're not implementing JS so can't compare things of different types
-                            if !lhs_val.is_number() || !rhs_val.is_number() {
-                                return Ok(false);
-                            }
-
-                            lhs_val = Cow::Owned(Value::Number(
-                                Number::from_f64(lhs_val.as_f64().unwrap()).unwrap(),
-                            ));
-                            rhs_val = Cow::Owned(Value::Number(
-                                Number::from_f64(rhs_val.as_f64().unwrap()).unwrap(),
-                            ));
-                        }
-
-                        match *operator {
-                            LogicOperator::Eq => *lhs_val == *rhs_val,
-                            LogicOperator::NotEq => *lhs_val != *rhs_val,
-                            _ => unreachable!(),
-                        }
+                }
+                LogicOperator::Eq | LogicOperator::NotEq => {
+                    let lhs_val = self.safe_eval_expression(lhs)?;
+                    let rhs_val = self.safe_eval_expression(rhs)?;
+                    let equal = values_equal(&lhs_val, &rhs_val);
+                    if *operator == LogicOperator::Eq {
+                        equal
+                    } else {
+                        !equal
                     }
                 }
-            }
-            ExprVal::Ident(_) => {
-                let mut res = self
-                    .eval_expression(&bool_expr)
-                    .unwrap_or(Cow::Owned(Value::Bool(false)))
-                    .is_truthy();
+            },
+            ExprVal::Ident(ref ident) => {
+                let mut res = match self.eval_expression(bool_expr) {
+                    Ok(val) => val.is_truthy(),
+                    Err(e) => {
+                        log!(debug, "Undefined `{}` evaluated as false: {}", ident, e);
+                        false
+                    }
+                };
                 if bool_expr.negated {
                     res = !res;
                 }
@@ -631,28 +1275,27 @@ impl<'a> Processor<'a> {
             ExprVal::In(ref in_cond) => self.eval_in_condition(&in_cond)?,
             ExprVal::Test(ref test) => self.eval_test(test)?,
             ExprVal::Bool(val) => val,
+            ExprVal::Null => false,
             ExprVal::String(ref string) => !string.is_empty(),
-            ExprVal::FunctionCall(ref fn_call) => {
-                let v = self.eval_tera_fn_call(fn_call, &mut false)?;
-                match v.as_bool() {
-                    Some(val) => val,
-                    None => {
-                        return Err(Error::msg(format!(
-                            "Function `{}` was used in a logic operation but is not returning a bool",
-                            fn_call.name,
-                        )));
-                    }
+            ExprVal::FunctionCall(_)
+            | ExprVal::Coalesce(_)
+            | ExprVal::Array(_)
+            | ExprVal::Map(_) => {
+                // `eval_expression` already applied the negation, undo it as it's done below
+                let mut res = self.eval_expression(bool_expr)?.is_truthy();
+                if bool_expr.negated {
+                    res = !res;
                 }
+                res
             }
             ExprVal::StringConcat(_) => {
                 let res = self.eval_expression(bool_expr)?;
                 !res.as_str().unwrap().is_empty()
             }
             ExprVal::MacroCall(ref macro_call) => {
-                let res = self.eval_macro_call(&macro_call)?;
+                let res = self.eval_macro_call(macro_call, None)?;
                 !res.is_empty()
             }
-            _ => unreachable!("unimplemented logic operation for {:?}", bool_expr),
         };
 
         if bool_expr.negated {
@@ -662,6 +1305,26 @@ impl<'a> Processor<'a> {
         Ok(res)
     }
 
+    /// Orders two numbers, ints and floats alike, or two strings lexicographically.
+    /// Any other pair of values can't be ordered and is an error.
+    fn eval_ordering(&mut self, lhs: &'a Expr, rhs: &'a Expr) -> Result<Ordering> {
+        let l = self.safe_eval_expression(lhs)?;
+        let r = self.safe_eval_expression(rhs)?;
+
+        match (&*l, &*r) {
+            (Value::Number(ref ln), Value::Number(ref rn)) => ln
+                .as_f64()
+                .unwrap()
+                .partial_cmp(&rn.as_f64().unwrap())
+                .ok_or_else(|| Error::msg("Comparison to NaN")),
+            (Value::String(ref ls), Value::String(ref rs)) => Ok(ls.cmp(rs)),
+            _ => Err(Error::msg(format!(
+                "Tried to compare {} with {}, but only two numbers or two strings can be compared",
+                l, r
+            ))),
+        }
+    }
+
     /// In some cases, we will have filters in lhs/rhs of a math expression
     /// `eval_as_number` only works on ExprVal rather than Expr
     fn eval_expr_as_number(&mut self, expr: &'a Expr) -> Result<Option<Number>> {
@@ -698,150 +1361,9 @@ impl<'a> Processor<'a> {
             ExprVal::Int(val) => Some(Number::from(val)),
             ExprVal::Float(val) => Some(Number::from_f64(val).unwrap()),
             ExprVal::Math(MathExpr { ref lhs, ref rhs, ref operator }) => {
-                let (l, r) = match (self.eval_expr_as_number(lhs)?, self.eval_expr_as_number(rhs)?)
-                {
-                    (Some(l), Some(r)) => (l, r),
-                    _ => return Ok(None),
-                };
-
-                match *operator {
-                    MathOperator::Mul => {
-                        if l.is_i64() && r.is_i64() {
-                            let ll = l.as_i64().unwrap();
-                            let rr = r.as_i64().unwrap();
-                            let res = match ll.checked_mul(rr) {
-                                Some(s) => s,
-                                None => {
-                                    return Err(Error::msg(format!(
-                                        "{} x {} results in an out of bounds i64",
-                                        ll, rr
-                                    )));
-                                }
-                            };
-
-                            Some(Number::from(res))
-                        } else if l.is_u64() && r.is_u64() {
-                            let ll = l.as_u64().unwrap();
-                            let rr = r.as_u64().unwrap();
-                            let res = match ll.checked_mul(rr) {
-                                Some(s) => s,
-                                None => {
-                                    return Err(Error::msg(format!(
-                                        "{} x {} results in an out of bounds u64",
-                                        ll, rr
-                                    )));
-                                }
-                            };
-                            Some(Number::from(res))
-                        } else {
-                            let ll = l.as_f64().unwrap();
-                            let rr = r.as_f64().unwrap();
-                            Number::from_f64(ll * rr)
-                        }
-                    }
-                    MathOperator::Div => {
-                        let ll = l.as_f64().unwrap();
-                        let rr = r.as_f64().unwrap();
-                        let res = ll / rr;
-                        if res.is_nan() {
-                            None
-                        } else {
-                            Number::from_f64(res)
-                        }
-                    }
-                    MathOperator::Add => {
-                        if l.is_i64() && r.is_i64() {
-                            let ll = l.as_i64().unwrap();
-                            let rr = r.as_i64().unwrap();
-                            let res = match ll.checked_add(rr) {
-                                Some(s) => s,
-                                None => {
-                                    return Err(Error::msg(format!(
-                                        "{} + {} results in an out of bounds i64",
-                                        ll, rr
-                                    )));
-                                }
-                            };
-                            Some(Number::from(res))
-                        } else if l.is_u64() && r.is_u64() {
-                            let ll = l.as_u64().unwrap();
-                            let rr = r.as_u64().unwrap();
-                            let res = match ll.checked_add(rr) {
-                                Some(s) => s,
-                                None => {
-                                    return Err(Error::msg(format!(
-                                        "{} + {} results in an out of bounds u64",
-                                        ll, rr
-                                    )));
-                                }
-                            };
-                            Some(Number::from(res))
-                        } else {
-                            let ll = l.as_f64().unwrap();
-                            let rr = r.as_f64().unwrap();
-                            Some(Number::from_f64(ll + rr).unwrap())
-                        }
-                    }
-                    MathOperator::Sub => {
-                        if l.is_i64() && r.is_i64() {
-                            let ll = l.as_i64().unwrap();
-                            let rr = r.as_i64().unwrap();
-                            let res = match ll.checked_sub(rr) {
-                                Some(s) => s,
-                                None => {
-                                    return Err(Error::msg(format!(
-                                        "{} - {} results in an out of bounds i64",
-                                        ll, rr
-                                    )));
-                                }
-                            };
-                            Some(Number::from(res))
-                        } else if l.is_u64() && r.is_u64() {
-                            let ll = l.as_u64().unwrap();
-                            let rr = r.as_u64().unwrap();
-                            let res = match ll.checked_sub(rr) {
-                                Some(s) => s,
-                                None => {
-                                    return Err(Error::msg(format!(
-                                        "{} - {} results in an out of bounds u64",
-                                        ll, rr
-                                    )));
-                                }
-                            };
-                            Some(Number::from(res))
-                        } else {
-                            let ll = l.as_f64().unwrap();
-                            let rr = r.as_f64().unwrap();
-                            Some(Number::from_f64(ll - rr).unwrap())
-                        }
-                    }
-                    MathOperator::Modulo => {
-                        if l.is_i64() && r.is_i64() {
-                            let ll = l.as_i64().unwrap();
-                            let rr = r.as_i64().unwrap();
-                            if rr == 0 {
-                                return Err(Error::msg(format!(
-                                    "Tried to do a modulo by zero: {:?}/{:?}",
-                                    lhs, rhs
-                                )));
-                            }
-                            Some(Number::from(ll % rr))
-                        } else if l.is_u64() && r.is_u64() {
-                            let ll = l.as_u64().unwrap();
-                            let rr = r.as_u64().unwrap();
-                            if rr == 0 {
-                                return Err(Error::msg(format!(
-                                    "Tried to do a modulo by zero: {:?}/{:?}",
-                                    lhs, rhs
-                                )));
-                            }
-                            Some(Number::from(ll % rr))
-                        } else {
-                            let ll = l.as_f64().unwrap();
-                            let rr = r.as_f64().unwrap();
-                            Number::from_f64(ll % rr)
-                        }
-                    }
+                match (self.eval_expr_as_number(lhs)?, self.eval_expr_as_number(rhs)?) {
+                    (Some(l), Some(r)) => number_math(&l, *operator, &r)?,
+                    _ => None,
                 }
             }
             ExprVal::FunctionCall(ref fn_call) => {
@@ -865,6 +1387,9 @@ impl<'a> Processor<'a> {
             ExprVal::Bool(val) => {
                 return Err(Error::msg(format!("Tried to do math with a boolean: `{}`", val)));
             }
+            ExprVal::Null => {
+                return Err(Error::msg("Tried to do math with `none`"));
+            }
             ExprVal::StringConcat(ref val) => {
                 return Err(Error::msg(format!(
                     "Tried to do math with a string concatenation: {}",
@@ -915,6 +1440,131 @@ impl<'a> Processor<'a> {
         Err(Error::msg("Tried to use super() in the top level block"))
     }
 
+    /// The values are all evaluated before being defined, so they can't refer to each other
+    fn render_with(&mut self, with: &'a With) -> Result<String> {
+        let mut context = FrameContext::with_capacity(with.assignments.len());
+        for (key, expr) in &with.assignments {
+            context.insert(key.as_str(), self.safe_eval_expression(expr)?);
+        }
+
+        self.call_stack.push_with_frame(context);
+        let res = self.render_body(&with.body);
+        self.call_stack.pop();
+        res
+    }
+
+    /// Renders a tag registered with `Tera::register_tag`, whose output is not escaped
+    fn render_custom_tag(&mut self, custom_tag: &'a CustomTag) -> Result<String> {
+        let tag = match self.tera.tags.get(&custom_tag.name) {
+            Some(tag) => tag.clone(),
+            None => return Err(Error::msg(format!("Tag `{}` not found", custom_tag.name))),
+        };
+        let err_wrap = |e| Error::chain(format!("Tag `{}` failed", custom_tag.name), e);
+
+        let arg_names: Vec<_> = custom_tag.args.keys().map(|k| k.as_str()).collect();
+        tag.check(&arg_names, custom_tag.body.is_some()).map_err(err_wrap)?;
+
+        let mut args = HashMap::with_capacity(custom_tag.args.len());
+        for (arg_name, expr) in &custom_tag.args {
+            args.insert(arg_name.to_string(), self.safe_eval_expression(expr)?.into_owned());
+        }
+        let body = match custom_tag.body {
+            Some(ref body) => Some(self.render_body(body)?),
+            None => None,
+        };
+
+        tag.render(&args, body.as_ref().map(|s| s.as_str())).map_err(err_wrap)
+    }
+
+    /// Renders the body of a `{% cache %}` block or reuses its output from the fragment cache.
+    /// Any assignment made in the body is lost when its output comes from the cache.
+    fn render_cache(&mut self, cache: &'a Cache) -> Result<String> {
+        let fragment_cache = match self.tera.fragment_cache {
+            Some(ref fragment_cache) => fragment_cache.clone(),
+            None => return self.render_body(&cache.body),
+        };
+
+        let key = format!(
+            "{}:{}",
+            self.call_stack.active_template().name,
+            self.safe_eval_expression(&cache.key)?.render()
+        );
+        if let Some(output) = fragment_cache.get(&key) {
+            return Ok(output);
+        }
+
+        let ttl = match cache.ttl {
+            Some(ref expr) => {
+                let value = self.safe_eval_expression(expr)?;
+                match value.as_f64() {
+                    Some(seconds) if seconds >= 0.0 && seconds < std::u64::MAX as f64 => {
+                        Some(Duration::new(seconds as u64, (seconds.fract() * 1e9) as u32))
+                    }
+                    _ => {
+                        return Err(Error::msg(format!(
+                        "The TTL of a `cache` tag should be a positive number of seconds, got `{}`",
+                        value
+                    )))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let output = self.render_body(&cache.body)?;
+        fragment_cache.set(&key, output.clone(), ttl);
+        Ok(output)
+    }
+
+    /// Translates the message with the translator of the Tera instance, in the language given by
+    /// the `locale` of the render options or the `lang` variable if there is one, and fills its
+    /// placeholders.
+    /// The message is used as is if there is no translator or no translation for it.
+    fn render_trans(&mut self, trans: &'a Trans) -> Result<String> {
+        let mut values = HashMap::with_capacity(trans.variables.len() + 1);
+        for (name, expr) in &trans.variables {
+            values.insert(name.as_str(), self.eval_expression(expr)?.render().into_owned());
+        }
+        let count = match trans.count {
+            Some(ref expr) => {
+                let value = self.eval_expression(expr)?;
+                let count = value.as_u64().ok_or_else(|| {
+                    Error::msg(format!(
+                        "The `count` of a `trans` tag should be a positive integer, got `{}`",
+                        value
+                    ))
+                })?;
+                values.insert("count", count.to_string());
+                Some(count)
+            }
+            None => None,
+        };
+
+        let lang = match self.options.locale {
+            Some(ref locale) => Some(locale.clone()),
+            None => self.lookup_ident("lang").ok().and_then(|l| l.as_str().map(|s| s.to_string())),
+        };
+        let lang = lang.as_ref().map(String::as_str);
+        let (message, translation) = match (&trans.plural, count) {
+            (Some(plural), Some(count)) => {
+                let message = if count == 1 { &trans.message } else { plural };
+                let translation = self
+                    .tera
+                    .translator
+                    .as_ref()
+                    .and_then(|t| t.translate_plural(lang, &trans.message, plural, count));
+                (message, translation)
+            }
+            _ => {
+                let translation =
+                    self.tera.translator.as_ref().and_then(|t| t.translate(lang, &trans.message));
+                (&trans.message, translation)
+            }
+        };
+
+        Ok(fill_placeholders(translation.as_ref().map(String::as_str).unwrap_or(message), &values))
+    }
+
     /// Looks up identifier and returns its value
     fn lookup_ident(&self, key: &str) -> Result<Val<'a>> {
         // Magical variable that just dumps the context
@@ -928,19 +1578,102 @@ impl<'a> Processor<'a> {
             ));
         }
 
+        if key.contains("?.") {
+            return process_path(&key.replace("?.", "."), &self.call_stack);
+        }
+
         process_path(key, &self.call_stack)
     }
 
+    /// Whether an ident that wasn't found uses optional access, `user?.profile.avatar`, and
+    /// exists up to the first `?.`: everything after it is allowed to be missing
+    fn is_missing_optional(&self, ident: &str) -> bool {
+        match ident.find("?.") {
+            Some(i) => self.lookup_ident(&ident[..i]).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Whether an ident that wasn't found ends with a subscript, `items[10]`, missing from an
+    /// array or object that exists while missing subscripts are undefined
+    fn is_missing_subscript(&self, ident: &str) -> bool {
+        if self.out_of_bounds != OutOfBounds::Undefined || !ident.ends_with(']') {
+            return false;
+        }
+
+        // Finds the `[` of the last subscript, which can contain other ones: `a[b[0]]`
+        let mut depth = 0;
+        for (i, c) in ident.char_indices().rev() {
+            match c {
+                ']' => depth += 1,
+                '[' if depth == 1 => {
+                    let index = &ident[i + 1..ident.len() - 1];
+                    let is_literal =
+                        index.starts_with(&['"', '\'', '`'][..]) || index.parse::<i64>().is_ok();
+                    return self.lookup_ident(&ident[..i]).is_ok()
+                        && (is_literal || self.lookup_ident(index).is_ok());
+                }
+                '[' => depth -= 1,
+                _ => (),
+            }
+        }
+        false
+    }
+
+    /// Dumps the variables in scope, or only the given one, as pretty-printed JSON: in a `<pre>`
+    /// block when escaping HTML and in an HTML comment otherwise
+    fn render_debug(&self, name: Option<&str>) -> Result<String> {
+        let value = match name {
+            Some(name) => self.lookup_ident(name)?.into_owned(),
+            None => self.call_stack.current_context_cloned(),
+        };
+        // Unwrap is safe since we are dealing with a Value
+        let dump = to_string_pretty(&value).unwrap();
+
+        if self.should_escape {
+            Ok(format!("<pre>{}</pre>", (self.escape_fn)(&dump)))
+        } else {
+            // `--` can't appear in a comment
+            let mut dump = dump;
+            while dump.contains("--") {
+                dump = dump.replace("--", "- -");
+            }
+            Ok(format!("<!--\n{}\n-->", dump))
+        }
+    }
+
     /// Process the given node, appending the string result to the buffer
     /// if it is possible
     fn render_node(&mut self, node: &'a Node, buffer: &mut String) -> Result<()> {
+        let hooks = self.tera.render_hooks.as_ref().map(|h| &**h);
+        if hooks.is_none() && self.warnings.is_none() {
+            return self.render_node_content(node, buffer);
+        }
+
+        let template = &self.call_stack.active_template().name;
+        let parent_span = std::mem::replace(&mut self.span, *node.span());
+        if let Some(hooks) = hooks {
+            hooks.on_node(HookPhase::Enter, node.kind(), node.span(), template);
+        }
+        let res = self.render_node_content(node, buffer);
+        if let Some(hooks) = hooks {
+            hooks.on_node(HookPhase::Exit, node.kind(), node.span(), template);
+        }
+        self.span = parent_span;
+        res
+    }
+
+    fn render_node_content(&mut self, node: &'a Node, buffer: &mut String) -> Result<()> {
         match *node {
-            Node::Text(ref s) | Node::Raw(_, ref s, _) => buffer.push_str(s),
-            Node::VariableBlock(_, ref expr) => {
+            Node::Text(ref s, _) | Node::Raw(_, ref s, _, _) => buffer.push_str(s),
+            Node::VariableBlock(_, ref expr, _) => {
                 buffer.push_str(&self.eval_expression(expr)?.render())
             }
-            Node::Set(_, ref set) => self.eval_set(set)?,
-            Node::FilterSection(_, FilterSection { ref filter, ref body }, _) => {
+            Node::Set(_, ref set, _) => self.eval_set(set)?,
+            Node::Do(_, ref expr, _) => {
+                self.eval_expression(expr)?;
+            }
+            Node::FilterSection(_, FilterSection { ref filter, ref body }, _, _) => {
                 let body = self.render_body(body)?;
                 buffer.push_str(
                     &self
@@ -948,34 +1681,75 @@ impl<'a> Processor<'a> {
                         .render(),
                 );
             }
+            Node::Trans(_, ref trans, _, _) => buffer.push_str(&self.render_trans(trans)?),
+            Node::With(_, ref with, _, _) => buffer.push_str(&self.render_with(with)?),
+            Node::Cache(_, ref cache, _, _) => buffer.push_str(&self.render_cache(cache)?),
+            Node::CustomTag(_, ref tag, _, _) => buffer.push_str(&self.render_custom_tag(tag)?),
+            Node::CallBlock(_, ref call_block, _, _) => {
+                let body = self.render_body(&call_block.body)?;
+                buffer.push_str(&self.eval_macro_call(&call_block.call, Some(body))?);
+            }
+            Node::Spaceless(_, ref body, _, _) => {
+                buffer.push_str(&remove_spaces_between_tags(&self.render_body(body)?))
+            }
             // Macros have been imported at the beginning
-            Node::ImportMacro(_, _, _) => (),
-            Node::If(ref if_node, _) => buffer.push_str(&self.render_if_node(if_node)?),
-            Node::Forloop(_, ref forloop, _) => buffer.push_str(&self.render_for_loop(forloop)?),
-            Node::Break(_) => {
+            Node::ImportMacro(..) => (),
+            Node::If(ref if_node, _, _) => buffer.push_str(&self.render_if_node(if_node)?),
+            Node::Switch(_, ref switch, _, _) => buffer.push_str(&self.render_switch(switch)?),
+            Node::Forloop(_, ref forloop, _, _) => buffer.push_str(&self.render_for_loop(forloop)?),
+            Node::While(_, ref while_loop, _, _) => {
+                buffer.push_str(&self.render_while(while_loop)?)
+            }
+            Node::Break(..) => {
                 self.call_stack.break_for_loop()?;
             }
-            Node::Continue(_) => {
+            Node::Continue(..) => {
                 self.call_stack.continue_for_loop()?;
             }
-            Node::Block(_, ref block, _) => buffer.push_str(&self.render_block(block, 0)?),
-            Node::Super => buffer.push_str(&self.do_super()?),
-            Node::Include(_, ref tpl_name) => {
-                let template = self.tera.get_template(tpl_name)?;
-                self.macros.add_macros_from_template(&self.tera, template)?;
-                self.call_stack.push_include_frame(tpl_name, template);
-                let result = self.render_body(&template.ast)?;
-                self.call_stack.pop();
-                buffer.push_str(&result);
+            Node::Debug(_, ref name, _) => {
+                buffer.push_str(&self.render_debug(name.as_ref().map(String::as_str))?)
+            }
+            Node::Block(_, ref block, _, _) => buffer.push_str(&self.profiled(
+                ProfileKind::Block,
+                &block.name,
+                |p| p.render_block(block, 0),
+            )?),
+            Node::Super(_) => buffer.push_str(&self.do_super()?),
+            Node::Include(_, ref include, _)
+                if include.ignore_missing && !self.tera.templates.contains_key(&include.name) => {}
+            Node::Include(_, ref include, ref span) => {
+                let mut context = FrameContext::with_capacity(include.variables.len());
+                for (key, expr) in &include.variables {
+                    context.insert(key.as_str(), self.safe_eval_expression(expr)?);
+                }
+                buffer.push_str(&self.render_include(
+                    &include.name,
+                    &[],
+                    context,
+                    include.only,
+                    span,
+                )?)
             }
-            Node::Extends(_, ref name) => {
+            Node::Embed(_, ref embed, _, ref span) => buffer.push_str(&self.render_include(
+                &embed.name,
+                &embed.blocks,
+                FrameContext::new(),
+                false,
+                span,
+            )?),
+            Node::Extends(_, ref name, _) => {
                 return Err(Error::msg(format!(
                     "Inheritance in included templates is currently not supported: extended `{}`",
                     name
                 )));
             }
+            Node::DynamicExtends(..) => {
+                return Err(Error::msg(
+                    "Inheritance in included templates is currently not supported",
+                ));
+            }
             // TODO: make that a compile time error
-            Node::MacroDefinition(_, ref def, _) => {
+            Node::MacroDefinition(_, ref def, _, _) => {
                 return Err(Error::invalid_macro_def(&def.name));
             }
         };
@@ -983,6 +1757,58 @@ impl<'a> Processor<'a> {
         Ok(())
     }
 
+    /// Renders an included or embedded template, `blocks` being the blocks replacing its own.
+    /// `context` are the variables given to it and it doesn't see the enclosing context if
+    /// `only` is set.
+    fn render_include(
+        &mut self,
+        tpl_name: &'a str,
+        blocks: &'a [Node],
+        context: FrameContext<'a>,
+        only: bool,
+        span: &Span,
+    ) -> Result<String> {
+        let hooks = self.tera.render_hooks.as_ref().map(|h| &**h);
+        let parent = &self.call_stack.active_template().name;
+        if self.warnings.is_some() {
+            if let Some(reason) = self.tera.get_template(tpl_name)?.deprecated.clone() {
+                let deprecation = Deprecation::Template { name: tpl_name.to_string(), reason };
+                self.warn(parent, Some(*span), deprecation);
+            }
+        }
+        if let Some(hooks) = hooks {
+            hooks.on_include(HookPhase::Enter, tpl_name, span, parent);
+        }
+        let result = self.render_included_template(tpl_name, blocks, context, only);
+        if let Some(hooks) = hooks {
+            hooks.on_include(HookPhase::Exit, tpl_name, span, parent);
+        }
+        result
+    }
+
+    fn render_included_template(
+        &mut self,
+        tpl_name: &'a str,
+        blocks: &'a [Node],
+        context: FrameContext<'a>,
+        only: bool,
+    ) -> Result<String> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("tera.include", template = tpl_name).entered();
+
+        let template = self.tera.get_template(tpl_name)?;
+        self.macros.add_macros_from_template(self.tera, template)?;
+        self.enter_nested()?;
+        self.call_stack.push_include_frame(tpl_name, template, context, only);
+        self.embedded_blocks.push((&template.name, blocks));
+        let result =
+            self.profiled(ProfileKind::Include, tpl_name, |p| p.render_body(&template.ast))?;
+        self.embedded_blocks.pop();
+        self.call_stack.pop();
+        self.depth -= 1;
+        Ok(result)
+    }
+
     /// Helper fn that tries to find the current context: are we in a macro? in a parent template?
     /// in order to give the best possible error when getting an error when rendering a tpl
     fn get_error_location(&self) -> String {
@@ -1024,12 +1850,76 @@ impl<'a> Processor<'a> {
     /// Entry point for the rendering
     pub fn render(&mut self) -> Result<String> {
         // 10000 is a random value
-        let mut output = String::with_capacity(10000);
+        let mut output = Vec::with_capacity(10000);
+        self.render_to(&mut output)?;
+        // Only `String`s are written to it
+        Ok(String::from_utf8(output).expect("rendered output is valid UTF-8"))
+    }
+
+    /// Entry point for the rendering to a writer: the output of each top-level node of the
+    /// template is written as soon as it is rendered
+    pub fn render_to(&mut self, write: &mut impl Write) -> Result<()> {
+        let name = &self.template.name;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("tera.render", template = name.as_str()).entered();
+
+        self.warn_deprecated_templates();
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.enter(ProfileKind::Template, name);
+        }
+        let res = self.render_nodes_to(write);
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.exit(*res.as_ref().unwrap_or(&0));
+        }
+        res.map(|_| ())
+    }
+
+    /// Renders the top-level nodes of the template to `write`, returning the length written
+    fn render_nodes_to(&mut self, write: &mut impl Write) -> Result<usize> {
+        let mut buffer = String::new();
+        let mut len = 0;
         for node in &self.template_root.ast {
-            self.render_node(node, &mut output)
+            self.render_node(node, &mut buffer)
                 .map_err(|e| Error::chain(self.get_error_location(), e))?;
+            write.write_all(buffer.as_bytes()).map_err(Error::io_error)?;
+            len += buffer.len();
+            buffer.clear();
         }
 
-        Ok(output)
+        Ok(len)
+    }
+
+    /// Evaluates the expression of a `{% extends layout %}` to the name of the parent template
+    pub fn eval_parent_name(&mut self, expr: &'a Expr) -> Result<String> {
+        match *self.safe_eval_expression(expr)? {
+            Value::String(ref name) => Ok(name.to_string()),
+            ref val => Err(Error::msg(format!(
+                "Tried to extend `{}` in '{}' but only template names (strings) can be extended",
+                val, self.template.name
+            ))),
+        }
+    }
+
+    /// Renders only the block `name` of the template, with the content coming from the
+    /// template of the hierarchy closest to this one defining it, as when rendering the whole
+    /// template
+    pub fn render_named_block(&mut self, name: &str) -> Result<String> {
+        let tera = self.tera;
+        let template = self.template;
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("tera.render", template = template.name.as_str(), block = name)
+                .entered();
+
+        let block = std::iter::once(template)
+            .chain(template.parents.iter().map(|parent| tera.get_template(parent).unwrap()))
+            .find_map(|t| t.blocks.get(name))
+            .ok_or_else(|| {
+                Error::msg(format!("Block `{}` not found in template '{}'", name, template.name))
+            })?;
+
+        self.profiled(ProfileKind::Block, name, |p| {
+            p.render_block(block, 0).map_err(|e| Error::chain(p.get_error_location(), e))
+        })
     }
 }
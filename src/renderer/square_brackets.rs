@@ -37,6 +37,43 @@ pub fn pull_out_square_bracket(s: &str) -> Vec<String> {
     results
 }
 
+/// Whether the content of a subscript is a slice, `1:3`, or a negative index, `-1`, which
+/// can't be turned into a JSON pointer
+fn is_slice(sub: &str) -> bool {
+    let quoted = sub.starts_with(&['"', '\'', '`'][..]);
+    !quoted && (sub.contains(':') || (sub.starts_with('-') && sub.parse::<i64>().is_ok()))
+}
+
+/// Splits the path at its first subscript that is a slice or a negative index, returning the
+/// path before it, the content of the subscript and the rest of the path:
+/// `items[-1].name` gives `("items", "-1", ".name")`
+pub fn split_at_slice(s: &str) -> Option<(&str, &str, &str)> {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') | (None, '`') => quote = Some(c),
+            (None, '[') => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            (None, ']') => {
+                depth -= 1;
+                if depth == 0 && is_slice(&s[start + 1..i]) {
+                    return Some((&s[..start], &s[start + 1..i], &s[i + 1..]));
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,4 +85,13 @@ mod tests {
         assert_eq!(pull_out_square_bracket("[hi] a[0]"), vec!["hi"]);
         assert_eq!(pull_out_square_bracket("hi [th[e]['r']e] [fish]"), vec!["th[e]['r']e", "fish"]);
     }
+
+    #[test]
+    fn can_split_at_slice() {
+        assert_eq!(split_at_slice("items[0].name"), None);
+        assert_eq!(split_at_slice("items['a:b']"), None);
+        assert_eq!(split_at_slice("items[-1].name"), Some(("items", "-1", ".name")));
+        assert_eq!(split_at_slice("title[:50]"), Some(("title", ":50", "")));
+        assert_eq!(split_at_slice("a[b[-1]][1:][0]"), Some(("a[b[-1]]", "1:", "[0]")));
+    }
 }
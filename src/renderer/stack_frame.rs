@@ -32,6 +32,10 @@ pub enum FrameType {
     ForLoop,
     /// Include template
     Include,
+    /// Include template with `only`, without access to the enclosing frames and user context
+    IsolatedInclude,
+    /// New frame for the variables of a with block
+    With,
 }
 
 /// Entry in the stack frame
@@ -94,11 +98,27 @@ impl<'a> StackFrame<'a> {
         }
     }
 
-    pub fn new_include(name: &'a str, tpl: &'a Template) -> Self {
+    pub fn new_include(
+        name: &'a str,
+        tpl: &'a Template,
+        context: FrameContext<'a>,
+        isolated: bool,
+    ) -> Self {
         StackFrame {
-            kind: FrameType::Include,
+            kind: if isolated { FrameType::IsolatedInclude } else { FrameType::Include },
             name,
-            context: FrameContext::new(),
+            context,
+            active_template: tpl,
+            for_loop: None,
+            macro_namespace: None,
+        }
+    }
+
+    pub fn new_with(tpl: &'a Template, context: FrameContext<'a>) -> Self {
+        StackFrame {
+            kind: FrameType::With,
+            name: "with",
+            context,
             active_template: tpl,
             for_loop: None,
             macro_namespace: None,
@@ -127,12 +147,17 @@ impl<'a> StackFrame<'a> {
 
         None
     }
+    /// Finds a value assigned in that frame, to modify it
+    pub fn find_value_mut(&mut self, key: &str) -> Option<&mut Val<'a>> {
+        self.context.get_mut(key)
+    }
+
     /// Finds a value in the `for_loop` if there is one
     pub fn find_value_in_for_loop(&self, key: &str) -> Option<Val<'a>> {
         if let Some(ref for_loop) = self.for_loop {
-            // 1st case: the variable is the key of a KeyValue for loop
+            // 1st case: the variable is/starts with the key of a KeyValue for loop
             if for_loop.is_key(key) {
-                return Some(Cow::Owned(Value::String(for_loop.get_current_key())));
+                return Some(for_loop.get_current_key());
             }
 
             let (real_key, tail) = if let Some(tail_pos) = key.find('.') {
@@ -141,6 +166,10 @@ impl<'a> StackFrame<'a> {
                 (key, "")
             };
 
+            if for_loop.is_key(real_key) && !tail.is_empty() {
+                return value_by_pointer(tail, &for_loop.get_current_key());
+            }
+
             // 2nd case: one of Tera loop built-in variable
             if real_key == "loop" {
                 match tail {
@@ -158,6 +187,9 @@ impl<'a> StackFrame<'a> {
                             for_loop.current == for_loop.len() - 1,
                         )));
                     }
+                    "length" => {
+                        return Some(Cow::Owned(Value::Number(for_loop.len().into())));
+                    }
                     _ => return None,
                 };
             }
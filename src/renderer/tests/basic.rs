@@ -1,15 +1,21 @@
 use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use lazy_static::lazy_static;
 use serde_derive::Serialize;
 use serde_json::{json, Value};
 
 use crate::builtins::functions::Function;
+use crate::cache::MemoryCache;
 use crate::context::Context;
 use crate::errors::Result;
+use crate::hooks::{HookPhase, RenderHooks};
+use crate::options::{MapOrder, OutOfBounds, RenderOptions};
+use crate::parser::lexer::Span;
+use crate::post_process::TemplateMeta;
+use crate::profile::{ProfileEntry, ProfileKind};
 use crate::tera::Tera;
 
 use super::Review;
@@ -47,20 +53,31 @@ fn render_variable_block_lit_expr() {
         ("{{ 3 - 1 }}", "2"),
         ("{{ 3 - 1.1 }}", "1.9"),
         ("{{ 2 * 5 }}", "10"),
-        ("{{ 10 / 5 }}", "2"),
+        ("{{ 10 / 5 }}", "2.0"),
         ("{{ 2.1 * 5 }}", "10.5"),
         ("{{ 2.1 * 5.05 }}", "10.605"),
-        ("{{ 2 / 0.5 }}", "4"),
+        ("{{ 2 / 0.5 }}", "4.0"),
         ("{{ 2.1 / 0.5 }}", "4.2"),
+        ("{{ 7 / 2 }}", "3.5"),
+        ("{{ 7 // 2 }}", "3"),
+        ("{{ -7 // 2 }}", "-4"),
+        ("{{ 7.5 // 2 }}", "3.0"),
+        ("{{ 1 + 7 // 2 * 2 }}", "7"),
         ("{{ 2 + 1 * 2 }}", "4"),
         ("{{ (2 + 1) * 2 }}", "6"),
         ("{{ 2 * 4 % 8 }}", "0"),
-        ("{{ 2.8 * 2 | round }}", "6"),
-        ("{{ 1 / 0 }}", "NaN"),
+        ("{{ 2.8 * 2 | round }}", "6.0"),
         ("{{ true and 10 }}", "true"),
         ("{{ true and not 10 }}", "false"),
         ("{{ not true }}", "false"),
         ("{{ [1, 2, 3] }}", "[1, 2, 3]"),
+        (r#"{{ {"a": 1, b: [2, 3]} | json_encode | safe }}"#, r#"{"a":1,"b":[2,3]}"#),
+        (r#"{% set m = {"a": 1 + 1} %}{{ m.a }}"#, "2"),
+        (r#"{{ {"a": 1} + {"b": 2} | length }}"#, "2"),
+        ("{% if {} %}full{% else %}empty{% endif %}", "empty"),
+        ("{% if not {} %}yes{% else %}no{% endif %}", "yes"),
+        ("{% if not [] %}yes{% else %}no{% endif %}", "yes"),
+        ("{% if not [1] %}yes{% else %}no{% endif %}", "no"),
     ];
 
     for (input, expected) in inputs {
@@ -108,6 +125,8 @@ fn render_variable_block_ident() {
         ("{{ not name is defined }}", "false"),
         ("{{ name is not defined }}", "false"),
         ("{{ not name is not defined }}", "true"),
+        ("{{ a is not divisibleby(2) }}", "false"),
+        ("{% if b is not divisibleby(2) %}odd{% endif %}", "odd"),
         ("{{ a is odd }}", "false"),
         ("{{ a is odd or b is odd  }}", "true"),
         ("{{ range(start=1, end=4) }}", "[1, 2, 3]"),
@@ -118,12 +137,16 @@ fn render_variable_block_ident() {
         ("{{ 1 - 1 + 1 }}", "1"),
         ("{{ 1 + get_number() }}", "11"),
         ("{{ get_number() + 1 }}", "11"),
-        ("{{ (1.9 + a) | round }}", "4"),
-        ("{{ 1.9 + a | round }}", "4"),
+        ("{{ -a }}", "-2"),
+        ("{{ -(a + 1) * 2 }}", "-6"),
+        ("{{ b - -a }}", "5"),
+        ("{{ -get_number() }}", "-10"),
+        ("{{ (1.9 + a) | round }}", "4.0"),
+        ("{{ 1.9 + a | round }}", "4.0"),
         ("{{ numbers | length - 1 }}", "2"),
-        ("{{ 1.9 + a | round - 1 }}", "3"),
-        ("{{ 1.9 + a | round - 1.8 + a | round }}", "0"),
-        ("{{ 1.9 + a | round - 1.8 + a | round - 1 }}", "-1"),
+        ("{{ 1.9 + a | round - 1 }}", "3.0"),
+        ("{{ 1.9 + a | round - 1.8 + a | round }}", "0.0"),
+        ("{{ 1.9 + a | round - 1.8 + a | round - 1 }}", "-1.0"),
         // https://github.com/Keats/tera/issues/435
         (
             "{{ with_newline | replace(from='\n', to='<br>') | safe }}",
@@ -137,6 +160,109 @@ fn render_variable_block_ident() {
     }
 }
 
+#[test]
+fn render_math_on_big_integers() {
+    let mut context = Context::new();
+    context.insert("big", &i64::max_value());
+    context.insert("huge", &u64::max_value());
+    context.insert("negative", &i64::min_value());
+
+    let inputs = vec![
+        ("{{ big + 1 }}", "9223372036854775808"),
+        ("{{ huge - big }}", "9223372036854775808"),
+        ("{{ huge + negative }}", "9223372036854775807"),
+        ("{{ negative // -2 }}", "4611686018427387904"),
+        ("{{ huge % -10 }}", "5"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_add_arrays_and_objects() {
+    let mut context = Context::new();
+    context.insert("numbers", &vec![1, 2]);
+    context.insert("defaults", &json!({"color": "red", "size": 1}));
+    context.insert("overrides", &json!({"size": 2}));
+
+    let inputs = vec![
+        ("{{ numbers + [3] }}", "[1, 2, 3]"),
+        ("{{ [0] + numbers + numbers }}", "[0, 1, 2, 1, 2]"),
+        ("{{ (numbers + [3]) | length }}", "3"),
+        ("{{ numbers + [] }}", "[1, 2]"),
+        ("{% for n in numbers + range(start=3, end=5) %}{{ n }}{% endfor %}", "1234"),
+        ("{% set merged = defaults + overrides %}{{ merged.color }} {{ merged.size }}", "red 2"),
+        ("{% set merged = overrides + defaults %}{{ merged.size }}", "1"),
+        ("{{ defaults.size + overrides.size }}", "3"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_multiline_expressions() {
+    let mut context = Context::new();
+    context.insert("name", &"bob");
+    let inputs = vec![
+        ("{{ name\n  | upper\n  | replace(from='B', to='b') }}", "bOb"),
+        ("{{\n  range(\n    start=1,\n    end=3,\n  )\n}}", "[1, 2]"),
+        ("{% set total =\n  1 +\n  2 %}{{ total }}", "3"),
+        ("{% for i in [1, 2]\n   if i > 1 %}{{ i }}{% endfor %}", "2"),
+        ("{% if name is defined\n  and name != 'alice' %}yes{% endif %}", "yes"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_comments_inside_tags() {
+    let mut context = Context::new();
+    context.insert("price", &250);
+    let inputs = vec![
+        ("{{ price {# in cents #} / 100 }}", "2.5"),
+        ("{% if price > 100 {# expensive #} %}yes{% endif %}", "yes"),
+        ("{% set total = price {# before taxes #} * 2 %}{{ total }}", "500"),
+        ("{% for i in [1, 2] {# items #} %}{{ i }}{% endfor {# items #} %}", "12"),
+        ("{{ '{# kept #}' }}", "{# kept #}"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_unicode_identifiers() {
+    let mut context = Context::new();
+    context.insert("ünïcode_name", &"Bob");
+    context.insert("名前", &"太郎");
+    let mut user = HashMap::new();
+    user.insert("prénom", "Éric");
+    context.insert("user", &user);
+    let inputs = vec![
+        ("{{ ünïcode_name }}", "Bob"),
+        ("{{ 名前 }}", "太郎"),
+        ("{{ user.prénom }}", "Éric"),
+        ("{% set größe = 2 %}{{ größe }}", "2"),
+        ("{% for été in [1, 2] %}{{ été }}{% endfor %}", "12"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
 #[test]
 fn render_variable_block_logic_expr() {
     let mut context = Context::new();
@@ -228,7 +354,7 @@ fn comments_are_ignored() {
 #[test]
 fn escaping_happens_at_the_end() {
     let inputs = vec![
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "builtins-string")]
         ("{{ url | urlencode | safe }}", "https%3A//www.example.org/apples-%26-oranges/"),
         ("{{ '<html>' }}", "&lt;html&gt;"),
         ("{{ '<html>' | safe }}", "<html>"),
@@ -265,6 +391,64 @@ fn render_include_tag() {
     assert_eq!(result, "<h1>Hello world</h1>".to_owned());
 }
 
+#[test]
+fn render_include_tag_with_variables() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "widget",
+            "{{ title | default(value=\"?\") }}:{% for item in items %}{{ item }}{% endfor %}",
+        ),
+        ("open", "{% include \"widget\" with {\"items\": cart} %}"),
+        ("only", "{% include \"widget\" with {\"items\": cart | reverse, title: \"Cart\"} only %}"),
+        ("empty", "{% set items = [1] %}{% include \"widget\" only %}"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("cart", &vec![1, 2]);
+    context.insert("title", &"Title");
+
+    assert_eq!(tera.render("open", &context).unwrap(), "Title:12");
+    assert_eq!(tera.render("only", &context).unwrap(), "Cart:21");
+    assert!(tera.render("empty", &context).is_err());
+}
+
+#[test]
+fn render_include_tag_ignore_missing() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("world", "world"),
+        ("hello", "Hello {% include \"world\" ignore missing %}{% include \"world\" %}{% include \"missing\" ignore missing with {\"a\": 1} %}!"),
+        ("error", "{% include \"missing\" %}"),
+    ])
+    .unwrap();
+    assert_eq!(tera.render("hello", &Context::new()).unwrap(), "Hello worldworld!");
+    assert!(tera.render("error", &Context::new()).is_err());
+    assert_eq!(tera.get_template("hello").unwrap().optional_includes, vec!["missing"]);
+}
+
+#[test]
+fn render_embed_tag() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "card",
+            "<div>{% block title %}Title{% endblock %}|{% block body %}Body{% endblock %}</div>",
+        ),
+        (
+            "page",
+            "{% set name = \"Bob\" %}{% embed \"card\" %}\n  {% block body %}Hello {{ name }}{% endblock %}\n{% endembed %}\
+             {% embed \"card\" %}{% block title %}Other{% endblock %}{% endembed %}{% include \"card\" %}",
+        ),
+    ])
+    .unwrap();
+    let result = tera.render("page", &Context::new()).unwrap();
+    assert_eq!(
+        result,
+        "<div>Title|Hello Bob</div><div>Other|Body</div><div>Title|Body</div>".to_owned()
+    );
+}
+
 #[test]
 fn can_set_variables_in_included_templates() {
     let mut tera = Tera::default();
@@ -312,7 +496,6 @@ fn add_set_values_in_context() {
         ("{% set i = range(end=3) %}{{ i }}", "[0, 1, 2]"),
         ("{% set i = admin or true %}{{ i }}", "true"),
         ("{% set i = admin and num > 0 %}{{ i }}", "true"),
-        ("{% set i = 0 / 0 %}{{ i }}", "NaN"),
         ("{% set i = [1,2] %}{{ i }}", "[1, 2]"),
     ];
 
@@ -342,6 +525,363 @@ fn render_filter_section() {
     }
 }
 
+#[test]
+fn render_with() {
+    let inputs = vec![
+        ("{% with total = items | length %}{{ total }}{% endwith %}", "3"),
+        ("{% with a = 1, b = items %}{{ a }}{{ b | first }}{% endwith %}{{ a | default(value='x') }}", "11x"),
+        // shadows the context only inside the body
+        ("{% with name = 'Tom' %}{{ name }}{% endwith %} {{ name }}", "Tom Bob"),
+        // values are evaluated in the outer scope
+        ("{% with name = 'Tom', other = name %}{{ other }}{% endwith %}", "Bob"),
+        ("{% with a = 1 %}{% with a = a + 1 %}{{ a }}{% endwith %}{{ a }}{% endwith %}", "21"),
+        ("{% with a = 1 %}{% set b = a %}{{ b }}{% endwith %}{{ b | default(value='x') }}", "1x"),
+        ("{% for i in items %}{% with double = i * 2 %}{{ loop.index }}{{ double }}{% endwith %}{% endfor %}", "122436"),
+    ];
+
+    let mut context = Context::new();
+    context.insert("name", "Bob");
+    context.insert("items", &[1, 2, 3]);
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_set_scoping() {
+    let inputs = vec![
+        // if blocks don't create a scope
+        ("{% if true %}{% set a = 1 %}{% endif %}{{ a }}", "1"),
+        // for loops do, for each iteration
+        ("{% set a = 0 %}{% for i in items %}{% set a = i %}{% endfor %}{{ a }}", "0"),
+        ("{% for i in items %}{{ a | default(value='x') }}{% set a = i %}{% endfor %}", "xxx"),
+        ("{% for i in items %}{% if i == 2 %}{% set a = i %}{% endif %}{% endfor %}{{ a | default(value='x') }}", "x"),
+        ("{% for i in items %}{% for j in items %}{% set a = j %}{% endfor %}{{ a | default(value='x') }}{% endfor %}", "xxx"),
+        // set_global escapes the for loops, even from a nested if
+        ("{% for i in items %}{% if i == 2 %}{% set_global a = i %}{% endif %}{% endfor %}{{ a }}", "2"),
+        // shadowing the context and loop variables
+        ("{% set name = 'Tom' %}{{ name }}", "Tom"),
+        ("{% for i in items %}{% set name = i %}{{ name }}{% endfor %}{{ name }}", "123Bob"),
+        ("{% for i in items %}{% set i = i * 10 %}{{ i }}{% endfor %}", "102030"),
+        ("{% for name in items %}{{ name }}{% endfor %}{{ name }}", "123Bob"),
+    ];
+
+    let mut context = Context::new();
+    context.insert("name", "Bob");
+    context.insert("items", &[1, 2, 3]);
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_namespace() {
+    let inputs = vec![
+        ("{% set ns = namespace(total=0) %}{% for i in items %}{% set ns.total = ns.total + i %}{% endfor %}{{ ns.total }}", "6"),
+        ("{% set ns = namespace() %}{% for i in items %}{% if i > 1 %}{% set ns.found = i %}{% break %}{% endif %}{% endfor %}{{ ns.found }}", "2"),
+        ("{% set ns = namespace(a=1) %}{% with x = 2 %}{% set ns.a = x %}{% endwith %}{{ ns.a }}", "2"),
+        ("{% for i in items %}{% set ns = namespace(last=i) %}{% set ns.last = ns.last * 2 %}{{ ns.last }}{% endfor %}", "246"),
+    ];
+
+    let mut context = Context::new();
+    context.insert("items", &[1, 2, 3]);
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_set_augmented_assignment() {
+    let inputs = vec![
+        ("{% set total = 10 %}{% set total += 5 %}{% set total -= 2 * 3 %}{{ total }}", "9"),
+        ("{% set s = 'a' %}{% for i in items %}{% set_global s ~= '-' ~ i %}{% endfor %}{{ s }}", "a-1-2-3"),
+        ("{% set ns = namespace(total=0) %}{% for i in items %}{% set ns.total += i %}{% endfor %}{{ ns.total }}", "6"),
+    ];
+
+    let mut context = Context::new();
+    context.insert("items", &[1, 2, 3]);
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+
+    assert!(render_template("{% set total += 1 %}", &context).is_err());
+}
+
+#[test]
+fn render_cycle() {
+    let inputs = vec![
+        ("{% for i in items %}{{ cycle(values=['odd', 'even']) }} {% endfor %}", "odd even odd "),
+        ("{% for i in items %}{{ cycle(values=[1]) }}{% endfor %}", "111"),
+        (
+            "{% for i in items %}{% for j in items | slice(end=2) %}{{ cycle(values=['a', 'b']) }}{% endfor %}{{ cycle(values=['x', 'y']) }}{% endfor %}",
+            "abxabyabx",
+        ),
+    ];
+
+    let mut context = Context::new();
+    context.insert("items", &[1, 2, 3]);
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+
+    assert!(render_template("{{ cycle(values=['a']) }}", &context).is_err());
+    assert!(render_template("{% for i in items %}{{ cycle(values=[]) }}{% endfor %}", &context)
+        .is_err());
+}
+
+#[test]
+fn render_set_global_in_with() {
+    let tpl = "{% for i in items %}{% with a = i %}{% set_global last = a %}{% endwith %}{% endfor %}{{ last }}";
+    let mut context = Context::new();
+    context.insert("items", &[1, 2, 3]);
+    assert_eq!(render_template(tpl, &context).unwrap(), "3");
+}
+
+#[test]
+fn render_spaceless() {
+    let inputs = vec![
+        ("{% spaceless %}  <p>\n  <a>{{ name }}</a>\n</p>\n{% endspaceless %}", "<p><a>Bob Marley</a></p>"),
+        ("{% spaceless %}<p>Hello {{ name }}</p> {% endspaceless %} <p></p>", "<p>Hello Bob Marley</p> <p></p>"),
+        (
+            "{% spaceless %}<ul>\n{% for i in range(end=2) %}  <li>{{ i }}</li>\n{% endfor %}</ul>{% endspaceless %}",
+            "<ul><li>0</li><li>1</li></ul>",
+        ),
+    ];
+
+    let mut context = Context::new();
+    context.insert("name", "Bob Marley");
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_none() {
+    let mut context = Context::new();
+    context.insert::<Option<usize>, _>("maybe", &None);
+    context.insert("age", &18);
+
+    let inputs = vec![
+        ("{{ none }}", ""),
+        ("{{ maybe }}", ""),
+        ("{% if maybe == none %}null{% endif %}", "null"),
+        ("{% if age != none %}not null{% endif %}", "not null"),
+        ("{% if none %}truthy{% else %}falsy{% endif %}", "falsy"),
+        ("{{ none | default(value=1) }}", "1"),
+        ("{{ maybe | default(value=1) }}", "1"),
+        ("{% set a = none %}{% if a is none %}null{% endif %}", "null"),
+        ("{{ [1, none] | json_encode() | safe }}", "[1,null]"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+
+    assert!(render_template("{{ none + 1 }}", &context).is_err());
+}
+
+#[test]
+fn render_optional_access() {
+    let mut context = Context::new();
+    context.insert("page", &json!({"title": "Home", "extra": {"author": "Bob"}, "tags": ["a"]}));
+
+    let inputs = vec![
+        ("{{ page?.extra.author }}", "Bob"),
+        ("{{ page?.description }}", ""),
+        ("{{ page?.meta.image.url }}", ""),
+        ("{{ page.extra?.avatar.url }}", ""),
+        ("{{ page?.meta.image | default(value='none.png') }}", "none.png"),
+        ("{% if page?.meta.image is defined %}yes{% else %}no{% endif %}", "no"),
+        ("{% if page?.meta.image %}yes{% else %}no{% endif %}", "no"),
+        ("{{ page?.tags[0] }}", "a"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+
+    // Only what comes after `?.` can be missing
+    assert!(render_template("{{ site?.title }}", &context).is_err());
+    assert!(render_template("{{ page.meta?.title }}", &context).is_err());
+}
+
+#[test]
+fn render_coalesce() {
+    let mut context = Context::new();
+    context.insert("user", &json!({"name": "<Bob>", "nickname": null, "age": 30}));
+
+    let inputs = vec![
+        ("{{ user.nickname ?? user.name ?? 'anonymous' }}", "&lt;Bob&gt;"),
+        ("{{ user.surname ?? user.nickname ?? 'anonymous' }}", "anonymous"),
+        ("{{ guest.name ?? user.name | safe }}", "<Bob>"),
+        ("{{ user.age ?? 0 + 1 }}", "30"),
+        ("{{ user.nickname ?? none }}", ""),
+        ("{% if user.nickname ?? false %}yes{% else %}no{% endif %}", "no"),
+        ("{% if user.age ?? 0 > 18 %}yes{% else %}no{% endif %}", "yes"),
+        ("{% set name = user.nickname ?? 'x' ~ 'y' %}{{ name }}", "xy"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+
+    // The last operand still needs to be defined
+    assert!(render_template("{{ user.nickname ?? guest.name }}", &context).is_err());
+}
+
+#[test]
+fn render_out_of_bounds_subscripts() {
+    let mut context = Context::new();
+    context.insert("items", &[1, 2, 3]);
+    context.insert("user", &json!({"name": "Bob"}));
+    context.insert("i", &10);
+
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("index", "{{ items[10] }}|{{ items[i] }}|{{ user['nickname'] }}|{{ items[1] }}"),
+        ("default", "{{ items[10] | default(value='none') }}"),
+        ("missing_array", "{{ others[0] }}"),
+        ("missing_index", "{{ items[j] }}"),
+    ])
+    .unwrap();
+
+    assert!(tera.render("index", &context).is_err());
+    assert_eq!(tera.render("default", &context).unwrap(), "none");
+
+    let options =
+        RenderOptions { out_of_bounds: Some(OutOfBounds::Undefined), ..RenderOptions::default() };
+    assert_eq!(tera.render_with_options("index", &context, &options).unwrap(), "|||2");
+    assert_eq!(tera.render_with_options("default", &context, &options).unwrap(), "none");
+    assert!(tera.render_with_options("missing_array", &context, &options).is_err());
+    assert!(tera.render_with_options("missing_index", &context, &options).is_err());
+
+    tera.set_out_of_bounds(OutOfBounds::Undefined);
+    assert_eq!(tera.render("index", &context).unwrap(), "|||2");
+    let options =
+        RenderOptions { out_of_bounds: Some(OutOfBounds::Error), ..RenderOptions::default() };
+    assert!(tera.render_with_options("index", &context, &options).is_err());
+}
+
+#[test]
+fn render_minified_html() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "page.html",
+            "<ul>\n  {% for i in items %}\n  <li>{{ i }}</li> <!-- item -->\n  {% endfor %}\n</ul>",
+        ),
+        ("page.txt", "a  b"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("items", &[1, 2]);
+
+    let full = "<ul>\n  \n  <li>1</li> <!-- item -->\n  \n  <li>2</li> <!-- item -->\n  \n</ul>";
+    assert_eq!(tera.render("page.html", &context).unwrap(), full);
+    let options = RenderOptions { minify_html: Some(true), ..RenderOptions::default() };
+    let minified = "<ul> <li>1</li> <li>2</li> </ul>";
+    assert_eq!(tera.render_with_options("page.html", &context, &options).unwrap(), minified);
+
+    tera.set_minify_html(true);
+    assert_eq!(tera.render("page.html", &context).unwrap(), minified);
+    assert_eq!(tera.render("page.txt", &context).unwrap(), "a  b");
+    let options = RenderOptions { minify_html: Some(false), ..RenderOptions::default() };
+    assert_eq!(tera.render_with_options("page.html", &context, &options).unwrap(), full);
+}
+
+#[test]
+fn render_with_per_render_options() {
+    let mut tera = Tera::default();
+    tera.set_translator(|lang: Option<&str>, message: &str| match (lang, message) {
+        (Some("fr"), "Hello") => Some("Bonjour".to_string()),
+        _ => None,
+    });
+    tera.add_raw_templates(vec![
+        ("missing.html", "[{{ user.name }}]"),
+        ("escape.txt", "{{ html }}"),
+        ("loops.html", "{% for i in items %}{% for j in items %}.{% endfor %}{% endfor %}"),
+        ("macros.html", "{% macro count(n) %}{{ n }}{% if n > 0 %}{{ self::count(n=n - 1) }}{% endif %}{% endmacro %}"),
+        ("depth.html", "{% import \"macros.html\" as m %}{{ m::count(n=2) }}"),
+        ("locale.html", "{% trans %}Hello{% endtrans %} {{ 1234.5 | num_format }}"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("html", "<b>");
+    context.insert("items", &[1, 2, 3]);
+    let render = |name: &str, options: &RenderOptions| {
+        tera.render_with_options(name, &context, options)
+            .map_err(|e| e.source().unwrap().to_string())
+    };
+    let default = RenderOptions::default();
+
+    assert!(render("missing.html", &default).is_err());
+    let lenient = RenderOptions { strict: Some(false), ..RenderOptions::default() };
+    assert_eq!(render("missing.html", &lenient).unwrap(), "[]");
+
+    assert_eq!(render("escape.txt", &default).unwrap(), "<b>");
+    let escaped = RenderOptions { autoescape: Some(true), ..RenderOptions::default() };
+    assert_eq!(render("escape.txt", &escaped).unwrap(), "&lt;b&gt;");
+
+    let limited = RenderOptions { max_loop_iterations: Some(12), ..RenderOptions::default() };
+    assert_eq!(render("loops.html", &limited).unwrap(), ".........");
+    let limited = RenderOptions { max_loop_iterations: Some(11), ..RenderOptions::default() };
+    assert_eq!(
+        render("loops.html", &limited).unwrap_err(),
+        "Reached the maximum of 11 loop iterations of the render"
+    );
+
+    let limited = RenderOptions { max_depth: Some(3), ..RenderOptions::default() };
+    assert_eq!(render("depth.html", &limited).unwrap(), "210");
+    let limited = RenderOptions { max_depth: Some(2), ..RenderOptions::default() };
+    assert!(render("depth.html", &limited).is_err());
+
+    assert_eq!(render("locale.html", &default).unwrap(), "Hello 1,234.5");
+    let french = RenderOptions { locale: Some("fr".to_string()), ..RenderOptions::default() };
+    assert_eq!(render("locale.html", &french).unwrap(), "Bonjour 1\u{202f}234,5");
+}
+
+#[cfg(feature = "builtins-date")]
+#[test]
+fn render_with_timezone_option() {
+    let mut tera = Tera::default();
+    tera.add_raw_template("date", "{{ 0 | date(format=\"%H:%M\") }}").unwrap();
+    let options =
+        RenderOptions { timezone: Some("Europe/Paris".to_string()), ..RenderOptions::default() };
+    assert_eq!(tera.render("date", &Context::new()).unwrap(), "00:00");
+    assert_eq!(tera.render_with_options("date", &Context::new(), &options).unwrap(), "01:00");
+}
+
+#[test]
+fn render_with_post_processors() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("nav.html", "<a href=\"/static/logo.png\">"),
+        ("page.html", "{% include \"nav.html\" %}\n\n<img src=\"/static/a.png\">"),
+    ])
+    .unwrap();
+    tera.set_minify_html(true);
+    tera.register_post_processor(|output: &str, _: &TemplateMeta| {
+        output.replace("/static/", "https://cdn.example.com/")
+    });
+    tera.register_post_processor(|output: &str, template: &TemplateMeta| {
+        format!("<!-- {} {} -->{}", template.name, template.autoescape, output)
+    });
+
+    assert_eq!(
+        tera.render("page.html", &Context::new()).unwrap(),
+        "<!-- page.html true --><a href=\"https://cdn.example.com/logo.png\"> <img src=\"https://cdn.example.com/a.png\">"
+    );
+}
+
 #[test]
 fn render_tests() {
     let mut context = Context::new();
@@ -366,17 +906,124 @@ fn render_tests() {
         ("{% if numbers is iterable %}Admin{% endif %}", "Admin"),
         ("{% if map is iterable %}Admin{% endif %}", "Admin"),
         ("{% if map is object %}Admin{% endif %}", "Admin"),
+        ("{% if map is mapping %}Admin{% endif %}", "Admin"),
+        ("{% if numbers is array %}Admin{% endif %}", "Admin"),
+        ("{% if map is array %}Admin{% else %}map{% endif %}", "map"),
+        ("{% if is_false is boolean %}Admin{% endif %}", "Admin"),
         ("{% if name is starting_with('j') %}Admin{% endif %}", "Admin"),
         ("{% if name is ending_with('n') %}Admin{% endif %}", "Admin"),
         ("{% if numbers is containing(2) %}Admin{% endif %}", "Admin"),
         ("{% if name is matching('^j.*') %}Admin{% endif %}", "Admin"),
         ("{% if maybe is defined %}Admin{% endif %}", "Admin"),
+        ("{% if maybe is none %}Admin{% endif %}", "Admin"),
+        ("{% if hello is none %}Admin{% else %}undefined{% endif %}", "undefined"),
+        ("{% if age is not none %}Admin{% endif %}", "Admin"),
+        ("{% if get_number() is even %}Admin{% endif %}", "Admin"),
+        ("{% if get_string() is not starting_with('W') %}Admin{% endif %}", "Admin"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_function_calls_in_expressions() {
+    let mut context = Context::new();
+    context.insert("age", &18);
+
+    let inputs = vec![
+        ("{% if get_number() < age %}yes{% endif %}", "yes"),
+        ("{% set n = get_number() * 2 %}{{ n }}", "20"),
+        ("{% if 'ell' in get_string() %}yes{% endif %}", "yes"),
+        ("{% if 'x' not in get_string() %}yes{% endif %}", "yes"),
+        ("{{ get_number() is odd or get_true() }}", "true"),
+        ("{% for i in range(end=get_number() // 5) %}{{ i }}{% endfor %}", "01"),
+        ("{{ get_string() ~ get_number() }}", "Hello10"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_truthiness() {
+    let mut context = Context::new();
+    context.insert("zero", &0);
+    context.insert("zero_float", &0.0);
+    context.insert("empty_array", &Vec::<i32>::new());
+    context.insert("empty_map", &HashMap::<String, i32>::new());
+    context.insert("numbers", &vec![1, 2, 3]);
+    context.insert::<Option<i32>, _>("null", &None);
+
+    let falsy = vec![
+        "zero",
+        "zero_float",
+        "empty_array",
+        "empty_map",
+        "null",
+        "none",
+        "range(end=0)",
+        "numbers | slice(end=0)",
+        "not numbers",
+        "not range(end=1)",
+    ];
+    let truthy = vec!["numbers", "range(end=1)", "not empty_map", "not range(end=0)"];
+
+    for cond in falsy {
+        let input = format!("{{% if {} %}}truthy{{% else %}}falsy{{% endif %}}", cond);
+        println!("{:?}", input);
+        assert_eq!(render_template(&input, &context).unwrap(), "falsy");
+    }
+    for cond in truthy {
+        let input = format!("{{% if {} %}}truthy{{% else %}}falsy{{% endif %}}", cond);
+        println!("{:?}", input);
+        assert_eq!(render_template(&input, &context).unwrap(), "truthy");
+    }
+}
+
+#[test]
+fn render_comparisons() {
+    let mut context = Context::new();
+    context.insert("name", "<b>bob</b>");
+    context.insert("int", &2);
+    context.insert("float", &2.0);
+    context.insert::<Option<i32>, _>("null", &None);
+
+    let inputs = vec![
+        // numbers, ints and floats alike
+        ("{{ int == float }}", "true"),
+        ("{{ int < 2.5 }}", "true"),
+        ("{{ float >= int }}", "true"),
+        ("{{ 1 + 1.5 > int }}", "true"),
+        // strings, lexicographically
+        ("{{ 'abc' < 'abd' }}", "true"),
+        ("{{ 'b' > 'abc' }}", "true"),
+        ("{{ 'B' < 'a' }}", "true"),
+        ("{{ 'abc' <= 'abc' }}", "true"),
+        ("{{ name == '<b>bob</b>' }}", "true"),
+        ("{{ name | upper > name }}", "false"),
+        // different types are never equal
+        ("{{ int == '2' }}", "false"),
+        ("{{ int != '2' }}", "true"),
+        ("{{ true == 1 }}", "false"),
+        ("{{ null == 0 }}", "false"),
+        ("{{ null == none }}", "true"),
     ];
 
     for (input, expected) in inputs {
         println!("{:?} -> {:?}", input, expected);
         assert_eq!(render_template(input, &context).unwrap(), expected);
     }
+
+    // but ordering them is an error
+    for input in &["{{ int < '3' }}", "{{ true > false }}", "{{ null < 1 }}", "{{ 0 / 0 < 1 }}"] {
+        println!("{:?}", input);
+        assert!(render_template(input, &context).is_err());
+    }
 }
 
 #[test]
@@ -414,8 +1061,6 @@ fn render_if_elif_else() {
         ("{% if not undefined %}a{% endif %}", "a"),
         ("{% if not is_false and is_true %}a{% endif %}", "a"),
         ("{% if not is_false or numbers | length > 0 %}a{% endif %}", "a"),
-        // doesn't panic with NaN results
-        ("{% if 0 / 0 %}a{% endif %}", ""),
         // if and else
         ("{% if is_true %}Admin{% else %}User{% endif %}", "Admin"),
         ("{% if is_false %}Admin{% else %}User{% endif %}", "User"),
@@ -446,6 +1091,80 @@ fn render_if_elif_else() {
     }
 }
 
+#[test]
+fn render_switch() {
+    let mut context = Context::new();
+    context.insert("status", &"shipped");
+    context.insert("count", &2);
+    context.insert("other", &"draft");
+
+    let inputs = vec![
+        ("{% switch status %}{% case 'draft' %}D{% case 'shipped' %}S{% endswitch %}", "S"),
+        ("{% switch status %}{% case 'draft', 'shipped' %}D or S{% endswitch %}", "D or S"),
+        ("{% switch status %}{% case 'draft' %}D{% default %}?{% endswitch %}", "?"),
+        ("{% switch status %}{% case 'draft' %}D{% endswitch %}", ""),
+        ("{% switch status %}{% endswitch %}", ""),
+        // numbers are compared like with `==`
+        ("{% switch count %}{% case 2.0 %}two{% default %}?{% endswitch %}", "two"),
+        ("{% switch count %}{% case '2' %}two{% default %}?{% endswitch %}", "?"),
+        // cases can be expressions and only the first match is rendered
+        ("{% switch count %}{% case 1 + 1 %}a{% case 2 %}b{% endswitch %}", "a"),
+        ("{% switch 'draft' %}{% case status %}S{% case other %}D{% endswitch %}", "D"),
+        ("{% switch status %}\n  {# hey #}\n  {% case 'shipped' %}S{% endswitch %}", "S"),
+        (
+            "{% for i in [1, 2, 3] %}{% switch i %}{% case 2 %}{% continue %}{% default %}{{ i }}{% endswitch %}{% endfor %}",
+            "13",
+        ),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_while() {
+    let mut context = Context::new();
+    context.insert("queue", &vec![1, 2, 3]);
+
+    let inputs = vec![
+        ("{% set i = 0 %}{% while i < 3 %}{{ i }}{% set i = i + 1 %}{% endwhile %}", "012"),
+        ("{% while false %}never{% endwhile %}", ""),
+        (
+            "{% while queue %}{{ queue | first }}{% set queue = queue | slice(start=1) %}{% endwhile %}",
+            "123",
+        ),
+        (
+            "{% for n in [2, 3] %}{% set i = 0 %}{% while i < n %}{{ n }}{% set i = i + 1 %}{% endwhile %}{% endfor %}",
+            "22333",
+        ),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn while_loop_stops_after_max_iterations() {
+    let mut tera = Tera::default();
+    tera.add_raw_template("while.html", "{% while true %}.{% endwhile %}").unwrap();
+    let err = tera.render("while.html", &Context::new()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Reached the maximum of 10000 iterations of a while loop, its condition is probably never false"
+    );
+
+    let options = RenderOptions { max_loop_iterations: Some(5), ..RenderOptions::default() };
+    let err = tera.render_with_options("while.html", &Context::new(), &options).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Reached the maximum of 5 loop iterations of the render"
+    );
+}
+
 #[test]
 fn render_for() {
     let mut context = Context::new();
@@ -537,6 +1256,119 @@ fn render_for() {
     }
 }
 
+#[test]
+fn render_for_with_condition() {
+    let mut context = Context::new();
+    let mut map = BTreeMap::new();
+    map.insert("a", 1);
+    map.insert("b", 2);
+    map.insert("c", 3);
+    context.insert("data", &vec![1, 2, 3, 4, 5]);
+    context.insert("map", &map);
+
+    let inputs = vec![
+        ("{% for i in data if i is odd %}{{ i }}{% endfor %}", "135"),
+        (
+            "{% for i in data if i > 2 %}{{ loop.index }}/{{ loop.length }}{% if loop.last %}!{% endif %} {% endfor %}",
+            "1/3 2/3 3/3! ",
+        ),
+        ("{% for k, v in map if v != 2 %}{{ k }}{{ loop.index }}{% endfor %}", "a1c2"),
+        ("{% for i in data | reverse if i < 3 %}{{ i }}{% endfor %}", "21"),
+        ("{% for i in [1, 2, 3] if i == 2 %}{{ i }}{% endfor %}", "2"),
+        ("{% for i in data if i > 10 %}{{ i }}{% else %}none{% endfor %}", "none"),
+        // The condition is evaluated before the body so `continue` doesn't affect it
+        ("{% for i in data if i is even %}{% if i == 2 %}{% continue %}{% endif %}{{ i }}{% endfor %}", "4"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_for_sorted_map() {
+    let mut context = Context::new();
+    context.insert("map", &json!({"b": 2, "c": 3, "a": 1}));
+
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("map", "{% for k, v in map %}{{ k }}{% endfor %}"),
+        ("sorted", "{% for k, v in map sorted if v != 2 %}{{ k }}{% endfor %}"),
+    ])
+    .unwrap();
+
+    // The order of the map itself depends on the `preserve_order` feature
+    let map_order = if cfg!(feature = "preserve_order") { "bca" } else { "abc" };
+    assert_eq!(tera.render("map", &context).unwrap(), map_order);
+    assert_eq!(tera.render("sorted", &context).unwrap(), "ac");
+
+    let options = RenderOptions { map_order: Some(MapOrder::Sorted), ..RenderOptions::default() };
+    assert_eq!(tera.render_with_options("map", &context, &options).unwrap(), "abc");
+
+    tera.set_map_order(MapOrder::Sorted);
+    assert_eq!(tera.render("map", &context).unwrap(), "abc");
+    let options =
+        RenderOptions { map_order: Some(MapOrder::Insertion), ..RenderOptions::default() };
+    assert_eq!(tera.render_with_options("map", &context, &options).unwrap(), map_order);
+}
+
+#[test]
+fn render_for_with_pairs() {
+    let mut context = Context::new();
+    // Tuples are serialized as arrays
+    context.insert("pairs", &vec![("a", 1), ("b", 2)]);
+    context.insert("people", &vec![(json!({"name": "Bob"}), 10)]);
+
+    let inputs = vec![
+        ("{% for name, score in pairs %}{{ name }}={{ score }} {% endfor %}", "a=1 b=2 "),
+        ("{% for person, score in people %}{{ person.name }}: {{ score }}{% endfor %}", "Bob: 10"),
+        ("{% for a, b in pairs %}{{ b * 2 }}{% endfor %}", "24"),
+        ("{% for a, b in pairs | reverse if b > 1 %}{{ a }}{{ loop.length }}{% endfor %}", "b1"),
+        ("{% for a, b in [] %}{{ a }}{% else %}empty{% endfor %}", "empty"),
+        (
+            "{% for name, score in ['x', 'y'] | zip(with=[3, 4]) %}{{ name }}{{ score }}{% endfor %}",
+            "x3y4",
+        ),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
+#[test]
+fn render_for_with_parent_loop() {
+    let mut context = Context::new();
+    context.insert("rows", &vec![vec![1, 2], vec![3, 4]]);
+
+    let inputs = vec![
+        (
+            "{% for row in rows %}{% for cell in row %}{{ loop.parent.index0 }}{{ loop.index0 }} {% endfor %}{% endfor %}",
+            "00 01 10 11 ",
+        ),
+        (
+            "{% for row in rows %}{% for cell in row %}{% if loop.parent.last and loop.last %}{{ cell }}{% endif %}{% endfor %}{% endfor %}",
+            "4",
+        ),
+        (
+            "{% for a in [1] %}{% for b in [2] %}{% for c in [3] %}{{ loop.parent.parent.index }}{% endfor %}{% endfor %}{% endfor %}",
+            "1",
+        ),
+        // The condition of a nested loop can use the parent loop too
+        (
+            "{% for row in rows %}{% for cell in row if loop.parent.first %}{{ cell }}{% endfor %}{% endfor %}",
+            "12",
+        ),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(render_template(input, &context).unwrap(), expected);
+    }
+}
+
 #[test]
 fn render_magic_variable_isnt_escaped() {
     let mut context = Context::new();
@@ -767,6 +1599,25 @@ fn render_magic_variable_gets_all_contexts() {
     );
 }
 
+#[test]
+fn render_magic_variable_uses_innermost_values() {
+    let mut context = Context::new();
+    context.insert("i", &10);
+
+    let result = render_template(
+        "{% for i in range(start=1, end=2) %}{% for i in range(start=2, end=3) %}{% with i = 3 %}{{ __tera_context }}{% endwith %}{% endfor %}{% endfor %}",
+        &context
+    );
+
+    assert_eq!(
+        result.unwrap(),
+        r#"{
+  "i": 3
+}"#
+        .to_owned()
+    );
+}
+
 #[test]
 fn render_magic_variable_macro_doesnt_leak() {
     let mut context = Context::new();
@@ -791,6 +1642,25 @@ fn render_magic_variable_macro_doesnt_leak() {
     );
 }
 
+#[test]
+fn render_debug_tag() {
+    let mut context = Context::new();
+    context.insert("user", &json!({"name": "<Bob>", "tags": ["a--b"]}));
+
+    // Escaped in a `<pre>` block in HTML templates
+    let result = render_template("{% for i in [1] %}{% debug %}{% endfor %}", &context);
+    assert_eq!(
+        result.unwrap(),
+        "<pre>{\n  &quot;i&quot;: 1,\n  &quot;user&quot;: {\n    &quot;name&quot;: &quot;&lt;Bob&gt;&quot;,\n    \
+         &quot;tags&quot;: [\n      &quot;a--b&quot;\n    ]\n  }\n}</pre>"
+    );
+
+    // In a comment otherwise
+    let mut tera = Tera::default();
+    tera.add_raw_template("hello.txt", "{%- debug user.tags -%}").unwrap();
+    assert_eq!(tera.render("hello.txt", &context).unwrap(), "<!--\n[\n  \"a- -b\"\n]\n-->");
+}
+
 // https://github.com/Keats/tera/issues/342
 #[test]
 fn redefining_loop_value_doesnt_break_loop() {
@@ -852,6 +1722,41 @@ lazy_static! {
     static ref NEXT_GLOBAL: SharedNext = SharedNext(Arc::new(Next(AtomicUsize::new(1))));
 }
 
+#[test]
+fn render_do_tag() {
+    let mut tera = Tera::default();
+    tera.add_raw_template("do.html", "{% do get_next() %}{% do get_next() + 1 %}{{ get_next() }}")
+        .unwrap();
+    tera.register_function("get_next", Next(AtomicUsize::new(1)));
+    assert_eq!(tera.render("do.html", &Context::new()).unwrap(), "3");
+}
+
+#[test]
+fn render_cache_tag() {
+    let mut tera = Tera::default();
+    tera.add_raw_template("cache.html", "{% cache 'count' ~ lang %}{{ get_next() }}{% endcache %}")
+        .unwrap();
+    tera.register_function("get_next", Next(AtomicUsize::new(1)));
+    let mut context = Context::new();
+    context.insert("lang", "en");
+
+    // Without a fragment cache, the block is rendered every time
+    assert_eq!(tera.render("cache.html", &context).unwrap(), "1");
+    assert_eq!(tera.render("cache.html", &context).unwrap(), "2");
+
+    tera.set_fragment_cache(MemoryCache::new(10));
+    assert_eq!(tera.render("cache.html", &context).unwrap(), "3");
+    assert_eq!(tera.render("cache.html", &context).unwrap(), "3");
+    context.insert("lang", "fr");
+    assert_eq!(tera.render("cache.html", &context).unwrap(), "4");
+    context.insert("lang", "en");
+    assert_eq!(tera.render("cache.html", &context).unwrap(), "3");
+
+    tera.add_raw_template("ttl.html", "{% cache 'ttl' ttl %}{% endcache %}").unwrap();
+    context.insert("ttl", &-1);
+    assert!(tera.render("ttl.html", &context).is_err());
+}
+
 #[test]
 fn stateful_global_fn() {
     fn make_tera() -> Tera {
@@ -939,3 +1844,82 @@ fn safe_function_works() {
     let res = tera.render("test.html", &Context::new());
     assert_eq!(res.unwrap(), "<div>Hello</div>");
 }
+
+#[test]
+fn can_profile_renders() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("macros", "{% macro hello(name) %}Hello {{ name }}{% endmacro %}"),
+        ("nav", "<nav>{{ macros::hello(name='nav') }}</nav>"),
+        ("base", "{% import \"macros\" as macros %}{% block content %}base{% endblock %}"),
+        (
+            "page",
+            "{% extends \"base\" %}{% import \"macros\" as macros %}\
+             {% block content %}{% include \"nav\" %}{{ super() }}{% endblock %}",
+        ),
+    ])
+    .unwrap();
+
+    let (output, profile) = tera.render_profiled("page", &Context::new()).unwrap();
+    assert_eq!(output, "<nav>Hello nav</nav>base");
+    assert_eq!(tera.render("page", &Context::new()).unwrap(), output);
+
+    let summary = |entry: &ProfileEntry| (entry.kind, entry.name.clone(), entry.bytes);
+    assert_eq!(summary(&profile), (ProfileKind::Template, "page".to_string(), output.len()));
+    let block = &profile.children[0];
+    assert_eq!(summary(block), (ProfileKind::Block, "content".to_string(), output.len()));
+    let include = &block.children[0];
+    assert_eq!(summary(include), (ProfileKind::Include, "nav".to_string(), 20));
+    assert_eq!(summary(&include.children[0]), (ProfileKind::Macro, "macros::hello".to_string(), 9));
+    assert!(profile.duration >= block.duration);
+}
+
+#[test]
+fn calls_render_hooks() {
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn record(&self, phase: HookPhase, what: &str, span: &Span, template: &str) {
+            let phase = if phase == HookPhase::Enter { ">" } else { "<" };
+            let line = format!("{}{} {}@{}", phase, what, template, span.start.offset);
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    impl RenderHooks for Arc<Recorder> {
+        fn on_node(&self, phase: HookPhase, kind: &str, span: &Span, template: &str) {
+            self.record(phase, kind, span, template);
+        }
+
+        fn on_include(&self, phase: HookPhase, name: &str, span: &Span, template: &str) {
+            self.record(phase, &format!("include {}", name), span, template);
+        }
+
+        fn on_filter(&self, phase: HookPhase, name: &str, span: &Span, template: &str) {
+            self.record(phase, &format!("filter {}", name), span, template);
+        }
+    }
+
+    let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+    let mut tera = Tera::default();
+    tera.set_render_hooks(recorder.clone());
+    tera.add_raw_templates(vec![("nav", "{{ 'a' | upper }}"), ("page", "<{% include \"nav\" %}")])
+        .unwrap();
+
+    assert_eq!(tera.render("page", &Context::new()).unwrap(), "<A");
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        vec![
+            ">text page@0",
+            "<text page@0",
+            ">include page@1",
+            ">include nav page@1",
+            ">variable nav@0",
+            ">filter upper nav@0",
+            "<filter upper nav@0",
+            "<variable nav@0",
+            "<include nav page@1",
+            "<include page@1",
+        ]
+    );
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::error::Error;
 
+use serde_json::json;
+
 use crate::context::Context;
 use crate::tera::Tera;
 
@@ -204,6 +206,37 @@ fn right_variable_name_is_needed_in_for_loop() {
     );
 }
 
+#[test]
+fn error_iterating_with_key_value_on_non_map() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("array", "{% for key, value in data %}{% endfor %}"),
+        ("string", "{% for key, value in name %}{% endfor %}"),
+        ("missing_key", "{% for value in map %}{% endfor %}"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("data", &vec![1, 2]);
+    context.insert("name", "Bob");
+    context.insert("map", &HashMap::<String, i32>::new());
+
+    let result = tera.render("array", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Tried to iterate using key value on variable `data`, but its elements aren't all arrays of 2 values"
+    );
+    let result = tera.render("string", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Tried to iterate using key value on variable `name`, but it isn't an object/map"
+    );
+    let result = tera.render("missing_key", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Tried to iterate using key value on variable `map`, but it is missing a key"
+    );
+}
+
 // https://github.com/Keats/tera/issues/370#issuecomment-453893826
 #[test]
 fn errors_when_calling_macros_defined_in_file() {
@@ -250,6 +283,31 @@ fn errors_with_inheritance_in_included_template() {
     );
 }
 
+#[test]
+fn errors_with_invalid_dynamic_parent() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("a", "{% extends parent %}{% block title %}A{% endblock %}"),
+        ("b", "{% extends \"a\" %}"),
+    ])
+    .unwrap();
+
+    let inputs = vec![
+        (json!(1), "Tried to extend `1` in 'a' but only template names (strings) can be extended"),
+        (json!("c"), "Template 'a' is inheriting from 'c', which doesn't exist or isn't loaded."),
+        (
+            json!("b"),
+            "Circular extend detected for template 'a'. Inheritance chain: `[\"b\", \"a\"]`",
+        ),
+    ];
+    for (parent, expected) in inputs {
+        let mut context = Context::new();
+        context.insert("parent", &parent);
+        let err = tera.render("a", &context).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
 #[test]
 fn error_string_concat_math_logic() {
     let mut tera = Tera::default();
@@ -261,7 +319,78 @@ fn error_string_concat_math_logic() {
 
     assert_eq!(
         result.unwrap_err().source().unwrap().to_string(),
-        "Tried to do math with a string concatenation: 'ho' ~ name"
+        "Tried to compare \"hojohn\" with 10, but only two numbers or two strings can be compared"
+    );
+}
+
+#[test]
+fn error_division_by_zero() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("int", "{{ 1 / 0 }}"),
+        ("float", "{% if 1.5 / zero %}{% endif %}"),
+        ("modulo", "{% set a = 1.5 % zero %}"),
+        ("floor", "{{ 1 // 0 }}"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("zero", &0.0);
+
+    let result = tera.render("int", &context);
+    assert_eq!(result.unwrap_err().source().unwrap().to_string(), "Tried to divide 1 by zero");
+    let result = tera.render("float", &context);
+    assert_eq!(result.unwrap_err().source().unwrap().to_string(), "Tried to divide 1.5 by zero");
+    assert!(tera.render("modulo", &context).is_err());
+    let result = tera.render("floor", &context);
+    assert_eq!(result.unwrap_err().source().unwrap().to_string(), "Tried to divide 1 by zero");
+}
+
+#[test]
+fn error_integer_overflow() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![("mul", "{{ big * big }}"), ("sub", "{{ 0 - huge - huge }}")])
+        .unwrap();
+    let mut context = Context::new();
+    context.insert("big", &i64::max_value());
+    context.insert("huge", &u64::max_value());
+
+    let result = tera.render("mul", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "9223372036854775807 * 9223372036854775807 results in an out of bounds integer"
+    );
+    let result = tera.render("sub", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "0 - 18446744073709551615 results in an out of bounds integer"
+    );
+}
+
+#[test]
+fn error_math_with_string() {
+    let mut tera = Tera::default();
+    tera.add_raw_template("tpl", "{{ 1 + name }}").unwrap();
+    let mut context = Context::new();
+    context.insert("name", "john");
+
+    let result = tera.render("tpl", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Variable `name` was used in a math operation but is not a number"
+    );
+}
+
+#[test]
+fn error_adding_array_and_number() {
+    let mut tera = Tera::default();
+    tera.add_raw_template("tpl", "{{ numbers + 1 }}").unwrap();
+    let mut context = Context::new();
+    context.insert("numbers", &[1, 2]);
+
+    let result = tera.render("tpl", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Tried to add 1 to [1,2]: arrays can only be added to arrays and objects to objects"
     );
 }
 
@@ -282,3 +411,26 @@ fn error_gives_source_on_tests() {
         "Tester `undefined` was called with some args but this test doesn\'t take args"
     );
 }
+
+#[test]
+fn error_set_attribute_of_undefined_namespace() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("undefined", "{% set ns.total = 1 %}"),
+        ("not_object", "{% set ns = 1 %}{% set ns.total = 1 %}"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("ns", &HashMap::<String, i32>::new());
+
+    let result = tera.render("undefined", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Tried to set `ns.total` but `ns` isn't assigned in the template: use `{% set ns = namespace() %}` first"
+    );
+    let result = tera.render("not_object", &context);
+    assert_eq!(
+        result.unwrap_err().source().unwrap().to_string(),
+        "Tried to set `ns.total` but `ns` is not an object"
+    );
+}
@@ -174,3 +174,51 @@ fn render_super_in_grandchild_without_redefining_in_parent_works() {
     let result = tera.render("child", &Context::new());
     assert_eq!(result.unwrap(), "Title - More".to_string());
 }
+
+#[test]
+fn render_single_block() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("macros", "{% macro hello(name) %}Hello {{ name }}{% endmacro hello %}"),
+        ("grandparent", "{% block hey %}hello{% endblock hey %}{% block footer %}footer{% endblock footer %}"),
+        ("parent", "{% extends \"grandparent\" %}{% block hey %}hi {% block name %}{% endblock name %}{% endblock hey %}"),
+        ("child", "{% extends \"parent\" %}{% import \"macros\" as macros %}{% block hey %}{{ super() }}!{% endblock hey %}{% block name %}{{ macros::hello(name=name) }}{% endblock name %}"),
+    ])
+    .unwrap();
+    let mut context = Context::new();
+    context.insert("name", &"Bob");
+
+    assert_eq!(tera.render_block("child", "hey", &context).unwrap(), "hi Hello Bob!");
+    assert_eq!(tera.render_block("child", "name", &context).unwrap(), "Hello Bob");
+    assert_eq!(tera.render_block("child", "footer", &context).unwrap(), "footer");
+    assert_eq!(tera.render_block("parent", "hey", &context).unwrap(), "hi ");
+
+    let err = tera.render_block("child", "nope", &context).unwrap_err();
+    assert_eq!(err.to_string(), "Block `nope` not found in template 'child'");
+}
+
+#[test]
+fn render_dynamic_inheritance() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("default/base", "{% block main %}default{% endblock main %}"),
+        ("dark/base", "[{% block main %}dark{% endblock main %}]"),
+        ("layout", "{% extends theme ~ \"/base\" %}{% block main %}{{ super() }} layout{% endblock main %}"),
+        ("page", "{% extends parent %}{% block main %}{{ super() }} page{% endblock main %}"),
+    ])
+    .unwrap();
+
+    let inputs = vec![
+        ("default/base", "default", "default page", "default page"),
+        ("dark/base", "default", "[dark page]", "dark page"),
+        ("layout", "default", "default layout page", "default layout page"),
+        ("layout", "dark", "[dark layout page]", "dark layout page"),
+    ];
+    for (parent, theme, expected, expected_block) in inputs {
+        let mut context = Context::new();
+        context.insert("parent", parent);
+        context.insert("theme", theme);
+        assert_eq!(tera.render("page", &context).unwrap(), expected);
+        assert_eq!(tera.render_block("page", "main", &context).unwrap(), expected_block);
+    }
+}
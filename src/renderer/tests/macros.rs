@@ -139,6 +139,48 @@ fn render_macros_override_default_args() {
     assert_eq!(result.unwrap(), "2".to_string());
 }
 
+#[test]
+fn render_macros_with_positional_args() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "forms",
+            "{% macro input(name, type=\"text\", size=10) %}{{name}}:{{type}}:{{size}}{% endmacro input %}",
+        ),
+        (
+            "hello.html",
+            "{% import \"forms\" as forms %}{{ forms::input(\"email\") }} {{ forms::input(\"pw\", \"password\", size=4) }}",
+        ),
+    ])
+    .unwrap();
+    let result = tera.render("hello.html", &Context::new());
+
+    assert_eq!(result.unwrap(), "email:text:10 pw:password:4".to_string());
+}
+
+#[test]
+fn error_macros_with_too_many_or_duplicate_positional_args() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("macros", "{% macro hello(val) %}{{val}}{% endmacro hello %}"),
+        ("too_many", "{% import \"macros\" as macros %}{{ macros::hello(1, 2) }}"),
+        ("duplicate", "{% import \"macros\" as macros %}{{ macros::hello(1, val=2) }}"),
+    ])
+    .unwrap();
+
+    let err = tera.render("too_many", &Context::new()).unwrap_err();
+    let reason = std::error::Error::source(&err).unwrap().to_string();
+    assert!(
+        reason.contains("takes 1 argument(s) but 2 positional argument(s) were given"),
+        "{}",
+        reason
+    );
+
+    let err = tera.render("duplicate", &Context::new()).unwrap_err();
+    let reason = std::error::Error::source(&err).unwrap().to_string();
+    assert!(reason.contains("got the argument `val` both by position and by name"), "{}", reason);
+}
+
 #[test]
 fn render_recursive_macro() {
     let mut tera = Tera::default();
@@ -354,3 +396,40 @@ fn parent_macro_cant_access_child_macro_context() {
     let result = tera.render("parent", &Context::new());
     assert_eq!(result.unwrap(), "ACAB-ACAB".to_string());
 }
+
+#[test]
+fn render_call_blocks() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        (
+            "macros",
+            "{% macro card(title) %}<div><h2>{{ title }}</h2>{{ caller() }}</div>{% endmacro card %}\
+             {% macro twice() %}{{ caller() }}{{ caller() }}{% endmacro twice %}\
+             {% macro framed() %}[{% call self::twice() %}{{ caller() }}{% endcall %}]{% endmacro framed %}",
+        ),
+        (
+            "tpl.html",
+            "{% import \"macros\" as macros %}{% set name = \"<Bob>\" %}\
+             {% call macros::card(title=\"Hi\") %}Hello {{ name }}{% endcall %}\
+             {% call macros::framed() %}{{ name | length }}{% endcall %}",
+        ),
+    ])
+    .unwrap();
+
+    let result = tera.render("tpl.html", &Context::new());
+    assert_eq!(result.unwrap(), "<div><h2>Hi</h2>Hello &lt;Bob&gt;</div>[55]");
+}
+
+#[test]
+fn error_caller_outside_call_block() {
+    let mut tera = Tera::default();
+    tera.add_raw_templates(vec![
+        ("macros", "{% macro hello() %}{{ caller() }}{% endmacro hello %}"),
+        ("tpl", "{% import \"macros\" as macros %}{{ macros::hello() }}"),
+    ])
+    .unwrap();
+
+    let err = tera.render("tpl", &Context::new()).unwrap_err();
+    let reason = std::error::Error::source(&err).unwrap().to_string();
+    assert!(reason.contains("Function call 'caller' failed"), "{}", reason);
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::error::Error;
 
 use crate::context::Context;
 use crate::tera::Tera;
@@ -92,3 +93,63 @@ fn var_access_by_loop_index_with_set() {
     );
     assert!(res.is_ok());
 }
+
+#[test]
+fn var_access_by_slices_and_negative_indices() {
+    let mut context = Context::new();
+    context.insert(
+        "var",
+        &Test { a: "hi".into(), b: "i_am_actually_b".into(), c: vec!["fred".into()] },
+    );
+    context.insert("items", &vec![1, 2, 3, 4]);
+    context.insert("title", "Héllo wörld");
+    context.insert("two", &2);
+    context.insert("users", &vec![Test { a: "a".into(), b: "b".into(), c: vec![] }]);
+
+    let inputs = vec![
+        ("{{ items[-1] }}", "4"),
+        ("{{ items[-4] }}", "1"),
+        ("{{ items[2:] }}", "[3, 4]"),
+        ("{{ items[:2] }}", "[1, 2]"),
+        ("{{ items[1:-1] }}", "[2, 3]"),
+        ("{{ items[-2:] }}", "[3, 4]"),
+        ("{{ items[:] }}", "[1, 2, 3, 4]"),
+        ("{{ items[3:1] }}", "[]"),
+        ("{{ items[:100] }}", "[1, 2, 3, 4]"),
+        ("{{ items[two:] }}", "[3, 4]"),
+        ("{{ items[1:][0] }}", "2"),
+        ("{{ items[1:][-1:] }}", "[4]"),
+        ("{{ title[:5] }}", "Héllo"),
+        ("{{ title[-5:] }}", "wörld"),
+        ("{{ var.c[-1] }}", "fred"),
+        ("{{ users[-1].a }}", "a"),
+        ("{{ users[-1]['b'] }}", "b"),
+        ("{{ items[-1] + 1 }}", "5"),
+        ("{% for i in items[:2] %}{{ i }}{% endfor %}", "12"),
+    ];
+
+    for (input, expected) in inputs {
+        println!("{:?} -> {:?}", input, expected);
+        assert_eq!(Tera::one_off(input, &context, true).unwrap(), expected);
+    }
+}
+
+#[test]
+fn var_access_by_slices_errors() {
+    let mut context = Context::new();
+    context.insert("items", &vec![1, 2, 3]);
+    context.insert("name", "a");
+
+    let err = Tera::one_off("{{ items[-4] }}", &context, true).unwrap_err();
+    assert!(err.source().unwrap().to_string().contains("Maybe the index is out of bounds?"));
+    let err = Tera::one_off("{{ items[name:] }}", &context, true).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "The bounds of a slice have to be integers (`name` of `[name:]`)"
+    );
+    let err = Tera::one_off("{{ items[0][1:] }}", &context, true).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Only arrays and strings can be sliced (tried to use `[1:]` on `1`)"
+    );
+}
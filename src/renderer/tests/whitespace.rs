@@ -15,6 +15,9 @@ fn can_remove_whitespace_basic() {
         ("  {%- if false -%}\n {{numbers}}\n {% else -%} Nope {%- endif -%} ", "Nope"),
         ("  {%- if false -%}\n {{numbers}}\n {% elif true -%} Nope {%- endif -%} ", "Nope"),
         ("  {%- if false -%}\n {{numbers}}\n {% elif false -%} Nope {% else %} else {%- endif -%} ", " else"),
+        ("  {%- switch 1 %}\n {% case 1 -%} one {%- default -%} x {%- endswitch -%} ", "one"),
+        (" {% switch 2 %} {% case 1 %}a{% default %} b {%- endswitch %} ", "  b "),
+        ("{% set n = 0 %} {%- while n < 2 -%} {{n}} {%- set n = n + 1 %} {%- endwhile %}", "01"),
         ("  {%- set var = 2 -%} {{var}}", "2"),
         ("  {% set var = 2 -%} {{var}}", "  2"),
         (" {% raw -%} {{2}} {% endraw -%} ", " {{2}} "),
@@ -111,3 +114,24 @@ fn make_sure_not_to_delete_whitespaces() {
     let res = Tera::one_off(input, &context, true).unwrap();
     assert_eq!(res, "    yaml_test:     ");
 }
+
+#[test]
+fn can_remove_whitespace_between_tags() {
+    let mut context = Context::new();
+    context.insert("numbers", &vec![1, 2, 3]);
+
+    let inputs = vec![
+        ("{% for n in numbers %}\n  {% if n > 1 %}\n    {% set a = n %}\n  {% endif %}\n{% endfor %}", ""),
+        ("<ul>\n  {% for n in numbers %}\n    <li>{{ n }}</li>\n  {% endfor %}\n</ul>", "<ul>\n  \n    <li>1</li>\n  \n    <li>2</li>\n  \n    <li>3</li>\n  \n</ul>"),
+        ("{% set a = 1 %}\n{# comment #}\n{% set b = 2 %}\n{{ a }} {{ b }}\n", "\n1 2\n"),
+        ("{% if true %} {% raw %} {% endraw %} {% endif %}", "   "),
+        ("  {% set a = 1 %}  ", "    "),
+    ];
+
+    for (input, expected) in inputs {
+        let mut tera = Tera::default();
+        tera.set_remove_whitespace_between_tags(true);
+        tera.add_raw_template("tpl", input).unwrap();
+        assert_eq!(tera.render("tpl", &context).unwrap(), expected);
+    }
+}
@@ -0,0 +1,140 @@
+//! The tags registered by the application with `Tera::register_tag`
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::errors::Result;
+
+/// A tag defined by the application: `{% widget name="sidebar" %}`, or
+/// `{% widget name="sidebar" %}...{% endwidget %}` if it takes a body.
+///
+/// The arguments are parsed like the ones of a function call and are given evaluated to `render`,
+/// along with the rendered body if there is one. What `render` returns is not escaped.
+pub trait Tag: Sync + Send {
+    /// Checks a use of the tag, given the names of its arguments and whether it has a body.
+    /// Called by `Tera::check` and before every render of the tag, accepts everything by default.
+    fn check(&self, _args: &[&str], _has_body: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Renders the tag with its evaluated arguments and its rendered body, if it has one
+    fn render(&self, args: &HashMap<String, Value>, body: Option<&str>) -> Result<String>;
+}
+
+impl<F> Tag for F
+where
+    F: Fn(&HashMap<String, Value>, Option<&str>) -> Result<String> + Sync + Send,
+{
+    fn render(&self, args: &HashMap<String, Value>, body: Option<&str>) -> Result<String> {
+        self(args, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    use super::Tag;
+    use crate::context::Context;
+    use crate::errors::{Error, Result};
+    use crate::tera::Tera;
+
+    struct Widget;
+
+    impl Tag for Widget {
+        fn check(&self, args: &[&str], has_body: bool) -> Result<()> {
+            if !args.contains(&"name") {
+                return Err(Error::msg("Tag `widget` expected an arg called `name`"));
+            }
+            if has_body {
+                return Err(Error::msg("Tag `widget` doesn't take a body"));
+            }
+            Ok(())
+        }
+
+        fn render(&self, args: &HashMap<String, Value>, _body: Option<&str>) -> Result<String> {
+            Ok(format!("<div class=\"widget\">{}</div>", args["name"].as_str().unwrap()))
+        }
+    }
+
+    fn card(args: &HashMap<String, Value>, body: Option<&str>) -> Result<String> {
+        let title = args.get("title").and_then(|t| t.as_str()).unwrap_or("");
+        Ok(format!("<section><h2>{}</h2>{}</section>", title, body.unwrap_or("")))
+    }
+
+    fn render(tera: &Tera, name: &str) -> String {
+        let mut context = Context::new();
+        context.insert("user", "Bob");
+        context.insert("items", &vec![1, 2]);
+        tera.render(name, &context).unwrap()
+    }
+
+    #[test]
+    fn can_render_custom_tags() {
+        let mut tera = Tera::default();
+        tera.register_tag("widget", Widget);
+        tera.register_tag("card", card);
+        tera.add_raw_templates(vec![
+            ("standalone", "{% widget name='side' ~ 'bar' %}"),
+            ("body", "{% card title=user %}{% for i in items %}{{ i }}{% endfor %}{% endcard %}"),
+            ("empty", "{% card %}{% endcard %}"),
+            (
+                "nested",
+                "{% card %}{% card title='in' %}{% widget name='w' %}{% endcard %}{% endcard %}",
+            ),
+            ("no_end", "{% card title='a' %}!"),
+            ("ws", "a  {%- card -%}  b  {%- endcard -%}  c"),
+        ])
+        .unwrap();
+
+        assert_eq!(render(&tera, "standalone"), "<div class=\"widget\">sidebar</div>");
+        assert_eq!(render(&tera, "body"), "<section><h2>Bob</h2>12</section>");
+        assert_eq!(render(&tera, "empty"), "<section><h2></h2></section>");
+        assert_eq!(
+            render(&tera, "nested"),
+            "<section><h2></h2><section><h2>in</h2><div class=\"widget\">w</div></section></section>"
+        );
+        assert_eq!(render(&tera, "no_end"), "<section><h2>a</h2></section>!");
+        assert_eq!(render(&tera, "ws"), "a<section><h2></h2>b</section>c");
+    }
+
+    #[test]
+    fn errors_on_invalid_custom_tags() {
+        let mut tera = Tera::default();
+        tera.register_tag("widget", Widget);
+        tera.add_raw_templates(vec![
+            ("missing_arg", "{% widget %}"),
+            ("body", "{% widget name='a' %}b{% endwidget %}"),
+            ("unknown", "{% gallery %}"),
+        ])
+        .unwrap();
+
+        let err = tera.render("missing_arg", &Context::new()).unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "Tag `widget` failed");
+        assert_eq!(
+            std::error::Error::source(source).unwrap().to_string(),
+            "Tag `widget` expected an arg called `name`"
+        );
+        let err = tera.render("unknown", &Context::new()).unwrap_err();
+        assert_eq!(std::error::Error::source(&err).unwrap().to_string(), "Tag `gallery` not found");
+
+        assert_eq!(
+            tera.check().unwrap_err().to_string(),
+            "3 problems found:\n\
+             Template 'body': tag `widget` is invalid at line 1, column 1: Tag `widget` doesn't take a body\n\
+             Template 'missing_arg': tag `widget` is invalid at line 1, column 1: Tag `widget` expected an arg called `name`\n\
+             Template 'unknown': tag `gallery` not found at line 1, column 1"
+        );
+    }
+
+    #[test]
+    fn tera_tags_cant_be_overridden() {
+        let mut tera = Tera::default();
+        assert!(tera.add_raw_template("tpl", "{% endwidget %}").is_err());
+        assert!(tera.add_raw_template("tpl", "{% include %}").is_err());
+        tera.add_raw_template("tpl", "{% include_all %}").unwrap();
+    }
+}
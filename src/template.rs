@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
 use crate::errors::{Error, Result};
-use crate::parser::ast::{Block, MacroDefinition, Node};
-use crate::parser::{parse, remove_whitespace};
+use crate::parser::ast::{Block, Embed, Expr, Include, MacroDefinition, Node};
+use crate::parser::lexer::{Lexer, TokenKind};
+use crate::parser::{parse, remove_whitespace, remove_whitespace_between_tags};
 
 /// This is the parsed equivalent of a template file.
 /// It also does some pre-processing to ensure it does as little as possible at runtime
@@ -24,9 +25,16 @@ pub struct Template {
     pub macros: HashMap<String, MacroDefinition>,
     /// (filename, namespace) for the macros imported in that file
     pub imported_macro_files: Vec<(String, String)>,
+    /// Templates included anywhere in that file, without duplicates
+    pub included_templates: Vec<String>,
+    /// The templates of `included_templates` only included with `ignore missing`, which
+    /// don't need to exist
+    pub optional_includes: Vec<String>,
 
     /// Only used during initial parsing. Rendering will use `self.parents`
     pub parent: Option<String>,
+    /// The expression of a `{% extends layout %}`, the parent only being known when rendering
+    pub dynamic_parent: Option<Expr>,
     /// Only used during initial parsing. Rendering will use `self.blocks_definitions`
     pub blocks: HashMap<String, Block>,
 
@@ -40,20 +48,42 @@ pub struct Template {
     /// The order of the Vec is from the first in hierarchy to the current template and the template
     /// name is needed in order to load its macros if necessary.
     pub blocks_definitions: HashMap<String, Vec<(String, Block)>>,
+
+    /// The reason given by a `{# deprecated: reason #}` comment if the template has one
+    pub deprecated: Option<String>,
 }
 
 impl Template {
     /// Parse the template string given
     pub fn new(tpl_name: &str, tpl_path: Option<String>, input: &str) -> Result<Template> {
-        let ast = remove_whitespace(parse(input)?, None);
+        let ast = parse(input)?;
+        let mut tpl = Template::from_ast(tpl_name, tpl_path, remove_whitespace(ast, None))?;
+        tpl.deprecated = find_deprecation(input);
+        Ok(tpl)
+    }
 
+    /// Like `new` but with the whitespace only text between tags removed,
+    /// see `Tera::set_remove_whitespace_between_tags`
+    pub(crate) fn new_without_whitespace_between_tags(
+        tpl_name: &str,
+        tpl_path: Option<String>,
+        input: &str,
+    ) -> Result<Template> {
+        let mut ast = remove_whitespace(parse(input)?, None);
+        remove_whitespace_between_tags(&mut ast, false);
+        let mut tpl = Template::from_ast(tpl_name, tpl_path, ast)?;
+        tpl.deprecated = find_deprecation(input);
+        Ok(tpl)
+    }
+
+    fn from_ast(tpl_name: &str, tpl_path: Option<String>, ast: Vec<Node>) -> Result<Template> {
         // First we want all the blocks used in that template
         // This is recursive as we can have blocks inside blocks
         let mut blocks = HashMap::new();
         fn find_blocks(ast: &[Node], blocks: &mut HashMap<String, Block>) -> Result<()> {
             for node in ast {
                 match *node {
-                    Node::Block(_, ref block, _) => {
+                    Node::Block(_, ref block, _, _) => {
                         if blocks.contains_key(&block.name) {
                             return Err(Error::msg(format!(
                                 "Block `{}` is duplicated",
@@ -72,15 +102,49 @@ impl Template {
         }
         find_blocks(&ast, &mut blocks)?;
 
+        // Includes can be anywhere, including in loops or macros
+        let mut included_templates = vec![];
+        let mut required_includes = vec![];
+        fn find_includes(ast: &[Node], includes: &mut Vec<String>, required: &mut Vec<String>) {
+            for node in ast {
+                let include = match *node {
+                    Node::Include(_, Include { ref name, ignore_missing, .. }, _) => {
+                        Some((name, ignore_missing))
+                    }
+                    Node::Embed(_, Embed { ref name, .. }, _, _) => Some((name, false)),
+                    _ => None,
+                };
+                if let Some((name, ignore_missing)) = include {
+                    if !includes.contains(name) {
+                        includes.push(name.to_string());
+                    }
+                    if !ignore_missing && !required.contains(name) {
+                        required.push(name.to_string());
+                    }
+                }
+                for body in node.bodies() {
+                    find_includes(body, includes, required);
+                }
+            }
+        }
+        find_includes(&ast, &mut included_templates, &mut required_includes);
+        let optional_includes = included_templates
+            .iter()
+            .filter(|name| !required_includes.contains(name))
+            .cloned()
+            .collect();
+
         // And now we find the potential parent and everything macro related (definition, import)
         let mut macros = HashMap::new();
         let mut imported_macro_files = vec![];
         let mut parent = None;
+        let mut dynamic_parent = None;
 
         for node in &ast {
             match *node {
-                Node::Extends(_, ref name) => parent = Some(name.to_string()),
-                Node::MacroDefinition(_, ref macro_def, _) => {
+                Node::Extends(_, ref name, _) => parent = Some(name.to_string()),
+                Node::DynamicExtends(_, ref expr, _) => dynamic_parent = Some(expr.clone()),
+                Node::MacroDefinition(_, ref macro_def, _, _) => {
                     if macros.contains_key(&macro_def.name) {
                         return Err(Error::msg(format!(
                             "Macro `{}` is duplicated",
@@ -89,7 +153,7 @@ impl Template {
                     }
                     macros.insert(macro_def.name.clone(), macro_def.clone());
                 }
-                Node::ImportMacro(_, ref tpl_name, ref namespace) => {
+                Node::ImportMacro(_, ref tpl_name, ref namespace, _) => {
                     imported_macro_files.push((tpl_name.to_string(), namespace.to_string()));
                 }
                 _ => continue,
@@ -101,14 +165,48 @@ impl Template {
             path: tpl_path,
             ast,
             parent,
+            dynamic_parent,
             blocks,
             macros,
             imported_macro_files,
+            included_templates,
+            optional_includes,
             parents: vec![],
             blocks_definitions: HashMap::new(),
             from_extend: false,
+            deprecated: None,
         })
     }
+
+    /// The templates this one directly needs to be rendered: its parent, the templates it
+    /// includes and the ones it imports macros from
+    pub(crate) fn direct_dependencies(&self) -> impl Iterator<Item = &str> {
+        self.parent
+            .iter()
+            .chain(self.included_templates.iter())
+            .chain(self.imported_macro_files.iter().map(|(name, _)| name))
+            .map(|name| name.as_str())
+    }
+}
+
+/// The reason of the first `{# deprecated: reason #}` comment of a valid template, if any
+fn find_deprecation(input: &str) -> Option<String> {
+    // Avoids tokenizing the templates that can't have one
+    if !input.contains("deprecated:") {
+        return None;
+    }
+    Lexer::new(input).tokenize().ok()?.into_iter().find_map(|token| {
+        if token.kind != TokenKind::Comment {
+            return None;
+        }
+        let content = token.value.trim_start_matches("{#").trim_end_matches("#}");
+        let content = content.trim_matches(|c: char| c == '-' || c.is_whitespace());
+        if content.starts_with("deprecated:") {
+            Some(content["deprecated:".len()..].trim().to_string())
+        } else {
+            None
+        }
+    })
 }
 
 #[cfg(test)]
@@ -120,6 +218,21 @@ mod tests {
         Template::new("hello", None, "Hello {{ world }}.").unwrap();
     }
 
+    #[test]
+    fn can_find_deprecation_comment() {
+        let tpl = Template::new(
+            "hello",
+            None,
+            "{# A comment #}{% raw %}{# deprecated: no #}{% endraw %}{#- deprecated: use `new.html` -#}",
+        )
+        .unwrap();
+        assert_eq!(tpl.deprecated, Some("use `new.html`".to_string()));
+
+        let tpl =
+            Template::new("hello", None, "{% raw %}{# deprecated: no #}{% endraw %}").unwrap();
+        assert_eq!(tpl.deprecated, None);
+    }
+
     #[test]
     fn can_find_parent_template() {
         let tpl = Template::new("hello", None, "{% extends \"base.html\" %}").unwrap();
@@ -167,4 +280,15 @@ mod tests {
             vec![("macros.html".to_string(), "macros".to_string())]
         );
     }
+
+    #[test]
+    fn can_find_included_templates() {
+        let tpl = Template::new(
+            "hello",
+            None,
+            "{% include \"a.html\" %}{% for i in b %}{% include \"b.html\" %}{% include \"a.html\" %}{% endfor %}",
+        )
+        .unwrap();
+        assert_eq!(tpl.included_templates, vec!["a.html".to_string(), "b.html".to_string()]);
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
@@ -6,15 +7,32 @@ use std::path::Path;
 use std::sync::Arc;
 
 use globwalk::glob;
+use serde_json::value::Value;
 
+use crate::builder::TeraBuilder;
 use crate::builtins::filters::{array, common, number, object, string, Filter};
 use crate::builtins::functions::{self, Function};
 use crate::builtins::testers::{self, Test};
+use crate::cache::FragmentCache;
 use crate::context::Context;
 use crate::errors::{Error, Result};
+use crate::hooks::RenderHooks;
+use crate::i18n::Translator;
+use crate::introspection::{self, BlockInfo, MacroSignature, ResolvedBlock};
+use crate::options::{MapOrder, OutOfBounds, RenderOptions};
+use crate::parser::delimiters::{translate as translate_delimiters, Delimiters};
+use crate::parser::django::translate as translate_django;
+use crate::parser::liquid::translate as translate_liquid;
+use crate::parser::recovery::collect_syntax_errors;
+use crate::post_process::PostProcessor;
+use crate::pre_process::PreProcessor;
+use crate::profile::ProfileEntry;
 use crate::renderer::Renderer;
+use crate::tags::Tag;
 use crate::template::Template;
-use crate::utils::escape_html;
+use crate::urls::{Asset, AssetResolver, UrlFor, UrlResolver};
+use crate::utils::{escape_html, escape_xml, normalize_template_name, RegexCache};
+use crate::warnings::Warning;
 
 /// The of the the template used for `Tera::render_str` and `Tera::one_off`.
 const ONE_OFF_TEMPLATE_NAME: &str = "__tera_one_off";
@@ -23,11 +41,15 @@ const ONE_OFF_TEMPLATE_NAME: &str = "__tera_one_off";
 pub type EscapeFn = fn(&str) -> String;
 
 /// The main point of interaction in this library.
+///
+/// `Tera` is `Send` and `Sync` and rendering only needs a shared reference so a single
+/// instance, in an `Arc` for example, can render templates from several threads at once.
 #[derive(Clone)]
 pub struct Tera {
-    // The glob used in `Tera::new`, None if Tera was instantiated differently
+    // The globs used in `Tera::new` or `Tera::new_layered`, highest priority first, empty if Tera
+    // was instantiated differently
     #[doc(hidden)]
-    glob: Option<String>,
+    globs: Vec<String>,
     #[doc(hidden)]
     pub templates: HashMap<String, Template>,
     #[doc(hidden)]
@@ -36,34 +58,89 @@ pub struct Tera {
     pub testers: HashMap<String, Arc<dyn Test>>,
     #[doc(hidden)]
     pub functions: HashMap<String, Arc<dyn Function>>,
+    // The tags registered by the application, used as `{% name %}` or `{% name %}...{% endname %}`
+    pub(crate) tags: HashMap<String, Arc<dyn Tag>>,
+    // The deprecated filters with their replacement, see `Tera::register_filter_deprecated`
+    pub(crate) deprecated_filters: HashMap<String, String>,
     // Which extensions does Tera automatically autoescape on.
-    // Defaults to [".html", ".htm", ".xml"]
+    // Defaults to [".html", ".htm", ".xml", ".svg", ".rss"]
     #[doc(hidden)]
     pub autoescape_suffixes: Vec<&'static str>,
     #[doc(hidden)]
     escape_fn: EscapeFn,
+    // Used instead of `escape_fn` for the autoescaped templates ending with their suffix
+    pub(crate) suffix_escape_fns: Vec<(&'static str, EscapeFn)>,
+    // The delimiters used when parsing templates
+    delimiters: Delimiters,
+    // Whether the newline at the end of templates is kept, true by default
+    keep_trailing_newline: bool,
+    // Whether `\r\n` in templates are replaced by `\n`, false by default
+    normalize_newlines: bool,
+    // Whether parsing goes on after a syntax error to report the following ones, false by default
+    report_all_syntax_errors: bool,
+    // Whether some of the Django template syntax is accepted, false by default
+    django_syntax: bool,
+    // Whether the whitespace only text between tags is removed, false by default
+    remove_whitespace_between_tags: bool,
+    // The suffixes of the templates using the Liquid syntax, none by default
+    liquid_suffixes: Vec<&'static str>,
+    // Used to translate the `{% trans %}` messages, kept as is if there isn't one
+    pub(crate) translator: Option<Arc<dyn Translator>>,
+    // Called by the renderer as it goes through templates, if set
+    pub(crate) render_hooks: Option<Arc<dyn RenderHooks>>,
+    // What missing subscripts evaluate to, an error by default
+    pub(crate) out_of_bounds: OutOfBounds,
+    // Whether the output of the HTML templates is minified, false by default
+    pub(crate) minify_html: bool,
+    // The order in which the for loops iterate on maps, the one of the maps by default
+    pub(crate) map_order: MapOrder,
+    // Whether adding a template fails if it uses filters, tests, functions or tags that are not
+    // registered, false by default
+    check_callables: bool,
+    // Applied in that order to the output of every render
+    pub(crate) post_processors: Vec<Arc<dyn PostProcessor>>,
+    // Applied in that order to the source of every template added
+    pre_processors: Vec<Arc<dyn PreProcessor>>,
+    // Keeps the output of the `{% cache %}` blocks, which are rendered every time if there isn't one
+    pub(crate) fragment_cache: Option<Arc<dyn FragmentCache>>,
 }
 
 impl Tera {
-    fn create(dir: &str, parse_only: bool) -> Result<Tera> {
-        if dir.find('*').is_none() {
-            return Err(Error::msg(format!(
-                "Tera expects a glob as input, no * were found in `{}`",
-                dir
-            )));
-        }
-
+    fn create(dirs: &[&str], parse_only: bool) -> Result<Tera> {
         let mut tera = Tera {
-            glob: Some(dir.to_string()),
+            globs: Vec::new(),
             templates: HashMap::new(),
             filters: HashMap::new(),
             functions: HashMap::new(),
             testers: HashMap::new(),
-            autoescape_suffixes: vec![".html", ".htm", ".xml"],
+            tags: HashMap::new(),
+            deprecated_filters: HashMap::new(),
+            autoescape_suffixes: vec![".html", ".htm", ".xml", ".svg", ".rss"],
             escape_fn: escape_html,
+            suffix_escape_fns: vec![
+                (".xml", escape_xml),
+                (".svg", escape_xml),
+                (".rss", escape_xml),
+            ],
+            delimiters: Delimiters::default(),
+            keep_trailing_newline: true,
+            normalize_newlines: false,
+            report_all_syntax_errors: false,
+            django_syntax: false,
+            remove_whitespace_between_tags: false,
+            liquid_suffixes: vec![],
+            translator: None,
+            render_hooks: None,
+            out_of_bounds: OutOfBounds::Error,
+            minify_html: false,
+            map_order: MapOrder::Insertion,
+            check_callables: false,
+            post_processors: Vec::new(),
+            pre_processors: Vec::new(),
+            fragment_cache: None,
         };
 
-        tera.load_from_glob()?;
+        tera.load_globs(dirs)?;
         if !parse_only {
             tera.build_inheritance_chains()?;
             tera.check_macro_files()?;
@@ -86,7 +163,22 @@ impl Tera {
     ///}
     ///```
     pub fn new(dir: &str) -> Result<Tera> {
-        Self::create(dir, false)
+        Self::create(&[dir], false)
+    }
+
+    /// Create a new instance of Tera from several globs in priority order, a template found by
+    /// one of them replacing the templates with the same name found by the following ones.
+    /// A theme can this way override only some of the default templates of an application.
+    ///
+    /// Templates are named relatively to their glob, as in `Tera::new`.
+    /// [Tera::full_reload](struct.Tera.html#method.full_reload) loads them all again.
+    ///
+    ///```ignore
+    ///// `themes/dark/base.html` is used instead of `templates/base.html` if it exists
+    ///let tera = Tera::new_layered(&["themes/dark/**/*", "templates/**/*"])?;
+    ///```
+    pub fn new_layered(dirs: &[&str]) -> Result<Tera> {
+        Self::create(dirs, false)
     }
 
     /// Create a new instance of Tera, containing all the parsed templates found in the `dir` glob
@@ -106,12 +198,42 @@ impl Tera {
     ///tera.build_inheritance_chains()?;
     ///```
     pub fn parse(dir: &str) -> Result<Tera> {
-        Self::create(dir, true)
+        Self::create(&[dir], true)
+    }
+
+    /// Returns a [TeraBuilder](struct.TeraBuilder.html) to configure an instance before loading
+    /// its templates.
+    ///
+    ///```rust,ignore
+    ///let tera = Tera::builder()
+    ///    .glob("templates/**/*")
+    ///    .autoescape_on(vec![".html"])
+    ///    .strict(true)
+    ///    .build()?;
+    ///```
+    pub fn builder() -> TeraBuilder {
+        TeraBuilder::new()
     }
 
-    /// Loads all the templates found in the glob that was given to Tera::new
+    /// Loads the templates matching the globs, highest priority first, which are kept for
+    /// [Tera::full_reload](struct.Tera.html#method.full_reload)
+    pub(crate) fn load_globs<S: AsRef<str>>(&mut self, globs: &[S]) -> Result<()> {
+        for glob in globs {
+            if glob.as_ref().find('*').is_none() {
+                return Err(Error::msg(format!(
+                    "Tera expects a glob as input, no * were found in `{}`",
+                    glob.as_ref()
+                )));
+            }
+        }
+        self.globs = globs.iter().map(|g| g.as_ref().to_string()).collect();
+        self.load_from_glob()
+    }
+
+    /// Loads all the templates found in the globs that were given to Tera::new or
+    /// Tera::new_layered
     fn load_from_glob(&mut self) -> Result<()> {
-        if self.glob.is_none() {
+        if self.globs.is_empty() {
             return Err(Error::msg("Tera can only load from glob if a glob is provided"));
         }
         // We want to preserve templates that have been added through
@@ -124,43 +246,59 @@ impl Tera {
             .collect();
 
         let mut errors = String::new();
+        // The names of the templates loaded from a glob, to skip the ones found by the
+        // following globs
+        let mut loaded = HashSet::new();
+
+        for dir in self.globs.clone() {
+            let before = loaded.len();
+            // We clean the filename by removing the dir given
+            // to Tera so users don't have to prefix everytime
+            let mut parent_dir = dir.split_at(dir.find('*').unwrap()).0;
+            // Remove `./` from the glob if used as it would cause an error in strip_prefix
+            if parent_dir.starts_with("./") {
+                parent_dir = &parent_dir[2..];
+            }
 
-        let dir = self.glob.clone().unwrap();
-        // We clean the filename by removing the dir given
-        // to Tera so users don't have to prefix everytime
-        let mut parent_dir = dir.split_at(dir.find('*').unwrap()).0;
-        // Remove `./` from the glob if used as it would cause an error in strip_prefix
-        if parent_dir.starts_with("./") {
-            parent_dir = &parent_dir[2..];
-        }
+            // We are parsing all the templates on instantiation
+            for entry in glob(&dir).unwrap().filter_map(std::result::Result::ok) {
+                let mut path = entry.into_path();
+                // We only care about actual files
+                if path.is_file() {
+                    if path.starts_with("./") {
+                        path = path.strip_prefix("./").unwrap().to_path_buf();
+                    }
 
-        // We are parsing all the templates on instantiation
-        for entry in glob(&dir).unwrap().filter_map(std::result::Result::ok) {
-            let mut path = entry.into_path();
-            // We only care about actual files
-            if path.is_file() {
-                if path.starts_with("./") {
-                    path = path.strip_prefix("./").unwrap().to_path_buf();
-                }
+                    let filepath = path
+                        .strip_prefix(parent_dir)
+                        .unwrap()
+                        .to_string_lossy()
+                        // unify on forward slash
+                        .replace("\\", "/");
 
-                let filepath = path
-                    .strip_prefix(&parent_dir)
-                    .unwrap()
-                    .to_string_lossy()
-                    // unify on forward slash
-                    .replace("\\", "/");
-
-                if let Err(e) = self.add_file(Some(&filepath), path) {
-                    use std::error::Error;
-
-                    errors += &format!("\n* {}", e);
-                    let mut cause = e.source();
-                    while let Some(e) = cause {
-                        errors += &format!("\n{}", e);
-                        cause = e.source();
+                    if loaded.contains(&filepath) {
+                        log!(debug, "Template `{}` of {:?} is overridden", filepath, path);
+                        continue;
+                    }
+                    if let Err(e) = self.add_file(Some(&filepath), path) {
+                        use std::error::Error;
+
+                        errors += &format!("\n* {}", e);
+                        let mut cause = e.source();
+                        while let Some(e) = cause {
+                            errors += &format!("\n{}", e);
+                            cause = e.source();
+                        }
                     }
+                    loaded.insert(filepath);
                 }
             }
+
+            if loaded.len() == before {
+                log!(warn, "No templates were found for the glob `{}`", dir);
+            } else {
+                log!(debug, "Loaded {} templates from the glob `{}`", loaded.len() - before, dir);
+            }
         }
 
         if !errors.is_empty() {
@@ -170,7 +308,75 @@ impl Tera {
         Ok(())
     }
 
-    // Add a template from a path: reads the file and parses it.
+    // Parses a template with the delimiters and newline options of that instance
+    pub(crate) fn parse_template(
+        &self,
+        name: &str,
+        path: Option<String>,
+        input: &str,
+    ) -> Result<Template> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("tera.parse", template = name).entered();
+
+        let mut input = Cow::Borrowed(input);
+        for processor in &self.pre_processors {
+            input = Cow::Owned(processor.process(name, &input).map_err(|e| {
+                Error::chain(format!("Failed to pre-process template '{}'", name), e)
+            })?);
+        }
+        if self.normalize_newlines && input.contains("\r\n") {
+            input = Cow::Owned(input.replace("\r\n", "\n"));
+        }
+        if !self.keep_trailing_newline {
+            let mut end = input.len();
+            if input.ends_with('\n') {
+                end -= 1;
+                if input[..end].ends_with('\r') {
+                    end -= 1;
+                }
+            }
+            if end != input.len() {
+                input = Cow::Owned(input[..end].to_string());
+            }
+        }
+        if !self.delimiters.is_default() {
+            input = Cow::Owned(translate_delimiters(&input, &self.delimiters)?);
+        }
+        if self.liquid_suffixes.iter().any(|s| name.ends_with(s)) {
+            input = Cow::Owned(translate_liquid(&input)?);
+        } else if self.django_syntax {
+            input = Cow::Owned(translate_django(&input)?);
+        }
+
+        let template = if self.remove_whitespace_between_tags {
+            Template::new_without_whitespace_between_tags(name, path, &input)
+        } else {
+            Template::new(name, path, &input)
+        };
+        let template = match template {
+            Err(e) if self.report_all_syntax_errors => {
+                let errors = collect_syntax_errors(&input);
+                if errors.len() < 2 {
+                    return Err(e);
+                }
+                return Err(Error::msg(format!(
+                    "{} syntax errors:\n{}",
+                    errors.len(),
+                    errors.join("\n\n")
+                )));
+            }
+            res => res?,
+        };
+
+        if self.check_callables {
+            let problems = introspection::missing_callables(self, &template);
+            if !problems.is_empty() {
+                return Err(Error::msg(problems.join("\n")));
+            }
+        }
+        Ok(template)
+    }
+
     // This will return an error if the template is invalid and doesn't check the validity of
     // inheritance chains.
     fn add_file<P: AsRef<Path>>(&mut self, name: Option<&str>, path: P) -> Result<()> {
@@ -184,9 +390,11 @@ impl Tera {
         f.read_to_string(&mut input)
             .map_err(|e| Error::chain(format!("Failed to read template '{:?}'", path), e))?;
 
-        let tpl = Template::new(tpl_name, Some(path.to_str().unwrap().to_string()), &input)
+        let tpl = self
+            .parse_template(tpl_name, Some(path.to_str().unwrap().to_string()), &input)
             .map_err(|e| Error::chain(format!("Failed to parse {:?}", path), e))?;
 
+        log!(debug, "Loaded template `{}` from {:?}", tpl_name, path);
         self.templates.insert(tpl_name.to_string(), tpl);
         Ok(())
     }
@@ -213,7 +421,10 @@ impl Tera {
             }
 
             match template.parent {
-                Some(ref p) => match templates.get(p) {
+                Some(ref p) => match templates
+                    .get(p)
+                    .or_else(|| normalize_template_name(p).and_then(|n| templates.get(&n)))
+                {
                     Some(parent) => {
                         parents.push(parent.name.clone());
                         build_chain(templates, start, parent, parents)
@@ -272,6 +483,39 @@ impl Tera {
         Ok(())
     }
 
+    /// Completes the inheritance chain of a template using `{% extends layout %}` once the
+    /// expression gave its parent, whose own chain must be complete.
+    /// This is the render time equivalent of `build_inheritance_chains` for that template.
+    pub(crate) fn with_dynamic_parent(
+        &self,
+        template: &Template,
+        parent: &Template,
+    ) -> Result<Template> {
+        let mut parents = vec![parent.name.clone()];
+        parents.extend(parent.parents.iter().cloned());
+        if parents.contains(&template.name) {
+            return Err(Error::circular_extend(&template.name, parents));
+        }
+
+        let mut blocks_definitions = HashMap::new();
+        for (block_name, def) in &template.blocks {
+            let mut definitions = vec![(template.name.clone(), def.clone())];
+            for parent in &parents {
+                let t = self.get_template(parent)?;
+                if let Some(b) = t.blocks.get(block_name) {
+                    definitions.push((t.name.clone(), b.clone()));
+                }
+            }
+            blocks_definitions.insert(block_name.clone(), definitions);
+        }
+
+        let mut resolved = template.clone();
+        resolved.parent = Some(parent.name.clone());
+        resolved.parents = parents;
+        resolved.blocks_definitions = blocks_definitions;
+        Ok(resolved)
+    }
+
     /// We keep track of macro files loaded in each Template so we can know whether one or them
     /// is missing and error accordingly before the user tries to render a template.
     ///
@@ -279,7 +523,7 @@ impl Tera {
     pub fn check_macro_files(&self) -> Result<()> {
         for template in self.templates.values() {
             for &(ref tpl_name, _) in &template.imported_macro_files {
-                if !self.templates.contains_key(tpl_name) {
+                if self.get_template(tpl_name).is_err() {
                     return Err(Error::msg(format!(
                         "Template `{}` loads macros from `{}` which isn't present in Tera",
                         template.name, tpl_name
@@ -309,6 +553,140 @@ impl Tera {
         renderer.render()
     }
 
+    /// Renders a Tera template like [Tera::render](struct.Tera.html#method.render) but writes
+    /// the output to `write`, a file or a socket for example, instead of returning a `String`.
+    ///
+    /// The output of each top-level node of the template, a text, a tag or a whole `{% for %}`
+    /// or `{% block %}` for example, is rendered in memory and then written before rendering
+    /// the next one. A template made of a single node is therefore written in one go, as is
+    /// each block of a template extending another one: only the nodes of the base template
+    /// are top-level. When the output is minified or post-processed, it is instead rendered
+    /// whole before being written.
+    /// An error is returned if writing fails, with an `ErrorKind::Io` kind.
+    ///
+    /// ```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("sitemap.xml", "{% for url in urls %}<url>{{ url }}</url>{% endfor %}").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("urls", &vec!["/", "/about"]);
+    ///
+    /// let mut output = Vec::new();
+    /// tera.render_to("sitemap.xml", &context, &mut output).unwrap();
+    /// assert_eq!(output, b"<url>/</url><url>/about</url>");
+    /// ```
+    pub fn render_to(
+        &self,
+        template_name: &str,
+        context: &Context,
+        write: impl Write,
+    ) -> Result<()> {
+        let template = self.get_template(template_name)?;
+        let renderer = Renderer::new(template, self, context);
+        renderer.render_to(write)
+    }
+
+    /// Renders only the block `block_name` of a template, to return a fragment of a page for
+    /// example. The content of the block is the one of the closest template defining it in
+    /// the inheritance chain of that template, as when rendering the whole template, and the
+    /// block can be defined in any of them.
+    ///
+    /// ```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("base.html", "<main>{% block content %}{% endblock %}</main><aside>{% block sidebar %}Links{% endblock %}</aside>"),
+    ///     ("page.html", "{% extends \"base.html\" %}{% block content %}Hello {{ name }}{% endblock %}"),
+    /// ]).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", "Bob");
+    /// assert_eq!(tera.render_block("page.html", "content", &context).unwrap(), "Hello Bob");
+    /// assert_eq!(tera.render_block("page.html", "sidebar", &context).unwrap(), "Links");
+    /// ```
+    pub fn render_block(
+        &self,
+        template_name: &str,
+        block_name: &str,
+        context: &Context,
+    ) -> Result<String> {
+        let template = self.get_template(template_name)?;
+        let renderer = Renderer::new(template, self, context);
+        renderer.render_block(block_name)
+    }
+
+    /// Renders a template like [Tera::render](struct.Tera.html#method.render), with some of the
+    /// settings of this instance overridden for this render only.
+    ///
+    /// See [RenderOptions](struct.RenderOptions.html) for an example.
+    pub fn render_with_options(
+        &self,
+        template_name: &str,
+        context: &Context,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        let template = self.get_template(template_name)?;
+        let renderer = Renderer::new(template, self, context).with_options(options);
+        renderer.render()
+    }
+
+    /// Renders a template like [Tera::render](struct.Tera.html#method.render), also returning
+    /// the time spent and the bytes output by the template and every include, block and macro
+    /// call it rendered, to find out what makes a page slow to render.
+    ///
+    /// Profiling only happens for this render: other renders have no overhead.
+    ///
+    /// ```rust
+    /// # use tera::{Context, ProfileKind, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("nav.html", "<nav></nav>"),
+    ///     ("page.html", "{% include \"nav.html\" %}{% block content %}Hello{% endblock %}"),
+    /// ]).unwrap();
+    ///
+    /// let (output, profile) = tera.render_profiled("page.html", &Context::new()).unwrap();
+    /// assert_eq!(profile.bytes, output.len());
+    /// assert_eq!(profile.children[0].kind, ProfileKind::Include);
+    /// for total in profile.totals() {
+    ///     println!("{} called {} times: {:?}", total.name, total.calls, total.self_duration);
+    /// }
+    /// ```
+    pub fn render_profiled(
+        &self,
+        template_name: &str,
+        context: &Context,
+    ) -> Result<(String, ProfileEntry)> {
+        let template = self.get_template(template_name)?;
+        let renderer = Renderer::new(template, self, context);
+        renderer.render_profiled()
+    }
+
+    /// Renders a template like [Tera::render](struct.Tera.html#method.render), also returning
+    /// the uses of deprecated filters and templates met while rendering, each of them once.
+    /// Only the filters applied and the templates rendered, extended or included are reported,
+    /// see [Tera::deprecations](struct.Tera.html#method.deprecations) to find all of them.
+    ///
+    /// ```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("old_nav.html", "{# deprecated: use nav.html #}<nav></nav>"),
+    ///     ("page.html", "{% if false %}{% include \"old_nav.html\" %}{% endif %}"),
+    /// ]).unwrap();
+    ///
+    /// let (_, warnings) = tera.render_with_warnings("page.html", &Context::new()).unwrap();
+    /// assert!(warnings.is_empty());
+    /// assert_eq!(tera.deprecations().len(), 1);
+    /// ```
+    pub fn render_with_warnings(
+        &self,
+        template_name: &str,
+        context: &Context,
+    ) -> Result<(String, Vec<Warning>)> {
+        let template = self.get_template(template_name)?;
+        let renderer = Renderer::new(template, self, context);
+        renderer.render_with_warnings()
+    }
+
     /// Renders a one off template (for example a template coming from a user
     /// input) given a `Context` and an instance of Tera. This allows you to
     /// render templates using custom filters or functions.
@@ -354,10 +732,257 @@ impl Tera {
     #[doc(hidden)]
     #[inline]
     pub fn get_template(&self, template_name: &str) -> Result<&Template> {
-        match self.templates.get(template_name) {
-            Some(tpl) => Ok(tpl),
-            None => Err(Error::template_not_found(template_name)),
+        if let Some(tpl) = self.templates.get(template_name) {
+            return Ok(tpl);
+        }
+        // The name can come from user input: `./users//profile.html` is `users/profile.html`
+        // but `../secrets.txt` can't be the name of a template found in the template directories
+        match normalize_template_name(template_name) {
+            Some(name) => {
+                self.templates.get(&name).ok_or_else(|| Error::template_not_found(template_name))
+            }
+            None => Err(Error::invalid_template_name(template_name)),
+        }
+    }
+
+    /// Loads all the templates of a directory embedded with `include_dir`.
+    /// Use the [embed_templates!](macro.embed_templates.html) macro rather than calling it directly.
+    #[cfg(feature = "embed")]
+    #[doc(hidden)]
+    pub fn from_embedded_dir(dir: &include_dir::Dir) -> Result<Tera> {
+        fn collect<'a>(
+            dir: &'a include_dir::Dir,
+            files: &mut Vec<(String, &'a str)>,
+        ) -> Result<()> {
+            for file in dir.files() {
+                let name = file.path().to_string_lossy().replace('\\', "/");
+                let content = file.contents_utf8().ok_or_else(|| {
+                    Error::msg(format!("Embedded template '{}' is not valid UTF-8", name))
+                })?;
+                files.push((name, content));
+            }
+            for sub_dir in dir.dirs() {
+                collect(sub_dir, files)?;
+            }
+            Ok(())
         }
+
+        let mut files = vec![];
+        collect(dir, &mut files)?;
+        let mut tera = Tera::default();
+        tera.add_raw_templates(files)?;
+        Ok(tera)
+    }
+
+    /// Returns the names of all the templates the given template needs in order to be rendered:
+    /// the ones it extends, includes or imports macros from, and their own dependencies.
+    /// The names are sorted and a dependency that isn't loaded in Tera is still listed.
+    ///
+    /// ```rust,ignore
+    /// // Rebuild everything `page.html` relies on
+    /// let deps = tera.dependencies("page.html")?;
+    /// ```
+    pub fn dependencies(&self, template_name: &str) -> Result<Vec<&str>> {
+        let mut dependencies = BTreeSet::new();
+        let mut to_visit = vec![self.get_template(template_name)?];
+
+        while let Some(template) = to_visit.pop() {
+            for name in template.direct_dependencies() {
+                if dependencies.insert(name) {
+                    if let Some(tpl) = self.templates.get(name) {
+                        to_visit.push(tpl);
+                    }
+                }
+            }
+        }
+
+        Ok(dependencies.into_iter().collect())
+    }
+
+    /// Returns the names of all the templates that need the given template in order to be rendered,
+    /// either directly (extends, include or macro import) or through another template.
+    /// This is the reverse of [Tera::dependencies](struct.Tera.html#method.dependencies): these are
+    /// the templates to re-render when the given one changes. The names are sorted.
+    ///
+    /// ```rust,ignore
+    /// // `partials/header.html` was modified, find out which pages are affected
+    /// let affected = tera.dependents("partials/header.html")?;
+    /// ```
+    pub fn dependents(&self, template_name: &str) -> Result<Vec<&str>> {
+        let template = self.get_template(template_name)?;
+
+        let mut reverse: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name, tpl) in &self.templates {
+            for dependency in tpl.direct_dependencies() {
+                reverse.entry(dependency).or_default().push(name);
+            }
+        }
+
+        let mut dependents = BTreeSet::new();
+        let mut to_visit = vec![template.name.as_str()];
+        while let Some(name) = to_visit.pop() {
+            for dependent in reverse.get(name).into_iter().flatten() {
+                if dependents.insert(*dependent) {
+                    to_visit.push(dependent);
+                }
+            }
+        }
+
+        Ok(dependents.into_iter().collect())
+    }
+
+    /// Returns the names of the top-level context variables the given template uses,
+    /// found by looking at the templates it extends and includes as well.
+    ///
+    /// Variables defined in the templates (`set`, forloop variables) are not returned, and neither
+    /// are the ones that are optional: used with the `default` filter or the `defined`/`undefined`
+    /// tests.
+    /// Since the analysis is done without a context, variables used in a branch of a `if` are always
+    /// returned even if that branch would not be rendered.
+    ///
+    /// ```rust,ignore
+    /// let variables = tera.required_variables("page.html")?;
+    /// for name in variables {
+    ///     assert!(context.contains_key(&name));
+    /// }
+    /// ```
+    pub fn required_variables(&self, template_name: &str) -> Result<BTreeSet<String>> {
+        introspection::required_variables(self, self.get_template(template_name)?)
+    }
+
+    /// Checks that the context has all the variables returned by `required_variables` for the
+    /// given template and that the ones used as arrays or objects, like `items[0]` or `user.name`,
+    /// aren't a string, a number, a boolean or null, before anything is rendered.
+    /// All the problems found are returned in a single error, one per line.
+    ///
+    /// ```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("page.html", "{{ user.name }}{{ title }}").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("user", "Bob");
+    /// assert_eq!(
+    ///     tera.validate_context("page.html", &context).unwrap_err().to_string(),
+    ///     "2 problems found in the context of 'page.html':\n\
+    ///      Variable `title` is missing\n\
+    ///      Variable `user` is used as an array or an object (`user.name`) but is a string"
+    /// );
+    /// ```
+    pub fn validate_context(&self, template_name: &str, context: &Context) -> Result<()> {
+        let problems =
+            introspection::validate_context(self, self.get_template(template_name)?, context)?;
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(Error::msg(format!(
+            "{} problems found in the context of '{}':\n{}",
+            problems.len(),
+            template_name,
+            problems.join("\n")
+        )))
+    }
+
+    /// Returns the blocks defined in the given template, including nested ones, sorted by name.
+    /// Blocks only defined in a parent template are not included.
+    ///
+    /// ```rust,ignore
+    /// for block in tera.blocks("page.html")? {
+    ///     println!("{} (calls super: {})", block.name, block.calls_super);
+    /// }
+    /// ```
+    pub fn blocks(&self, template_name: &str) -> Result<Vec<BlockInfo>> {
+        Ok(introspection::blocks(self.get_template(template_name)?))
+    }
+
+    /// Returns all the blocks rendered by the given template, including the ones only defined in
+    /// the templates it extends, sorted by name. Each block comes with the template its content
+    /// is taken from and the parent templates it overrides.
+    ///
+    /// ```rust,ignore
+    /// for block in tera.resolved_blocks("page.html")? {
+    ///     println!("{} comes from {}", block.name, block.template);
+    /// }
+    /// ```
+    pub fn resolved_blocks(&self, template_name: &str) -> Result<Vec<ResolvedBlock>> {
+        introspection::resolved_blocks(self, self.get_template(template_name)?)
+    }
+
+    /// Checks that every template, filter, function, test and macro used by the templates
+    /// exists, without rendering anything. All the problems found are returned in a single
+    /// error, one per line, so it can be used to fail fast in CI before deploying.
+    ///
+    /// ```rust
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_template("page.html", "{{ name | shout }}{% include \"nav.html\" %}").unwrap();
+    /// let err = tera.check().unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "2 problems found:\n\
+    ///      Template 'page.html': template `nav.html` not found\n\
+    ///      Template 'page.html': filter `shout` not found at line 1, column 1"
+    /// );
+    /// ```
+    pub fn check(&self) -> Result<()> {
+        let mut names: Vec<_> = self.templates.keys().collect();
+        names.sort();
+
+        let mut problems = vec![];
+        for name in names {
+            for problem in introspection::missing_references(self, &self.templates[name]) {
+                problems.push(format!("Template '{}': {}", name, problem));
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(Error::msg(format!("{} problems found:\n{}", problems.len(), problems.join("\n"))))
+    }
+
+    /// Returns every use of a deprecated filter or template in the templates, sorted by template
+    /// and position. Filters are deprecated with
+    /// [Tera::register_filter_deprecated](struct.Tera.html#method.register_filter_deprecated)
+    /// and templates with a `{# deprecated: reason #}` comment, reported where they are
+    /// extended, included, embedded or imported.
+    ///
+    /// ```rust
+    /// # use tera::{Deprecation, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.add_raw_templates(vec![
+    ///     ("old_base.html", "{# deprecated: extend base.html instead #}{% block content %}{% endblock %}"),
+    ///     ("page.html", "{% extends \"old_base.html\" %}"),
+    /// ]).unwrap();
+    ///
+    /// for warning in tera.deprecations() {
+    ///     println!("{}", warning);
+    /// }
+    /// assert_eq!(
+    ///     tera.deprecations()[0].deprecation,
+    ///     Deprecation::Template {
+    ///         name: "old_base.html".to_string(),
+    ///         reason: "extend base.html instead".to_string(),
+    ///     }
+    /// );
+    /// ```
+    pub fn deprecations(&self) -> Vec<Warning> {
+        let mut names: Vec<_> = self.templates.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .flat_map(|name| introspection::deprecations(self, &self.templates[name]))
+            .collect()
+    }
+
+    /// Returns the signatures of the macros defined in the given template, sorted by name.
+    ///
+    /// ```rust,ignore
+    /// for signature in tera.macros("macros.html")? {
+    ///     println!("{} takes {} arguments", signature.name, signature.args.len());
+    /// }
+    /// ```
+    pub fn macros(&self, template_name: &str) -> Result<Vec<MacroSignature>> {
+        Ok(introspection::macros(self.get_template(template_name)?))
     }
 
     /// Add a single template to the Tera instance
@@ -370,8 +995,10 @@ impl Tera {
     /// tera.add_raw_template("new.html", "Blabla");
     /// ```
     pub fn add_raw_template(&mut self, name: &str, content: &str) -> Result<()> {
-        let tpl = Template::new(name, None, content)
+        let tpl = self
+            .parse_template(name, None, content)
             .map_err(|e| Error::chain(format!("Failed to parse '{}'", name), e))?;
+        log!(debug, "Added template `{}`", name);
         self.templates.insert(name.to_string(), tpl);
         self.build_inheritance_chains()?;
         self.check_macro_files()?;
@@ -397,8 +1024,10 @@ impl Tera {
     {
         for (name, content) in templates {
             let name = name.as_ref();
-            let tpl = Template::new(name, None, content.as_ref())
+            let tpl = self
+                .parse_template(name, None, content.as_ref())
                 .map_err(|e| Error::chain(format!("Failed to parse '{}'", name), e))?;
+            log!(debug, "Added template `{}`", name);
             self.templates.insert(name.to_string(), tpl);
         }
         self.build_inheritance_chains()?;
@@ -472,6 +1101,25 @@ impl Tera {
         self.filters.insert(name.to_string(), Arc::new(filter));
     }
 
+    /// Registers the filter `name` as deprecated in favor of `replacement`, so its uses are
+    /// reported by [Tera::deprecations](struct.Tera.html#method.deprecations) and
+    /// [Tera::render_with_warnings](struct.Tera.html#method.render_with_warnings) while the
+    /// templates are migrated. The filter keeps working and can be a built-in one.
+    ///
+    /// ```rust
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.register_filter_deprecated("json_encode", "tojson");
+    /// tera.add_raw_template("page.html", "{{ user | json_encode }}").unwrap();
+    /// assert_eq!(
+    ///     tera.deprecations()[0].to_string(),
+    ///     "Template 'page.html' at line 1, column 1: filter `json_encode` is deprecated, use `tojson` instead"
+    /// );
+    /// ```
+    pub fn register_filter_deprecated(&mut self, name: &str, replacement: &str) {
+        self.deprecated_filters.insert(name.to_string(), replacement.to_string());
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn get_tester(&self, tester_name: &str) -> Result<&dyn Test> {
@@ -512,6 +1160,31 @@ impl Tera {
         self.functions.insert(name.to_string(), Arc::new(function));
     }
 
+    /// Register a tag with Tera, used as `{% name key=value %}` or with a body as
+    /// `{% name key=value %}...{% endname %}`.
+    ///
+    /// If a tag with that name already exists, it will be overwritten. The name of a Tera tag,
+    /// like `include`, or a name starting with `end` can't be used.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use tera::{Context, Result, Tera, Value};
+    /// let mut tera = Tera::default();
+    /// tera.register_tag("alert", |args: &HashMap<String, Value>, body: Option<&str>| -> Result<String> {
+    ///     let level = args.get("level").and_then(|l| l.as_str()).unwrap_or("info");
+    ///     Ok(format!("<p class=\"alert-{}\">{}</p>", level, body.unwrap_or("")))
+    /// });
+    /// tera.add_raw_template("page", "{% alert level='warning' %}Careful{% endalert %}")?;
+    /// assert_eq!(
+    ///     tera.render("page", &Context::new())?,
+    ///     "<p class=\"alert-warning\">Careful</p>"
+    /// );
+    /// # Ok::<(), tera::Error>(())
+    /// ```
+    pub fn register_tag<T: Tag + 'static>(&mut self, name: &str, tag: T) {
+        self.tags.insert(name.to_string(), Arc::new(tag));
+    }
+
     fn register_tera_filters(&mut self) {
         self.register_filter("upper", string::upper);
         self.register_filter("lower", string::lower);
@@ -520,22 +1193,50 @@ impl Tera {
         self.register_filter("trim_end", string::trim_end);
         self.register_filter("trim_start_matches", string::trim_start_matches);
         self.register_filter("trim_end_matches", string::trim_end_matches);
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "builtins-string")]
         self.register_filter("truncate", string::truncate);
+        self.register_filter("truncatewords", string::truncatewords);
         self.register_filter("wordcount", string::wordcount);
+        self.register_filter("pad_left", string::pad_left);
+        self.register_filter("pad_right", string::pad_right);
+        self.register_filter("center", string::center);
+        self.register_filter("repeat", string::repeat);
         self.register_filter("replace", string::replace);
+        let regexes = RegexCache::default();
+        self.register_filter(
+            "regex_replace",
+            move |value: &Value, args: &HashMap<String, Value>| {
+                string::regex_replace(&regexes, value, args)
+            },
+        );
         self.register_filter("capitalize", string::capitalize);
         self.register_filter("title", string::title);
         self.register_filter("striptags", string::striptags);
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "markdown")]
+        self.register_filter("markdown", string::Markdown);
+        #[cfg(feature = "hashes")]
+        self.register_filter("sha256", string::sha256);
+        #[cfg(feature = "hashes")]
+        self.register_filter("sha1", string::sha1);
+        #[cfg(feature = "hashes")]
+        self.register_filter("md5", string::md5);
+        #[cfg(feature = "builtins-string")]
         self.register_filter("urlencode", string::urlencode);
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "builtins-string")]
         self.register_filter("urlencode_strict", string::urlencode_strict);
+        #[cfg(feature = "builtins-string")]
+        self.register_filter("base64_encode", string::base64_encode);
+        #[cfg(feature = "builtins-string")]
+        self.register_filter("base64_decode", string::base64_decode);
         self.register_filter("escape", string::escape_html);
         self.register_filter("escape_xml", string::escape_xml);
-        #[cfg(feature = "builtins")]
+        self.register_filter("xml_escape", string::escape_xml);
+        self.register_filter("latex_escape", string::latex_escape);
+        #[cfg(feature = "builtins-string")]
         self.register_filter("slugify", string::slugify);
         self.register_filter("addslashes", string::addslashes);
+        self.register_filter("shell_quote", string::shell_quote);
+        self.register_filter("csv_escape", string::csv_escape);
         self.register_filter("split", string::split);
         self.register_filter("int", string::int);
         self.register_filter("float", string::float);
@@ -544,6 +1245,7 @@ impl Tera {
         self.register_filter("last", array::last);
         self.register_filter("nth", array::nth);
         self.register_filter("join", array::join);
+        self.register_filter("csv_row", array::csv_row);
         self.register_filter("sort", array::sort);
         self.register_filter("unique", array::unique);
         self.register_filter("slice", array::slice);
@@ -551,26 +1253,48 @@ impl Tera {
         self.register_filter("filter", array::filter);
         self.register_filter("map", array::map);
         self.register_filter("concat", array::concat);
+        self.register_filter("flatten", array::flatten);
+        self.register_filter("zip", array::zip);
+        self.register_filter("sum", array::sum);
+        self.register_filter("avg", array::avg);
+        self.register_filter("min", array::min);
+        self.register_filter("max", array::max);
+        #[cfg(feature = "builtins-random")]
+        self.register_filter("random_choice", array::random_choice);
+        #[cfg(feature = "builtins-random")]
+        self.register_filter("shuffle", array::shuffle);
 
         self.register_filter("pluralize", number::pluralize);
         self.register_filter("round", number::round);
+        self.register_filter("num_format", number::num_format);
+        self.register_filter("currency", number::currency);
 
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "builtins-number")]
         self.register_filter("filesizeformat", number::filesizeformat);
 
         self.register_filter("length", common::length);
         self.register_filter("reverse", common::reverse);
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "builtins-date")]
         self.register_filter("date", common::date);
+        #[cfg(feature = "builtins-date")]
+        self.register_filter("timesince", common::timesince);
         self.register_filter("json_encode", common::json_encode);
+        self.register_filter("pprint", common::pprint);
         self.register_filter("as_str", common::as_str);
+        self.register_filter("format", common::format);
 
         self.register_filter("get", object::get);
+        self.register_filter("items", object::items);
+        self.register_filter("keys", object::keys);
+        self.register_filter("values", object::values);
+        self.register_filter("dictsort", object::dictsort);
+        self.register_filter("merge", object::merge);
     }
 
     fn register_tera_testers(&mut self) {
         self.register_tester("defined", testers::defined);
         self.register_tester("undefined", testers::undefined);
+        self.register_tester("none", testers::none);
         self.register_tester("odd", testers::odd);
         self.register_tester("even", testers::even);
         self.register_tester("string", testers::string);
@@ -578,24 +1302,33 @@ impl Tera {
         self.register_tester("divisibleby", testers::divisible_by);
         self.register_tester("iterable", testers::iterable);
         self.register_tester("object", testers::object);
+        self.register_tester("mapping", testers::object);
+        self.register_tester("array", testers::array);
+        self.register_tester("boolean", testers::boolean);
         self.register_tester("starting_with", testers::starting_with);
         self.register_tester("ending_with", testers::ending_with);
         self.register_tester("containing", testers::containing);
-        self.register_tester("matching", testers::matching);
+        let regexes = RegexCache::default();
+        self.register_tester("matching", move |value: Option<&Value>, params: &[Value]| {
+            testers::matching(&regexes, value, params)
+        });
     }
 
     fn register_tera_functions(&mut self) {
         self.register_function("range", functions::range);
-        #[cfg(feature = "builtins")]
+        #[cfg(feature = "builtins-date")]
         self.register_function("now", functions::now);
         self.register_function("throw", functions::throw);
-        #[cfg(feature = "builtins")]
+        self.register_function("namespace", functions::namespace);
+        #[cfg(feature = "builtins-random")]
         self.register_function("get_random", functions::get_random);
         self.register_function("get_env", functions::get_env);
     }
 
     /// Select which suffix(es) to automatically do HTML escaping on,
-    ///`[".html", ".htm", ".xml"]` by default.
+    ///`[".html", ".htm", ".xml", ".svg", ".rss"]` by default.
+    /// The `.xml`, `.svg` and `.rss` templates are escaped with `escape_xml`, see
+    /// [Tera::set_escape_fn_for](struct.Tera.html#method.set_escape_fn_for).
     ///
     /// Only call this function if you wish to change the defaults.
     ///
@@ -634,11 +1367,414 @@ impl Tera {
         self.escape_fn = function;
     }
 
+    /// Set the escape function of the templates whose name, or path if they have one, ends with
+    /// `suffix`, adding it to the suffixes autoescaped if it isn't there already.
+    /// By default, `escape_xml` is used for the `.xml`, `.svg` and `.rss` templates.
+    /// It takes precedence over the function of `set_escape_fn` for those templates, and over
+    /// the ones of shorter suffixes: a full template name can be given to only change the
+    /// escaping of that template.
+    ///
+    ///```rust
+    /// # use tera::{escape_json_string, Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_escape_fn_for(".json", escape_json_string);
+    /// tera.add_raw_template("user.json", r#"{"name": "{{ name }}"}"#).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", "Bob \"The Builder\"");
+    /// let result = tera.render("user.json", &context).unwrap();
+    /// assert_eq!(result, r#"{"name": "Bob \"The Builder\""}"#);
+    ///```
+    pub fn set_escape_fn_for(&mut self, suffix: &'static str, function: EscapeFn) {
+        self.suffix_escape_fns.retain(|(s, _)| *s != suffix);
+        self.suffix_escape_fns.push((suffix, function));
+        if !self.autoescape_suffixes.contains(&suffix) {
+            self.autoescape_suffixes.push(suffix);
+        }
+    }
+
     /// Reset escape function to default `tera::escape_html`.
     pub fn reset_escape_fn(&mut self) {
         self.escape_fn = escape_html;
     }
 
+    /// Set the delimiters used for variables, tags and comments in the templates
+    /// added from now on.
+    /// If you are using a glob, call [Tera::full_reload](struct.Tera.html#method.full_reload)
+    /// after it to parse the templates already loaded again with those delimiters.
+    ///
+    /// This will error if a delimiter is empty, contains whitespace or if a start
+    /// delimiter is a prefix of another one.
+    ///
+    ///```rust,ignore
+    /// let mut tera = Tera::default();
+    /// tera.set_delimiters(Delimiters {
+    ///     variable_start: "<%=".to_string(),
+    ///     variable_end: "%>".to_string(),
+    ///     block_start: "<%".to_string(),
+    ///     block_end: "%>".to_string(),
+    ///     ..Delimiters::default()
+    /// })?;
+    ///```
+    pub fn set_delimiters(&mut self, delimiters: Delimiters) -> Result<()> {
+        delimiters.validate()?;
+        self.delimiters = delimiters;
+        Ok(())
+    }
+
+    /// Set whether the newline at the end of a template is kept in the rendered output,
+    /// which is the default.
+    /// Like [Tera::set_delimiters](struct.Tera.html#method.set_delimiters), this only
+    /// applies to the templates added from now on.
+    ///
+    ///```rust,ignore
+    /// tera.set_keep_trailing_newline(false);
+    /// tera.add_raw_template("hello", "Hello\n").unwrap();
+    /// assert_eq!(tera.render("hello", &Context::new()).unwrap(), "Hello");
+    ///```
+    pub fn set_keep_trailing_newline(&mut self, keep: bool) {
+        self.keep_trailing_newline = keep;
+    }
+
+    /// Set whether the Windows line endings (`\r\n`) of templates are replaced by `\n`
+    /// when parsing them, off by default.
+    /// Only the templates content is normalized, not the values coming from the context.
+    /// Like [Tera::set_delimiters](struct.Tera.html#method.set_delimiters), this only
+    /// applies to the templates added from now on.
+    pub fn set_normalize_newlines(&mut self, normalize: bool) {
+        self.normalize_newlines = normalize;
+    }
+
+    /// Set whether parsing a template goes on after a syntax error to report all the errors of
+    /// the template at once instead of only the first one, off by default.
+    /// This is meant for checking templates, in CI for example: the errors following the
+    /// first one can be caused by it.
+    /// Like [Tera::set_delimiters](struct.Tera.html#method.set_delimiters), this only
+    /// applies to the templates added from now on.
+    ///
+    ///```rust
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.set_report_all_syntax_errors(true);
+    /// let err = tera.add_raw_template("hello", "{{ a + }} {% if %}{% endif %} {{ b | }}").unwrap_err();
+    /// let message = std::error::Error::source(&err).unwrap().to_string();
+    /// assert!(message.starts_with("3 syntax errors"));
+    ///```
+    pub fn set_report_all_syntax_errors(&mut self, report: bool) {
+        self.report_all_syntax_errors = report;
+    }
+
+    /// Set whether the text made only of whitespace found between two tags, or between a tag
+    /// and the start or end of its body, is removed, off by default. This drops the indentation
+    /// and newlines of heavily structured templates without adding `{%-` and `-%}` everywhere,
+    /// while the whitespace around `{{ }}` is kept.
+    /// Like [Tera::set_delimiters](struct.Tera.html#method.set_delimiters), this only
+    /// applies to the templates added from now on.
+    ///
+    ///```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_remove_whitespace_between_tags(true);
+    /// tera.add_raw_template(
+    ///     "hello",
+    ///     "{% set greeting = 'Hello' %}\n{% if name %}\n  {% set greeting = greeting ~ ' ' ~ name %}\n{% endif %}\n{{ greeting }}",
+    /// ).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", "Bob");
+    /// assert_eq!(tera.render("hello", &context).unwrap(), "\nHello Bob");
+    ///```
+    pub fn set_remove_whitespace_between_tags(&mut self, remove: bool) {
+        self.remove_whitespace_between_tags = remove;
+    }
+
+    /// Set whether some of the syntax of Django templates is accepted, to ease porting them,
+    /// off by default:
+    ///
+    /// - `{% empty %}` in `for` loops, the same as `{% else %}`
+    /// - `{% comment %}...{% endcomment %}` blocks
+    /// - filter arguments given as `value|filter:arg`. The argument is passed to the built-in
+    ///   filters under the name of their main argument, `value|join:", "` being the same as
+    ///   `value|join(sep=", ")`, and to the other filters as `arg`
+    ///
+    /// Like [Tera::set_delimiters](struct.Tera.html#method.set_delimiters), this only
+    /// applies to the templates added from now on.
+    ///
+    ///```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_django_syntax(true);
+    /// tera.add_raw_template(
+    ///     "list",
+    ///     "{% comment %}Names{% endcomment %}{% for n in names %}{{ n }}{% empty %}{{ none|default:\"-\" }}{% endfor %}",
+    /// ).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("names", &Vec::<String>::new());
+    /// assert_eq!(tera.render("list", &context).unwrap(), "-");
+    ///```
+    pub fn set_django_syntax(&mut self, enabled: bool) {
+        self.django_syntax = enabled;
+    }
+
+    /// Select which suffix(es) the templates using the syntax of Liquid templates end with,
+    /// none by default. Those are translated to the Tera syntax when added and then render
+    /// like any other template, with the Tera filters:
+    ///
+    /// - `{% assign %}` is `{% set %}` and `{% elsif %}` is `{% elif %}`
+    /// - `{% unless %}` and `{% case %}`/`{% when %}` become `if` tags
+    /// - `(1..5)` ranges and the `reversed`, `limit:` and `offset:` of `for` loops
+    /// - `contains`, `nil` and `forloop`
+    /// - filter arguments given as `value | filter: arg1, arg2`, the Liquid filters with a
+    ///   Tera equivalent being renamed, `upcase` becoming `upper` for example
+    /// - `{% comment %}...{% endcomment %}` blocks
+    ///
+    /// Like [Tera::set_delimiters](struct.Tera.html#method.set_delimiters), this only
+    /// applies to the templates added from now on.
+    ///
+    ///```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.liquid_syntax_on(vec![".liquid"]);
+    /// tera.add_raw_template(
+    ///     "list.liquid",
+    ///     "{% assign names = list | split: \",\" %}{% for n in names limit: 2 %}{{ n | upcase }}{% endfor %}",
+    /// ).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("list", "a,b,c");
+    /// assert_eq!(tera.render("list.liquid", &context).unwrap(), "AB");
+    ///```
+    pub fn liquid_syntax_on(&mut self, suffixes: Vec<&'static str>) {
+        self.liquid_suffixes = suffixes;
+    }
+
+    /// Set the translator used for the `{% trans %}` tags of all templates.
+    ///
+    /// ```rust,ignore
+    /// let mut fr = HashMap::new();
+    /// fr.insert("Hello {{ name }}".to_string(), "Bonjour {{ name }}".to_string());
+    /// let mut catalog = HashMap::new();
+    /// catalog.insert("fr".to_string(), fr);
+    /// tera.set_translator(catalog);
+    /// ```
+    pub fn set_translator<T: Translator + 'static>(&mut self, translator: T) {
+        self.translator = Some(Arc::new(translator));
+    }
+
+    /// Set the resolver building the URLs of the `url_for` function, usually given by the router
+    /// of a web framework: `{{ url_for(name="post", slug=post.slug) }}` gives it the name of the
+    /// route and the other arguments as parameters.
+    ///
+    /// If `strict` is true, a route the resolver doesn't know fails the render. Otherwise, its URL
+    /// is an empty string.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use tera::{Context, Tera, Value};
+    /// let mut tera = Tera::default();
+    /// tera.set_url_resolver(
+    ///     |name: &str, params: &HashMap<String, Value>| match name {
+    ///         "post" => Some(format!("/blog/{}/", params.get("slug")?.as_str()?)),
+    ///         _ => None,
+    ///     },
+    ///     true,
+    /// );
+    /// let output = tera.render_str("{{ url_for(name='post', slug='hello') }}", &Context::new());
+    /// assert_eq!(output.unwrap(), "/blog/hello/");
+    /// assert!(tera.render_str("{{ url_for(name='page') }}", &Context::new()).is_err());
+    /// ```
+    pub fn set_url_resolver<R: UrlResolver + 'static>(&mut self, resolver: R, strict: bool) {
+        self.register_function("url_for", UrlFor { resolver: Arc::new(resolver), strict });
+    }
+
+    /// Set the resolver giving the URLs of the static files for the `asset` function, such as a
+    /// manifest of the fingerprinted files written by an asset pipeline:
+    /// `{{ asset(path="app.css") }}` renders the URL the resolver gives for `app.css`, and a path
+    /// it doesn't know fails the render.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use tera::{Context, Tera};
+    /// let manifest: HashMap<String, String> =
+    ///     serde_json::from_str(r#"{"app.css": "/static/app.3f2a.css"}"#).unwrap();
+    /// let mut tera = Tera::default();
+    /// tera.set_asset_resolver(manifest);
+    /// let output = tera.render_str("{{ asset(path='app.css') }}", &Context::new());
+    /// assert_eq!(output.unwrap(), "/static/app.3f2a.css");
+    /// ```
+    pub fn set_asset_resolver<R: AssetResolver + 'static>(&mut self, resolver: R) {
+        self.register_function("asset", Asset { resolver: Arc::new(resolver) });
+    }
+
+    /// Set the hooks called by the renderer as it goes through the nodes, includes and filters
+    /// of templates, for example to trace renders.
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use tera::{Context, HookPhase, RenderHooks, Span, Tera};
+    /// struct FilterCounter(AtomicUsize);
+    ///
+    /// impl RenderHooks for FilterCounter {
+    ///     fn on_filter(&self, phase: HookPhase, _: &str, _: &Span, _: &str) {
+    ///         if phase == HookPhase::Enter {
+    ///             self.0.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut tera = Tera::default();
+    /// tera.set_render_hooks(FilterCounter(AtomicUsize::new(0)));
+    /// tera.add_raw_template("hello", "{{ name | upper | trim }}").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", "Bob");
+    /// tera.render("hello", &context).unwrap();
+    /// ```
+    pub fn set_render_hooks<H: RenderHooks + 'static>(&mut self, hooks: H) {
+        self.render_hooks = Some(Arc::new(hooks));
+    }
+
+    /// Sets what a subscript missing from its array or object, like `items[10]` on an array of
+    /// 3 elements, evaluates to: an error by default. It can be overridden for a single render
+    /// with [Tera::render_with_options](struct.Tera.html#method.render_with_options).
+    ///
+    /// ```rust
+    /// # use tera::{Context, OutOfBounds, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_out_of_bounds(OutOfBounds::Undefined);
+    /// tera.add_raw_template("hello", "Hello {{ names[3] }}").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("names", &["Bob"]);
+    /// assert_eq!(tera.render("hello", &context).unwrap(), "Hello ");
+    /// ```
+    pub fn set_out_of_bounds(&mut self, out_of_bounds: OutOfBounds) {
+        self.out_of_bounds = out_of_bounds;
+    }
+
+    /// Set whether the output of the templates whose name ends with `.html` or `.htm` is
+    /// minified, off by default: the runs of whitespace are collapsed into a single space and
+    /// the comments are removed, except the conditional ones (`<!--[if IE]>...<![endif]-->`).
+    /// The values of the attributes and the content of the `pre`, `textarea`, `script` and
+    /// `style` elements are kept as is.
+    /// It can be overridden for a single render with
+    /// [Tera::render_with_options](struct.Tera.html#method.render_with_options).
+    ///
+    /// ```rust
+    /// # use tera::{Context, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_minify_html(true);
+    /// tera.add_raw_template("hello.html", "<!-- Greeting -->\n<p>\n  Hello {{ name }}\n</p>\n").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", "Bob");
+    /// assert_eq!(tera.render("hello.html", &context).unwrap(), " <p> Hello Bob </p> ");
+    /// ```
+    pub fn set_minify_html(&mut self, minify: bool) {
+        self.minify_html = minify;
+    }
+
+    /// Set the order in which `{% for key, value in map %}` loops iterate on maps. By default it
+    /// is the order of the map itself, which is the insertion order with the `preserve_order`
+    /// feature: the order of a `HashMap` inserted in the context then changes between runs.
+    /// A single loop can be sorted with `{% for key, value in map sorted %}`.
+    /// It can be overridden for a single render with
+    /// [Tera::render_with_options](struct.Tera.html#method.render_with_options).
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use tera::{Context, MapOrder, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_map_order(MapOrder::Sorted);
+    /// tera.add_raw_template("env", "{% for k, v in env %}{{ k }}={{ v }};{% endfor %}").unwrap();
+    /// let mut env = HashMap::new();
+    /// env.insert("b", 2);
+    /// env.insert("a", 1);
+    /// env.insert("c", 3);
+    /// let mut context = Context::new();
+    /// context.insert("env", &env);
+    /// assert_eq!(tera.render("env", &context).unwrap(), "a=1;b=2;c=3;");
+    /// ```
+    pub fn set_map_order(&mut self, order: MapOrder) {
+        self.map_order = order;
+    }
+
+    /// Whether adding a template fails if it uses a filter, test, function or tag that is not
+    /// registered, instead of only failing the renders using it. Off by default since those are
+    /// often registered after the templates are loaded, in which case
+    /// [Tera::check](struct.Tera.html#method.check) can be used once everything is registered.
+    ///
+    /// ```rust
+    /// # use tera::Tera;
+    /// let mut tera = Tera::default();
+    /// tera.set_check_callables(true);
+    /// let err = tera.add_raw_template("hello.html", "Hello\n{{ name | shout }}").unwrap_err();
+    /// assert_eq!(
+    ///     std::error::Error::source(&err).unwrap().to_string(),
+    ///     "filter `shout` not found at line 2, column 1"
+    /// );
+    /// ```
+    pub fn set_check_callables(&mut self, check: bool) {
+        self.check_callables = check;
+    }
+
+    /// Register a transform applied to the source of every template added from now on, before
+    /// it is parsed and before the other syntax options such as the delimiters apply. They are
+    /// applied in the order they were registered, each getting the name of the template and the
+    /// output of the previous one. An error prevents the template from being added.
+    ///
+    /// ```rust
+    /// # use tera::{Context, Result, Tera};
+    /// let mut tera = Tera::default();
+    /// // Adds the macros to every template
+    /// tera.register_pre_processor(|name: &str, source: &str| -> Result<String> {
+    ///     if name == "macros.html" {
+    ///         return Ok(source.to_string());
+    ///     }
+    ///     Ok(format!("{{% import \"macros.html\" as macros %}}{}", source))
+    /// });
+    /// tera.add_raw_templates(vec![
+    ///     ("macros.html", "{% macro hello(name) %}Hello {{ name }}{% endmacro %}"),
+    ///     ("page.html", "{{ macros::hello(name=\"Bob\") }}"),
+    /// ]).unwrap();
+    /// assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "Hello Bob");
+    /// ```
+    pub fn register_pre_processor<P: PreProcessor + 'static>(&mut self, processor: P) {
+        self.pre_processors.push(Arc::new(processor));
+    }
+
+    /// Register a transform applied to the output of every render, after the HTML minification
+    /// if it is enabled. They are applied in the order they were registered, each getting the
+    /// output of the previous one and the name of the template rendered.
+    ///
+    /// ```rust
+    /// # use tera::{Context, Tera, TemplateMeta};
+    /// let mut tera = Tera::default();
+    /// tera.register_post_processor(|output: &str, template: &TemplateMeta| {
+    ///     if template.autoescape {
+    ///         output.replace("<script>", "<script nonce=\"abc\">")
+    ///     } else {
+    ///         output.to_string()
+    ///     }
+    /// });
+    /// tera.add_raw_template("page.html", "<script>init()</script>").unwrap();
+    /// let output = tera.render("page.html", &Context::new()).unwrap();
+    /// assert_eq!(output, "<script nonce=\"abc\">init()</script>");
+    /// ```
+    pub fn register_post_processor<P: PostProcessor + 'static>(&mut self, processor: P) {
+        self.post_processors.push(Arc::new(processor));
+    }
+
+    /// Set the cache keeping the output of the `{% cache key %}...{% endcache %}` blocks of all
+    /// templates between renders. Without one, those blocks are rendered every time.
+    ///
+    /// ```rust
+    /// # use tera::{Context, MemoryCache, Tera};
+    /// let mut tera = Tera::default();
+    /// tera.set_fragment_cache(MemoryCache::new(1000));
+    /// tera.add_raw_template("menu.html", "{% cache 'menu' ~ lang 300 %}{{ lang }}{% endcache %}").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("lang", "en");
+    /// assert_eq!(tera.render("menu.html", &context).unwrap(), "en");
+    /// ```
+    pub fn set_fragment_cache<C: FragmentCache + 'static>(&mut self, cache: C) {
+        self.fragment_cache = Some(Arc::new(cache));
+    }
+
     /// Re-parse all templates found in the glob given to Tera
     /// Use this when you are watching a directory and want to reload everything,
     /// for example when a file is added.
@@ -646,7 +1782,8 @@ impl Tera {
     /// If you are adding templates without using a glob, we can't know when a template
     /// is deleted, which would result in an error if we are trying to reload that file
     pub fn full_reload(&mut self) -> Result<()> {
-        if self.glob.is_some() {
+        if !self.globs.is_empty() {
+            log!(debug, "Reloading the templates of the globs `{}`", self.globs.join("`, `"));
             self.load_from_glob()?;
         } else {
             return Err(Error::msg("Reloading is only available if you are using a glob"));
@@ -686,6 +1823,18 @@ impl Tera {
             }
         }
 
+        for (name, tag) in &other.tags {
+            if !self.tags.contains_key(name) {
+                self.tags.insert(name.to_string(), tag.clone());
+            }
+        }
+
+        for (name, replacement) in &other.deprecated_filters {
+            if !self.deprecated_filters.contains_key(name) {
+                self.deprecated_filters.insert(name.to_string(), replacement.clone());
+            }
+        }
+
         self.build_inheritance_chains()?;
         self.check_macro_files()
     }
@@ -694,13 +1843,36 @@ impl Tera {
 impl Default for Tera {
     fn default() -> Tera {
         let mut tera = Tera {
-            glob: None,
+            globs: Vec::new(),
             templates: HashMap::new(),
             filters: HashMap::new(),
             testers: HashMap::new(),
             functions: HashMap::new(),
-            autoescape_suffixes: vec![".html", ".htm", ".xml"],
+            tags: HashMap::new(),
+            deprecated_filters: HashMap::new(),
+            autoescape_suffixes: vec![".html", ".htm", ".xml", ".svg", ".rss"],
             escape_fn: escape_html,
+            suffix_escape_fns: vec![
+                (".xml", escape_xml),
+                (".svg", escape_xml),
+                (".rss", escape_xml),
+            ],
+            delimiters: Delimiters::default(),
+            keep_trailing_newline: true,
+            normalize_newlines: false,
+            report_all_syntax_errors: false,
+            django_syntax: false,
+            remove_whitespace_between_tags: false,
+            liquid_suffixes: vec![],
+            translator: None,
+            render_hooks: None,
+            out_of_bounds: OutOfBounds::Error,
+            minify_html: false,
+            map_order: MapOrder::Insertion,
+            check_callables: false,
+            post_processors: Vec::new(),
+            pre_processors: Vec::new(),
+            fragment_cache: None,
         };
 
         tera.register_tera_filters();
@@ -742,10 +1914,14 @@ mod tests {
     use tempfile::tempdir;
 
     use std::collections::HashMap;
+    use std::error::Error;
     use std::fs::File;
+    use std::sync::Arc;
+    use std::thread;
 
-    use super::Tera;
+    use super::{Delimiters, Tera};
     use crate::context::Context;
+    use crate::errors::ErrorKind;
     use serde_json::{json, Value as JsonValue};
 
     #[test]
@@ -768,6 +1944,27 @@ mod tests {
         assert_eq!(tera.get_template("d").unwrap().parents.len(), 0);
     }
 
+    #[test]
+    fn test_dependencies_and_dependents() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base", "{% import \"macros\" as macros %}{% block content %}{% endblock content %}"),
+            ("macros", "{% macro hey() %}{% include \"partial\" %}{% endmacro hey %}"),
+            ("partial", "Hello"),
+            ("page", "{% extends \"base\" %}{% block content %}{% include \"footer\" %}{% endblock content %}"),
+            ("footer", ""),
+            ("other", "{% include \"footer\" %}"),
+        ])
+        .unwrap();
+
+        assert_eq!(tera.dependencies("page").unwrap(), vec!["base", "footer", "macros", "partial"]);
+        assert_eq!(tera.dependencies("partial").unwrap(), Vec::<&str>::new());
+        assert_eq!(tera.dependents("partial").unwrap(), vec!["base", "macros", "page"]);
+        assert_eq!(tera.dependents("footer").unwrap(), vec!["other", "page"]);
+        assert_eq!(tera.dependents("page").unwrap(), Vec::<&str>::new());
+        assert!(tera.dependencies("unknown").is_err());
+    }
+
     #[test]
     fn test_missing_parent_template() {
         let mut tera = Tera::default();
@@ -897,6 +2094,54 @@ mod tests {
         assert_eq!(result, "Hello\n&#x27;world&quot;!");
     }
 
+    #[test]
+    fn test_set_escape_function_for_suffix() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("data.json", "\"{{ content }}\""),
+            ("page.html", "{{ content }}"),
+            ("raw.json", "{{ content }}"),
+        ])
+        .unwrap();
+        tera.set_escape_fn_for(".json", crate::escape_json_string);
+        tera.set_escape_fn_for("raw.json", |input| input.to_string());
+        let mut context = Context::new();
+        context.insert("content", &"<a href=\"/\">\\\n\u{1}</a>");
+
+        let result = tera.render("data.json", &context).unwrap();
+        assert_eq!(result, r#""<a href=\"/\">\\\n\u0001</a>""#);
+        let result = tera.render("page.html", &context).unwrap();
+        assert_eq!(result, "&lt;a href=&quot;&#x2F;&quot;&gt;\\\n\u{1}&lt;&#x2F;a&gt;");
+        // The longest suffix matching is used
+        let result = tera.render("raw.json", &context).unwrap();
+        assert_eq!(result, "<a href=\"/\">\\\n\u{1}</a>");
+    }
+
+    #[test]
+    fn test_xml_templates_are_escaped_as_xml() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("feed.rss", "{{ content }}"),
+            ("image.svg", "{{ content }}"),
+            ("sitemap.xml", "{{ content }}"),
+            ("page.html", "{{ content | xml_escape | safe }}"),
+        ])
+        .unwrap();
+        let mut context = Context::new();
+        context.insert("content", &"<a href='/'>&</a>");
+
+        for name in &["feed.rss", "image.svg", "sitemap.xml"] {
+            let result = tera.render(name, &context).unwrap();
+            assert_eq!(result, "&lt;a href=&apos;/&apos;&gt;&amp;&lt;/a&gt;");
+        }
+        let result = tera.render("page.html", &context).unwrap();
+        assert_eq!(result, "&lt;a href=&apos;/&apos;&gt;&amp;&lt;/a&gt;");
+
+        tera.autoescape_on(vec![]);
+        let result = tera.render("feed.rss", &context).unwrap();
+        assert_eq!(result, "<a href='/'>&</a>");
+    }
+
     #[test]
     fn test_value_one_off_template() {
         let m = json!({
@@ -921,6 +2166,52 @@ mod tests {
         assert_eq!(result, "Hello world");
     }
 
+    #[test]
+    fn test_render_to_writes_the_same_output_as_render() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("base.html", "<ul>\n  {% block items %}{% endblock items %}\n</ul>\n"),
+            (
+                "page.html",
+                "{% extends \"base.html\" %}{% block items %}{% for i in items %}<li>{{ i }}</li>{% endfor %}{% endblock items %}",
+            ),
+        ])
+        .unwrap();
+        let mut context = Context::new();
+        context.insert("items", &vec!["<a>", "b"]);
+
+        for minify in &[false, true] {
+            tera.set_minify_html(*minify);
+            let mut output = Vec::new();
+            tera.render_to("page.html", &context, &mut output).unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                tera.render("page.html", &context).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_to_returns_io_errors() {
+        struct Failing;
+        impl std::io::Write for Failing {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello", "Hello").unwrap();
+        let err = tera.render_to("hello", &Context::new(), Failing).unwrap_err();
+        match err.kind {
+            ErrorKind::Io(kind) => assert_eq!(kind, std::io::ErrorKind::BrokenPipe),
+            _ => panic!("expected an io error, got {:?}", err.kind),
+        }
+    }
+
     #[test]
     fn test_extend_no_overlap() {
         let mut my_tera = Tera::default();
@@ -1055,6 +2346,60 @@ mod tests {
         assert_eq!(tera.templates.len(), 2);
     }
 
+    #[test]
+    fn template_names_are_normalized() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(vec![
+            ("users/profile.html", "profile"),
+            ("../shared.html", "shared"),
+        ])
+        .unwrap();
+
+        let render = |name: &str| tera.render(name, &Context::new());
+        assert_eq!(render("./users//profile.html").unwrap(), "profile");
+        assert_eq!(render("users/../users/profile.html").unwrap(), "profile");
+        // Templates added with such a name can still be rendered with it
+        assert_eq!(render("../shared.html").unwrap(), "shared");
+        match render("users/nope.html").unwrap_err().kind {
+            ErrorKind::TemplateNotFound(_) => (),
+            kind => panic!("expected a template not found error, got {:?}", kind),
+        }
+        for name in &["../../etc/passwd", "/etc/passwd", "users/../../secrets.txt"] {
+            match render(name).unwrap_err().kind {
+                ErrorKind::InvalidTemplateName(ref n) => assert_eq!(n, name),
+                kind => panic!("expected an invalid template name error, got {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn layered_globs_override_templates_in_order() {
+        let tmp_dir = tempdir().expect("create temp dir");
+        let cwd = tmp_dir.path().canonicalize().unwrap();
+        for (path, content) in &[
+            ("default/base.html", "<{% block body %}{% endblock %}>"),
+            ("default/page.html", "{% extends 'base.html' %}{% block body %}default{% endblock %}"),
+            ("default/nav.html", "nav"),
+            ("theme/page.html", "{% extends 'base.html' %}{% block body %}theme{% endblock %}"),
+        ] {
+            std::fs::create_dir_all(cwd.join(path).parent().unwrap()).unwrap();
+            std::fs::write(cwd.join(path), content).unwrap();
+        }
+        let glob = |dir: &str| cwd.join(dir).join("**").join("*").to_string_lossy().into_owned();
+
+        let mut tera = Tera::new_layered(&[&glob("theme"), &glob("default")]).unwrap();
+        assert_eq!(tera.templates.len(), 3);
+        assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "<theme>");
+        assert_eq!(tera.render("nav.html", &Context::new()).unwrap(), "nav");
+
+        // Removing the override falls back to the default template
+        std::fs::remove_file(cwd.join("theme/page.html")).unwrap();
+        tera.full_reload().unwrap();
+        assert_eq!(tera.render("page.html", &Context::new()).unwrap(), "<default>");
+
+        assert!(Tera::new_layered(&[&glob("theme"), "default"]).is_err());
+    }
+
     // https://github.com/Keats/tera/issues/396
     #[test]
     fn issues_found_fuzzing_expressions_are_fixed() {
@@ -1106,4 +2451,149 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn can_use_custom_delimiters() {
+        let mut tera = Tera::default();
+        tera.set_delimiters(Delimiters {
+            variable_start: "<%=".to_string(),
+            variable_end: "%>".to_string(),
+            block_start: "<%".to_string(),
+            block_end: "%>".to_string(),
+            comment_start: "<%#".to_string(),
+            comment_end: "%>".to_string(),
+        })
+        .unwrap();
+        tera.add_raw_templates(vec![
+            ("base", "<%# a comment %>[<% block content %><% endblock content %>]"),
+            ("child", "<% extends \"base\" %><% block content %><% for i in items -%> <%= i %>{{ i }}<%- endfor %><% endblock content %>"),
+        ])
+        .unwrap();
+        let mut context = Context::new();
+        context.insert("items", &[1, 2]);
+        assert_eq!(tera.render("child", &context).unwrap(), "[1{{ i }}2{{ i }}]");
+    }
+
+    #[test]
+    fn errors_on_invalid_delimiters() {
+        let mut tera = Tera::default();
+        let res = tera
+            .set_delimiters(Delimiters { block_start: "{{".to_string(), ..Delimiters::default() });
+        assert!(res.is_err());
+        assert_eq!(tera.delimiters, Delimiters::default());
+    }
+
+    #[test]
+    fn can_remove_trailing_newline() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("kept", "Hello\n").unwrap();
+        tera.set_keep_trailing_newline(false);
+        tera.add_raw_templates(vec![
+            ("unix", "Hello\n\n"),
+            ("windows", "Hello\r\n"),
+            ("none", "Hello"),
+        ])
+        .unwrap();
+        assert_eq!(tera.render("kept", &Context::new()).unwrap(), "Hello\n");
+        assert_eq!(tera.render("unix", &Context::new()).unwrap(), "Hello\n");
+        assert_eq!(tera.render("windows", &Context::new()).unwrap(), "Hello");
+        assert_eq!(tera.render("none", &Context::new()).unwrap(), "Hello");
+    }
+
+    #[test]
+    fn can_normalize_newlines() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("raw", "a\r\nb\r\n").unwrap();
+        tera.set_normalize_newlines(true);
+        tera.add_raw_template("normalized", "a\r\n{% if true %}b\r\n{% endif %}").unwrap();
+        assert_eq!(tera.render("raw", &Context::new()).unwrap(), "a\r\nb\r\n");
+        assert_eq!(tera.render("normalized", &Context::new()).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn can_pre_process_sources() {
+        let mut tera = Tera::default();
+        // Strips a front-matter
+        tera.register_pre_processor(|_: &str, source: &str| -> crate::Result<String> {
+            if !source.starts_with("+++\n") {
+                return Ok(source.to_string());
+            }
+            match source[4..].find("+++\n") {
+                Some(end) => Ok(source[end + 8..].to_string()),
+                None => Err(crate::Error::msg("Unclosed front-matter")),
+            }
+        });
+        tera.register_pre_processor(|name: &str, source: &str| -> crate::Result<String> {
+            Ok(format!("{}: {}", name, source))
+        });
+
+        tera.add_raw_template("page", "+++\ntitle = \"Hello\"\n+++\n{{ 1 + 1 }}").unwrap();
+        assert_eq!(tera.render("page", &Context::new()).unwrap(), "page: 2");
+        let err = tera.add_raw_template("broken", "+++\ntitle = \"Hello\"").unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse 'broken'");
+        assert_eq!(err.source().unwrap().to_string(), "Failed to pre-process template 'broken'");
+    }
+
+    #[test]
+    fn can_report_all_syntax_errors() {
+        let mut tera = Tera::default();
+        let template = "{{ a + }}{% if %}{% endif %}";
+        let err = tera.add_raw_template("first", template).unwrap_err();
+        assert!(!err.source().unwrap().to_string().contains("syntax errors"));
+
+        tera.set_report_all_syntax_errors(true);
+        let err = tera.add_raw_template("all", template).unwrap_err();
+        assert!(err.source().unwrap().to_string().starts_with("2 syntax errors:\n"));
+        // A single error is reported as usual
+        let err = tera.add_raw_template("single", "{{ a + }}").unwrap_err();
+        assert!(err.source().unwrap().to_string().starts_with(" --> 1:8"));
+    }
+
+    #[test]
+    fn can_check_callables_when_adding_templates() {
+        let mut tera = Tera::default();
+        let template = "{{ a | shout }}\n{% if a is odd and a is weird %}{{ hey() }}{% endif %}";
+        tera.add_raw_template("unchecked", template).unwrap();
+
+        tera.set_check_callables(true);
+        let err = tera.add_raw_template("checked", template).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse 'checked'");
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            "filter `shout` not found at line 1, column 1\n\
+             test `weird` not found at line 2, column 1\n\
+             function `hey` not found at line 2, column 33"
+        );
+        // Templates and macros can be added later so they are not checked
+        tera.add_raw_template("include", "{% include 'nope' %}{{ m::hey() }}").unwrap();
+    }
+
+    #[test]
+    fn tera_is_send_and_sync() {
+        fn test_send_sync<T: Send + Sync>() {}
+
+        test_send_sync::<Tera>();
+    }
+
+    #[test]
+    fn can_render_from_several_threads() {
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello", "{% for i in range(end=n) %}{{ i }}{% endfor %}").unwrap();
+        let tera = Arc::new(tera);
+
+        let handles: Vec<_> = (0..8)
+            .map(|n| {
+                let tera = Arc::clone(&tera);
+                thread::spawn(move || {
+                    let mut context = Context::new();
+                    context.insert("n", &n);
+                    tera.render("hello", &context).unwrap()
+                })
+            })
+            .collect();
+        for (n, handle) in handles.into_iter().enumerate() {
+            let expected: String = (0..n).map(|i| i.to_string()).collect();
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
 }
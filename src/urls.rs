@@ -0,0 +1,173 @@
+//! The functions building URLs: `url_for` with the routes of the web framework rendering and
+//! `asset` with the fingerprinted names of the static files
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::builtins::functions::Function;
+use crate::errors::{Error, Result};
+
+/// Something that can build the URL of a named route, usually the router of a web framework.
+///
+/// `{{ url_for(name="post", slug=post.slug) }}` asks for the URL of the `post` route with
+/// `{"slug": ...}` as parameters.
+pub trait UrlResolver: Sync + Send {
+    /// Returns the URL of the route `name` with these parameters, or `None` if there isn't
+    /// such a route
+    fn url_for(&self, name: &str, params: &HashMap<String, Value>) -> Option<String>;
+}
+
+impl<F> UrlResolver for F
+where
+    F: Fn(&str, &HashMap<String, Value>) -> Option<String> + Sync + Send,
+{
+    fn url_for(&self, name: &str, params: &HashMap<String, Value>) -> Option<String> {
+        self(name, params)
+    }
+}
+
+/// The `url_for` function registered by `Tera::set_url_resolver`
+pub(crate) struct UrlFor {
+    pub(crate) resolver: Arc<dyn UrlResolver>,
+    /// Whether an unknown route fails the render instead of rendering as an empty string
+    pub(crate) strict: bool,
+}
+
+impl Function for UrlFor {
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        let name = match args.get("name") {
+            Some(Value::String(name)) => name,
+            Some(val) => {
+                return Err(Error::msg(format!(
+                    "Function `url_for` received name={} but `name` can only be a string",
+                    val
+                )))
+            }
+            None => return Err(Error::msg("Function `url_for` expected an arg called `name`")),
+        };
+        let mut params = args.clone();
+        params.remove("name");
+
+        match self.resolver.url_for(name, &params) {
+            Some(url) => Ok(Value::String(url)),
+            None if self.strict => {
+                Err(Error::msg(format!("Function `url_for` found no route named `{}`", name)))
+            }
+            None => Ok(Value::String(String::new())),
+        }
+    }
+}
+
+/// Something that can give the URL of a static file from its logical path, usually the name of
+/// the file with a hash of its content added by the asset pipeline.
+///
+/// `{{ asset(path="app.css") }}` asks for the URL of `app.css`, which could be `/static/app.3f2a.css`.
+/// A `HashMap` of the logical paths to the URLs, such as a manifest written by the asset pipeline,
+/// can be used directly.
+pub trait AssetResolver: Sync + Send {
+    /// Returns the URL of the file at `path`, or `None` if there isn't such a file
+    fn asset_url(&self, path: &str) -> Option<String>;
+}
+
+impl<F> AssetResolver for F
+where
+    F: Fn(&str) -> Option<String> + Sync + Send,
+{
+    fn asset_url(&self, path: &str) -> Option<String> {
+        self(path)
+    }
+}
+
+/// A manifest: `logical path -> URL`
+impl AssetResolver for HashMap<String, String> {
+    fn asset_url(&self, path: &str) -> Option<String> {
+        self.get(path).cloned()
+    }
+}
+
+/// The `asset` function registered by `Tera::set_asset_resolver`
+pub(crate) struct Asset {
+    pub(crate) resolver: Arc<dyn AssetResolver>,
+}
+
+impl Function for Asset {
+    fn call(&self, args: &HashMap<String, Value>) -> Result<Value> {
+        let path = match args.get("path") {
+            Some(Value::String(path)) => path,
+            Some(val) => {
+                return Err(Error::msg(format!(
+                    "Function `asset` received path={} but `path` can only be a string",
+                    val
+                )))
+            }
+            None => return Err(Error::msg("Function `asset` expected an arg called `path`")),
+        };
+
+        match self.resolver.asset_url(path) {
+            Some(url) => Ok(Value::String(url)),
+            None => Err(Error::msg(format!("Function `asset` found no asset at `{}`", path))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    use crate::context::Context;
+    use crate::tera::Tera;
+
+    fn resolve(name: &str, params: &HashMap<String, Value>) -> Option<String> {
+        let mut keys: Vec<_> = params.keys().map(|k| k.as_str()).collect();
+        keys.sort_unstable();
+        match name {
+            "search" => Some(format!("/search?{}", keys.join("&"))),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn can_resolve_assets() {
+        let mut manifest = HashMap::new();
+        manifest.insert("app.css".to_string(), "/static/app.3f2a.css".to_string());
+        let mut tera = Tera::default();
+        tera.add_raw_template("page", "{{ asset(path='app.css') }}").unwrap();
+        tera.add_raw_template("missing", "{{ asset(path='app.js') }}").unwrap();
+        tera.set_asset_resolver(manifest);
+
+        assert_eq!(tera.render("page", &Context::new()).unwrap(), "/static/app.3f2a.css");
+        let err = tera.render("missing", &Context::new()).unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(
+            std::error::Error::source(source).unwrap().to_string(),
+            "Function `asset` found no asset at `app.js`"
+        );
+    }
+
+    #[test]
+    fn can_resolve_urls() {
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "page.html",
+            "<a href=\"{{ url_for(name='search', q=1, page=2) }}\">",
+        )
+        .unwrap();
+        tera.add_raw_template("missing.html", "[{{ url_for(name='nope') }}]").unwrap();
+        tera.set_url_resolver(resolve, false);
+
+        let output = tera.render("page.html", &Context::new()).unwrap();
+        assert_eq!(output, "<a href=\"&#x2F;search?page&amp;q\">");
+        assert_eq!(tera.render("missing.html", &Context::new()).unwrap(), "[]");
+
+        tera.set_url_resolver(resolve, true);
+        let err = tera.render("missing.html", &Context::new()).unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(
+            std::error::Error::source(source).unwrap().to_string(),
+            "Function `url_for` found no route named `nope`"
+        );
+    }
+}
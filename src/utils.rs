@@ -1,3 +1,13 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref SPACES_BETWEEN_TAGS_RE: Regex = Regex::new(r">\s+<").unwrap();
+}
+
 /// Escape HTML following [OWASP](https://www.owasp.org/index.php/XSS_(Cross_Site_Scripting)_Prevention_Cheat_Sheet)
 ///
 /// Escape the following characters with HTML entity encoding to prevent switching
@@ -33,9 +43,246 @@ pub fn escape_html(input: &str) -> String {
     output
 }
 
+/// Escape XML, for XML documents such as feeds, sitemaps or SVG images
+///
+/// ```text
+/// & --> &amp;
+/// < --> &lt;
+/// > --> &gt;
+/// " --> &quot;
+/// ' --> &apos;
+/// ```
+pub fn escape_xml(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() * 2);
+    for c in input.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Escape the characters with a special meaning in LaTeX, for values written in `.tex` documents
+///
+/// ```text
+/// & % $ # _ { } --> \& \% \$ \# \_ \{ \}
+/// ~ --> \textasciitilde{}
+/// ^ --> \textasciicircum{}
+/// \ --> \textbackslash{}
+/// < --> \textless{}
+/// > --> \textgreater{}
+/// ```
+pub fn escape_latex(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() * 2);
+    for c in input.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                output.push('\\');
+                output.push(c);
+            }
+            '~' => output.push_str("\\textasciitilde{}"),
+            '^' => output.push_str("\\textasciicircum{}"),
+            '\\' => output.push_str("\\textbackslash{}"),
+            '<' => output.push_str("\\textless{}"),
+            '>' => output.push_str("\\textgreater{}"),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Escape a CSV field following [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180), for fields
+/// separated by commas: it is wrapped in double quotes, its own double quotes being doubled,
+/// if it contains a comma, a double quote or a line break.
+pub fn escape_csv(input: &str) -> String {
+    quote_csv_field(input, ",")
+}
+
+/// Quotes a CSV field like `escape_csv`, for fields separated by `sep`
+pub(crate) fn quote_csv_field(input: &str, sep: &str) -> String {
+    let needs_quotes =
+        input.contains(&['"', '\n', '\r'][..]) || (!sep.is_empty() && input.contains(sep));
+    if !needs_quotes {
+        return input.to_string();
+    }
+    format!("\"{}\"", input.replace('"', "\"\""))
+}
+
+/// Escape a string following the JSON string rules, for values written between quotes in
+/// JSON templates.
+///
+/// ```text
+/// " --> \"
+/// \ --> \\
+/// newline, carriage return, tab, backspace and form feed --> \n, \r, \t, \b and \f
+/// other control characters --> \u00XX
+/// ```
+pub fn escape_json_string(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() + 2);
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{8}' => output.push_str("\\b"),
+            '\u{c}' => output.push_str("\\f"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Removes the whitespace between HTML tags as well as the leading and trailing whitespace,
+/// used by the `spaceless` tag
+pub(crate) fn remove_spaces_between_tags(input: &str) -> String {
+    SPACES_BETWEEN_TAGS_RE.replace_all(input.trim(), "><").into_owned()
+}
+
+/// The elements whose content is kept as is when minifying HTML
+const RAW_HTML_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+/// Minifies HTML by collapsing the runs of whitespace into a single space and removing the
+/// comments, except the conditional comments (`<!--[if IE]>...<![endif]-->`).
+/// The values of the attributes and the content of the `pre`, `textarea`, `script` and
+/// `style` elements are kept as is.
+pub(crate) fn minify_html(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + 3);
+            let comment = &rest[..end];
+            if comment.starts_with("<!--[if") || comment.starts_with("<!--<![endif]") {
+                output.push_str(comment);
+            }
+            rest = &rest[end..];
+        } else if c == '<' && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/') {
+            let end = end_of_tag(rest);
+            let mut quote = None;
+            let mut previous_was_space = false;
+            for c in rest[..end].chars() {
+                match quote {
+                    Some(q) if c == q => quote = None,
+                    Some(_) => (),
+                    None if c == '"' || c == '\'' => quote = Some(c),
+                    None if c.is_whitespace() => {
+                        if !previous_was_space {
+                            output.push(' ');
+                        }
+                        previous_was_space = true;
+                        continue;
+                    }
+                    None => (),
+                }
+                previous_was_space = false;
+                output.push(c);
+            }
+
+            let name: String = rest[1..end]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .map(|c| c.to_ascii_lowercase())
+                .collect();
+            rest = &rest[end..];
+            if RAW_HTML_ELEMENTS.contains(&name.as_str()) {
+                let close = format!("</{}", name);
+                let content_end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+                output.push_str(&rest[..content_end]);
+                rest = &rest[content_end..];
+            }
+        } else if c.is_whitespace() {
+            // The whitespace around a removed comment is collapsed as well
+            if !output.ends_with(' ') {
+                output.push(' ');
+            }
+            rest = rest.trim_start();
+        } else {
+            output.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    output
+}
+
+/// The index following the `>` closing the tag `input` starts with, skipping the quoted values
+fn end_of_tag(input: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return i + 1,
+            None => (),
+        }
+    }
+    input.len()
+}
+
+/// Normalizes the name of a template the way a path relative to the template directories would be:
+/// `\\` becomes `/` and the empty, `.` and `..` segments are resolved.
+/// Returns `None` if it is absolute or goes above the template directories.
+pub(crate) fn normalize_template_name(name: &str) -> Option<String> {
+    let name = name.replace('\\', "/");
+    let is_drive =
+        name.len() >= 2 && name.as_bytes()[1] == b':' && name.as_bytes()[0].is_ascii_alphabetic();
+    if name.starts_with('/') || is_drive {
+        return None;
+    }
+
+    let mut segments = Vec::new();
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+/// How many patterns a `RegexCache` keeps before starting over, in case they are built dynamically
+const REGEX_CACHE_SIZE: usize = 500;
+
+/// The regexes compiled by the `matching` test and the `regex_replace` filter of a Tera instance,
+/// so a pattern used in a loop is only compiled once
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RegexCache(Arc<RwLock<HashMap<String, Regex>>>);
+
+impl RegexCache {
+    pub(crate) fn get(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        if let Some(regex) = self.0.read().unwrap().get(pattern) {
+            return Ok(regex.clone());
+        }
+
+        let regex = Regex::new(pattern)?;
+        let mut regexes = self.0.write().unwrap();
+        if regexes.len() >= REGEX_CACHE_SIZE {
+            regexes.clear();
+        }
+        regexes.insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::escape_html;
+    use super::{
+        escape_csv, escape_html, escape_json_string, escape_latex, minify_html,
+        normalize_template_name, remove_spaces_between_tags, RegexCache,
+    };
 
     #[test]
     fn test_escape_html() {
@@ -54,4 +301,117 @@ mod tests {
         let empty = String::new();
         assert_eq!(escape_html(&empty), empty);
     }
+
+    #[test]
+    fn test_escape_latex() {
+        let tests = vec![
+            (r"", ""),
+            (r"50% of $10 & #1_{a}", r"50\% of \$10 \& \#1\_\{a\}"),
+            (
+                r"~^\<>",
+                r"\textasciitilde{}\textasciicircum{}\textbackslash{}\textless{}\textgreater{}",
+            ),
+            (r"Zürich", r"Zürich"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(escape_latex(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        let tests = vec![
+            (r"", ""),
+            (r"plain text", "plain text"),
+            (r"a,b", r#""a,b""#),
+            (r#"say "hi""#, r#""say ""hi""""#),
+            ("two\nlines", "\"two\nlines\""),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(escape_csv(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_escape_json_string() {
+        let tests = vec![
+            (r"", ""),
+            (r#"say "hi""#, r#"say \"hi\""#),
+            (r"a\b", r"a\\b"),
+            ("a\nb\tc", r"a\nb\tc"),
+            ("\u{0}\u{1f}\u{7f}", "\\u0000\\u001f\u{7f}"),
+            (r"<a href='/'>大阪</a>", r"<a href='/'>大阪</a>"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(escape_json_string(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_remove_spaces_between_tags() {
+        let tests = vec![
+            ("", ""),
+            ("  <p>\n  <a>link</a> </p>\n", "<p><a>link</a></p>"),
+            ("<p>Hello  world</p> <b>bold</b>", "<p>Hello  world</p><b>bold</b>"),
+            ("<p> text </p>", "<p> text </p>"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(remove_spaces_between_tags(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_minify_html() {
+        let tests = vec![
+            ("<p>\n    Hello   <b>world</b>\n</p>\n", "<p> Hello <b>world</b> </p> "),
+            ("<a  href=\"/a  b\"\n   title='x  y'>a</a>", "<a href=\"/a  b\" title='x  y'>a</a>"),
+            (
+                "a <!-- comment --> b<!--[if IE]> <p>IE</p> <![endif]-->",
+                "a b<!--[if IE]> <p>IE</p> <![endif]-->",
+            ),
+            (
+                "<!--[if !IE]><!--> <p>not IE</p> <!--<![endif]-->",
+                "<!--[if !IE]><!--> <p>not IE</p> <!--<![endif]-->",
+            ),
+            (
+                "<PRE>  a\n  b </PRE>  <script>\nif (a  <b) {}\n</script>",
+                "<PRE>  a\n  b </PRE> <script>\nif (a  <b) {}\n</script>",
+            ),
+            (
+                "<textarea>  x  </textarea>\t<style> a  { } </style>",
+                "<textarea>  x  </textarea> <style> a  { } </style>",
+            ),
+            ("1 < 2  and   é", "1 < 2 and é"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(minify_html(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_normalize_template_name() {
+        let tests = vec![
+            ("users/profile.html", Some("users/profile.html".to_string())),
+            ("./users//profile.html", Some("users/profile.html".to_string())),
+            ("users\\..\\base.html", Some("base.html".to_string())),
+            ("users/./../users/profile.html", Some("users/profile.html".to_string())),
+            ("../secrets.txt", None),
+            ("users/../../secrets.txt", None),
+            ("/etc/passwd", None),
+            ("C:\\Windows\\win.ini", None),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(normalize_template_name(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_regex_cache() {
+        let cache = RegexCache::default();
+        assert!(cache.get("^a+$").unwrap().is_match("aaa"));
+        assert!(cache.get("^a+$").unwrap().is_match("a"));
+        assert_eq!(cache.0.read().unwrap().len(), 1);
+        assert!(cache.get("(unclosed").is_err());
+        assert_eq!(cache.0.read().unwrap().len(), 1);
+    }
 }
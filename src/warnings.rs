@@ -0,0 +1,148 @@
+//! Warnings about deprecated filters and templates, see `Tera::deprecations` and
+//! `Tera::render_with_warnings`.
+use std::fmt;
+
+use crate::parser::lexer::Span;
+
+/// What is deprecated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Deprecation {
+    /// A filter marked with `Tera::register_filter_deprecated`
+    Filter {
+        /// The name of the filter
+        name: String,
+        /// What to use instead
+        replacement: String,
+    },
+    /// A template containing a `{# deprecated: reason #}` comment
+    Template {
+        /// The name of the template
+        name: String,
+        /// The reason given in the comment
+        reason: String,
+    },
+}
+
+/// A use of something deprecated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// The template using it
+    pub template: String,
+    /// Where in `template`, `None` for a deprecated template rendered directly
+    pub span: Option<Span>,
+    /// What is deprecated
+    pub deprecation: Deprecation,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Template '{}'", self.template)?;
+        if let Some(ref span) = self.span {
+            write!(f, " at line {}, column {}", span.start.line, span.start.column)?;
+        }
+        match self.deprecation {
+            Deprecation::Filter { ref name, ref replacement } => {
+                write!(f, ": filter `{}` is deprecated, use `{}` instead", name, replacement)
+            }
+            Deprecation::Template { ref name, ref reason } => {
+                write!(f, ": template `{}` is deprecated: {}", name, reason)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deprecation, Warning};
+    use crate::context::Context;
+    use crate::parser::lexer::{Position, Span};
+    use crate::tera::Tera;
+
+    fn tera() -> Tera {
+        let mut tera = Tera::default();
+        tera.register_filter_deprecated("json_encode", "tojson");
+        tera.add_raw_templates(vec![
+            ("base", "{# deprecated: extend `layout` #}{% block content %}{% endblock %}"),
+            ("macros", "{# deprecated: no more macros #}{% macro hey() %}{% endmacro %}"),
+            ("nav", "{#- deprecated: moved to `menu` -#}<nav></nav>"),
+            (
+                "page",
+                "{% extends \"base\" %}{% import \"macros\" as m %}{% block content %}\n\
+                 {% for i in [1, 2] %}{{ i | json_encode }}{% endfor %}\n\
+                 {% include \"nav\" %}{% if false %}{% include \"nav\" %}{% endif %}{% endblock %}",
+            ),
+        ])
+        .unwrap();
+        tera
+    }
+
+    fn warning(template: &str, at: Option<(usize, usize)>, deprecation: Deprecation) -> Warning {
+        Warning {
+            template: template.to_string(),
+            span: at.map(|(line, column)| Span {
+                start: Position { offset: 0, line, column },
+                end: Position::default(),
+            }),
+            deprecation,
+        }
+    }
+
+    fn json_encode() -> Deprecation {
+        Deprecation::Filter { name: "json_encode".to_string(), replacement: "tojson".to_string() }
+    }
+
+    fn template(name: &str, reason: &str) -> Deprecation {
+        Deprecation::Template { name: name.to_string(), reason: reason.to_string() }
+    }
+
+    // Only compares the start line and column of the spans
+    fn located(warnings: Vec<Warning>) -> Vec<Warning> {
+        warnings
+            .into_iter()
+            .map(|mut w| {
+                w.span = w.span.map(|s| Span {
+                    start: Position { offset: 0, ..s.start },
+                    end: Position::default(),
+                });
+                w
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collects_warnings_while_rendering() {
+        let tera = tera();
+        let (output, warnings) = tera.render_with_warnings("page", &Context::new()).unwrap();
+        assert_eq!(output, "\n12\n<nav></nav>");
+        assert_eq!(
+            located(warnings),
+            vec![
+                warning("page", Some((1, 1)), template("base", "extend `layout`")),
+                warning("page", Some((2, 22)), json_encode()),
+                warning("page", Some((3, 1)), template("nav", "moved to `menu`")),
+            ]
+        );
+
+        let (_, warnings) = tera.render_with_warnings("nav", &Context::new()).unwrap();
+        assert_eq!(warnings, vec![warning("nav", None, template("nav", "moved to `menu`"))]);
+    }
+
+    #[test]
+    fn finds_all_deprecations() {
+        let tera = tera();
+        assert_eq!(
+            located(tera.deprecations()),
+            vec![
+                warning("page", Some((1, 1)), template("base", "extend `layout`")),
+                warning("page", Some((1, 21)), template("macros", "no more macros")),
+                warning("page", Some((2, 22)), json_encode()),
+                warning("page", Some((3, 1)), template("nav", "moved to `menu`")),
+                warning("page", Some((3, 34)), template("nav", "moved to `menu`")),
+            ]
+        );
+        assert_eq!(
+            tera.deprecations()[2].to_string(),
+            "Template 'page' at line 2, column 22: filter `json_encode` is deprecated, use `tojson` instead"
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! JavaScript bindings, to render the same templates in the browser as on the server.
+//!
+//! They are exported by any `cdylib` built for `wasm32-unknown-unknown` depending on Tera with
+//! the `wasm` feature. Without the `builtins` feature, nothing needs the filesystem, the clock
+//! or a source of randomness of the host.
+use std::error::Error as StdError;
+
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::context::Context;
+use crate::errors::Error;
+use crate::tera::Tera;
+
+/// Turns an error and its causes into a JavaScript `Error`
+fn to_js_error(e: Error) -> JsError {
+    let mut message = e.to_string();
+    let mut cause = e.source();
+    while let Some(e) = cause {
+        message += &format!("\n{}", e);
+        cause = e.source();
+    }
+    JsError::new(&message)
+}
+
+/// Turns a JavaScript object into a context
+fn to_context(context: JsValue) -> Result<Context, JsError> {
+    let value: Value = serde_wasm_bindgen::from_value(context)?;
+    Context::from_value(value).map_err(to_js_error)
+}
+
+/// A Tera instance, exported as `Tera` to JavaScript:
+///
+/// ```js
+/// const tera = new Tera();
+/// tera.addTemplate("hello.html", "Hello {{ name }}");
+/// tera.render("hello.html", { name: "<b>Bob</b>" }); // Hello &lt;b&gt;Bob&lt;&#x2F;b&gt;
+/// ```
+#[wasm_bindgen(js_name = Tera)]
+#[derive(Default)]
+pub struct WasmTera {
+    tera: Tera,
+}
+
+#[wasm_bindgen(js_class = Tera)]
+impl WasmTera {
+    /// Creates an instance without any templates
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmTera {
+        WasmTera::default()
+    }
+
+    /// Adds a template, see [Tera::add_raw_template](struct.Tera.html#method.add_raw_template)
+    #[wasm_bindgen(js_name = addTemplate)]
+    pub fn add_template(&mut self, name: &str, content: &str) -> Result<(), JsError> {
+        self.tera.add_raw_template(name, content).map_err(to_js_error)
+    }
+
+    /// Renders a template with an object as context, see [Tera::render](struct.Tera.html#method.render)
+    pub fn render(&self, name: &str, context: JsValue) -> Result<String, JsError> {
+        self.tera.render(name, &to_context(context)?).map_err(to_js_error)
+    }
+
+    /// Renders a template that isn't added to the instance, see
+    /// [Tera::render_str](struct.Tera.html#method.render_str)
+    #[wasm_bindgen(js_name = renderStr)]
+    pub fn render_str(&mut self, template: &str, context: JsValue) -> Result<String, JsError> {
+        self.tera.render_str(template, &to_context(context)?).map_err(to_js_error)
+    }
+}
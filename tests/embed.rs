@@ -0,0 +1,21 @@
+#![cfg(feature = "embed")]
+extern crate tera;
+
+use tera::{embed_templates, Context};
+
+#[test]
+fn can_embed_a_directory_of_templates() {
+    let tera = embed_templates!("$CARGO_MANIFEST_DIR/examples/basic/templates").unwrap();
+
+    let mut names: Vec<_> = tera.templates.keys().map(|k| k.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["base.html", "macros.html", "users/profile.html"]);
+
+    let mut context = Context::new();
+    context.insert("username", "Bob");
+    context.insert("numbers", &vec![1, 2, 3]);
+    context.insert("show_all", &false);
+    context.insert("bio", "<script>alert('pwnd');</script>");
+    let res = tera.render("users/profile.html", &context).unwrap();
+    assert!(res.contains("Bob"));
+}